@@ -7,11 +7,13 @@ use rust_decimal::prelude::FromPrimitive;
 use std::collections::HashSet;
 use std::io::{self, Cursor, Seek, SeekFrom};
 use std::sync::mpsc;
-use tx_engine::csv_input::transactions_from_reader;
-use tx_engine::model::{ClientId, Clients, InputCsvRecord, OutputMode, TransactionId};
+use tx_engine::csv_input::{transactions_from_byte_reader, transactions_from_reader};
+use tx_engine::model::{
+    ClientId, Clients, InputCsvRecord, OutputMode, Transaction, TransactionId, TransactionKind,
+};
 use tx_engine::spawn_writer_thread;
 
-const NUM_TRANSACTIONS_BENCH: u32 = 1000_000; // We can adjust size for benchmark duration
+const NUM_TRANSACTIONS_BENCH: u32 = 1_000_000; // We can adjust size for benchmark duration
 const NUM_CLIENTS_BENCH: u16 = u16::MAX;
 const MAX_AMOUNT_BENCH: f64 = 1000.0;
 
@@ -25,7 +27,7 @@ fn generate_records(num_records: u32) -> Vec<InputCsvRecord> {
     for tx in 1..num_records {
         let client_id = rng.random_range(1..=NUM_CLIENTS_BENCH);
 
-        let mut tx_type: Option<String> = None;
+        let mut tx_type: Option<TransactionKind> = None;
         let mut amount: Option<Decimal> = None;
         let mut new_tx_id = tx;
 
@@ -33,7 +35,7 @@ fn generate_records(num_records: u32) -> Vec<InputCsvRecord> {
             // retry until we get a decision
             match rng.random_range(0.0..1.0) {
                 x if x < 0.5 => {
-                    tx_type = Some("deposit".to_string());
+                    tx_type = Some(TransactionKind::Deposit);
                     let val = Decimal::from_f64(rng.random_range(0.01..=MAX_AMOUNT_BENCH))
                         .unwrap_or_default()
                         .round_dp(4);
@@ -41,7 +43,7 @@ fn generate_records(num_records: u32) -> Vec<InputCsvRecord> {
                     deposit_transactions.insert((new_tx_id, client_id));
                 }
                 x if x < 0.7 => {
-                    tx_type = Some("withdrawal".to_string());
+                    tx_type = Some(TransactionKind::Withdrawal);
                     let val = Decimal::from_f64(rng.random_range(0.01..=MAX_AMOUNT_BENCH / 2.0)) // Withdraw less
                         .unwrap_or_default()
                         .round_dp(4);
@@ -49,7 +51,7 @@ fn generate_records(num_records: u32) -> Vec<InputCsvRecord> {
                 }
                 x if x < 0.8 => {
                     if let Some((tx_id, client_id)) = deposit_transactions.iter().next().cloned() {
-                        tx_type = Some("dispute".to_string());
+                        tx_type = Some(TransactionKind::Dispute);
                         disputed_transactions.insert((tx_id, client_id));
                         deposit_transactions.remove(&(tx_id, client_id));
                         new_tx_id = tx_id;
@@ -60,7 +62,7 @@ fn generate_records(num_records: u32) -> Vec<InputCsvRecord> {
                 }
                 x if x < 0.95 => {
                     if let Some((tx_id, client_id)) = disputed_transactions.iter().next().cloned() {
-                        tx_type = Some("resolve".to_string());
+                        tx_type = Some(TransactionKind::Resolve);
                         disputed_transactions.remove(&(tx_id, client_id));
                         amount = None;
                         new_tx_id = tx_id;
@@ -70,7 +72,7 @@ fn generate_records(num_records: u32) -> Vec<InputCsvRecord> {
                 }
                 _ => {
                     if let Some((tx_id, client_id)) = disputed_transactions.iter().next().cloned() {
-                        tx_type = Some("chargeback".to_string());
+                        tx_type = Some(TransactionKind::Chargeback);
                         disputed_transactions.remove(&(tx_id, client_id));
                         amount = None;
                         new_tx_id = tx_id;
@@ -127,7 +129,7 @@ fn benchmark_transaction_processing(c: &mut Criterion) {
     let records = generate_records(NUM_TRANSACTIONS_BENCH);
 
     group.bench_function(
-        &format!("Process {} transactions in-memory", NUM_TRANSACTIONS_BENCH),
+        format!("Process {} transactions in-memory", NUM_TRANSACTIONS_BENCH),
         |b: &mut Bencher| {
             // Use iter_batched to separate setup (CSV creation) from the routine (processing)
             b.iter_batched(
@@ -145,16 +147,13 @@ fn benchmark_transaction_processing(c: &mut Criterion) {
                     let mut clients = Clients::new(tx);
 
                     // The actual work: consume the iterator and update client state also serializes and sends output to sink
-                    let transcations_result = clients.load_transactions(transactions_iter);
+                    clients.load_transactions(transactions_iter);
 
                     // write remaining output to sink
-                    clients
-                        .send_to_output(OutputMode::SkipLocked)
-                        .expect("failed to write output");
+                    clients.send_to_output(OutputMode::SkipLocked);
                     let thread_result = thread_handle.join();
 
                     // Use black_box to prevent the compiler optimizing away the result
-                    criterion::black_box(transcations_result);
                     criterion::black_box(thread_result).expect("failed to join thread");
                 },
                 BatchSize::SmallInput,
@@ -162,6 +161,61 @@ fn benchmark_transaction_processing(c: &mut Criterion) {
         },
     );
 
+    // only the account updates, which the `fast-money`, `fast-hash` and `dense-accounts` features make cheaper
+    let transactions: Vec<Transaction> = generate_records(NUM_TRANSACTIONS_BENCH)
+        .into_iter()
+        .filter_map(|record| Transaction::try_from(record).ok())
+        .collect();
+    group.bench_function(
+        format!("Apply {} parsed transactions", NUM_TRANSACTIONS_BENCH),
+        |b: &mut Bencher| {
+            b.iter_batched(
+                || transactions.clone(),
+                |transactions| {
+                    let mut clients = Clients::new(Vec::new());
+                    clients.load_transactions(transactions.into_iter().map(Ok));
+                    criterion::black_box(clients.accounts.len())
+                },
+                BatchSize::SmallInput,
+            );
+        },
+    );
+
+    group.bench_function(
+        format!(
+            "Parse {} transactions from byte records",
+            NUM_TRANSACTIONS_BENCH
+        ),
+        |b: &mut Bencher| {
+            b.iter_batched(
+                || create_csv_buffer(&records),
+                |mut csv_buffer| {
+                    let reader = ReaderBuilder::new()
+                        .trim(csv::Trim::All)
+                        .from_reader(&mut csv_buffer);
+                    criterion::black_box(transactions_from_byte_reader(reader).count())
+                },
+                BatchSize::SmallInput,
+            );
+        },
+    );
+
+    group.bench_function(
+        format!("Parse {} transactions with serde", NUM_TRANSACTIONS_BENCH),
+        |b: &mut Bencher| {
+            b.iter_batched(
+                || create_csv_buffer(&records),
+                |mut csv_buffer| {
+                    let reader = ReaderBuilder::new()
+                        .trim(csv::Trim::All)
+                        .from_reader(&mut csv_buffer);
+                    criterion::black_box(transactions_from_reader(reader).count())
+                },
+                BatchSize::SmallInput,
+            );
+        },
+    );
+
     group.finish();
 }
 
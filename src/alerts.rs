@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    io,
+};
+
+use csv::Reader;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{instrument, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{Account, ClientId, Transaction, TransactionId},
+    observer::TransactionObserver,
+};
+
+/// Balance limits of a client, unset limits are not monitored
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Threshold {
+    pub min_available: Option<Decimal>, // alert when available drops below
+    pub max_total: Option<Decimal>,     // alert when total rises above
+}
+
+/// Global limits with per-client overrides
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BalanceThresholds {
+    pub global: Threshold,
+    pub per_client: HashMap<ClientId, Threshold>,
+}
+
+/// Row of a per-client thresholds file: `client, min_available, max_total` (empty fields are unset)
+#[derive(Debug, Deserialize)]
+struct CsvThresholdRecord {
+    client: ClientId,
+    min_available: Option<Decimal>,
+    max_total: Option<Decimal>,
+}
+
+impl BalanceThresholds {
+    pub fn threshold(&self, client: ClientId) -> &Threshold {
+        self.per_client.get(&client).unwrap_or(&self.global)
+    }
+
+    /// Reads the per-client overrides from csv
+    pub fn load_per_client<R: io::Read>(
+        &mut self,
+        csv_reader: Reader<R>,
+    ) -> Result<(), ConversionError> {
+        for record in csv_reader.into_deserialize::<CsvThresholdRecord>() {
+            let record = record?;
+            let threshold = Threshold {
+                min_available: record.min_available,
+                max_total: record.max_total,
+            };
+            self.per_client.insert(record.client, threshold);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    AvailableBelowMinimum,
+    TotalAboveMaximum,
+}
+
+/// A balance that crossed one of its thresholds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceAlert {
+    pub client: ClientId,
+    pub tx: TransactionId, // transaction that caused the crossing
+    pub kind: AlertKind,
+    pub balance: Decimal,
+    pub threshold: Decimal,
+}
+
+impl Display for BalanceAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "client {} {:?} after tx {}: {} (threshold {})",
+            self.client, self.kind, self.tx, self.balance, self.threshold
+        )
+    }
+}
+
+/// Observer raising an alert when a balance crosses a threshold. Alerts are edge triggered:
+/// a client that stays beyond its threshold is not alerted again until it comes back.
+pub struct ThresholdMonitor {
+    thresholds: BalanceThresholds,
+    on_alert: Box<dyn FnMut(&BalanceAlert) + Send>,
+}
+
+impl Debug for ThresholdMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThresholdMonitor")
+            .field("thresholds", &self.thresholds)
+            .finish()
+    }
+}
+
+impl ThresholdMonitor {
+    pub fn new<F: FnMut(&BalanceAlert) + Send + 'static>(
+        thresholds: BalanceThresholds,
+        on_alert: F,
+    ) -> ThresholdMonitor {
+        ThresholdMonitor {
+            thresholds,
+            on_alert: Box::new(on_alert),
+        }
+    }
+}
+
+impl TransactionObserver for ThresholdMonitor {
+    #[instrument(level = "trace", skip(self, before, after))]
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        let threshold = *self.thresholds.threshold(client);
+        let mut alerts = Vec::new();
+        if let Some(min) = threshold.min_available
+            && before.available() >= min
+            && after.available() < min
+        {
+            alerts.push((AlertKind::AvailableBelowMinimum, after.available(), min));
+        }
+        if let Some(max) = threshold.max_total
+            && before.total() <= max
+            && after.total() > max
+        {
+            alerts.push((AlertKind::TotalAboveMaximum, after.total(), max));
+        }
+        for (kind, balance, threshold) in alerts {
+            let alert = BalanceAlert {
+                client,
+                tx: transaction.tx_id(),
+                kind,
+                balance,
+                threshold,
+            };
+            warn!(%alert, "Balance threshold crossed");
+            (self.on_alert)(&alert);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    fmt::Display,
+    future, mem, pin,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+use tracing::{error, instrument};
+
+use crate::model::{Account, ClientId, Clients, OutputMode, ProcessingStats, Transaction};
+
+/// Transactions applied between two yields to the runtime, so that a stream that is always ready
+/// does not starve the other tasks of its thread
+const YIELD_EVERY: u64 = 1024;
+
+/// `Clients` for tokio services: the transactions come from a `Stream` (e.g. received over the
+/// network) and the accounts go to an async channel. Applying a transaction never blocks, the
+/// accounts it emits are sent once it is applied, waiting while the channel is full so that a slow
+/// consumer slows the loading down.
+#[derive(Debug)]
+pub struct AsyncClients {
+    pub clients: Clients, // to configure (observers, sparse accounts, rounding...) and query
+    pending: Arc<Mutex<Vec<(ClientId, Account)>>>, // emitted by the clients, not sent yet
+    output: mpsc::Sender<(ClientId, Account)>,
+}
+
+impl AsyncClients {
+    /// Clients sending their accounts to `output`, e.g. the sender of a task writing them out
+    pub fn new(output: mpsc::Sender<(ClientId, Account)>) -> AsyncClients {
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        AsyncClients {
+            clients: Clients::new(Arc::clone(&pending)),
+            pending,
+            output,
+        }
+    }
+
+    /// Mutate the client Accounts with a stream of transactions, like `Clients::load_transactions`.
+    /// Any error type of the stream is accepted, an error is counted as an invalid transaction.
+    #[instrument(skip(self, transactions))]
+    pub async fn load_transactions<S, E>(&mut self, transactions: S) -> ProcessingStats
+    where
+        S: Stream<Item = Result<Transaction, E>>,
+        E: Display,
+    {
+        let started = Instant::now();
+        let mut stats = ProcessingStats::default();
+        let mut transactions = pin::pin!(transactions);
+        let mut applied = 0u64;
+        while let Some(transaction) =
+            future::poll_fn(|cx| transactions.as_mut().poll_next(cx)).await
+        {
+            self.clients.load_transaction(transaction, &mut stats);
+            self.flush().await;
+            applied += 1;
+            if applied.is_multiple_of(YIELD_EVERY) {
+                tokio::task::yield_now().await;
+            }
+        }
+        self.clients.finish_loading(&mut stats, started);
+        stats
+    }
+
+    /// Send the accounts to the output, like `Clients::send_to_output`, and close it. The accounts
+    /// are collected in memory first, then sent as the consumer receives them.
+    pub async fn send_to_output(mut self, output_mode: OutputMode) {
+        let clients = mem::replace(&mut self.clients, Clients::new(Vec::new()));
+        clients.send_to_output(output_mode);
+        self.flush().await;
+    }
+
+    // sends the accounts emitted so far, in order
+    async fn flush(&mut self) {
+        let pending = mem::take(&mut *self.pending.lock().expect("account sink mutex poisoned"));
+        for (client, account) in pending {
+            if self.output.send((client, account)).await.is_err() {
+                error!(%client, "the output receiver stopped, dropping the account");
+            }
+        }
+    }
+}
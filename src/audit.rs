@@ -0,0 +1,123 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{error, instrument};
+
+use crate::{
+    model::{Account, ClientId, OPENING_TX, Transaction, TransactionId},
+    observer::TransactionObserver,
+};
+
+/// Effect of one applied transaction on its account
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct JournalEntry {
+    pub tx: TransactionId,
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+    pub client: ClientId,
+    pub available_before: Decimal,
+    pub held_before: Decimal,
+    pub available_after: Decimal,
+    pub held_after: Decimal,
+    pub held_delta: Decimal,
+    pub locked: bool, // after the transaction
+}
+
+/// Observer appending every applied transaction, with the balances before and after it, to a csv
+/// journal. Replaying the entries of a client from its first one explains its final state.
+/// Ignored transactions (not enough funds, unknown tx, locked account...) are not journaled.
+/// An opening balance is a synthetic `opening` entry, with tx 0, from empty balances.
+#[derive(Debug)]
+pub struct AuditJournal<W: io::Write> {
+    wtr: csv::Writer<W>,
+    entries: u64,
+}
+
+impl AuditJournal<File> {
+    /// Opens the journal for appending, the header is only written when it is empty
+    #[instrument]
+    pub fn open(path: &Path) -> io::Result<AuditJournal<File>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let empty = file.metadata()?.len() == 0;
+        Ok(AuditJournal::new(file, empty))
+    }
+}
+
+impl<W: io::Write> AuditJournal<W> {
+    pub fn new(wtr: W, header: bool) -> AuditJournal<W> {
+        AuditJournal {
+            wtr: csv::WriterBuilder::new()
+                .has_headers(header)
+                .from_writer(wtr),
+            entries: 0,
+        }
+    }
+
+    /// Number of entries written by this journal (not the ones of earlier runs)
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+
+    fn append(&mut self, entry: JournalEntry) {
+        match self.wtr.serialize(entry) {
+            Ok(()) => self.entries += 1,
+            Err(err) => error!(%err, "failed to write the audit journal entry"),
+        }
+    }
+}
+
+impl<W: io::Write + Send> TransactionObserver for AuditJournal<W> {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        if before == after {
+            return; // ignored transaction
+        }
+        let entry = JournalEntry {
+            tx: transaction.tx_id(),
+            transaction_type: transaction.type_name().to_string(),
+            client,
+            available_before: before.available(),
+            held_before: before.held(),
+            available_after: after.available(),
+            held_after: after.held(),
+            held_delta: after.held() - before.held(),
+            locked: after.locked(),
+        };
+        self.append(entry);
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        self.append(JournalEntry {
+            tx: OPENING_TX,
+            transaction_type: "opening".to_string(),
+            client,
+            available_before: Decimal::ZERO,
+            held_before: Decimal::ZERO,
+            available_after: account.available(),
+            held_after: account.held(),
+            held_delta: account.held(),
+            locked: account.locked(),
+        });
+    }
+}
+
+/// Reads the entries of a journal back, in the order they were applied
+pub fn read_journal<R: io::Read>(
+    csv_reader: csv::Reader<R>,
+) -> impl Iterator<Item = Result<JournalEntry, csv::Error>> {
+    csv_reader.into_deserialize()
+}
@@ -0,0 +1,107 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use tracing::{error, warn};
+
+use crate::{csv_input::ConversionError, model::Transaction};
+
+/// Cooperative cancellation shared by the stages of a run: once cancelled, ingestion stops at the
+/// next transaction and the transactions already read are applied and written out as usual.
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Token cancelled on SIGINT (Ctrl-C) or SIGTERM, a second signal exits right away
+    pub fn on_signals() -> Result<CancellationToken, ctrlc::Error> {
+        let token = CancellationToken::new();
+        let handler = token.clone();
+        ctrlc::set_handler(move || {
+            if handler.is_cancelled() {
+                error!("Interrupted again, exiting without draining the output");
+                std::process::exit(130);
+            }
+            warn!(
+                "Interrupted, stopping ingestion and draining the output (interrupt again to exit now)"
+            );
+            handler.cancel();
+        })?;
+        Ok(token)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Progress of a cancellable ingestion, still readable once the iterator was moved into the pipeline
+#[derive(Debug, Default, Clone)]
+pub struct IngestionProgress {
+    token: CancellationToken,
+    records: Arc<AtomicU64>,   // input records read so far
+    finished: Arc<AtomicBool>, // the input ran out on its own
+}
+
+impl IngestionProgress {
+    /// Number of input records read, the checkpoint a rerun would pick up from after an interruption
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::SeqCst)
+    }
+
+    /// Whether ingestion stopped before the end of the input
+    pub fn interrupted(&self) -> bool {
+        self.token.is_cancelled() && !self.finished.load(Ordering::SeqCst)
+    }
+}
+
+/// Transactions of an input until the token is cancelled
+#[derive(Debug)]
+pub struct Cancellable<I> {
+    inner: I,
+    progress: IngestionProgress,
+}
+
+impl<I: Iterator<Item = Result<Transaction, ConversionError>>> Iterator for Cancellable<I> {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.progress.token.is_cancelled() {
+            return None;
+        }
+        let next = self.inner.next();
+        if next.is_some() {
+            self.progress.records.fetch_add(1, Ordering::SeqCst);
+        } else if !self.progress.token.is_cancelled() {
+            // a followed input or a topic also ends when cancelled, that is not the end of the input
+            self.progress.finished.store(true, Ordering::SeqCst);
+        }
+        next
+    }
+}
+
+/// Stops `transactions` once `token` is cancelled
+pub fn cancellable<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+    token: &CancellationToken,
+    transactions: I,
+) -> (Cancellable<I>, IngestionProgress) {
+    let progress = IngestionProgress {
+        token: token.clone(),
+        ..Default::default()
+    };
+    let transactions = Cancellable {
+        inner: transactions,
+        progress: progress.clone(),
+    };
+    (transactions, progress)
+}
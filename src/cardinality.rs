@@ -0,0 +1,120 @@
+use tracing::{info, instrument, warn};
+
+use crate::{csv_input::ConversionError, model::Transaction};
+
+const PRECISION: u32 = 12; // 4096 registers, about 1.6% standard error
+const REGISTERS: usize = 1 << PRECISION;
+
+/// HyperLogLog distinct count estimator, constant memory whatever the number of values
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+// splitmix64 finalizer, spreads sequential ids over the whole hash space
+fn hash(value: u64) -> u64 {
+    let mut hash = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+impl HyperLogLog {
+    pub fn insert(&mut self, value: u64) {
+        let hash = hash(value);
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // position of the first set bit of the remaining bits, the sentinel bounds it
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() + 1;
+        self.registers[register] = self.registers[register].max(rank as u8);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let registers = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / registers);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|rank| 2f64.powi(-(*rank as i32)))
+            .sum();
+        let raw = alpha * registers * registers / sum;
+        let empty = self.registers.iter().filter(|rank| **rank == 0).count();
+        if raw <= 2.5 * registers && empty > 0 {
+            // linear counting is more accurate for small cardinalities
+            return (registers * (registers / empty as f64).ln()).round() as u64;
+        }
+        raw.round() as u64
+    }
+}
+
+/// Estimated number of distinct clients and transaction ids of an input
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CardinalityEstimate {
+    pub clients: u64,
+    pub transactions: u64,
+    pub rows: u64, // exact, including invalid rows
+}
+
+/// Limits above which the pre-pass warns, e.g. to stop before running out of memory
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CardinalityLimits {
+    pub max_clients: Option<u64>,
+    pub max_transactions: Option<u64>,
+}
+
+impl CardinalityEstimate {
+    /// Logs a warning for every limit exceeded, returns whether all the limits hold
+    pub fn check(&self, limits: &CardinalityLimits) -> bool {
+        let mut within = true;
+        if let Some(max_clients) = limits.max_clients
+            && self.clients > max_clients
+        {
+            warn!(
+                estimate = self.clients,
+                max_clients, "Too many distinct clients"
+            );
+            within = false;
+        }
+        if let Some(max_transactions) = limits.max_transactions
+            && self.transactions > max_transactions
+        {
+            warn!(
+                estimate = self.transactions,
+                max_transactions, "Too many distinct transaction ids"
+            );
+            within = false;
+        }
+        within
+    }
+}
+
+/// Fast pre-pass over an input: only ids are looked at, nothing is applied
+#[instrument(skip(transactions))]
+pub fn estimate_cardinality<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+    transactions: I,
+) -> CardinalityEstimate {
+    let mut clients = HyperLogLog::default();
+    let mut transaction_ids = HyperLogLog::default();
+    let mut rows = 0;
+    for transaction in transactions {
+        rows += 1;
+        if let Ok(transaction) = transaction {
+            clients.insert(transaction.client_id().0 as u64);
+            transaction_ids.insert(transaction.tx_id().0 as u64);
+        }
+    }
+    let estimate = CardinalityEstimate {
+        clients: clients.estimate(),
+        transactions: transaction_ids.estimate(),
+        rows,
+    };
+    info!(?estimate, "Estimated input cardinality");
+    estimate
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io,
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{
+        Account, ClientId, Clients, DisputableTransaction, DisputableTransactionStatus,
+        DisputableTransactions, Transaction, TransactionId,
+    },
+    reports::open_disputes,
+};
+
+/// An open dispute as a case for the case-management system
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvDisputeCase {
+    pub case_id: String, // `<client>-<tx>`, echoed back in the decisions
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub amount: Decimal,
+    pub status: &'static str,
+}
+
+/// Outcome decided externally for a dispute case
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseOutcome {
+    Resolve,
+    Chargeback,
+}
+
+/// One row of the decisions file imported back from the case-management system
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct CaseDecision {
+    pub case_id: String,
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub outcome: CaseOutcome,
+    pub decided_by: String, // provenance: who (or which system) took the decision
+}
+
+impl From<&CaseDecision> for Transaction {
+    fn from(decision: &CaseDecision) -> Self {
+        let (client, tx) = (decision.client, decision.tx);
+        match decision.outcome {
+            CaseOutcome::Resolve => Transaction::Resolve { client, tx },
+            CaseOutcome::Chargeback => Transaction::Chargeback { client, tx },
+        }
+    }
+}
+
+/// Counts of an import of decisions
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct CaseImportSummary {
+    pub applied: u64,
+    pub rejected: u64, // invalid rows and decisions on disputes that are not open (anymore)
+}
+
+/// Writes the open disputes as cases (case_id, client, tx, amount, status) as csv
+#[instrument(skip(clients, wtr))]
+pub fn write_dispute_cases<W: io::Write>(clients: &Clients, wtr: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::WriterBuilder::new()
+        .has_headers(false) // header is written explicitly so that an export without disputes still has it
+        .from_writer(wtr);
+    csv_writer.write_record(["case_id", "client", "tx", "amount", "status"])?;
+    for dispute in open_disputes(clients) {
+        csv_writer.serialize(CsvDisputeCase {
+            case_id: format!("{}-{}", dispute.client, dispute.tx),
+            client: dispute.client,
+            tx: dispute.tx,
+            amount: dispute.amount,
+            status: "open",
+        })?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+fn is_open(clients: &Clients, decision: &CaseDecision) -> bool {
+    is_open_dispute(clients.disputable_transactions.get(&decision.tx), decision)
+}
+
+fn is_open_dispute(disputable: Option<&DisputableTransaction>, decision: &CaseDecision) -> bool {
+    disputable.is_some_and(|disputable| {
+        disputable.client == decision.client
+            && matches!(
+                disputable.status,
+                DisputableTransactionStatus::DisputedAmount(_)
+            )
+    })
+}
+
+/// Applies the decisions of the case-management system as resolve/chargeback transactions.
+/// Every applied decision is logged with its case and decider, decisions on disputes that are not open are rejected.
+#[instrument(skip(clients, csv_reader))]
+pub fn apply_case_decisions<R: io::Read>(
+    clients: &mut Clients,
+    csv_reader: csv::Reader<R>,
+) -> CaseImportSummary {
+    let mut summary = CaseImportSummary::default();
+    for record in csv_reader.into_deserialize::<CaseDecision>() {
+        let decision = match record.map_err(ConversionError::from) {
+            Ok(decision) => decision,
+            Err(err) => {
+                error!(error=%err, "Skipping invalid case decision");
+                summary.rejected += 1;
+                continue;
+            }
+        };
+        if !is_open(clients, &decision) {
+            warn!(?decision, "Rejected decision on a dispute that is not open");
+            summary.rejected += 1;
+            continue;
+        }
+        info!(
+            case_id = decision.case_id,
+            decided_by = decision.decided_by,
+            outcome = ?decision.outcome,
+            "Applying case decision"
+        );
+        clients.apply_transaction(Transaction::from(&decision));
+        summary.applied += 1;
+    }
+    info!(?summary, "Imported case decisions");
+    summary
+}
+
+/// Balances of a client before and after the previewed decisions
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct DecisionImpact {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+    pub projected_available: Decimal,
+    pub projected_held: Decimal,
+    pub projected_total: Decimal,
+    pub projected_locked: bool,
+}
+
+/// Outcome of hypothetical decisions
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct DecisionPreview {
+    pub summary: CaseImportSummary, // decisions that would be applied or rejected
+    pub impacts: Vec<DecisionImpact>, // clients whose balances would change, by client id
+}
+
+/// Previews decisions without applying them: they are applied in order to copies of the accounts and
+/// disputes they touch, the clients (and their observers) are left untouched.
+/// Like `apply_case_decisions`, decisions on disputes that are not open (anymore) are rejected, and
+/// so are decisions on locked accounts, which the engine would ignore.
+#[instrument(skip(clients, decisions))]
+pub fn preview_case_decisions(clients: &Clients, decisions: &[CaseDecision]) -> DecisionPreview {
+    let mut accounts: BTreeMap<ClientId, Account> = BTreeMap::new(); // copied on first decision
+    let mut disputes = DisputableTransactions::default(); // copied on first decision
+    let mut decided: HashSet<TransactionId> = HashSet::new(); // a chargeback drops the copy
+    let mut summary = CaseImportSummary::default();
+    for decision in decisions {
+        let disputable = if decided.contains(&decision.tx) {
+            disputes.get(&decision.tx)
+        } else {
+            clients.disputable_transactions.get(&decision.tx)
+        };
+        if !is_open_dispute(disputable, decision) {
+            warn!(
+                ?decision,
+                "Previewed decision on a dispute that is not open"
+            );
+            summary.rejected += 1;
+            continue;
+        }
+        let account = accounts
+            .entry(decision.client)
+            .or_insert_with(|| clients.accounts[&decision.client].clone());
+        if account.locked() {
+            warn!(?decision, "Previewed decision on a locked account");
+            summary.rejected += 1;
+            continue;
+        }
+        if decided.insert(decision.tx) {
+            let disputable = clients.disputable_transactions[&decision.tx].clone();
+            disputes.insert(decision.tx, disputable);
+        }
+        account.apply(&Transaction::from(decision), &mut disputes);
+        summary.applied += 1;
+    }
+    let impacts = accounts
+        .into_iter()
+        .map(|(client, projected)| {
+            let current = &clients.accounts[&client];
+            DecisionImpact {
+                client,
+                available: current.available(),
+                held: current.held(),
+                total: current.total(),
+                locked: current.locked(),
+                projected_available: projected.available(),
+                projected_held: projected.held(),
+                projected_total: projected.total(),
+                projected_locked: projected.locked(),
+            }
+        })
+        .filter(|impact| {
+            (impact.available, impact.held, impact.locked)
+                != (
+                    impact.projected_available,
+                    impact.projected_held,
+                    impact.projected_locked,
+                )
+        })
+        .collect();
+    info!(?summary, "Previewed case decisions");
+    DecisionPreview { summary, impacts }
+}
+
+/// Reads decisions (same columns as the decisions file), invalid rows are logged and skipped
+pub fn read_case_decisions<R: io::Read>(csv_reader: csv::Reader<R>) -> Vec<CaseDecision> {
+    csv_reader
+        .into_deserialize::<CaseDecision>()
+        .filter_map(|record| {
+            record
+                .map_err(|err| error!(error=%ConversionError::from(err), "Skipping invalid case decision"))
+                .ok()
+        })
+        .collect()
+}
+
+/// Writes the impacts of a preview as csv, one row per client with its current and projected balances
+pub fn write_decision_preview<W: io::Write>(
+    preview: &DecisionPreview,
+    wtr: W,
+) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::WriterBuilder::new()
+        .has_headers(false) // header is written explicitly so that an empty preview still has it
+        .from_writer(wtr);
+    csv_writer.write_record([
+        "client",
+        "available",
+        "held",
+        "total",
+        "locked",
+        "projected_available",
+        "projected_held",
+        "projected_total",
+        "projected_locked",
+    ])?;
+    for impact in &preview.impacts {
+        csv_writer.serialize(impact)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
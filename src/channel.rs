@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+#[cfg(feature = "crossbeam")]
+pub use crossbeam_channel::{Receiver, SendError, Sender, Sender as BoundedSender};
+#[cfg(not(feature = "crossbeam"))]
+pub use std::sync::mpsc::{Receiver, SendError, Sender, SyncSender as BoundedSender};
+
+/// Channels between the threads of the engine: the workers of `Clients::load_transactions_parallel`,
+/// the parser of `load_transactions_pipelined`, the writer threads of `sink::account_channel` and
+/// the part writers. `std::sync::mpsc` by default, `crossbeam-channel` with the `crossbeam`
+/// feature, which sends faster when many threads contend on the same channel.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    #[cfg(feature = "crossbeam")]
+    return crossbeam_channel::unbounded();
+    #[cfg(not(feature = "crossbeam"))]
+    return std::sync::mpsc::channel();
+}
+
+/// A channel holding at most `capacity` messages, the senders wait while it is full
+pub fn bounded<T>(capacity: usize) -> (BoundedSender<T>, Receiver<T>) {
+    #[cfg(feature = "crossbeam")]
+    return crossbeam_channel::bounded(capacity);
+    #[cfg(not(feature = "crossbeam"))]
+    return std::sync::mpsc::sync_channel(capacity);
+}
+
+/// Waits up to `period` for the shutdown of a background thread, signalled by a message or by
+/// dropping the senders: true once shut down, false when the period elapsed first
+pub fn wait_for_shutdown(shutdown: &Receiver<()>, period: Duration) -> bool {
+    #[cfg(feature = "crossbeam")]
+    return crossbeam_channel::select! {
+        recv(shutdown) -> _ => true,
+        default(period) => false,
+    };
+    #[cfg(not(feature = "crossbeam"))]
+    return !matches!(
+        shutdown.recv_timeout(period),
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout)
+    );
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    num::NonZeroU64,
+    path::Path,
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    model::{ClientId, TransactionId},
+    output::AtomicOutput,
+};
+
+/// Format of the checkpoint files, a resume refuses the other ones
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Transactions applied between two checkpoints when `--checkpoint-every` is not given
+pub const DEFAULT_CHECKPOINT_EVERY: NonZeroU64 = NonZeroU64::new(1_000_000).unwrap();
+
+/// State of `Clients` at a checkpoint (`Clients::snapshot`), enough to resume a run from it with
+/// `Clients::from_snapshot` instead of applying the input again from its first row. The outputs,
+/// observers and options (rounding, settle horizon...) are not part of it, the resumed run sets
+/// them again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub input_offset: u64, // input records consumed when it was taken, skipped when resuming
+    pub applied: u64,      // clock of the settle horizon
+    pub accounts: Vec<SnapshotAccount>, // by client id
+    pub disputable_transactions: Vec<SnapshotDeposit>, // by tx id
+    pub settling: Vec<(u64, TransactionId)>, // deposits by age, see `Clients::settle_horizon`
+    pub erased: Vec<ClientId>, // by client id
+}
+
+/// An account of a `Snapshot`, with its balances unrounded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotAccount {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub locked: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A deposit that can still be disputed, or is disputed, in a `Snapshot`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDeposit {
+    pub tx: TransactionId,
+    pub client: ClientId,
+    pub amount: Decimal,
+    pub disputed: bool,
+}
+
+impl Snapshot {
+    /// Writes the snapshot as json through a temporary file renamed over `path`, so that a crash
+    /// while writing leaves the previous checkpoint
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let (atomic_output, file) = AtomicOutput::create(path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer); // closes the file before it is renamed
+        atomic_output.commit()?;
+        info!(
+            ?path,
+            input_offset = self.input_offset,
+            accounts = self.accounts.len(),
+            "Wrote checkpoint"
+        );
+        Ok(())
+    }
+
+    /// Reads a snapshot written by `write`, failing on the ones of another version
+    pub fn read(path: &Path) -> io::Result<Snapshot> {
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checkpoint version {} is not supported (expected {SNAPSHOT_VERSION})",
+                    snapshot.version
+                ),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,1042 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs, io,
+    num::{NonZeroU64, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use crate::{
+    alerts::Threshold,
+    cardinality::CardinalityLimits,
+    csv_input::{CsvOptions, FollowConfig},
+    encoding::InputEncoding,
+    filter::{ClientFilter, TRANSACTION_TYPES},
+    input_registry::DuplicatePolicy,
+    invariants::InvariantMode,
+    ledger_export::LedgerFormat,
+    model::{ClientId, ErasurePolicy, RoundingPolicy, TransactionId},
+    normalize::CanonicalFormat,
+    notify::BufferConfig,
+    output::{LineEnding, OutputFormat, OutputOptions, SerializationFailurePolicy},
+    partition::{PartitionPlan, PartitionScheme, Partitioner},
+    query::TransactionQuery,
+    simulation::SimulationConfig,
+    trust::SourceTrust,
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ArgsError {
+    #[error("No input file was provided")]
+    MissingInput,
+
+    #[error("Missing value for option: {0}")]
+    MissingValue(String),
+
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+
+    #[error("Invalid value for option {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+pub const USAGE: &str = "usage: tx_engine <input.csv> [options]
+       tx_engine simulate [simulate options]
+       tx_engine query <input.csv> [query options]
+       tx_engine normalize <input> [normalize options]
+       tx_engine plan <input.csv> [plan options]
+       tx_engine verify <output>         check an output file against its trailer (checksum, schema, row count)
+       tx_engine run <manifest.json>     process the inputs of a run manifest (inputs, outputs, options, expected counts)
+                                         and write its completion manifest (status, stats, artifact hashes)
+       <input.csv> may be an http(s):// URL (`remote` feature) or an s3://<bucket>/<key> one (`s3` feature),
+                                         streamed while it is downloaded
+options:
+  --input-format <format>            csv, json (one object per line), iso20022, fix (`fix` feature),
+                                     parquet (`parquet` feature), msgpack (`msgpack` feature) or kafka (`kafka` feature, input is <brokers>/<topic>) (default: csv)
+  --kafka-group <id>                 (feature `kafka`) consumer group (default: tx_engine)
+  --kafka-payload json|csv           (feature `kafka`) json objects or headerless csv rows (default: json)
+  --kafka-exit-at-end                (feature `kafka`) stop at the end of the topic instead of waiting for new messages
+  --delimiter <char>                 field delimiter of the csv input, e.g. ';' or tab (default: ,)
+  --quote <char>                     quote character of the csv input (default: \")
+  --flexible                         allow csv rows with a varying number of fields
+  --no-headers                       the csv input has no header row, columns are type, client, tx, amount
+  --input-encoding <encoding>        utf-8, utf-16le, utf-16be or latin1: encoding of the csv input without a byte order
+                                     mark (default: utf-8, UTF-8 and UTF-16 byte order marks are detected)
+  --columns <mapping>                where the csv columns are, by header name or 0 based position,
+                                     e.g. type=kind,client=customer,tx=tx_id,amount=value or tx=0,type=1,client=2,amount=3
+  --fast-csv                         parse the csv rows from their raw bytes instead of with serde, same transactions,
+                                     about twice the throughput on large inputs (ignored with --columns)
+  --mmap                             (feature `mmap`) read the csv input through a memory map instead of read calls
+  --io-uring                         (feature `uring`, Linux) read the csv input through io_uring, the next part of the
+                                     file is read while the previous one is parsed
+  --estimate-cardinality             pre-pass over the csv input estimating the distinct clients and tx ids to pre-size the maps
+  --max-clients <n>                  warn when the estimated distinct clients exceed <n> (implies --estimate-cardinality)
+  --max-transactions <n>             warn when the estimated distinct tx ids exceed <n> (implies --estimate-cardinality)
+  --expected-clients <n>             pre-size the accounts for <n> clients, instead of the estimate when there is one
+  --expected-transactions <n>        pre-size the disputable deposits for <n> transactions, like --expected-clients
+  --follow                           keep reading the csv input as lines are appended to it (like tail -f)
+  --follow-idle-timeout <seconds>    stop following after <seconds> without new lines (default: never)
+  --merge-input <file>               also process this csv input, after the first one, in its own tx id space (repeatable)
+  --tx-id-map <file>                 write the (source, source_tx, engine_tx) tx ids allocated to the merged inputs as csv
+  --trust trusted|standard|strict    validation profile of the inputs: trusted only parses, standard also rejects duplicate
+                                     tx ids, strict also zero or over-precise amounts and references to txs the input never
+                                     deposited (default: trusted)
+  --source-trust <file>=<level>      trust level of one input (the file as given, e.g. a --merge-input), overriding --trust (repeatable)
+  --quarantine <file>                copy the csv rows that fail conversion, as read, to <file> for resubmission
+  --tenant-output-dir <dir>          multi-tenant input, one output file per tenant in <dir>
+  --output <file>                    write the accounts to <file> instead of stdout, through a temporary file renamed
+                                     over it once complete so that a failed run never leaves a partial output
+  --extra-output <format>:<file>     also write the accounts to <file> in csv, json, ndjson or parquet, e.g. json:audit.json
+  --output-trailer                   write <file>.trailer.json (format, columns, row count, sha256) next to --output and
+                                     every --extra-output once committed, for `tx_engine verify`
+                                     (repeatable, same dialect and atomic write as --output)
+  --partition-dir <dir>              write the accounts into <dir>/accounts_<n>.csv partitions plus a manifest.json instead of stdout, one writer thread per partition
+  --parts-dir <dir>                  write the accounts into <dir>/part-<n>.csv plus a manifest.json, one part per --threads
+                                     worker shard (client id modulo <n>) written concurrently, without a router thread
+                                     (replaces --partition-dir, --output and --extra-output)
+  --partitions <n>                   number of partitions (default: 4)
+  --partition-by range|hash          contiguous client id ranges or client id modulo n (default: range)
+  --auto-tune                        pick the number of partitions, the partition writer queues and the csv read buffer
+                                     from the cores, input size and available memory (replaces --partitions), logged at info
+  --on-write-error <policy>          skip, retry, retry:<n>, placeholder (client id with empty balances) or abort
+                                     (nonzero exit) when an account fails to serialize (default: skip)
+  --output-format <format>           csv, json (an array of account objects), ndjson (one account object per line),
+                                     table (aligned columns for humans, alias --format) or parquet (`parquet` feature,
+                                     amounts as DECIMAL(38, 4)) (default: csv)
+  --output-delimiter <char>          field delimiter of the account output, e.g. ';' or tab (default: ,)
+  --output-quote <char>              quote character of the account output (default: \")
+  --output-quoting <style>           necessary, always, non-numeric or never: which output fields are quoted (default: necessary)
+  --output-line-ending <ending>      lf, crlf or a single character ending the output rows (default: lf)
+  --output-batch <n>                 send the accounts to the writer threads <n> at a time instead of one by one, an account
+                                     locked early is written once its batch is full (default: unbatched)
+  --delta-output <file>              write the accounts that changed (created, updated or erased) every --delta-every
+                                     transactions as csv, for caches applying deltas instead of full dumps
+  --delta-every <n>                  transactions between two emissions of the delta output (default: 1000)
+  --stats-output <file|->             write the processing statistics (transactions by type, invalid and rejected counts,
+                                     locked accounts, deposit and withdrawal volumes, elapsed time) as json, - for stderr
+  --checkpoint <file>                write the state of the accounts and disputable deposits with the number of input
+                                     records consumed to <file> every --checkpoint-every transactions, for --resume
+  --checkpoint-every <n>             transactions between two checkpoints (default: 1000000)
+  --resume <file>                    start from the checkpoint <file> of an interrupted run of the same input instead
+                                     of its first row: the records it consumed are skipped
+  --metrics-every <seconds>          print the throughput metrics (transactions/s, transactions by type, output queue
+                                     depth) to stderr every <seconds> during the run, and once more at the end
+  --audit-journal <file>             append every applied transaction with its effect (balances before and after, held
+                                     delta) to the csv journal <file>, kept across runs
+  --update-feed <file>               write the account (client, available, held, total, locked, tx) after every transaction
+                                     that changed it as NDJSON, one line at a time, to follow the balances during the run
+  --exposure-report <file>           write the open disputes (client, tx, amount) as csv
+  --alert-min-available <amount>     alert when a client's available funds drop below <amount>
+  --alert-max-total <amount>         alert when a client's total funds rise above <amount>
+  --alert-thresholds <file>          per-client thresholds csv (client, min_available, max_total)
+  --alerts-output <file>             write the alerts as csv (they are always logged)
+  --alerts-buffer <n>                alerts pending delivery to --alerts-output kept in memory (default: 1024)
+  --alerts-overflow <policy>         when the alerts buffer is full: block, drop-oldest or spill:<file> (default: block)
+  --segments <file>                  client segments csv (client, segment)
+  --segment-report <file>            write per-segment aggregates (clients, locked, available, held, total) as csv
+  --cost-report <file>               write the processing done per client (transactions applied and ignored, disputes
+                                     handled, weighted cost) with its segment as csv, for internal cost chargeback
+  --dispute-cases <file>             write the open disputes as cases for the case-management system
+  --opening-balances <file>          seed the accounts from a csv (client, available, held, locked) before the transactions
+  --case-decisions <file>            apply resolve/chargeback decisions (case_id, client, tx, outcome, decided_by) after loading
+  --preview-decisions <file>         preview hypothetical decisions (same columns) on the final state without applying them,
+                                     the clients whose balances would change are logged at info
+  --preview-report <file>            write the preview as csv (client, current and projected balances)
+  --activity-report <file>           write per-client activity statements (balances, deposits, disputes...) as csv
+  --ledger-export <file>             write the applied transactions as double-entry postings
+  --ledger-format beancount|ledger   format of --ledger-export (default: beancount)
+  --statements-dir <dir>             write one QIF statement per client into <dir>
+  --remap-clients <file>             rewrite client ids before processing, csv (from, to)
+  --minor-units <decimals>           amounts are integers in minor units, e.g. 2 for cents (fractional amounts are rejected)
+  --amount-scale <factor>            multiply deposit/withdrawal amounts before processing, e.g. 0.01 for cents
+  --disable-types <type,type,...>    skip every transaction of these types, e.g. dispute,resolve,chargeback
+  --rules <file>                     rejection rules, one per line, e.g. reject when type == \"withdrawal\" && amount > 10000
+                                     (fields: type, client, tx, amount, client.segment from --segments)
+  --plan-partition <plan>:<index>    only process the clients of partition <index> of a `tx_engine plan` output
+  --allow-clients <id,id,...|@file>  only process these clients (ids in a file with @path)
+  --block-clients <id,id,...|@file>  skip these clients
+  --sample <percent>%                only process a deterministic subset of the clients (with their full history)
+  --sample-seed <n>                  seed of the sampled subset (default: 0)
+  --output-scale <0-4>               decimal places of the balances in the account output (default: 4)
+  --output-rounding <strategy>       bankers, half-up, half-down, down or up (default: bankers)
+  --threads <n>                      apply the transactions on <n> worker threads, each owning the accounts of the client
+                                     ids equal to it modulo <n>, while the main thread reads the input (default: 1)
+  --sparse-accounts                  only create the account of a client once a transaction changed it: clients that only
+                                     sent ignored transactions (e.g. disputes of unknown txs) are left out of the output
+  --settle-horizon <n>               deposits can only be disputed within <n> transactions: older undisputed deposits are
+                                     dropped from memory, which then stays bounded on inputs of any length
+  --erase-clients <id,id,...|@file>  anonymize these clients after loading (dropped from every output)
+  --erasure-policy zero|transfer-out what happens to the funds of erased clients (default: zero)
+  --merge-clients <from:into,...|@file>
+                                     merge the account of each <from> client into <into> after loading (summed balances,
+                                     open disputes reassigned), before the erasures
+  --check-conservation warn|strict   check at the end of the run that opening balances + deposits - withdrawals - chargebacks
+                                     equals the accounts total, strict fails the run on a discrepancy
+  --check-balance-floor warn|strict  report the clients whose available funds went below --balance-floor during the run
+                                     with the transactions that took them there, strict fails the run if there is any
+  --balance-floor <amount>           floor of the available funds (default: 0)
+  --balance-floor-report <file>      write the clients that went below the floor as csv (client, lowest_available,
+                                     available, txs) (implies --check-balance-floor warn)
+  --processed-registry <file>        registry of already processed inputs (by content hash) shared across runs
+  --on-duplicate refuse|skip         an input already in the registry fails the run or is skipped (default: refuse)
+  --run-metadata                     write <output>.meta.json (version, input hashes, config hash, run id) next to every output file
+  --reproducible                     byte-identical outputs for identical inputs and options: accounts written by client id,
+                                     configured partitions and buffers (--auto-tune is ignored), alerts never dropped, and
+                                     --run-metadata without start time, its run id derived from the config and input hashes
+  --inject-faults <spec>             (feature `fault-injection`) e.g. io_error=0.01,delay=0.1,delay_ms=5,panic=0.001,truncate_at=4096,seed=42
+simulate options (synthetic load applied in memory, prints throughput and latency percentiles):
+  --transactions <n>                 number of generated transactions (default: 1000000)
+  --clients <n>                      number of distinct clients, 1-65535 (default: 65535)
+  --zipf <s>                         client skew exponent, 0 is uniform (default: 1.0)
+  --mix <spec>                       e.g. deposit=0.5,withdrawal=0.2,dispute=0.1,resolve=0.15,chargeback=0.05
+  --rate <tx/s>                      target throughput (default: as fast as possible)
+  --seed <n>                         seed of the generator (default: 0)
+query options (prints the matching transactions as csv, nothing is applied):
+  --client <id,id,...|@file>         only these clients
+  --type <type,type,...>             only these transaction types, e.g. dispute,chargeback
+  --tx <id>                          only this transaction id
+  --delimiter <char>, --no-headers   csv dialect of the input, as above
+plan options (prints client id ranges with balanced transaction counts as json, for multi-machine processing):
+  --partitions <n>                   number of partitions, fewer when the input has fewer clients (default: 4)
+  --delimiter <char>, --no-headers   csv dialect of the input, as above
+  --output <file>                    write the plan to <file> instead of stdout
+normalize options (writes the valid transactions in the canonical format, nothing is applied):
+  --input-format <format>            any input format but kafka, as above (default: csv)
+  --delimiter, --quote, --flexible, --no-headers, --input-encoding
+                                     csv dialect of the input, as above
+  --to csv|msgpack                   canonical csv with a header, or msgpack (`msgpack` feature) (default: csv)
+  --output <file>                    write to <file>, replaced atomically, instead of stdout";
+
+/// What the application was asked to do
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Process(Box<Args>),         // apply an input file and write the accounts
+    Simulate(SimulationConfig), // apply a synthetic stream and report the performance
+    Query(QueryConfig),         // print the transactions of an input matching filters
+    Normalize(NormalizeConfig), // validate an input and write it in the canonical format
+    Verify(PathBuf),            // check an output file against its trailer before loading it
+    Plan(PlanConfig),           // propose balanced client id partitions of an input
+    Run(PathBuf),               // process as described by a run manifest
+}
+
+/// Arguments of `tx_engine plan <input.csv> [plan options]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanConfig {
+    pub input: PathBuf,
+    pub csv_options: CsvOptions,
+    pub partitions: u16,
+    pub output: Option<PathBuf>, // default: stdout
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self {
+        PlanConfig {
+            input: PathBuf::new(),
+            csv_options: CsvOptions::default(),
+            partitions: Partitioner::default().partitions,
+            output: None,
+        }
+    }
+}
+
+/// Arguments of `tx_engine normalize <input> [normalize options]`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NormalizeConfig {
+    pub input: PathBuf,
+    pub input_format: InputFormat,
+    pub csv_options: CsvOptions,
+    pub format: CanonicalFormat,
+    pub output: Option<PathBuf>, // default: stdout
+}
+
+/// Arguments of `tx_engine query <input.csv> [query options]`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryConfig {
+    pub input: PathBuf,
+    pub csv_options: CsvOptions,
+    pub query: TransactionQuery,
+}
+
+impl Command {
+    /// Parses the arguments (without the program name), `simulate` as first argument selects the load simulation
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, ArgsError> {
+        let mut args = args.into_iter().peekable();
+        if args.peek().is_some_and(|arg| arg == "simulate") {
+            args.next();
+            return parse_simulation(args).map(Command::Simulate);
+        }
+        if args.peek().is_some_and(|arg| arg == "query") {
+            args.next();
+            return parse_query(args).map(Command::Query);
+        }
+        if args.peek().is_some_and(|arg| arg == "verify") {
+            args.next();
+            let output = args.next().ok_or(ArgsError::MissingInput)?;
+            if let Some(extra) = args.next() {
+                return Err(ArgsError::UnknownOption(extra));
+            }
+            return Ok(Command::Verify(output.into()));
+        }
+        if args.peek().is_some_and(|arg| arg == "run") {
+            args.next();
+            let manifest = args.next().ok_or(ArgsError::MissingInput)?;
+            if let Some(extra) = args.next() {
+                return Err(ArgsError::UnknownOption(extra));
+            }
+            return Ok(Command::Run(manifest.into()));
+        }
+        if args.peek().is_some_and(|arg| arg == "plan") {
+            args.next();
+            return parse_plan(args).map(Command::Plan);
+        }
+        if args.peek().is_some_and(|arg| arg == "normalize") {
+            args.next();
+            return parse_normalize(args).map(Command::Normalize);
+        }
+        Args::parse(args).map(|args| Command::Process(Box::new(args)))
+    }
+}
+
+/// Supported input file formats
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    Json,     // newline-delimited json objects with the csv columns
+    Iso20022, // camt.053 statements or pain.001 payment initiations
+    #[cfg(feature = "fix")]
+    Fix, // FIX drop-copy execution reports
+    #[cfg(feature = "parquet")]
+    Parquet, // archives with the csv columns
+    #[cfg(feature = "msgpack")]
+    Msgpack, // stream of MessagePack records with the csv columns
+    #[cfg(feature = "kafka")]
+    Kafka, // topic consumer, the input is <brokers>/<topic>
+}
+
+/// Command line arguments: `tx_engine <input.csv> [options]`
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub input: PathBuf,
+    pub input_format: InputFormat,
+    pub csv_options: CsvOptions,    // csv dialect of the input
+    pub estimate_cardinality: bool, // pre-pass over the input before loading it
+    pub cardinality_limits: CardinalityLimits,
+    pub expected_clients: Option<usize>, // capacity hints, known workloads do not need the pre-pass
+    pub expected_transactions: Option<usize>,
+    pub follow: Option<FollowConfig>,       // growing input file
+    pub merge_inputs: Vec<PathBuf>,         // more csv sources, their tx ids are remapped
+    pub tx_id_map: Option<PathBuf>,         // tx id allocations of the merged sources
+    pub quarantine: Option<PathBuf>,        // failed csv rows, in the input format
+    pub tenant_output_dir: Option<PathBuf>, // multi-tenant mode, one output file per tenant
+    pub output: Option<PathBuf>,            // account output file instead of stdout
+    pub extra_outputs: Vec<(OutputFormat, PathBuf)>, // more account outputs, fed the same accounts
+    pub output_trailer: bool,               // handoff contract next to the output files
+    pub partition_dir: Option<PathBuf>,     // sharded output instead of stdout
+    pub parts_dir: Option<PathBuf>, // one output part per worker shard, written concurrently
+    pub partitioner: Partitioner,
+    pub auto_tune: bool, // knobs derived from the machine and the input
+    pub write_failure_policy: SerializationFailurePolicy,
+    pub output_options: OutputOptions, // format and csv dialect of the account output
+    pub delta_output: Option<PathBuf>, // changed accounts stream
+    pub delta_every: Option<u64>,      // default: DEFAULT_DELTA_EVERY
+    pub update_feed: Option<PathBuf>,  // every account change, as it happens
+    pub audit_journal: Option<PathBuf>, // append-only record of the applied transactions
+    pub stats_output: Option<PathBuf>, // run summary, "-" for stderr
+    pub metrics_every: Option<Duration>, // period of the metrics printed to stderr
+    pub checkpoint: Option<PathBuf>,   // state written while loading, to resume from
+    pub checkpoint_every: Option<NonZeroU64>, // default: DEFAULT_CHECKPOINT_EVERY
+    pub resume: Option<PathBuf>,       // checkpoint of an interrupted run
+    pub exposure_report: Option<PathBuf>, // csv with the open disputes that make up each held amount
+    pub alert_threshold: Threshold,       // global balance thresholds
+    pub alert_thresholds_file: Option<PathBuf>, // per-client balance thresholds
+    pub alerts_output: Option<PathBuf>,
+    pub alerts_buffer: BufferConfig, // alerts are delivered on their own thread
+    pub segments: Option<PathBuf>,   // client to segment sidecar file
+    pub segment_report: Option<PathBuf>,
+    pub cost_report: Option<PathBuf>, // per client processing cost, charged to its segment
+    pub dispute_cases: Option<PathBuf>, // export of the open disputes for case management
+    pub case_decisions: Option<PathBuf>, // externally decided outcomes to import
+    pub opening_balances: Option<PathBuf>, // accounts migrated from another ledger
+    pub preview_decisions: Option<PathBuf>, // hypothetical outcomes, not applied
+    pub preview_report: Option<PathBuf>,
+    pub activity_report: Option<PathBuf>, // per client activity statements
+    pub ledger_export: Option<PathBuf>,   // double-entry postings of the applied transactions
+    pub ledger_format: LedgerFormat,
+    pub statements_dir: Option<PathBuf>, // per client QIF statements
+    pub remap_clients: Option<PathBuf>, // external to engine client ids, applied before the filters
+    pub minor_units: Option<u32>,       // decimals of the integer amounts of the input
+    pub amount_scale: Option<Decimal>,
+    pub disabled_types: BTreeSet<&'static str>, // transaction types skipped for this run
+    pub rules: Option<PathBuf>,                 // user-defined rejection rules
+    pub client_filters: Vec<ClientFilter>, // allowlist, blocklist or sample applied at ingestion
+    pub sparse_accounts: bool,             // no empty placeholder accounts
+    pub settle_horizon: Option<NonZeroU64>, // transactions after which a deposit can no longer be disputed
+    pub threads: Option<NonZeroUsize>,      // worker threads applying the transactions, by client
+    pub output_batch: Option<NonZeroUsize>, // accounts per message to the writer threads
+    pub trust: SourceTrust,                 // validation profile of each input
+    pub rounding: RoundingPolicy,           // of the account output balances
+    pub erase_clients: Vec<ClientId>,       // GDPR erasure requests
+    pub erasure_policy: ErasurePolicy,
+    pub merge_clients: Vec<(ClientId, ClientId)>, // (from, into) account consolidations
+    pub conservation_check: Option<InvariantMode>, // end of run funds conservation check
+    pub balance_floor_check: Option<InvariantMode>, // available funds below the floor
+    pub balance_floor: Decimal,
+    pub balance_floor_report: Option<PathBuf>,
+    pub processed_registry: Option<PathBuf>, // guards against processing the same file twice
+    pub duplicate_policy: DuplicatePolicy,
+    pub run_metadata: bool, // provenance sidecars next to the output files
+    pub reproducible: bool, // deterministic outputs and metadata
+    #[cfg(feature = "fault-injection")]
+    pub inject_faults: Option<crate::fault_injection::FaultConfig>, // chaos testing of the input and output
+    #[cfg(feature = "kafka")]
+    pub kafka_group: Option<String>, // consumer group, default: tx_engine
+    #[cfg(feature = "kafka")]
+    pub kafka_payload: crate::kafka_input::PayloadFormat,
+    #[cfg(feature = "kafka")]
+    pub kafka_exit_at_end: bool, // batch replay of a topic instead of a long-lived consumer
+}
+
+impl Args {
+    /// Parses the arguments (without the program name)
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, ArgsError> {
+        let mut args = args.into_iter();
+        let mut parsed = Args::default();
+        let mut input = None;
+        let mut sample_seed = 0;
+        while let Some(arg) = args.next() {
+            let mut value = |option: &str| {
+                args.next()
+                    .ok_or(ArgsError::MissingValue(option.to_string()))
+            };
+            match arg.as_str() {
+                "--input-format" => parsed.input_format = input_format(&arg, value(&arg)?)?,
+                "--delimiter" => parsed.csv_options.delimiter = csv_char(&arg, value(&arg)?)?,
+                "--quote" => parsed.csv_options.quote = csv_char(&arg, value(&arg)?)?,
+                "--flexible" => parsed.csv_options.flexible = true,
+                "--no-headers" => parsed.csv_options.has_headers = false,
+                "--fast-csv" => parsed.csv_options.byte_records = true,
+                #[cfg(feature = "mmap")]
+                "--mmap" => parsed.csv_options.memory_map = true,
+                #[cfg(all(feature = "uring", target_os = "linux"))]
+                "--io-uring" => parsed.csv_options.io_uring = true,
+                "--input-encoding" => {
+                    parsed.csv_options.encoding = Some(input_encoding(&arg, value(&arg)?)?)
+                }
+                "--columns" => {
+                    let mapping = value(&arg)?;
+                    parsed.csv_options.column_mapping = Some(
+                        mapping
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, mapping))?,
+                    )
+                }
+                "--estimate-cardinality" => parsed.estimate_cardinality = true,
+                "--max-clients" => {
+                    let max = value(&arg)?;
+                    parsed.cardinality_limits.max_clients =
+                        Some(max.parse().map_err(|_| ArgsError::InvalidValue(arg, max))?);
+                    parsed.estimate_cardinality = true;
+                }
+                "--max-transactions" => {
+                    let max = value(&arg)?;
+                    parsed.cardinality_limits.max_transactions =
+                        Some(max.parse().map_err(|_| ArgsError::InvalidValue(arg, max))?);
+                    parsed.estimate_cardinality = true;
+                }
+                "--expected-clients" => {
+                    let clients = value(&arg)?;
+                    parsed.expected_clients = Some(
+                        clients
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, clients))?,
+                    );
+                }
+                "--expected-transactions" => {
+                    let transactions = value(&arg)?;
+                    parsed.expected_transactions = Some(
+                        transactions
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, transactions))?,
+                    );
+                }
+                "--follow" => {
+                    parsed.follow.get_or_insert_default();
+                }
+                "--follow-idle-timeout" => {
+                    let seconds = value(&arg)?;
+                    let timeout = seconds
+                        .parse()
+                        .ok()
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                        .ok_or(ArgsError::InvalidValue(arg, seconds))?;
+                    parsed.follow.get_or_insert_default().idle_timeout = Some(timeout);
+                }
+                "--merge-input" => parsed.merge_inputs.push(value(&arg)?.into()),
+                "--tx-id-map" => parsed.tx_id_map = Some(value(&arg)?.into()),
+                "--trust" => {
+                    let level = value(&arg)?;
+                    parsed.trust.default = level
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, level))?
+                }
+                "--source-trust" => {
+                    let source = value(&arg)?;
+                    let Some((path, Ok(level))) = source
+                        .rsplit_once('=')
+                        .map(|(path, level)| (path, level.parse()))
+                    else {
+                        return Err(ArgsError::InvalidValue(arg, source));
+                    };
+                    parsed.trust.sources.insert(path.into(), level);
+                }
+                "--quarantine" => parsed.quarantine = Some(value(&arg)?.into()),
+                "--tenant-output-dir" => parsed.tenant_output_dir = Some(value(&arg)?.into()),
+                "--output" => parsed.output = Some(value(&arg)?.into()),
+                "--output-trailer" => parsed.output_trailer = true,
+                "--extra-output" => {
+                    let output = value(&arg)?;
+                    let Some((Ok(format), path)) = output
+                        .split_once(':')
+                        .map(|(format, path)| (format.parse(), path))
+                    else {
+                        return Err(ArgsError::InvalidValue(arg, output));
+                    };
+                    parsed.extra_outputs.push((format, path.into()));
+                }
+                "--partition-dir" => parsed.partition_dir = Some(value(&arg)?.into()),
+                "--parts-dir" => parsed.parts_dir = Some(value(&arg)?.into()),
+                "--partitions" => {
+                    let partitions = value(&arg)?;
+                    parsed.partitioner.partitions = partitions
+                        .parse()
+                        .ok()
+                        .filter(|partitions| *partitions > 0)
+                        .ok_or(ArgsError::InvalidValue(arg, partitions))?
+                }
+                "--auto-tune" => parsed.auto_tune = true,
+                "--partition-by" => {
+                    parsed.partitioner.scheme = match value(&arg)?.as_str() {
+                        "range" => PartitionScheme::Range,
+                        "hash" => PartitionScheme::Hash,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    }
+                }
+                "--output-format" | "--format" => {
+                    let format = value(&arg)?;
+                    parsed.output_options.format = format
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, format))?
+                }
+                "--output-delimiter" => {
+                    parsed.output_options.dialect.delimiter = csv_char(&arg, value(&arg)?)?
+                }
+                "--output-quote" => {
+                    parsed.output_options.dialect.quote = csv_char(&arg, value(&arg)?)?
+                }
+                "--output-quoting" => {
+                    let quoting = value(&arg)?;
+                    parsed.output_options.dialect.quoting = quoting
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, quoting))?
+                }
+                "--output-line-ending" => {
+                    parsed.output_options.dialect.line_ending = match value(&arg)?.as_str() {
+                        "lf" => LineEnding::Lf,
+                        "crlf" => LineEnding::Crlf,
+                        other => LineEnding::Custom(csv_char(&arg, other.to_string())?),
+                    }
+                }
+                "--delta-output" => parsed.delta_output = Some(value(&arg)?.into()),
+                "--update-feed" => parsed.update_feed = Some(value(&arg)?.into()),
+                "--audit-journal" => parsed.audit_journal = Some(value(&arg)?.into()),
+                "--stats-output" => parsed.stats_output = Some(value(&arg)?.into()),
+                "--checkpoint" => parsed.checkpoint = Some(value(&arg)?.into()),
+                "--checkpoint-every" => {
+                    let every = value(&arg)?;
+                    parsed.checkpoint_every = Some(
+                        every
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, every))?,
+                    )
+                }
+                "--resume" => parsed.resume = Some(value(&arg)?.into()),
+                "--metrics-every" => {
+                    let seconds = value(&arg)?;
+                    parsed.metrics_every = Some(Duration::from_secs(
+                        seconds
+                            .parse()
+                            .ok()
+                            .filter(|seconds| *seconds > 0)
+                            .ok_or(ArgsError::InvalidValue(arg, seconds))?,
+                    ))
+                }
+                "--delta-every" => {
+                    let every = value(&arg)?;
+                    parsed.delta_every = Some(
+                        every
+                            .parse()
+                            .ok()
+                            .filter(|every| *every > 0)
+                            .ok_or(ArgsError::InvalidValue(arg, every))?,
+                    )
+                }
+                "--exposure-report" => parsed.exposure_report = Some(value(&arg)?.into()),
+                "--alert-min-available" => {
+                    let amount = value(&arg)?;
+                    parsed.alert_threshold.min_available = Some(
+                        Decimal::from_str_exact(&amount)
+                            .map_err(|_| ArgsError::InvalidValue(arg, amount))?,
+                    )
+                }
+                "--alert-max-total" => {
+                    let amount = value(&arg)?;
+                    parsed.alert_threshold.max_total = Some(
+                        Decimal::from_str_exact(&amount)
+                            .map_err(|_| ArgsError::InvalidValue(arg, amount))?,
+                    )
+                }
+                "--alert-thresholds" => parsed.alert_thresholds_file = Some(value(&arg)?.into()),
+                "--alerts-output" => parsed.alerts_output = Some(value(&arg)?.into()),
+                "--alerts-buffer" => {
+                    let capacity = value(&arg)?;
+                    parsed.alerts_buffer.capacity = capacity
+                        .parse()
+                        .ok()
+                        .filter(|capacity| *capacity > 0)
+                        .ok_or(ArgsError::InvalidValue(arg, capacity))?
+                }
+                "--alerts-overflow" => {
+                    let policy = value(&arg)?;
+                    parsed.alerts_buffer.overflow = policy
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, policy))?
+                }
+                "--segments" => parsed.segments = Some(value(&arg)?.into()),
+                "--segment-report" => parsed.segment_report = Some(value(&arg)?.into()),
+                "--cost-report" => parsed.cost_report = Some(value(&arg)?.into()),
+                "--dispute-cases" => parsed.dispute_cases = Some(value(&arg)?.into()),
+                "--case-decisions" => parsed.case_decisions = Some(value(&arg)?.into()),
+                "--opening-balances" => parsed.opening_balances = Some(value(&arg)?.into()),
+                "--preview-decisions" => parsed.preview_decisions = Some(value(&arg)?.into()),
+                "--preview-report" => parsed.preview_report = Some(value(&arg)?.into()),
+                "--activity-report" => parsed.activity_report = Some(value(&arg)?.into()),
+                #[cfg(feature = "fault-injection")]
+                "--inject-faults" => {
+                    let spec = value(&arg)?;
+                    parsed.inject_faults = Some(
+                        spec.parse()
+                            .map_err(|err| ArgsError::InvalidValue(arg, err))?,
+                    )
+                }
+                #[cfg(feature = "kafka")]
+                "--kafka-group" => parsed.kafka_group = Some(value(&arg)?),
+                #[cfg(feature = "kafka")]
+                "--kafka-payload" => {
+                    parsed.kafka_payload = match value(&arg)?.as_str() {
+                        "json" => crate::kafka_input::PayloadFormat::Json,
+                        "csv" => crate::kafka_input::PayloadFormat::Csv,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    }
+                }
+                #[cfg(feature = "kafka")]
+                "--kafka-exit-at-end" => parsed.kafka_exit_at_end = true,
+                "--statements-dir" => parsed.statements_dir = Some(value(&arg)?.into()),
+                "--sample" => {
+                    let percent = value(&arg)?;
+                    let parsed_percent = percent
+                        .trim_end_matches('%')
+                        .parse()
+                        .ok()
+                        .filter(|percent| (0.0..=100.0).contains(percent))
+                        .ok_or(ArgsError::InvalidValue(arg, percent))?;
+                    parsed.client_filters.push(ClientFilter::Sample {
+                        percent: parsed_percent,
+                        seed: 0,
+                    })
+                }
+                "--sample-seed" => {
+                    let seed = value(&arg)?;
+                    sample_seed = seed
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, seed))?
+                }
+                "--erase-clients" => {
+                    let ids = value(&arg)?;
+                    parsed.erase_clients = client_ids(arg, ids)?
+                }
+                "--merge-clients" => {
+                    let pairs = value(&arg)?;
+                    parsed.merge_clients.extend(client_pairs(arg, pairs)?)
+                }
+                "--remap-clients" => parsed.remap_clients = Some(value(&arg)?.into()),
+                "--minor-units" => {
+                    let decimals = value(&arg)?;
+                    parsed.minor_units = Some(
+                        decimals
+                            .parse()
+                            .ok()
+                            .filter(|decimals| *decimals <= 28) // rust_decimal precision
+                            .ok_or(ArgsError::InvalidValue(arg, decimals))?,
+                    )
+                }
+                "--amount-scale" => {
+                    let factor = value(&arg)?;
+                    parsed.amount_scale = Some(
+                        Decimal::from_str_exact(&factor)
+                            .ok()
+                            .filter(|factor| factor.is_sign_positive() && !factor.is_zero())
+                            .ok_or(ArgsError::InvalidValue(arg, factor))?,
+                    )
+                }
+                "--rules" => parsed.rules = Some(value(&arg)?.into()),
+                "--disable-types" => {
+                    let types = value(&arg)?;
+                    for name in types.split(',').map(str::trim) {
+                        let known = TRANSACTION_TYPES.iter().find(|known| **known == name);
+                        let Some(known) = known else {
+                            return Err(ArgsError::InvalidValue(arg, name.to_string()));
+                        };
+                        parsed.disabled_types.insert(known);
+                    }
+                }
+                "--allow-clients" => {
+                    let ids = value(&arg)?;
+                    let clients = client_ids(arg, ids)?.into_iter().collect();
+                    parsed.client_filters.push(ClientFilter::Allow(clients))
+                }
+                "--plan-partition" => {
+                    let partition = value(&arg)?;
+                    let clients = planned_clients(arg, partition)?;
+                    parsed.client_filters.push(ClientFilter::Allow(clients))
+                }
+                "--block-clients" => {
+                    let ids = value(&arg)?;
+                    let clients = client_ids(arg, ids)?.into_iter().collect();
+                    parsed.client_filters.push(ClientFilter::Block(clients))
+                }
+                "--erasure-policy" => {
+                    parsed.erasure_policy = match value(&arg)?.as_str() {
+                        "zero" => ErasurePolicy::Zero,
+                        "transfer-out" => ErasurePolicy::TransferOut,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    }
+                }
+                "--processed-registry" => parsed.processed_registry = Some(value(&arg)?.into()),
+                "--on-duplicate" => {
+                    parsed.duplicate_policy = match value(&arg)?.as_str() {
+                        "refuse" => DuplicatePolicy::Refuse,
+                        "skip" => DuplicatePolicy::Skip,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    }
+                }
+                "--on-write-error" => {
+                    let policy = value(&arg)?;
+                    parsed.write_failure_policy = policy
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, policy))?
+                }
+                "--check-conservation" => {
+                    parsed.conservation_check = Some(match value(&arg)?.as_str() {
+                        "warn" => InvariantMode::Warn,
+                        "strict" => InvariantMode::Strict,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    })
+                }
+                "--check-balance-floor" => {
+                    parsed.balance_floor_check = Some(match value(&arg)?.as_str() {
+                        "warn" => InvariantMode::Warn,
+                        "strict" => InvariantMode::Strict,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    })
+                }
+                "--balance-floor" => {
+                    let amount = value(&arg)?;
+                    parsed.balance_floor = Decimal::from_str_exact(&amount)
+                        .map_err(|_| ArgsError::InvalidValue(arg, amount))?
+                }
+                "--balance-floor-report" => parsed.balance_floor_report = Some(value(&arg)?.into()),
+                "--run-metadata" => parsed.run_metadata = true,
+                "--reproducible" => parsed.reproducible = true,
+                "--sparse-accounts" => parsed.sparse_accounts = true,
+                "--settle-horizon" => {
+                    let transactions = value(&arg)?;
+                    parsed.settle_horizon = Some(
+                        transactions
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, transactions))?,
+                    )
+                }
+                "--threads" => {
+                    let threads = value(&arg)?;
+                    parsed.threads = Some(
+                        threads
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, threads))?,
+                    )
+                }
+                "--output-batch" => {
+                    let batch = value(&arg)?;
+                    parsed.output_batch = Some(
+                        batch
+                            .parse()
+                            .map_err(|_| ArgsError::InvalidValue(arg, batch))?,
+                    )
+                }
+                "--output-scale" => {
+                    let scale = value(&arg)?;
+                    parsed.rounding.scale = scale
+                        .parse()
+                        .ok()
+                        .filter(|scale| *scale <= 4)
+                        .ok_or(ArgsError::InvalidValue(arg, scale))?
+                }
+                "--output-rounding" => {
+                    let strategy = value(&arg)?;
+                    parsed.rounding.strategy = strategy
+                        .parse()
+                        .map_err(|_| ArgsError::InvalidValue(arg, strategy))?
+                }
+                "--ledger-export" => parsed.ledger_export = Some(value(&arg)?.into()),
+                "--ledger-format" => {
+                    parsed.ledger_format = match value(&arg)?.as_str() {
+                        "beancount" => LedgerFormat::Beancount,
+                        "ledger" => LedgerFormat::LedgerCli,
+                        other => return Err(ArgsError::InvalidValue(arg, other.to_string())),
+                    }
+                }
+                option if option.starts_with("--") => {
+                    return Err(ArgsError::UnknownOption(option.to_string()));
+                }
+                _ if input.is_none() => input = Some(PathBuf::from(arg)),
+                _ => return Err(ArgsError::UnknownOption(arg)),
+            }
+        }
+        parsed.input = input.ok_or(ArgsError::MissingInput)?;
+        for filter in parsed.client_filters.iter_mut() {
+            if let ClientFilter::Sample { seed, .. } = filter {
+                *seed = sample_seed; // the seed option can come before or after --sample
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// A single ascii character, `tab` or `\t` for tab separated files
+fn csv_char(option: &str, value: String) -> Result<u8, ArgsError> {
+    match value.as_str() {
+        "tab" | "\\t" => Ok(b'\t'),
+        single if single.len() == 1 && single.is_ascii() => Ok(single.as_bytes()[0]),
+        _ => Err(ArgsError::InvalidValue(option.to_string(), value)),
+    }
+}
+
+fn input_encoding(option: &str, value: String) -> Result<InputEncoding, ArgsError> {
+    value
+        .parse()
+        .map_err(|_| ArgsError::InvalidValue(option.to_string(), value))
+}
+
+/// Client ids separated by commas or newlines, `@path` reads them from a file
+fn client_ids(option: String, ids: String) -> Result<Vec<ClientId>, ArgsError> {
+    let list = match ids.strip_prefix('@') {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|err| ArgsError::InvalidValue(option.clone(), format!("{path}: {err}")))?,
+        None => ids.clone(),
+    };
+    list.split([',', '\n'])
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map(ClientId))
+        .collect::<Result<_, _>>()
+        .map_err(|_| ArgsError::InvalidValue(option, ids))
+}
+
+// `from:into` pairs, like `client_ids`
+fn client_pairs(option: String, pairs: String) -> Result<Vec<(ClientId, ClientId)>, ArgsError> {
+    let list = match pairs.strip_prefix('@') {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|err| ArgsError::InvalidValue(option.clone(), format!("{path}: {err}")))?,
+        None => pairs.clone(),
+    };
+    list.split([',', '\n'])
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (from, into) = pair.split_once(':')?;
+            Some((
+                ClientId(from.trim().parse().ok()?),
+                ClientId(into.trim().parse().ok()?),
+            ))
+        })
+        .collect::<Option<_>>()
+        .ok_or(ArgsError::InvalidValue(option, pairs))
+}
+
+fn input_format(option: &str, format: String) -> Result<InputFormat, ArgsError> {
+    Ok(match format.as_str() {
+        "csv" => InputFormat::Csv,
+        "json" => InputFormat::Json,
+        "iso20022" => InputFormat::Iso20022,
+        #[cfg(feature = "fix")]
+        "fix" => InputFormat::Fix,
+        #[cfg(feature = "parquet")]
+        "parquet" => InputFormat::Parquet,
+        #[cfg(feature = "msgpack")]
+        "msgpack" => InputFormat::Msgpack,
+        #[cfg(feature = "kafka")]
+        "kafka" => InputFormat::Kafka,
+        _ => return Err(ArgsError::InvalidValue(option.to_string(), format)),
+    })
+}
+
+fn parse_plan<I: Iterator<Item = String>>(mut args: I) -> Result<PlanConfig, ArgsError> {
+    let mut config = PlanConfig::default();
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        let mut value = |arg: &String| args.next().ok_or(ArgsError::MissingValue(arg.clone()));
+        match arg.as_str() {
+            "--partitions" => {
+                let partitions = value(&arg)?;
+                config.partitions = partitions
+                    .parse()
+                    .ok()
+                    .filter(|partitions| *partitions > 0)
+                    .ok_or(ArgsError::InvalidValue(arg, partitions))?
+            }
+            "--delimiter" => config.csv_options.delimiter = csv_char(&arg, value(&arg)?)?,
+            "--no-headers" => config.csv_options.has_headers = false,
+            "--output" => config.output = Some(PathBuf::from(value(&arg)?)),
+            option if option.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(ArgsError::UnknownOption(arg)),
+        }
+    }
+    config.input = input.ok_or(ArgsError::MissingInput)?;
+    Ok(config)
+}
+
+// clients of one partition of a `PartitionPlan` file, `<plan>:<index>`
+fn planned_clients(option: String, value: String) -> Result<HashSet<ClientId>, ArgsError> {
+    let invalid = |reason: String| ArgsError::InvalidValue(option.clone(), reason);
+    let Some((path, index)) = value.rsplit_once(':') else {
+        return Err(invalid(value.clone()));
+    };
+    let index: usize = index.parse().map_err(|_| invalid(value.clone()))?;
+    let plan: PartitionPlan = fs::read(path)
+        .and_then(|plan| serde_json::from_slice(&plan).map_err(io::Error::from))
+        .map_err(|err| invalid(format!("{path}: {err}")))?;
+    plan.clients(index)
+        .ok_or_else(|| invalid(format!("{path} has no partition {index}")))
+}
+
+fn parse_normalize<I: Iterator<Item = String>>(mut args: I) -> Result<NormalizeConfig, ArgsError> {
+    let mut config = NormalizeConfig::default();
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        let mut value = |arg: &String| args.next().ok_or(ArgsError::MissingValue(arg.clone()));
+        match arg.as_str() {
+            "--input-format" => config.input_format = input_format(&arg, value(&arg)?)?,
+            "--delimiter" => config.csv_options.delimiter = csv_char(&arg, value(&arg)?)?,
+            "--quote" => config.csv_options.quote = csv_char(&arg, value(&arg)?)?,
+            "--flexible" => config.csv_options.flexible = true,
+            "--no-headers" => config.csv_options.has_headers = false,
+            "--input-encoding" => {
+                config.csv_options.encoding = Some(input_encoding(&arg, value(&arg)?)?)
+            }
+            "--to" => {
+                let format = value(&arg)?;
+                config.format = format
+                    .parse()
+                    .map_err(|_| ArgsError::InvalidValue(arg, format))?
+            }
+            "--output" => config.output = Some(PathBuf::from(value(&arg)?)),
+            option if option.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(ArgsError::UnknownOption(arg)),
+        }
+    }
+    config.input = input.ok_or(ArgsError::MissingInput)?;
+    #[cfg(feature = "kafka")]
+    if config.input_format == InputFormat::Kafka {
+        return Err(ArgsError::InvalidValue(
+            "--input-format".to_string(),
+            "kafka".to_string(),
+        ));
+    }
+    Ok(config)
+}
+
+fn parse_query<I: Iterator<Item = String>>(mut args: I) -> Result<QueryConfig, ArgsError> {
+    let mut config = QueryConfig::default();
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        let mut value = |arg: &String| args.next().ok_or(ArgsError::MissingValue(arg.clone()));
+        match arg.as_str() {
+            "--client" => config
+                .query
+                .clients
+                .extend(client_ids(arg.clone(), value(&arg)?)?),
+            "--type" => {
+                for name in value(&arg)?.split(',').map(str::trim) {
+                    let known = TRANSACTION_TYPES.iter().find(|known| **known == name);
+                    let Some(known) = known else {
+                        return Err(ArgsError::InvalidValue(arg, name.to_string()));
+                    };
+                    config.query.types.insert(known);
+                }
+            }
+            "--tx" => {
+                let tx = value(&arg)?;
+                config.query.tx = Some(TransactionId(
+                    tx.parse().map_err(|_| ArgsError::InvalidValue(arg, tx))?,
+                ))
+            }
+            "--delimiter" => config.csv_options.delimiter = csv_char(&arg, value(&arg)?)?,
+            "--no-headers" => config.csv_options.has_headers = false,
+            option if option.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(ArgsError::UnknownOption(arg)),
+        }
+    }
+    config.input = input.ok_or(ArgsError::MissingInput)?;
+    Ok(config)
+}
+
+fn parse_simulation<I: Iterator<Item = String>>(
+    mut args: I,
+) -> Result<SimulationConfig, ArgsError> {
+    let mut config = SimulationConfig::default();
+    while let Some(arg) = args.next() {
+        if !matches!(
+            arg.as_str(),
+            "--transactions" | "--clients" | "--zipf" | "--mix" | "--rate" | "--seed"
+        ) {
+            return Err(ArgsError::UnknownOption(arg));
+        }
+        let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+        let invalid = || ArgsError::InvalidValue(arg.clone(), value.clone());
+        match arg.as_str() {
+            "--transactions" => config.transactions = value.parse().map_err(|_| invalid())?,
+            "--clients" => {
+                config.clients = value
+                    .parse()
+                    .ok()
+                    .filter(|clients| *clients > 0)
+                    .ok_or_else(invalid)?
+            }
+            "--zipf" => config.zipf_exponent = value.parse().map_err(|_| invalid())?,
+            "--mix" => {
+                config.mix = value
+                    .parse()
+                    .map_err(|err| ArgsError::InvalidValue(arg.clone(), err))?
+            }
+            "--rate" => config.rate = Some(value.parse().map_err(|_| invalid())?),
+            _ => config.seed = value.parse().map_err(|_| invalid())?, // --seed
+        }
+    }
+    Ok(config)
+}
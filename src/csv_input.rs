@@ -1,10 +1,23 @@
-use csv::Reader;
-use model::{InputCsvRecord, Transaction};
-use std::path::Path;
+use csv::{ByteRecord, Reader, StringRecord, StringRecordsIntoIter, Writer};
+use model::{InputCsvRecord, Transaction, TransactionKind};
+use rust_decimal::Decimal;
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufRead},
+    path::Path,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
 use thiserror::Error;
-use tracing::instrument;
+use tracing::{error, info, instrument, warn};
 
-use crate::model;
+use crate::{
+    cancel::CancellationToken,
+    encoding::{InputEncoding, decode_input},
+    model,
+};
 
 #[derive(Error, Debug)]
 pub enum ConversionError {
@@ -14,7 +27,7 @@ pub enum ConversionError {
     #[error("Invalid transaction type: {0}")]
     InvalidTransactionType(String),
 
-    #[error("CSV parsing error")]
+    #[error("CSV parsing error: {0}")]
     CsvError(#[from] csv::Error),
 
     #[error("Failed to parse decimal amount")]
@@ -23,32 +36,762 @@ pub enum ConversionError {
     #[error("Decimal amount must be positive")]
     NegativeAmount(String),
 
+    #[error("Invalid ISO 20022 message: {0}")]
+    Iso20022(String),
+
+    #[error("Invalid JSON record: {0}")]
+    Json(String),
+
+    #[error("Invalid MessagePack record: {0}")]
+    Msgpack(String),
+
+    #[error("Invalid parquet row: {0}")]
+    Parquet(String),
+
+    #[error("Amount is not an integer number of minor units: {0}")]
+    MinorUnits(String),
+
+    #[error("Kafka error: {0}")]
+    Kafka(String),
+
+    #[error("Invalid FIX message: {0}")]
+    Fix(String),
+
+    #[error("Failed validation: {0}")]
+    Validation(String),
+
+    #[error("Invalid column mapping: {0}")]
+    ColumnMapping(String),
+
     #[error("An unexpected error occurred: {0}")]
     Unexpected(String), // Catch-all if needed
 }
 
+/// Csv dialect of the input, the defaults match the documented input format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8, // e.g. b'\t' for TSV or b';' for exports from European banks
+    pub quote: u8,
+    pub flexible: bool,    // rows may have a varying number of fields
+    pub has_headers: bool, // without headers the columns are type, client, tx, amount in that order
+    pub column_mapping: Option<ColumnMapping>, // where the columns are when they differ from the default
+    pub buffer_capacity: Option<usize>, // read buffer in bytes, the csv crate default (8 KiB) otherwise
+    pub encoding: Option<InputEncoding>, // detected from a byte order mark, UTF-8 without one, when not given
+    pub byte_records: bool, // parse the rows without serde (`transactions_from_byte_reader`)
+    #[cfg(feature = "mmap")]
+    pub memory_map: bool, // read the input files through a memory map (`open_input_mapped`)
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    pub io_uring: bool, // read the input files through io_uring (`uring_input::open_input_uring`)
+}
+
+/// A column of the input, by position (0 based) or by header name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Position(usize),
+    Name(String),
+}
+
+/// Maps the input columns to the transaction fields, e.g. for an upstream system emitting
+/// `tx_id, kind, customer, value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    pub transaction_type: Column,
+    pub client: Column,
+    pub tx: Column,
+    pub amount: Column,
+}
+
+impl FromStr for ColumnMapping {
+    type Err = String;
+
+    /// `type=kind,client=customer,tx=tx_id,amount=value`, a number is a column position
+    /// (e.g. `tx=0,type=1,client=2,amount=3`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns: [Option<Column>; 4] = Default::default();
+        for field in s.split(',') {
+            let (name, column) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected field=column, found {field}"))?;
+            let index = INPUT_COLUMNS
+                .iter()
+                .position(|input_column| *input_column == name.trim())
+                .ok_or_else(|| format!("unknown field {name}"))?;
+            let column = column.trim();
+            columns[index] = Some(match column.parse() {
+                Ok(position) => Column::Position(position),
+                Err(_) => Column::Name(column.to_string()),
+            });
+        }
+        let [Some(transaction_type), Some(client), Some(tx), Some(amount)] = columns else {
+            return Err(format!(
+                "every field must be mapped: {}",
+                INPUT_COLUMNS.join(", ")
+            ));
+        };
+        Ok(ColumnMapping {
+            transaction_type,
+            client,
+            tx,
+            amount,
+        })
+    }
+}
+
+impl ColumnMapping {
+    /// Positions of the type, client, tx and amount columns, names are looked up in the headers
+    pub fn resolve(&self, headers: Option<&StringRecord>) -> Result<[usize; 4], ConversionError> {
+        let resolve = |column: &Column| match column {
+            Column::Position(position) => Ok(*position),
+            Column::Name(name) => headers
+                .and_then(|headers| headers.iter().position(|header| header == name))
+                .ok_or_else(|| ConversionError::ColumnMapping(format!("no column named {name}"))),
+        };
+        Ok([
+            resolve(&self.transaction_type)?,
+            resolve(&self.client)?,
+            resolve(&self.tx)?,
+            resolve(&self.amount)?,
+        ])
+    }
+}
+
+// reorders a row in the `INPUT_COLUMNS` order, missing fields are empty
+fn map_columns(record: &StringRecord, columns: &[usize; 4]) -> StringRecord {
+    columns
+        .iter()
+        .map(|column| record.get(*column).unwrap_or(""))
+        .collect()
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            flexible: false,
+            has_headers: true,
+            column_mapping: None,
+            buffer_capacity: None,
+            encoding: None,
+            byte_records: false,
+            #[cfg(feature = "mmap")]
+            memory_map: false,
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            io_uring: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Opens the input file at `path` in this dialect's encoding, memory-mapped or read through
+    /// io_uring if asked to. An http(s) URL is downloaded (feature `remote`), an `s3://` one is
+    /// streamed from S3 (feature `s3`).
+    pub fn open(&self, path: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+        if let Some((bucket, key)) = input_s3_object(path) {
+            #[cfg(feature = "s3")]
+            return crate::s3_input::open_s3_input(bucket, key, self.encoding);
+            #[cfg(not(feature = "s3"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("s3://{bucket}/{key}: s3 input requires the `s3` feature"),
+            ));
+        }
+        if let Some(url) = input_url(path) {
+            #[cfg(feature = "remote")]
+            return crate::remote_input::open_url(url, self.encoding);
+            #[cfg(not(feature = "remote"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{url}: http(s) input requires the `remote` feature"),
+            ));
+        }
+        #[cfg(feature = "mmap")]
+        if self.memory_map {
+            return open_input_mapped(path, self.encoding);
+        }
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        if self.io_uring {
+            return crate::uring_input::open_input_uring(path, self.encoding);
+        }
+        open_input_with_encoding(path, self.encoding)
+    }
+
+    /// Builder of the readers given to `transactions_from_reader`
+    pub fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .flexible(self.flexible)
+            .has_headers(self.has_headers);
+        if let Some(capacity) = self.buffer_capacity {
+            builder.buffer_capacity(capacity);
+        }
+        builder
+    }
+}
+
 // Loads the csv in path as a Iterator over transactions
 #[instrument]
 pub fn read_transactions_from_csv(
     csv_path: &Path,
 ) -> Result<impl Iterator<Item = Result<Transaction, ConversionError>>, ConversionError> {
-    let csv_reader = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All) //trim whitespace around fields
-        .from_path(csv_path)
-        .map_err(ConversionError::from)?;
+    read_transactions_from_csv_with_options(csv_path, &CsvOptions::default())
+}
+
+// Loads the csv in path, written in the given dialect, as a Iterator over transactions
+#[instrument]
+pub fn read_transactions_from_csv_with_options(
+    csv_path: &Path,
+    options: &CsvOptions,
+) -> Result<Box<dyn Iterator<Item = Result<Transaction, ConversionError>>>, ConversionError> {
+    let csv_reader = options
+        .reader_builder()
+        .from_reader(options.open(csv_path).map_err(csv::Error::from)?);
+    transactions_with_options(csv_reader, options)
+}
+
+// picks the reader of the rows for the options
+fn transactions_with_options<R: io::Read + 'static>(
+    csv_reader: Reader<R>,
+    options: &CsvOptions,
+) -> Result<Box<dyn Iterator<Item = Result<Transaction, ConversionError>>>, ConversionError> {
+    Ok(match &options.column_mapping {
+        Some(mapping) => Box::new(transactions_from_mapped_reader(csv_reader, mapping)?),
+        None if options.byte_records => Box::new(transactions_from_byte_reader(csv_reader)),
+        None => Box::new(transactions_from_reader(csv_reader)),
+    })
+}
+
+/// How long a followed input is watched for new lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowConfig {
+    pub poll_interval: Duration,
+    pub idle_timeout: Option<Duration>, // end of input after this long without new data, never by default
+}
+
+impl Default for FollowConfig {
+    fn default() -> Self {
+        FollowConfig {
+            poll_interval: Duration::from_millis(200),
+            idle_timeout: None,
+        }
+    }
+}
+
+/// Reader over a file that is still being written (like `tail -f`): at the end of the file it waits
+/// for more data instead of returning end of file, so a half written line is completed before parsing.
+#[derive(Debug)]
+pub struct FollowReader<R> {
+    inner: R,
+    config: FollowConfig,
+    cancellation: CancellationToken, // stops waiting for new data
+}
+
+impl<R: io::Read> FollowReader<R> {
+    pub fn new(inner: R, config: FollowConfig) -> FollowReader<R> {
+        FollowReader {
+            inner,
+            config,
+            cancellation: CancellationToken::default(),
+        }
+    }
+
+    /// End of input as soon as the token is cancelled, instead of after the idle timeout
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> FollowReader<R> {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+impl<R: io::Read> io::Read for FollowReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut idle = Duration::ZERO;
+        loop {
+            let read = self.inner.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            if self
+                .config
+                .idle_timeout
+                .is_some_and(|timeout| idle >= timeout)
+            {
+                info!(?idle, "No new data in the followed input, stopping");
+                return Ok(0);
+            }
+            if self.cancellation.is_cancelled() {
+                info!("Cancelled, no longer following the input");
+                return Ok(0);
+            }
+            thread::sleep(self.config.poll_interval);
+            idle += self.config.poll_interval;
+        }
+    }
+}
+
+// Follows the csv in path as it grows, yielding the transactions of the lines appended to it.
+// Rotated or truncated files are not detected, compressed files cannot be followed. The encoding
+// is only detected from a byte order mark already written when the file is opened.
+#[instrument]
+pub fn follow_transactions_from_csv(
+    csv_path: &Path,
+    options: &CsvOptions,
+    follow: FollowConfig,
+    cancellation: &CancellationToken,
+) -> Result<Box<dyn Iterator<Item = Result<Transaction, ConversionError>>>, ConversionError> {
+    let file = decode_input(
+        io::BufReader::new(open_input_file(csv_path).map_err(csv::Error::from)?),
+        options.encoding,
+    )
+    .map_err(csv::Error::from)?;
+    let csv_reader = options
+        .reader_builder()
+        .from_reader(FollowReader::new(file, follow).with_cancellation(cancellation.clone()));
+    transactions_with_options(csv_reader, options)
+}
+
+pub(crate) const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+pub(crate) const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Attempts to open an input that another process still holds open exclusively, waiting twice as
+/// long after each failed attempt
+const OPEN_ATTEMPTS: u32 = 6;
+const OPEN_BACKOFF: Duration = Duration::from_millis(50);
 
-    Ok(transactions_from_reader(csv_reader))
+/// Opens an input file, gzip and zstd compressed files (`.gz`/`.zst`) are detected by their magic
+/// bytes and decompressed on the fly (feature `compression`). The content is read as UTF-8, a byte
+/// order mark, like the one Windows editors put in front of the header, is skipped.
+pub fn open_input(path: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+    open_input_with_encoding(path, None)
 }
 
-// Transforms a reader over a file into a iterator over transactions
+/// Like `open_input`, transcoding the content from `encoding` (`decode_input`)
+pub fn open_input_with_encoding(
+    path: &Path,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    read_input(open_input_file(path)?, encoding)
+}
+
+/// Like `open_input_with_encoding` for an input that is not a file, e.g. a download: compressed
+/// content is detected by its magic bytes and decompressed, then transcoded from `encoding`
+pub fn read_input<R: io::Read + Send + 'static>(
+    input: R,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    let mut file = io::BufReader::new(input);
+    let head = file.fill_buf()?;
+    if head.starts_with(GZIP_MAGIC) {
+        #[cfg(feature = "compression")]
+        return decode_input(
+            io::BufReader::new(flate2::bufread::MultiGzDecoder::new(file)),
+            encoding,
+        );
+        #[cfg(not(feature = "compression"))]
+        return Err(compression_disabled("gzip"));
+    }
+    if head.starts_with(ZSTD_MAGIC) {
+        #[cfg(feature = "compression")]
+        return decode_input(
+            io::BufReader::new(zstd::Decoder::with_buffer(file)?),
+            encoding,
+        );
+        #[cfg(not(feature = "compression"))]
+        return Err(compression_disabled("zstd"));
+    }
+    decode_input(file, encoding)
+}
+
+/// Like `open_input_with_encoding`, reading an uncompressed file through a memory map (feature
+/// `mmap`): the rows are parsed from the pages of the file as the kernel maps them, without a read
+/// system call per buffer, which adds up on inputs of tens of GB. The csv reader still copies the
+/// rows into its record buffer. Compressed files are read as usual. The file must not be
+/// truncated while it is read: accessing the unmapped pages kills the process (SIGBUS).
+#[cfg(feature = "mmap")]
+pub fn open_input_mapped(
+    path: &Path,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    let file = open_input_file(path)?;
+    // SAFETY: the map is only read, the input files are not modified while they are processed
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    if map.starts_with(GZIP_MAGIC) || map.starts_with(ZSTD_MAGIC) {
+        info!(
+            ?path,
+            "Compressed input, decompressing it instead of mapping it"
+        );
+        return open_input_with_encoding(path, encoding);
+    }
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential)?; // read-ahead of the next pages
+    decode_input(io::Cursor::new(map), encoding)
+}
+
+/// Opens a file for reading, retrying with backoff while it is locked by the process writing it
+/// (sharing violation on Windows). Long and UNC paths are opened through their `\\?\` form.
+pub fn open_input_file(path: &Path) -> io::Result<File> {
+    let path = long_path(path);
+    let mut backoff = OPEN_BACKOFF;
+    for attempt in 1.. {
+        match File::open(&path) {
+            Err(err) if attempt < OPEN_ATTEMPTS && is_sharing_violation(&err) => {
+                warn!(%err, attempt, ?backoff, "Input is locked by another process, retrying");
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    unreachable!("the last attempt returns")
+}
+
+/// Windows limits paths to 260 characters (`MAX_PATH`) unless they are given in their extended form:
+/// longer absolute paths are prefixed with `\\?\` (`\\?\UNC\` for network shares). Other paths, and
+/// every path on other platforms, are returned as is.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+        let text = path.as_os_str().to_string_lossy();
+        if path.is_absolute() && text.len() >= MAX_PATH && !text.starts_with(r"\\?\") {
+            // the extended form is not normalized by Windows, separators must be backslashes
+            let text = text.replace('/', r"\");
+            return Cow::Owned(match text.strip_prefix(r"\\") {
+                Some(share) => format!(r"\\?\UNC\{share}").into(),
+                None => format!(r"\\?\{text}").into(),
+            });
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+// ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION, the file is open without read sharing
+fn is_sharing_violation(err: &io::Error) -> bool {
+    cfg!(windows) && matches!(err.raw_os_error(), Some(32 | 33))
+}
+
+/// The URL of an input given as an `http://` or `https://` URL instead of a file path
+pub fn input_url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| path.starts_with("https://") || path.starts_with("http://"))
+}
+
+/// The bucket and key of an input given as an `s3://<bucket>/<key>` URL instead of a file path
+pub fn input_s3_object(path: &Path) -> Option<(&str, &str)> {
+    let (bucket, key) = path.to_str()?.strip_prefix("s3://")?.split_once('/')?;
+    (!bucket.is_empty() && !key.is_empty()).then_some((bucket, key))
+}
+
+#[cfg(not(feature = "compression"))]
+fn compression_disabled(format: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{format} compressed input requires the `compression` feature"),
+    )
+}
+
+/// Columns of a headerless input, in order
+pub const INPUT_COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+// Transforms a reader over a file into a iterator over transactions.
+// Rows of a reader without headers are read as `INPUT_COLUMNS`, a missing trailing amount is allowed.
 #[instrument(skip(csv_reader))]
 pub fn transactions_from_reader<T: std::io::Read>(
-    csv_reader: Reader<T>,
+    mut csv_reader: Reader<T>,
 ) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
-    csv_reader
-        .into_deserialize()
-        .map(|record: Result<InputCsvRecord, csv::Error>| {
-            let csv_record: InputCsvRecord = record.map_err(ConversionError::from)?;
-            Transaction::try_from(csv_record)
+    let headers = match csv_reader.has_headers() {
+        true => csv_reader
+            .headers()
+            .map_err(|err| error!(%err, "Failed to read the csv header"))
+            .ok()
+            .cloned(),
+        false => Some(StringRecord::from(INPUT_COLUMNS.to_vec())),
+    };
+    csv_reader.into_records().map(move |record| {
+        let csv_record: InputCsvRecord = record?.deserialize(headers.as_ref())?;
+        Transaction::try_from(csv_record)
+    })
+}
+
+// Like `transactions_from_reader` with the columns picked by a `ColumnMapping`,
+// fails if a mapped column name is not in the headers
+#[instrument(skip(csv_reader))]
+pub fn transactions_from_mapped_reader<T: std::io::Read>(
+    mut csv_reader: Reader<T>,
+    mapping: &ColumnMapping,
+) -> Result<impl Iterator<Item = Result<Transaction, ConversionError>> + use<T>, ConversionError> {
+    let headers = match csv_reader.has_headers() {
+        true => Some(csv_reader.headers()?),
+        false => None,
+    };
+    let columns = mapping.resolve(headers)?;
+    let input_columns = StringRecord::from(INPUT_COLUMNS.to_vec());
+    Ok(csv_reader.into_records().map(move |record| {
+        let csv_record: InputCsvRecord =
+            map_columns(&record?, &columns).deserialize(Some(&input_columns))?;
+        Transaction::try_from(csv_record)
+    }))
+}
+
+/// Significant digits of the amounts parsed without serde: serde reads a decimal amount as a
+/// `f64`, which keeps up to 15 significant digits, longer amounts are left to it
+const BYTE_AMOUNT_DIGITS: u64 = 1_000_000_000_000_000;
+
+/// Transactions of a csv parsed from its raw bytes (`csv::ByteRecord`) without serde, reusing one
+/// record for every row. Rows the hand-rolled parsing is not sure about (custom types, signed,
+/// exponent or long amounts, a wrong number of fields...) are deserialized with serde, so the
+/// transactions are the same as the ones of `transactions_from_reader`.
+pub struct ByteRecordTransactions<R> {
+    csv_reader: Reader<R>,
+    record: ByteRecord,
+    headers: Option<ByteRecord>,
+    columns: Option<[usize; 4]>, // positions of type, client, tx and amount, every row goes through serde without them
+    fallbacks: u64,
+}
+
+// Transforms a reader over a file into a iterator over transactions, parsed without serde.
+// Rows of a reader without headers are read as `INPUT_COLUMNS`, a missing trailing amount is allowed.
+#[instrument(skip(csv_reader))]
+pub fn transactions_from_byte_reader<R: io::Read>(
+    mut csv_reader: Reader<R>,
+) -> ByteRecordTransactions<R> {
+    let headers = match csv_reader.has_headers() {
+        true => csv_reader
+            .byte_headers()
+            .map_err(|err| error!(%err, "Failed to read the csv header"))
+            .ok()
+            .cloned(),
+        false => Some(ByteRecord::from(INPUT_COLUMNS.to_vec())),
+    };
+    let mut columns = [0; 4];
+    for (column, name) in columns.iter_mut().zip(INPUT_COLUMNS) {
+        let mut positions = headers
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, header)| *header == name.as_bytes());
+        *column = match (positions.next(), positions.next()) {
+            (Some((position, _)), None) => position,
+            _ => usize::MAX, // missing or repeated, left to serde
+        };
+    }
+    ByteRecordTransactions {
+        csv_reader,
+        record: ByteRecord::new(),
+        headers,
+        columns: (!columns.contains(&usize::MAX)).then_some(columns),
+        fallbacks: 0,
+    }
+}
+
+impl<R> ByteRecordTransactions<R> {
+    /// Rows that were deserialized with serde so far
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks
+    }
+
+    // the row parsed from its bytes, None when it must go through serde
+    fn parse_record(&self) -> Option<InputCsvRecord> {
+        let [transaction_type, client, tx, amount] = self.columns?;
+        if Some(self.record.len()) != self.headers.as_ref().map(ByteRecord::len) {
+            return None;
+        }
+        let transaction_type = match &self.record[transaction_type] {
+            b"deposit" => TransactionKind::Deposit,
+            b"withdrawal" => TransactionKind::Withdrawal,
+            b"dispute" => TransactionKind::Dispute,
+            b"resolve" => TransactionKind::Resolve,
+            b"chargeback" => TransactionKind::Chargeback,
+            _ => return None, // custom and unknown types
+        };
+        Some(InputCsvRecord {
+            transaction_type,
+            client: model::ClientId(parse_digits(&self.record[client])?),
+            tx: model::TransactionId(parse_digits(&self.record[tx])?),
+            amount: parse_amount(&self.record[amount])?,
         })
+    }
+}
+
+impl<R: io::Read> Iterator for ByteRecordTransactions<R> {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.csv_reader.read_byte_record(&mut self.record) {
+            Ok(true) => {}
+            Ok(false) => {
+                info!(fallbacks = self.fallbacks, "Rows deserialized with serde");
+                return None;
+            }
+            Err(err) => return Some(Err(err.into())),
+        }
+        let csv_record = match self.parse_record() {
+            Some(csv_record) => csv_record,
+            None => {
+                self.fallbacks += 1;
+                match self.record.deserialize(self.headers.as_ref()) {
+                    Ok(csv_record) => csv_record,
+                    Err(err) => return Some(Err(err.into())),
+                }
+            }
+        };
+        Some(Transaction::try_from(csv_record))
+    }
+}
+
+// an unsigned integer written with ascii digits only
+fn parse_digits<T: TryFrom<u64>>(field: &[u8]) -> Option<T> {
+    if field.is_empty() || field.len() > 19 {
+        return None; // 19 digits always fit in a u64
+    }
+    let mut value = 0u64;
+    for digit in field {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + u64::from(digit - b'0');
+    }
+    T::try_from(value).ok()
+}
+
+// `digits` or `digits.digits`, None for anything else (signs, exponents, `.5`, `5.`...) or too many
+// significant digits. Trailing zeros of the fraction are dropped, like serde does.
+fn parse_amount(field: &[u8]) -> Option<Option<Decimal>> {
+    if field.is_empty() {
+        return Some(None);
+    }
+    let (integer, fraction) = match field.iter().position(|byte| *byte == b'.') {
+        Some(dot) if dot > 0 && dot + 1 < field.len() => (&field[..dot], &field[dot + 1..]),
+        Some(_) => return None,
+        None => (field, &[][..]),
+    };
+    let fraction = match fraction.iter().rposition(|digit| *digit != b'0') {
+        Some(last) => &fraction[..=last],
+        None => &[][..],
+    };
+    let mut mantissa = 0u64;
+    for digit in integer.iter().chain(fraction) {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        mantissa = mantissa * 10 + u64::from(digit - b'0');
+        if mantissa >= BYTE_AMOUNT_DIGITS {
+            return None;
+        }
+    }
+    let scale = u32::try_from(fraction.len()).ok()?;
+    Decimal::try_from_i128_with_scale(i128::from(mantissa), scale)
+        .ok()
+        .map(Some)
+}
+
+/// Transactions of a csv where every row that fails conversion is also copied, as it was read,
+/// to a quarantine csv with the same header so that it can be fixed and resubmitted
+pub struct QuarantiningTransactions<R: io::Read, W: io::Write> {
+    records: StringRecordsIntoIter<R>,
+    headers: StringRecord,
+    has_headers: bool,           // headerless rows may omit the trailing amount
+    columns: Option<[usize; 4]>, // resolved column mapping
+    input_columns: StringRecord,
+    quarantine: Writer<W>,
+}
+
+// Loads the csv in path as a Iterator over transactions, failed rows are written to quarantine_path
+#[instrument]
+pub fn read_transactions_from_csv_with_quarantine(
+    csv_path: &Path,
+    quarantine_path: &Path,
+    options: &CsvOptions,
+) -> Result<QuarantiningTransactions<Box<dyn io::Read + Send>, File>, ConversionError> {
+    let csv_reader = options
+        .reader_builder()
+        .flexible(true) // rows with a wrong number of fields are quarantined instead of aborting the read
+        .from_reader(options.open(csv_path).map_err(csv::Error::from)?);
+    let quarantine = File::create(quarantine_path).map_err(csv::Error::from)?;
+    transactions_with_quarantine(csv_reader, quarantine, options)
+}
+
+/// The reader should be `flexible` so that rows with a wrong number of fields can be quarantined too.
+/// The quarantine is written in the same dialect as the input so that it can be resubmitted as is.
+pub fn transactions_with_quarantine<R: io::Read, W: io::Write>(
+    mut csv_reader: Reader<R>,
+    quarantine: W,
+    options: &CsvOptions,
+) -> Result<QuarantiningTransactions<R, W>, ConversionError> {
+    let headers = match options.has_headers {
+        true => csv_reader.headers()?.clone(),
+        false => StringRecord::from(INPUT_COLUMNS.to_vec()),
+    };
+    let columns = match &options.column_mapping {
+        Some(mapping) => Some(mapping.resolve(options.has_headers.then_some(&headers))?),
+        None => None,
+    };
+    let mut quarantine = csv::WriterBuilder::new()
+        .flexible(true)
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .from_writer(quarantine);
+    if options.has_headers {
+        quarantine.write_record(&headers)?;
+    }
+    quarantine.flush().map_err(csv::Error::from)?;
+    Ok(QuarantiningTransactions {
+        records: csv_reader.into_records(),
+        headers,
+        has_headers: options.has_headers,
+        columns,
+        input_columns: StringRecord::from(INPUT_COLUMNS.to_vec()),
+        quarantine,
+    })
+}
+
+impl<R: io::Read, W: io::Write> QuarantiningTransactions<R, W> {
+    fn convert(&self, record: &StringRecord) -> Result<Transaction, ConversionError> {
+        if let Some(columns) = &self.columns {
+            let mapped = map_columns(record, columns);
+            return Transaction::try_from(
+                mapped.deserialize::<InputCsvRecord>(Some(&self.input_columns))?,
+            );
+        }
+        if record.len() > self.headers.len()
+            || (self.has_headers && record.len() != self.headers.len())
+        {
+            return Err(ConversionError::Unexpected(format!(
+                "expected {} fields, found {}",
+                self.headers.len(),
+                record.len()
+            )));
+        }
+        Transaction::try_from(record.deserialize::<InputCsvRecord>(Some(&self.headers))?)
+    }
+
+    fn quarantine(&mut self, record: &StringRecord) {
+        // flushed right away: failed rows are rare and must not be lost if the run dies
+        let written = self
+            .quarantine
+            .write_record(record)
+            .and_then(|()| Ok(self.quarantine.flush()?));
+        if let Err(err) = written {
+            error!(%err, ?record, "failed to quarantine row");
+        }
+    }
+}
+
+impl<R: io::Read, W: io::Write> Iterator for QuarantiningTransactions<R, W> {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err.into())), // unreadable (e.g. invalid utf8), nothing to mirror
+        };
+        let transaction = self.convert(&record);
+        if let Err(err) = &transaction {
+            warn!(error=%err, ?record, "Quarantining row");
+            self.quarantine(&record);
+        }
+        Some(transaction)
+    }
 }
@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    csv_input::ConversionError,
+    filter::TRANSACTION_TYPES,
+    model::{Account, CustomTransaction, DisputableTransactions},
+};
+
+/// Behavior of a transaction type defined outside of the engine.
+/// Registered types are parsed from every input format (their `type` column is the registered
+/// name), applied through `apply`, seen by the observers (audit, exports, reports) and written back
+/// like the built-in types, e.g. to the quarantine.
+pub trait CustomTransactionHandler: Send + Sync {
+    /// Checks a transaction of this type once it is parsed, by default any amount is accepted
+    fn validate(&self, _transaction: &CustomTransaction) -> Result<(), ConversionError> {
+        Ok(())
+    }
+
+    /// Applies the transaction to the account of its client, which is not locked.
+    /// Funds it credits can be made disputable by inserting the tx into `disputable_transactions`.
+    fn apply(
+        &self,
+        transaction: &CustomTransaction,
+        account: &mut Account,
+        disputable_transactions: &mut DisputableTransactions,
+    );
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RegistrationError {
+    #[error("{0} is a built-in transaction type")]
+    BuiltIn(String),
+}
+
+type Handlers = HashMap<&'static str, Arc<dyn CustomTransactionHandler>>;
+
+// process wide so that every input format parses the registered types
+fn handlers() -> &'static RwLock<Handlers> {
+    static HANDLERS: OnceLock<RwLock<Handlers>> = OnceLock::new();
+    HANDLERS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Registers (or replaces) the handler of a transaction type, for the rest of the process
+pub fn register_transaction_type<H: CustomTransactionHandler + 'static>(
+    type_name: &'static str,
+    handler: H,
+) -> Result<(), RegistrationError> {
+    if TRANSACTION_TYPES.contains(&type_name) {
+        return Err(RegistrationError::BuiltIn(type_name.to_string()));
+    }
+    handlers()
+        .write()
+        .expect("poisoned transaction type registry")
+        .insert(type_name, Arc::new(handler));
+    info!(type_name, "Registered custom transaction type");
+    Ok(())
+}
+
+/// Registered name and handler of a transaction type
+pub fn custom_handler(
+    type_name: &str,
+) -> Option<(&'static str, Arc<dyn CustomTransactionHandler>)> {
+    handlers()
+        .read()
+        .expect("poisoned transaction type registry")
+        .get_key_value(type_name)
+        .map(|(type_name, handler)| (*type_name, handler.clone()))
+}
@@ -0,0 +1,204 @@
+use std::{
+    iter::{Flatten, Map},
+    mem,
+    ops::Index,
+    slice, vec,
+};
+
+use crate::model::{Account, ClientId};
+
+/// Slots of a full table, one per possible `ClientId`
+const CLIENT_IDS: usize = u16::MAX as usize + 1;
+
+/// Client accounts in a table indexed by the ClientId (feature `dense-accounts`), the `HashMap`
+/// API the engine uses without any hashing. The table of 65 536 slots is allocated with the
+/// first account, so a `Clients` that never sees a transaction costs nothing. Iterating visits
+/// every slot: the accounts come out by client id, at the cost of a full scan, which only pays
+/// off when most of the id space is used.
+#[derive(Debug, Default, Clone)]
+pub struct DenseAccounts {
+    slots: Vec<Option<(ClientId, Account)>>, // the id is kept to iterate like a map, by reference
+    len: usize,
+}
+
+impl DenseAccounts {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Accounts held without allocating: none before the table is allocated, any after
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Allocates the table, which always has a slot for every client id
+    pub fn reserve(&mut self, additional: usize) {
+        if additional > 0 {
+            self.allocate();
+        }
+    }
+
+    pub fn contains_key(&self, client: &ClientId) -> bool {
+        self.get(client).is_some()
+    }
+
+    pub fn get(&self, client: &ClientId) -> Option<&Account> {
+        match self.slots.get(client.0 as usize) {
+            Some(Some((_, account))) => Some(account),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, client: &ClientId) -> Option<&mut Account> {
+        match self.slots.get_mut(client.0 as usize) {
+            Some(Some((_, account))) => Some(account),
+            _ => None,
+        }
+    }
+
+    /// Returns the previous account of the client, like `HashMap::insert`
+    pub fn insert(&mut self, client: ClientId, account: Account) -> Option<Account> {
+        let previous = self.slot(client).replace((client, account));
+        self.len += previous.is_none() as usize;
+        previous.map(|(_, account)| account)
+    }
+
+    pub fn remove(&mut self, client: &ClientId) -> Option<Account> {
+        let (_, account) = self.slots.get_mut(client.0 as usize)?.take()?;
+        self.len -= 1;
+        Some(account)
+    }
+
+    pub fn entry(&mut self, client: ClientId) -> Entry<'_> {
+        self.allocate();
+        Entry {
+            client,
+            len: &mut self.len,
+            slot: &mut self.slots[client.0 as usize],
+        }
+    }
+
+    /// The accounts by client id
+    pub fn iter(&self) -> Iter<'_> {
+        self.slots.iter().flatten().map(pair_ref as _)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &ClientId> {
+        self.iter().map(|(client, _)| client)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Account> {
+        self.iter().map(|(_, account)| account)
+    }
+
+    /// Takes every account out, releasing the table
+    pub fn drain(&mut self) -> Flatten<vec::IntoIter<Option<(ClientId, Account)>>> {
+        self.len = 0;
+        mem::take(&mut self.slots).into_iter().flatten()
+    }
+
+    // the slot of the client, allocating the table on first use
+    fn slot(&mut self, client: ClientId) -> &mut Option<(ClientId, Account)> {
+        self.allocate();
+        &mut self.slots[client.0 as usize]
+    }
+
+    fn allocate(&mut self) {
+        if self.slots.is_empty() {
+            self.slots.resize_with(CLIENT_IDS, || None);
+        }
+    }
+}
+
+fn pair_ref((client, account): &(ClientId, Account)) -> (&ClientId, &Account) {
+    (client, account)
+}
+
+/// Accounts of a `DenseAccounts` by reference, by client id
+pub type Iter<'a> = Map<
+    Flatten<slice::Iter<'a, Option<(ClientId, Account)>>>,
+    fn(&'a (ClientId, Account)) -> (&'a ClientId, &'a Account),
+>;
+
+/// A slot of a `DenseAccounts`, like `hash_map::Entry` for the `or_` methods
+pub struct Entry<'a> {
+    client: ClientId,
+    len: &'a mut usize,
+    slot: &'a mut Option<(ClientId, Account)>,
+}
+
+impl<'a> Entry<'a> {
+    pub fn or_insert(self, account: Account) -> &'a mut Account {
+        if self.slot.is_none() {
+            *self.len += 1;
+        }
+        let (_, account) = self.slot.get_or_insert((self.client, account));
+        account
+    }
+
+    pub fn or_default(self) -> &'a mut Account {
+        if self.slot.is_none() {
+            *self.len += 1;
+        }
+        let (_, account) = self
+            .slot
+            .get_or_insert_with(|| (self.client, Account::default()));
+        account
+    }
+}
+
+// the same accounts, whether the tables were allocated or not
+impl PartialEq for DenseAccounts {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for DenseAccounts {}
+
+impl Index<&ClientId> for DenseAccounts {
+    type Output = Account;
+
+    /// Panics when the client has no account, like a `HashMap`
+    fn index(&self, client: &ClientId) -> &Account {
+        self.get(client).expect("no account for this client")
+    }
+}
+
+impl Extend<(ClientId, Account)> for DenseAccounts {
+    fn extend<T: IntoIterator<Item = (ClientId, Account)>>(&mut self, accounts: T) {
+        for (client, account) in accounts {
+            self.insert(client, account);
+        }
+    }
+}
+
+impl FromIterator<(ClientId, Account)> for DenseAccounts {
+    fn from_iter<T: IntoIterator<Item = (ClientId, Account)>>(accounts: T) -> Self {
+        let mut dense = DenseAccounts::default();
+        dense.extend(accounts);
+        dense
+    }
+}
+
+impl IntoIterator for DenseAccounts {
+    type Item = (ClientId, Account);
+    type IntoIter = Flatten<vec::IntoIter<Option<(ClientId, Account)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slots.into_iter().flatten()
+    }
+}
+
+impl<'a> IntoIterator for &'a DenseAccounts {
+    type Item = (&'a ClientId, &'a Account);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    io::{self, BufRead, Read},
+    str::FromStr,
+};
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+/// Character encoding of a text input. UTF-8 and UTF-16 inputs with a byte order mark are
+/// detected, Latin-1 (ISO-8859-1) cannot be told apart from UTF-8 and must be given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(InputEncoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(InputEncoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(InputEncoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(InputEncoding::Latin1),
+            _ => Err(format!(
+                "unknown encoding {s}, expected utf-8, utf-16le, utf-16be or latin1"
+            )),
+        }
+    }
+}
+
+/// Reader over the input as UTF-8, whatever its encoding: a byte order mark is skipped, and takes
+/// precedence over the given encoding for UTF-16. Without one the input is read as `encoding`,
+/// UTF-8 when not given.
+pub fn decode_input<R: BufRead + Send + 'static>(
+    mut reader: R,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    // the head is read in full, a short first read must not hide the byte order mark
+    let mut head = [0; UTF8_BOM.len()];
+    let mut len = 0;
+    while len < head.len() {
+        match reader.read(&mut head[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    let head = &head[..len];
+    let (bom, encoding) =
+        if head.starts_with(UTF8_BOM) && matches!(encoding, None | Some(InputEncoding::Utf8)) {
+            (UTF8_BOM.len(), InputEncoding::Utf8)
+        } else if head.starts_with(UTF16LE_BOM) && encoding != Some(InputEncoding::Latin1) {
+            (UTF16LE_BOM.len(), InputEncoding::Utf16Le)
+        } else if head.starts_with(UTF16BE_BOM) && encoding != Some(InputEncoding::Latin1) {
+            (UTF16BE_BOM.len(), InputEncoding::Utf16Be)
+        } else {
+            (0, encoding.unwrap_or_default())
+        };
+    let reader = io::Cursor::new(head[bom..].to_vec()).chain(reader);
+    Ok(match encoding {
+        InputEncoding::Utf8 => Box::new(reader),
+        encoding => Box::new(TranscodingReader::new(reader, encoding)),
+    })
+}
+
+/// Transcodes UTF-16 or Latin-1 to UTF-8 as it is read. Invalid UTF-16 (an unpaired surrogate)
+/// fails the read; a code unit cut at the end of the input is kept until more data is available,
+/// so that a followed input can complete it.
+#[derive(Debug)]
+pub struct TranscodingReader<R> {
+    inner: R,
+    encoding: InputEncoding,
+    decoded: Vec<u8>, // UTF-8 not returned yet
+    position: usize,
+    odd_byte: Option<u8>,        // first byte of a UTF-16 code unit
+    high_surrogate: Option<u16>, // first code unit of a UTF-16 pair
+    offset: u64,                 // bytes of the input consumed, for errors
+}
+
+impl<R: BufRead> TranscodingReader<R> {
+    pub fn new(inner: R, encoding: InputEncoding) -> TranscodingReader<R> {
+        TranscodingReader {
+            inner,
+            encoding,
+            decoded: Vec::new(),
+            position: 0,
+            odd_byte: None,
+            high_surrogate: None,
+            offset: 0,
+        }
+    }
+
+    // decodes the next chunk of the input, false at the end of the input
+    fn decode_chunk(&mut self) -> io::Result<bool> {
+        self.decoded.clear();
+        self.position = 0;
+        let input = self.inner.fill_buf()?;
+        if input.is_empty() {
+            return Ok(false);
+        }
+        let read = input.len();
+        let mut bytes = input.iter().copied();
+        match self.encoding {
+            InputEncoding::Utf8 => self.decoded.extend(bytes),
+            InputEncoding::Latin1 => {
+                // every byte is the code point of the same value
+                for byte in bytes {
+                    let mut utf8 = [0; 2];
+                    let char = char::from(byte).encode_utf8(&mut utf8);
+                    self.decoded.extend_from_slice(char.as_bytes());
+                }
+            }
+            InputEncoding::Utf16Le | InputEncoding::Utf16Be => {
+                while let Some(first) = self.odd_byte.take().or_else(|| bytes.next()) {
+                    let Some(second) = bytes.next() else {
+                        self.odd_byte = Some(first);
+                        break;
+                    };
+                    let unit = match self.encoding {
+                        InputEncoding::Utf16Le => u16::from_le_bytes([first, second]),
+                        _ => u16::from_be_bytes([first, second]),
+                    };
+                    push_unit(
+                        &mut self.decoded,
+                        &mut self.high_surrogate,
+                        unit,
+                        self.offset,
+                    )?;
+                }
+            }
+        }
+        self.inner.consume(read);
+        self.offset += read as u64;
+        Ok(true)
+    }
+}
+
+// appends the character of a UTF-16 code unit, or keeps the unit until the second one of its pair
+fn push_unit(
+    decoded: &mut Vec<u8>,
+    high_surrogate: &mut Option<u16>,
+    unit: u16,
+    offset: u64,
+) -> io::Result<()> {
+    let char = match (high_surrogate.take(), unit) {
+        (None, 0xd800..=0xdbff) => {
+            *high_surrogate = Some(unit);
+            return Ok(());
+        }
+        (Some(high), 0xdc00..=0xdfff) => char::decode_utf16([high, unit]).next(),
+        (None, _) => char::decode_utf16([unit]).next(),
+        (Some(_), _) => None,
+    };
+    match char {
+        Some(Ok(char)) => {
+            let mut utf8 = [0; 4];
+            decoded.extend_from_slice(char.encode_utf8(&mut utf8).as_bytes());
+            Ok(())
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid UTF-16 near byte {offset} of the input, unpaired surrogate"),
+        )),
+    }
+}
+
+impl<R: BufRead> io::Read for TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.decoded.len() {
+            if !self.decode_chunk()? {
+                return Ok(0);
+            }
+        }
+        let available = &self.decoded[self.position..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read;
+        Ok(read)
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    io::{self, Read, Write},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use tracing::warn;
+
+use crate::rng::XorShiftRng;
+
+/// Probabilities and settings of the injected faults, all disabled by default.
+/// Parsed from a spec like `io_error=0.01,delay=0.1,delay_ms=5,panic=0.001,truncate_at=4096,seed=42`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    pub io_error: f64,            // probability of an io error on each read/write call
+    pub delay: f64,               // probability of delaying a write
+    pub delay_ms: u64,            // duration of a delayed write
+    pub panic: f64, // probability of panicking inside a write (kills the writer thread)
+    pub truncate_at: Option<u64>, // input ends (clean EOF) after this many bytes
+    pub seed: u64,  // faults are deterministic for a given seed
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        FaultConfig {
+            io_error: 0.0,
+            delay: 0.0,
+            delay_ms: 10,
+            panic: 0.0,
+            truncate_at: None,
+            seed: 0x5EED,
+        }
+    }
+}
+
+impl FromStr for FaultConfig {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut config = FaultConfig::default();
+        for setting in spec.split(',').filter(|setting| !setting.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or(format!("expected key=value, got {setting}"))?;
+            let invalid = || format!("invalid value for {key}: {value}");
+            match key {
+                "io_error" => config.io_error = value.parse().map_err(|_| invalid())?,
+                "delay" => config.delay = value.parse().map_err(|_| invalid())?,
+                "delay_ms" => config.delay_ms = value.parse().map_err(|_| invalid())?,
+                "panic" => config.panic = value.parse().map_err(|_| invalid())?,
+                "truncate_at" => config.truncate_at = Some(value.parse().map_err(|_| invalid())?),
+                "seed" => config.seed = value.parse().map_err(|_| invalid())?,
+                _ => return Err(format!("unknown fault: {key}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Wraps the input reader, failing reads and truncating the stream
+#[derive(Debug)]
+pub struct FaultyReader<R: Read> {
+    inner: R,
+    config: FaultConfig,
+    rng: XorShiftRng,
+    bytes_read: u64,
+}
+
+impl<R: Read> FaultyReader<R> {
+    pub fn new(inner: R, config: FaultConfig) -> FaultyReader<R> {
+        FaultyReader {
+            inner,
+            rng: XorShiftRng::new(config.seed),
+            config,
+            bytes_read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for FaultyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rng.roll(self.config.io_error) {
+            warn!(bytes_read = self.bytes_read, "Injecting read error");
+            return Err(io::Error::other("injected read error"));
+        }
+        let limit = match self.config.truncate_at {
+            Some(truncate_at) if self.bytes_read >= truncate_at => {
+                warn!(bytes_read = self.bytes_read, "Injecting input truncation");
+                return Ok(0);
+            }
+            Some(truncate_at) => buf.len().min((truncate_at - self.bytes_read) as usize),
+            None => buf.len(),
+        };
+        let read = self.inner.read(&mut buf[..limit])?;
+        self.bytes_read += read as u64;
+        Ok(read)
+    }
+}
+
+/// Wraps the output writer, failing, delaying or panicking on writes
+#[derive(Debug)]
+pub struct FaultyWriter<W: Write> {
+    inner: W,
+    config: FaultConfig,
+    rng: XorShiftRng,
+}
+
+impl<W: Write> FaultyWriter<W> {
+    pub fn new(inner: W, config: FaultConfig) -> FaultyWriter<W> {
+        FaultyWriter {
+            inner,
+            rng: XorShiftRng::new(config.seed),
+            config,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for FaultyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.rng.roll(self.config.panic) {
+            panic!("injected writer panic");
+        }
+        if self.rng.roll(self.config.delay) {
+            thread::sleep(Duration::from_millis(self.config.delay_ms));
+        }
+        if self.rng.roll(self.config.io_error) {
+            warn!("Injecting write error");
+            return Err(io::Error::other("injected write error"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
+
+use tracing::{debug, trace, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{ClientId, Transaction},
+    rules::{Rule, RuleSet},
+};
+
+/// Restricts processing to a subset of the clients
+#[derive(Clone, PartialEq)]
+pub enum ClientFilter {
+    Allow(HashSet<ClientId>), // only these clients are processed
+    Block(HashSet<ClientId>), // these clients are skipped
+    // a deterministic pseudo random subset of the clients, a sampled client keeps its full history
+    Sample { percent: f64, seed: u64 },
+}
+
+// the clients are listed in order so that the Debug output (hashed in the run metadata) is stable
+impl Debug for ClientFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sorted = |clients: &HashSet<ClientId>| {
+            let mut clients: Vec<u16> = clients.iter().map(|client| client.0).collect();
+            clients.sort_unstable();
+            clients
+        };
+        match self {
+            ClientFilter::Allow(clients) => f.debug_tuple("Allow").field(&sorted(clients)).finish(),
+            ClientFilter::Block(clients) => f.debug_tuple("Block").field(&sorted(clients)).finish(),
+            ClientFilter::Sample { percent, seed } => f
+                .debug_struct("Sample")
+                .field("percent", percent)
+                .field("seed", seed)
+                .finish(),
+        }
+    }
+}
+
+impl ClientFilter {
+    pub fn keeps(&self, client: ClientId) -> bool {
+        match self {
+            ClientFilter::Allow(clients) => clients.contains(&client),
+            ClientFilter::Block(clients) => !clients.contains(&client),
+            ClientFilter::Sample { percent, seed } => {
+                let position = client_hash(client, *seed) as f64 / u64::MAX as f64;
+                position * 100.0 < *percent
+            }
+        }
+    }
+
+    /// Wraps a transaction stream, dropping the transactions of filtered out clients
+    pub fn apply<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+        self,
+        transactions: I,
+    ) -> FilteredTransactions<I> {
+        filter_transactions(vec![self], transactions)
+    }
+}
+
+// splitmix64 finalizer: the same client always lands on the same side of the sample for a given seed
+fn client_hash(client: ClientId, seed: u64) -> u64 {
+    let mut hash = seed ^ (client.0 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+/// Names of the transaction types, as in the input `type` column
+pub const TRANSACTION_TYPES: [&str; 5] =
+    ["deposit", "withdrawal", "dispute", "resolve", "chargeback"];
+
+/// Wraps a transaction stream, only keeping the transactions of clients that pass every filter.
+/// Invalid rows are passed through so that they are still reported.
+pub fn filter_transactions<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+    filters: Vec<ClientFilter>,
+    transactions: I,
+) -> FilteredTransactions<I> {
+    FilteredTransactions {
+        filters,
+        disabled_types: HashSet::new(),
+        rules: RuleSet::default(),
+        transactions,
+        skipped: 0,
+        skipped_types: HashMap::new(),
+        rejected: Vec::new(),
+    }
+}
+
+/// Transaction stream with client filters applied, counts what was skipped
+#[derive(Debug)]
+pub struct FilteredTransactions<I> {
+    filters: Vec<ClientFilter>,
+    disabled_types: HashSet<&'static str>, // e.g. only replay deposits during an incident
+    rules: RuleSet,                        // user-defined rejections, after the other filters
+    transactions: I,
+    skipped: u64,
+    skipped_types: HashMap<&'static str, u64>,
+    rejected: Vec<u64>, // per rule
+}
+
+impl<I> FilteredTransactions<I> {
+    /// Also drops every transaction of these types (names from `TRANSACTION_TYPES`)
+    pub fn with_disabled_types(mut self, types: HashSet<&'static str>) -> Self {
+        self.disabled_types = types;
+        self
+    }
+
+    /// Also drops every transaction matched by one of these rules
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rejected = vec![0; rules.rules().len()];
+        self.rules = rules;
+        self
+    }
+
+    /// Number of transactions of filtered out clients dropped so far
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Number of transactions dropped so far because their type is disabled, per type
+    pub fn skipped_types(&self) -> &HashMap<&'static str, u64> {
+        &self.skipped_types
+    }
+
+    /// Number of transactions rejected so far, per rule (by the first rule that matched)
+    pub fn rejected(&self) -> impl Iterator<Item = (&Rule, u64)> {
+        self.rules.rules().iter().zip(self.rejected.iter().copied())
+    }
+}
+
+impl<I: Iterator<Item = Result<Transaction, ConversionError>>> Iterator
+    for FilteredTransactions<I>
+{
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for transaction in self.transactions.by_ref() {
+            match &transaction {
+                Ok(disabled) if self.disabled_types.contains(disabled.type_name()) => {
+                    debug!(?disabled, "Skipping disabled transaction type");
+                    *self.skipped_types.entry(disabled.type_name()).or_default() += 1;
+                }
+                Ok(kept)
+                    if !self
+                        .filters
+                        .iter()
+                        .all(|filter| filter.keeps(kept.client_id())) =>
+                {
+                    trace!(?kept, "Skipping filtered out client");
+                    self.skipped += 1;
+                }
+                Ok(rejected) if let Some(rule) = self.rules.rejecting(rejected) => {
+                    warn!(?rejected, rule = %self.rules.rules()[rule], "Rejected transaction");
+                    self.rejected[rule] += 1;
+                }
+                _ => return Some(transaction),
+            }
+        }
+        None
+    }
+}
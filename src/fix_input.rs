@@ -0,0 +1,420 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    str::FromStr,
+};
+
+use rust_decimal::Decimal;
+use tracing::{debug, info, instrument, trace, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{ClientId, Transaction, TransactionId},
+};
+
+const SOH: u8 = 0x01; // FIX field delimiter
+const FIX_VERSION: &str = "FIX.4.4";
+
+// Tags used by the drop-copy mapping
+const TAG_ACCOUNT: u32 = 1;
+const TAG_AVG_PX: u32 = 6;
+const TAG_CHECKSUM: u32 = 10;
+const TAG_EXEC_ID: u32 = 17;
+const TAG_LAST_PX: u32 = 31;
+const TAG_LAST_QTY: u32 = 32;
+const TAG_MSG_SEQ_NUM: u32 = 34;
+const TAG_MSG_TYPE: u32 = 35;
+const TAG_SENDER_COMP_ID: u32 = 49;
+const TAG_SENDING_TIME: u32 = 52;
+const TAG_SIDE: u32 = 54;
+const TAG_TARGET_COMP_ID: u32 = 56;
+const TAG_ALLOC_TRANS_TYPE: u32 = 71;
+const TAG_ALLOC_ACCOUNT: u32 = 79;
+const TAG_ALLOC_QTY: u32 = 80;
+const TAG_TEST_REQ_ID: u32 = 112;
+const TAG_EXEC_TYPE: u32 = 150;
+const TAG_ALLOC_NET_MONEY: u32 = 154;
+const TAG_GROSS_TRADE_AMT: u32 = 381;
+const TAG_INDIVIDUAL_ALLOC_ID: u32 = 467;
+
+/// A FIX tag=value message, fields kept in wire order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixMessage {
+    pub fields: Vec<(u32, String)>,
+}
+
+impl FixMessage {
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field_tag, _)| *field_tag == tag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn msg_type(&self) -> Option<&str> {
+        self.get(TAG_MSG_TYPE)
+    }
+
+    fn parse_field<T: FromStr>(&self, tag: u32, name: &str) -> Result<T, ConversionError> {
+        let value = self
+            .get(tag)
+            .ok_or(ConversionError::Fix(format!("missing {name} ({tag})")))?;
+        value
+            .parse()
+            .map_err(|_| ConversionError::Fix(format!("invalid {name} ({tag}): {value}")))
+    }
+
+    /// Parses the raw bytes of one message (from `8=` up to and including the checksum field)
+    pub fn parse(raw: &[u8]) -> Result<FixMessage, ConversionError> {
+        let mut fields = Vec::new();
+        for field in raw
+            .split(|byte| *byte == SOH)
+            .filter(|field| !field.is_empty())
+        {
+            let field = std::str::from_utf8(field)
+                .map_err(|_| ConversionError::Fix("field is not valid utf8".to_string()))?;
+            let (tag, value) = field
+                .split_once('=')
+                .ok_or(ConversionError::Fix(format!("malformed field: {field}")))?;
+            let tag = tag
+                .parse()
+                .map_err(|_| ConversionError::Fix(format!("invalid tag: {tag}")))?;
+            fields.push((tag, value.to_string()));
+        }
+        let message = FixMessage { fields };
+
+        // the checksum is the sum of every byte before the checksum field, modulo 256
+        let checksum_start = raw
+            .windows(4)
+            .rposition(|window| window == b"\x0110=")
+            .map(|position| position + 1)
+            .ok_or(ConversionError::Fix("missing checksum".to_string()))?;
+        let expected: u32 = message.parse_field(TAG_CHECKSUM, "CheckSum")?;
+        let actual = raw[..checksum_start]
+            .iter()
+            .fold(0u32, |sum, byte| sum + *byte as u32)
+            % 256;
+        if expected != actual {
+            return Err(ConversionError::Fix(format!(
+                "checksum mismatch: expected {expected} got {actual}"
+            )));
+        }
+        Ok(message)
+    }
+
+    /// Serializes the message, computing BodyLength (9) and CheckSum (10)
+    pub fn encode(msg_type: &str, fields: &[(u32, String)]) -> Vec<u8> {
+        let mut body = format!("{TAG_MSG_TYPE}={msg_type}\x01").into_bytes();
+        for (tag, value) in fields {
+            body.extend_from_slice(format!("{tag}={value}\x01").as_bytes());
+        }
+        let mut message = format!("8={FIX_VERSION}\x019={}\x01", body.len()).into_bytes();
+        message.extend_from_slice(&body);
+        let checksum = message.iter().fold(0u32, |sum, byte| sum + *byte as u32) % 256;
+        message.extend_from_slice(format!("10={checksum:03}\x01").as_bytes());
+        message
+    }
+
+    /// Maps a fill (execution report with ExecType Trade) to a transaction on the account (1) of the fill:
+    /// a sell credits the proceeds (deposit), a buy debits the cost (withdrawal).
+    /// The amount is GrossTradeAmt (381) when present, LastQty (32) * LastPx (31) otherwise, and the
+    /// ExecID (17) is the transaction id. Returns `None` for messages that are not fills.
+    pub fn to_transaction(&self) -> Option<Result<Transaction, ConversionError>> {
+        if self.msg_type() != Some("8") || self.get(TAG_EXEC_TYPE) != Some("F") {
+            return None;
+        }
+        Some(self.fill_to_transaction())
+    }
+
+    /// Transactions of a fill (see `to_transaction`) or of a new allocation (see `allocations`),
+    /// empty for every other message
+    pub fn to_transactions(&self) -> Vec<Result<Transaction, ConversionError>> {
+        match self.msg_type() {
+            Some("J") | Some("AS") if self.get(TAG_ALLOC_TRANS_TYPE) == Some("0") => {
+                self.allocations()
+            }
+            _ => self.to_transaction().into_iter().collect(),
+        }
+    }
+
+    /// Maps every entry of the NoAllocs (78) group of an AllocationInstruction (J) or AllocationReport
+    /// (AS) to a transaction on its AllocAccount (79), with the Side (54) of the block as for a fill.
+    /// The amount is AllocNetMoney (154) when present, AllocQty (80) * AvgPx (6) otherwise, and the
+    /// IndividualAllocID (467) is the transaction id. A feed should carry either the fills or the
+    /// allocations of a trade, not both, or the trade is booked twice.
+    pub fn allocations(&self) -> Vec<Result<Transaction, ConversionError>> {
+        // an entry starts at its AllocAccount, the following group fields belong to it
+        let mut entries: Vec<FixMessage> = Vec::new();
+        for (tag, value) in &self.fields {
+            match *tag {
+                TAG_ALLOC_ACCOUNT => entries.push(FixMessage {
+                    fields: vec![(*tag, value.clone())],
+                }),
+                TAG_ALLOC_QTY | TAG_ALLOC_NET_MONEY | TAG_INDIVIDUAL_ALLOC_ID => {
+                    if let Some(entry) = entries.last_mut() {
+                        entry.fields.push((*tag, value.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if entries.is_empty() {
+            return vec![Err(ConversionError::Fix(
+                "allocation without NoAllocs (78) entries".to_string(),
+            ))];
+        }
+        entries
+            .iter()
+            .map(|entry| {
+                let client = ClientId(entry.parse_field(TAG_ALLOC_ACCOUNT, "AllocAccount")?);
+                let tx =
+                    TransactionId(entry.parse_field(TAG_INDIVIDUAL_ALLOC_ID, "IndividualAllocID")?);
+                let amount = match entry.get(TAG_ALLOC_NET_MONEY) {
+                    Some(_) => {
+                        entry.parse_field::<Decimal>(TAG_ALLOC_NET_MONEY, "AllocNetMoney")?
+                    }
+                    None => {
+                        let quantity: Decimal = entry.parse_field(TAG_ALLOC_QTY, "AllocQty")?;
+                        let price: Decimal = self.parse_field(TAG_AVG_PX, "AvgPx")?;
+                        quantity * price
+                    }
+                };
+                self.booked(client, tx, amount)
+            })
+            .collect()
+    }
+
+    fn fill_to_transaction(&self) -> Result<Transaction, ConversionError> {
+        let client = ClientId(self.parse_field(TAG_ACCOUNT, "Account")?);
+        let tx = TransactionId(self.parse_field(TAG_EXEC_ID, "ExecID")?);
+        let amount = match self.get(TAG_GROSS_TRADE_AMT) {
+            Some(_) => self.parse_field::<Decimal>(TAG_GROSS_TRADE_AMT, "GrossTradeAmt")?,
+            None => {
+                let quantity: Decimal = self.parse_field(TAG_LAST_QTY, "LastQty")?;
+                let price: Decimal = self.parse_field(TAG_LAST_PX, "LastPx")?;
+                quantity * price
+            }
+        };
+        self.booked(client, tx, amount)
+    }
+
+    // Sells credit the account, buys debit it
+    fn booked(
+        &self,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Decimal,
+    ) -> Result<Transaction, ConversionError> {
+        if amount.is_sign_negative() {
+            return Err(ConversionError::NegativeAmount(format!(
+                "FIX amount: {amount} must be positive"
+            )));
+        }
+        match self.get(TAG_SIDE) {
+            Some("1") => Ok(Transaction::Withdrawal { client, tx, amount }), // buy
+            Some("2") => Ok(Transaction::Deposit { client, tx, amount }),    // sell
+            side => Err(ConversionError::Fix(format!(
+                "unsupported Side (54): {side:?}"
+            ))),
+        }
+    }
+}
+
+/// Splits a byte stream into FIX messages
+pub struct FixReader<R: Read> {
+    reader: BufReader<R>,
+}
+
+impl<R: Read> FixReader<R> {
+    pub fn new(reader: R) -> FixReader<R> {
+        FixReader {
+            reader: BufReader::new(reader),
+        }
+    }
+
+    /// Reads the next complete message, `Ok(None)` at the end of the stream
+    pub fn read_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut raw = Vec::new();
+        let mut field = Vec::new();
+        loop {
+            field.clear();
+            if self.reader.read_until(SOH, &mut field)? == 0 {
+                return match raw.iter().all(u8::is_ascii_whitespace) {
+                    true => Ok(None),
+                    false => Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "stream ended in the middle of a FIX message",
+                    )),
+                };
+            }
+            // ignore line breaks between messages of a drop-copy file
+            let start = field
+                .iter()
+                .take_while(|byte| byte.is_ascii_whitespace())
+                .count();
+            let field = if raw.is_empty() {
+                &field[start..]
+            } else {
+                &field[..]
+            };
+            raw.extend_from_slice(field);
+            if field.starts_with(b"10=") {
+                return Ok(Some(raw));
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for FixReader<R> {
+    type Item = Result<FixMessage, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_message() {
+            Ok(Some(raw)) => Some(FixMessage::parse(&raw)),
+            Ok(None) => None,
+            Err(err) => Some(Err(ConversionError::Fix(err.to_string()))),
+        }
+    }
+}
+
+// Transforms a reader over FIX drop-copy messages into a iterator over transactions, messages that are
+// neither fills nor allocations are skipped
+#[instrument(skip(reader))]
+pub fn transactions_from_fix_reader<R: Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+    FixReader::new(reader).flat_map(|message| match message {
+        Ok(message) => {
+            trace!(msg_type = ?message.msg_type(), "Received FIX message");
+            message.to_transactions()
+        }
+        Err(err) => vec![Err(err)],
+    })
+}
+
+/// Current time as a FIX UTCTimestamp (YYYYMMDD-HH:MM:SS)
+fn utc_timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}:{:02}:{:02}",
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Initiator side of a FIX drop-copy session: logs on and yields the fills as transactions,
+/// answering heartbeats and test requests on the way
+pub struct FixSession {
+    stream: TcpStream,
+    messages: FixReader<TcpStream>,
+    sender_comp_id: String,
+    target_comp_id: String,
+    next_seq_num: u64,
+}
+
+impl FixSession {
+    /// Connects and sends the Logon (A) message
+    #[instrument]
+    pub fn connect<A: ToSocketAddrs + std::fmt::Debug>(
+        addr: A,
+        sender_comp_id: &str,
+        target_comp_id: &str,
+        heartbeat_interval_secs: u32,
+    ) -> io::Result<FixSession> {
+        let stream = TcpStream::connect(addr)?;
+        let mut session = FixSession {
+            messages: FixReader::new(stream.try_clone()?),
+            stream,
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            next_seq_num: 1,
+        };
+        session.send(
+            "A",
+            vec![
+                (98, "0".to_string()), // EncryptMethod: none
+                (108, heartbeat_interval_secs.to_string()),
+            ],
+        )?;
+        info!("Sent FIX logon");
+        Ok(session)
+    }
+
+    fn send(&mut self, msg_type: &str, mut fields: Vec<(u32, String)>) -> io::Result<()> {
+        let mut header = vec![
+            (TAG_SENDER_COMP_ID, self.sender_comp_id.clone()),
+            (TAG_TARGET_COMP_ID, self.target_comp_id.clone()),
+            (TAG_MSG_SEQ_NUM, self.next_seq_num.to_string()),
+            (TAG_SENDING_TIME, utc_timestamp()),
+        ];
+        header.append(&mut fields);
+        self.stream
+            .write_all(&FixMessage::encode(msg_type, &header))?;
+        self.next_seq_num += 1;
+        Ok(())
+    }
+
+    /// Session level handling, returns true if the message was consumed by the session
+    fn handle_admin(&mut self, message: &FixMessage) -> io::Result<bool> {
+        match message.msg_type() {
+            Some("1") => {
+                // TestRequest: answer with a Heartbeat echoing the TestReqID
+                let test_req_id = message.get(TAG_TEST_REQ_ID).unwrap_or_default().to_string();
+                self.send("0", vec![(TAG_TEST_REQ_ID, test_req_id)])?;
+                Ok(true)
+            }
+            Some("0") | Some("A") => Ok(true), // heartbeat, logon acknowledgement
+            Some("5") => {
+                info!("Counterparty logged out");
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Iterator over the fills and allocations received in the session, ends when the connection is closed
+    pub fn transactions(mut self) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+        let mut pending = VecDeque::new(); // the other transactions of a multi-account allocation
+        std::iter::from_fn(move || {
+            if let Some(transaction) = pending.pop_front() {
+                return Some(transaction);
+            }
+            loop {
+                let message = match self.messages.next()? {
+                    Ok(message) => message,
+                    Err(err) => return Some(Err(err)),
+                };
+                match self.handle_admin(&message) {
+                    Ok(true) => debug!(msg_type = ?message.msg_type(), "Handled session message"),
+                    Ok(false) => {
+                        pending.extend(message.to_transactions());
+                        if let Some(transaction) = pending.pop_front() {
+                            return Some(transaction);
+                        }
+                        trace!(msg_type = ?message.msg_type(), "Skipping non fill message");
+                    }
+                    Err(err) => {
+                        warn!(%err, "Failed to answer session message");
+                        return Some(Err(ConversionError::Fix(err.to_string())));
+                    }
+                }
+            }
+        })
+    }
+}
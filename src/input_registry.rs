@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+/// What to do with an input file that was already processed by an earlier run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    #[default]
+    Refuse, // fail the run
+    Skip, // warn and exit without processing
+}
+
+/// One processed input file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ProcessedInput {
+    pub sha256: String,
+    pub path: PathBuf,
+    pub processed_at: u64, // unix seconds
+}
+
+/// Registry of the input files processed across runs, persisted as an append-only csv.
+/// Files are identified by content hash so a renamed copy of an old file is still detected.
+#[derive(Debug)]
+pub struct InputRegistry {
+    path: PathBuf,
+    processed: HashMap<String, ProcessedInput>,
+}
+
+impl InputRegistry {
+    /// Loads the registry, a missing file is an empty registry
+    #[instrument]
+    pub fn open(path: &Path) -> Result<InputRegistry, csv::Error> {
+        let mut processed = HashMap::new();
+        if path.exists() {
+            for record in csv::Reader::from_path(path)?.into_deserialize::<ProcessedInput>() {
+                let record = record?;
+                processed.insert(record.sha256.clone(), record);
+            }
+        }
+        info!(files = processed.len(), "Loaded processed inputs registry");
+        Ok(InputRegistry {
+            path: path.to_path_buf(),
+            processed,
+        })
+    }
+
+    /// The earlier run of a file with this content, if any
+    pub fn find(&self, sha256: &str) -> Option<&ProcessedInput> {
+        self.processed.get(sha256)
+    }
+
+    /// Records a processed input, to be called once the run succeeded
+    pub fn record(&mut self, sha256: &str, input: &Path) -> Result<(), csv::Error> {
+        let new_file = !self.path.exists();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(new_file)
+            .from_writer(file);
+        let processed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let record = ProcessedInput {
+            sha256: sha256.to_string(),
+            path: input.to_path_buf(),
+            processed_at,
+        };
+        csv_writer.serialize(&record)?;
+        csv_writer.flush()?;
+        self.processed.insert(record.sha256.clone(), record);
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    model::{Account, ClientId, Clients, Transaction, TransactionId},
+    observer::TransactionObserver,
+};
+
+/// What to do when the end of run conservation check fails
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantMode {
+    #[default]
+    Warn, // log the discrepancy
+    Strict, // fail the run
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error(
+    "funds are not conserved: opening + deposits - withdrawals - chargebacks - erased = {expected}, accounts total {actual}"
+)]
+pub struct ConservationError {
+    pub expected: Decimal,
+    pub actual: Decimal,
+}
+
+/// Counts the money flowing in and out of the engine, independently of the account balances.
+/// At the end of the run opening balances + deposits - withdrawals - chargebacks - erased funds
+/// (+ the net change of the custom transactions) must equal the sum of the account totals, a difference means a
+/// transaction was applied inconsistently.
+#[derive(Debug, Default)]
+pub struct ConservationCheck {
+    pub opening: Decimal, // seeded totals, see `Clients::open_account`
+    pub deposits: Decimal,
+    pub withdrawals: Decimal,
+    pub chargebacks: Decimal,
+    pub erased: Decimal, // funds of erased clients, written off or transferred out
+    pub custom: Decimal, // net change of the totals by custom transaction types
+    totals: HashMap<ClientId, Decimal>, // latest total of each client, to account for erasures
+}
+
+impl TransactionObserver for ConservationCheck {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        if before == after {
+            return; // ignored transaction
+        }
+        match transaction {
+            Transaction::Deposit { amount, .. } => self.deposits += amount,
+            Transaction::Withdrawal { amount, .. } => self.withdrawals += amount,
+            // the charged back amount is the disputed deposit, released from the held funds
+            Transaction::Chargeback { .. } => self.chargebacks += before.held() - after.held(),
+            Transaction::Dispute { .. } | Transaction::Resolve { .. } => {}
+            Transaction::Custom(_) => self.custom += after.total() - before.total(),
+        }
+        self.totals.insert(client, after.total());
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.erased += self.totals.remove(&client).unwrap_or_default();
+    }
+
+    fn on_merge(&mut self, from: ClientId, into: ClientId, account: &Account) {
+        self.totals.remove(&from);
+        self.totals.insert(into, account.total());
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        self.opening += account.total();
+        self.totals.insert(client, account.total());
+    }
+}
+
+impl ConservationCheck {
+    /// Compares the counters with the accounts once every transaction was applied
+    #[instrument(skip(self, clients))]
+    pub fn verify(&self, clients: &Clients) -> Result<(), ConservationError> {
+        let expected =
+            self.opening + self.deposits - self.withdrawals - self.chargebacks - self.erased
+                + self.custom;
+        let actual: Decimal = clients.accounts.values().map(Account::total).sum();
+        if expected != actual {
+            error!(%expected, %actual, ?self, "Funds are not conserved");
+            return Err(ConservationError { expected, actual });
+        }
+        info!(%actual, "Funds are conserved");
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("the available funds of {clients} clients went below {floor}")]
+pub struct BalanceFloorError {
+    pub floor: Decimal,
+    pub clients: usize,
+}
+
+/// A client whose available funds went below the floor during the run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloorBreach {
+    pub lowest: Decimal,         // lowest available funds reached
+    pub available: Decimal,      // latest available funds, possibly back above the floor
+    pub txs: Vec<TransactionId>, // transactions that lowered the available funds below the floor
+}
+
+/// Row of the balance floor report, the tx ids are space separated
+#[derive(Debug, Serialize)]
+struct CsvFloorBreach<'a> {
+    client: ClientId,
+    lowest_available: Decimal,
+    available: Decimal,
+    txs: &'a str,
+}
+
+/// Records the clients whose available funds drop below a floor (0 by default), e.g. a dispute
+/// of a deposit that was already withdrawn, with the transactions that took them there.
+#[derive(Debug, Default)]
+pub struct BalanceFloorCheck {
+    pub floor: Decimal,
+    breaches: BTreeMap<ClientId, FloorBreach>,
+}
+
+impl TransactionObserver for BalanceFloorCheck {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        let available = after.available();
+        if available < self.floor && available < before.available() {
+            warn!(%client, tx = %transaction.tx_id(), %available, floor = %self.floor, "Available funds below the floor");
+            let breach = self.breaches.entry(client).or_insert(FloorBreach {
+                lowest: available,
+                available,
+                txs: Vec::new(),
+            });
+            breach.lowest = breach.lowest.min(available);
+            breach.txs.push(transaction.tx_id());
+        }
+        if let Some(breach) = self.breaches.get_mut(&client) {
+            breach.available = available;
+        }
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.breaches.remove(&client);
+    }
+}
+
+impl BalanceFloorCheck {
+    pub fn new(floor: Decimal) -> BalanceFloorCheck {
+        BalanceFloorCheck {
+            floor,
+            ..Default::default()
+        }
+    }
+
+    /// Clients that went below the floor, by client id
+    pub fn breaches(&self) -> &BTreeMap<ClientId, FloorBreach> {
+        &self.breaches
+    }
+
+    /// Logs every client that went below the floor, fails if there is any
+    #[instrument(skip(self))]
+    pub fn verify(&self) -> Result<(), BalanceFloorError> {
+        if self.breaches.is_empty() {
+            info!(floor = %self.floor, "No available funds went below the floor");
+            return Ok(());
+        }
+        for (client, breach) in &self.breaches {
+            error!(%client, lowest = %breach.lowest, available = %breach.available, txs = ?breach.txs, "Available funds went below the floor");
+        }
+        Err(BalanceFloorError {
+            floor: self.floor,
+            clients: self.breaches.len(),
+        })
+    }
+
+    /// Writes the clients that went below the floor as csv
+    pub fn write_report<W: io::Write>(&self, wtr: W) -> Result<(), csv::Error> {
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(wtr);
+        wtr.write_record(["client", "lowest_available", "available", "txs"])?; // even when empty
+        for (client, breach) in &self.breaches {
+            let txs: Vec<String> = breach.txs.iter().map(ToString::to_string).collect();
+            wtr.serialize(CsvFloorBreach {
+                client: *client,
+                lowest_available: breach.lowest,
+                available: breach.available,
+                txs: &txs.join(" "),
+            })?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+use quick_xml::{Reader, events::Event};
+use rust_decimal::Decimal;
+use tracing::{instrument, trace};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{ClientId, Transaction, TransactionId},
+};
+
+/// Direction of a booked entry (`CdtDbtInd`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Credit,
+    Debit,
+}
+
+/// Fields collected while reading one statement entry (`Ntry`) or payment (`CdtTrfTxInf`)
+#[derive(Debug, Default)]
+struct Entry {
+    amount: Option<String>,
+    direction: Option<Direction>,
+    reversal: bool,            // reversed (`RvslInd`) or returned (`RtrInf`) entry
+    reference: Option<String>, // NtryRef or EndToEndId, used as the transaction id
+    original_reference: Option<String>, // for returns: reference of the entry being returned
+}
+
+/// Streams the entries of ISO 20022 messages as transactions:
+/// - camt.053 (bank to customer statement): the statement account (`Acct/Id/Othr/Id`) is the client,
+///   credit entries are deposits, debit entries are withdrawals and `NtryRef` is the tx id.
+///   A returned entry (`RvslInd` true) reverses the deposit referenced by its `EndToEndId`, it is
+///   mapped to a dispute immediately followed by a chargeback of that deposit.
+/// - camt.054 (bank to customer debit/credit notification): entries like camt.053, of the
+///   notification account. An entry without `NtryRef` uses the `EndToEndId` of its transaction as
+///   the tx id, and an entry with return information (`RtrInf`) is a return like a reversed entry.
+/// - pain.001 (customer credit transfer initiation): every `CdtTrfTxInf` is a withdrawal from the
+///   debtor account (`DbtrAcct/Id/Othr/Id`) with its `EndToEndId` as the tx id.
+pub struct Iso20022Transactions<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    path: Vec<String>, // local names of the currently open elements
+    account: Option<String>,
+    entry: Option<Entry>,
+    pending: VecDeque<Result<Transaction, ConversionError>>,
+    done: bool,
+}
+
+// Loads the xml in path as a Iterator over transactions
+#[instrument]
+pub fn read_transactions_from_iso20022(
+    xml_path: &Path,
+) -> Result<Iso20022Transactions<BufReader<File>>, ConversionError> {
+    let file = File::open(xml_path).map_err(|err| ConversionError::Iso20022(err.to_string()))?;
+    Ok(transactions_from_iso20022_reader(BufReader::new(file)))
+}
+
+// Transforms a reader over an ISO 20022 xml document into a iterator over transactions
+pub fn transactions_from_iso20022_reader<R: BufRead>(reader: R) -> Iso20022Transactions<R> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+    Iso20022Transactions {
+        reader,
+        buf: Vec::new(),
+        path: Vec::new(),
+        account: None,
+        entry: None,
+        pending: VecDeque::new(),
+        done: false,
+    }
+}
+
+fn parse_id<T: FromStr>(field: &str, value: Option<&String>) -> Result<T, ConversionError> {
+    let value = value.ok_or(ConversionError::Iso20022(format!("missing {field}")))?;
+    value
+        .parse()
+        .map_err(|_| ConversionError::Iso20022(format!("{field} is not a numeric id: {value}")))
+}
+
+impl<R: BufRead> Iso20022Transactions<R> {
+    fn ends_with(&self, suffix: &[&str]) -> bool {
+        self.path.len() >= suffix.len()
+            && self.path[self.path.len() - suffix.len()..]
+                .iter()
+                .zip(suffix)
+                .all(|(element, expected)| element == expected)
+    }
+
+    fn on_text(&mut self, text: String) {
+        if self.ends_with(&["Acct", "Id", "Othr", "Id"])
+            || self.ends_with(&["DbtrAcct", "Id", "Othr", "Id"])
+        {
+            self.account = Some(text);
+            return;
+        }
+        let is_payment = self.path.iter().any(|element| element == "CdtTrfTxInf");
+        let amount = if is_payment {
+            self.ends_with(&["InstdAmt"])
+        } else {
+            self.ends_with(&["Ntry", "Amt"])
+        };
+        let reference = if is_payment {
+            self.ends_with(&["PmtId", "EndToEndId"])
+        } else {
+            self.ends_with(&["Ntry", "NtryRef"])
+        };
+        let original_reference = !is_payment && self.ends_with(&["Refs", "EndToEndId"]);
+        let direction = self.ends_with(&["Ntry", "CdtDbtInd"]);
+        let reversal = self.ends_with(&["Ntry", "RvslInd"]);
+
+        let Some(entry) = self.entry.as_mut() else {
+            return;
+        };
+        if amount {
+            entry.amount = Some(text);
+        } else if reference {
+            entry.reference = Some(text);
+        } else if original_reference {
+            entry.original_reference = Some(text);
+        } else if direction {
+            entry.direction = match text.as_str() {
+                "CRDT" => Some(Direction::Credit),
+                "DBIT" => Some(Direction::Debit),
+                _ => None,
+            };
+        } else if reversal {
+            entry.reversal |= text == "true";
+        }
+    }
+
+    /// Converts a complete entry into one or more transactions
+    fn convert(&self, entry: Entry) -> Result<Vec<Transaction>, ConversionError> {
+        let client: ClientId = ClientId(parse_id("account id", self.account.as_ref())?);
+        if entry.reversal {
+            let tx = TransactionId(parse_id(
+                "returned entry reference",
+                entry
+                    .original_reference
+                    .as_ref()
+                    .or(entry.reference.as_ref()),
+            )?);
+            return Ok(vec![
+                Transaction::Dispute { client, tx },
+                Transaction::Chargeback { client, tx },
+            ]);
+        }
+        let tx = TransactionId(parse_id(
+            "entry reference",
+            entry
+                .reference
+                .as_ref()
+                .or(entry.original_reference.as_ref()),
+        )?);
+        let amount = entry.amount.ok_or(ConversionError::MissingAmount(
+            "ISO 20022 entry".to_string(),
+        ))?;
+        let amount = Decimal::from_str_exact(&amount)?;
+        if amount.is_sign_negative() {
+            return Err(ConversionError::NegativeAmount(format!(
+                "entry amount: {amount} must be positive"
+            )));
+        }
+        Ok(vec![match entry.direction {
+            Some(Direction::Credit) => Transaction::Deposit { client, tx, amount },
+            Some(Direction::Debit) => Transaction::Withdrawal { client, tx, amount },
+            None => Err(ConversionError::Iso20022(format!(
+                "entry {tx} has no valid credit/debit indicator"
+            )))?,
+        }])
+    }
+
+    fn on_end(&mut self, name: &str) {
+        if matches!(name, "Ntry" | "CdtTrfTxInf")
+            && let Some(entry) = self.entry.take()
+        {
+            trace!(?entry, "Converting ISO 20022 entry");
+            match self.convert(entry) {
+                Ok(transactions) => self.pending.extend(transactions.into_iter().map(Ok)),
+                Err(err) => self.pending.push_back(Err(err)),
+            }
+        }
+        self.path.pop();
+    }
+}
+
+impl<R: BufRead> Iterator for Iso20022Transactions<R> {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(transaction) = self.pending.pop_front() {
+                return Some(transaction);
+            }
+            if self.done {
+                return None;
+            }
+            self.buf.clear();
+            // owned event so that the buffer is not borrowed while the parser state is updated
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map(|event| event.into_owned());
+            match event {
+                Ok(Event::Start(element)) => {
+                    let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                    match name.as_str() {
+                        "Ntry" => self.entry = Some(Entry::default()),
+                        "CdtTrfTxInf" => {
+                            self.entry = Some(Entry {
+                                direction: Some(Direction::Debit), // payments leave the debtor account
+                                ..Default::default()
+                            })
+                        }
+                        "RtrInf" => {
+                            if let Some(entry) = self.entry.as_mut() {
+                                entry.reversal = true;
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.path.push(name);
+                }
+                Ok(Event::End(element)) => {
+                    let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                    self.on_end(&name);
+                }
+                Ok(Event::Text(text)) => match text.unescape() {
+                    Ok(text) => self.on_text(text.into_owned()),
+                    Err(err) => return Some(Err(ConversionError::Iso20022(err.to_string()))),
+                },
+                Ok(Event::Eof) => self.done = true,
+                Ok(_) => {}
+                Err(err) => {
+                    // the document is broken, there is no way to resynchronize
+                    self.done = true;
+                    return Some(Err(ConversionError::Iso20022(err.to_string())));
+                }
+            }
+        }
+    }
+}
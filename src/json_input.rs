@@ -0,0 +1,80 @@
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::{
+    csv_input::{ConversionError, open_input},
+    model::{ClientId, InputCsvRecord, Transaction, TransactionId, TransactionKind},
+};
+
+/// Amounts are accepted as json numbers or as strings (exact, e.g. "1.0001")
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonAmount {
+    Text(String),
+    Number(serde_json::Number),
+}
+
+/// One line of the input: `{"type": "deposit", "client": 1, "tx": 1, "amount": 1.5}`,
+/// also the shape of the records of the other self-describing formats (e.g. MessagePack)
+#[derive(Debug, Deserialize)]
+pub(crate) struct JsonRecord {
+    #[serde(rename = "type")]
+    transaction_type: TransactionKind,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<JsonAmount>,
+}
+
+impl TryFrom<JsonRecord> for InputCsvRecord {
+    type Error = ConversionError;
+
+    fn try_from(record: JsonRecord) -> Result<Self, Self::Error> {
+        let amount = match record.amount {
+            Some(JsonAmount::Text(amount)) => Some(Decimal::from_str_exact(&amount)?),
+            // the shortest representation of the number is what the producer wrote
+            Some(JsonAmount::Number(amount)) => Some(Decimal::from_str_exact(&amount.to_string())?),
+            None => None,
+        };
+        Ok(InputCsvRecord {
+            transaction_type: record.transaction_type,
+            client: record.client,
+            tx: record.tx,
+            amount,
+        })
+    }
+}
+
+// Loads the json lines file in path as a Iterator over transactions
+#[instrument]
+pub fn read_transactions_from_json(
+    json_path: &Path,
+) -> Result<impl Iterator<Item = Result<Transaction, ConversionError>>, ConversionError> {
+    let file = open_input(json_path).map_err(|err| ConversionError::Json(err.to_string()))?;
+    Ok(transactions_from_json_reader(BufReader::new(file)))
+}
+
+// Transforms a reader over newline-delimited json into a iterator over transactions, blank lines are skipped
+pub fn transactions_from_json_reader<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+    reader
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|line| {
+            let line = line.map_err(|err| ConversionError::Json(err.to_string()))?;
+            transaction_from_json(&line)
+        })
+}
+
+/// Converts a single json object, e.g. a line of the input or a message payload
+pub fn transaction_from_json(json: &str) -> Result<Transaction, ConversionError> {
+    let record: JsonRecord = serde_json::from_str(json)
+        .map_err(|err| ConversionError::Json(format!("{err}: {json}")))?;
+    Transaction::try_from(InputCsvRecord::try_from(record)?)
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use rdkafka::{
+    ClientConfig, Message,
+    consumer::{BaseConsumer, Consumer},
+    error::KafkaError,
+    types::RDKafkaErrorCode,
+};
+use tracing::{info, instrument, trace};
+
+use crate::{
+    cancel::CancellationToken,
+    csv_input::{ConversionError, CsvOptions, transactions_from_reader},
+    json_input::transaction_from_json,
+    model::Transaction,
+};
+
+/// Encoding of the message payloads
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    #[default]
+    Json, // `{"type": "deposit", "client": 1, "tx": 1, "amount": 1.5}`
+    Csv, // a headerless row: `deposit,1,1,1.5`
+}
+
+/// Where and how to consume the transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KafkaConfig {
+    pub brokers: String, // e.g. localhost:9092
+    pub topic: String,
+    pub group_id: String,
+    pub payload: PayloadFormat,
+    pub exit_at_end: bool, // stop once every partition is consumed instead of waiting for new messages
+}
+
+impl KafkaConfig {
+    /// Parses `<brokers>/<topic>`, e.g. `localhost:9092/transactions`
+    pub fn from_source(source: &str) -> Option<KafkaConfig> {
+        let (brokers, topic) = source.rsplit_once('/')?;
+        (!brokers.is_empty() && !topic.is_empty()).then(|| KafkaConfig {
+            brokers: brokers.to_string(),
+            topic: topic.to_string(),
+            group_id: "tx_engine".to_string(),
+            payload: PayloadFormat::default(),
+            exit_at_end: false,
+        })
+    }
+}
+
+/// Converts a message payload to a transaction
+pub fn decode_payload(
+    format: PayloadFormat,
+    payload: &[u8],
+) -> Result<Transaction, ConversionError> {
+    match format {
+        PayloadFormat::Json => {
+            let json = std::str::from_utf8(payload)
+                .map_err(|err| ConversionError::Json(err.to_string()))?;
+            transaction_from_json(json)
+        }
+        PayloadFormat::Csv => {
+            let options = CsvOptions {
+                has_headers: false,
+                flexible: true, // the amount of disputes, resolves and chargebacks can be omitted
+                ..Default::default()
+            };
+            transactions_from_reader(options.reader_builder().from_reader(payload))
+                .next()
+                .unwrap_or_else(|| Err(ConversionError::Kafka("empty payload".to_string())))
+        }
+    }
+}
+
+/// Transactions consumed from a topic, the iterator blocks while waiting for new messages and only
+/// ends with `exit_at_end` once the end of every partition is reached.
+/// Offsets are committed automatically (at least once: a restarted consumer may see a message twice).
+pub struct KafkaTransactions {
+    consumer: BaseConsumer,
+    config: KafkaConfig,
+    partitions_at_end: usize,
+    stopped: bool,
+    cancellation: CancellationToken,
+}
+
+impl KafkaTransactions {
+    /// Stops consuming as soon as the token is cancelled, the offsets of the messages returned are committed
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> KafkaTransactions {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+// Subscribes to the topic of the config
+#[instrument]
+pub fn consume_transactions_from_kafka(
+    config: KafkaConfig,
+) -> Result<KafkaTransactions, ConversionError> {
+    let kafka_error = |err: KafkaError| ConversionError::Kafka(err.to_string());
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &config.brokers)
+        .set("group.id", &config.group_id)
+        .set("auto.offset.reset", "earliest")
+        .set("enable.partition.eof", config.exit_at_end.to_string())
+        .create()
+        .map_err(kafka_error)?;
+    consumer.subscribe(&[&config.topic]).map_err(kafka_error)?;
+    info!(topic = config.topic, "Subscribed to kafka topic");
+    Ok(KafkaTransactions {
+        consumer,
+        config,
+        partitions_at_end: 0,
+        stopped: false,
+        cancellation: CancellationToken::default(),
+    })
+}
+
+impl Iterator for KafkaTransactions {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
+        loop {
+            if self.cancellation.is_cancelled() {
+                info!("Cancelled, no longer consuming the topic");
+                return None;
+            }
+            let Some(polled) = self.consumer.poll(Duration::from_secs(1)) else {
+                continue; // no new message yet
+            };
+            match polled {
+                Ok(message) => {
+                    trace!(
+                        partition = message.partition(),
+                        offset = message.offset(),
+                        "Consumed message"
+                    );
+                    return Some(decode_payload(
+                        self.config.payload,
+                        message.payload().unwrap_or_default(),
+                    ));
+                }
+                Err(KafkaError::PartitionEOF(partition)) => {
+                    info!(partition, "Reached the end of a partition");
+                    self.partitions_at_end += 1;
+                    let assigned = self
+                        .consumer
+                        .assignment()
+                        .map(|assignment| assignment.count())
+                        .unwrap_or_default();
+                    if self.partitions_at_end >= assigned {
+                        return None;
+                    }
+                }
+                Err(KafkaError::MessageConsumption(RDKafkaErrorCode::UnknownTopicOrPartition)) => {
+                    self.stopped = true; // would fail forever
+                    return Some(Err(ConversionError::Kafka(format!(
+                        "unknown topic {}",
+                        self.config.topic
+                    ))));
+                }
+                Err(err) => return Some(Err(ConversionError::Kafka(err.to_string()))),
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::HashSet, io};
+
+use rust_decimal::Decimal;
+use tracing::error;
+
+use crate::{
+    model::{Account, ClientId, Transaction},
+    observer::TransactionObserver,
+};
+
+/// Engine side of every posting: funds entering or leaving the client accounts
+const CLEARING_ACCOUNT: &str = "Equity:Engine:Clearing";
+/// Other side of the opening balances, funds that were in the client accounts before the engine
+const OPENING_ACCOUNT: &str = "Equity:Opening-Balances";
+
+/// Text format of the double-entry export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LedgerFormat {
+    #[default]
+    Beancount,
+    LedgerCli,
+}
+
+/// Settings of the double-entry export
+#[derive(Debug, Clone)]
+pub struct LedgerExportConfig {
+    pub format: LedgerFormat,
+    pub date: String, // transactions carry no timestamp, every entry is booked on this date (YYYY-MM-DD)
+    pub commodity: String, // currency/commodity symbol of the amounts
+}
+
+impl Default for LedgerExportConfig {
+    fn default() -> Self {
+        LedgerExportConfig {
+            format: LedgerFormat::Beancount,
+            date: "1970-01-01".to_string(),
+            commodity: "CUR".to_string(),
+        }
+    }
+}
+
+/// Renders every applied transaction as balanced double-entry postings.
+/// Each client has an `Available` and a `Held` asset account, the other side of deposits, withdrawals
+/// and chargebacks is the engine clearing account. Ignored transactions produce no entry.
+pub struct LedgerExporter<W: io::Write> {
+    wtr: W,
+    config: LedgerExportConfig,
+    opened_accounts: HashSet<String>, // beancount requires an open directive before the first use
+}
+
+impl<W: io::Write> LedgerExporter<W> {
+    pub fn new(wtr: W, config: LedgerExportConfig) -> LedgerExporter<W> {
+        LedgerExporter {
+            wtr,
+            config,
+            opened_accounts: HashSet::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+
+    fn open_account(&mut self, account: &str) -> io::Result<()> {
+        if self.config.format == LedgerFormat::Beancount
+            && self.opened_accounts.insert(account.to_string())
+        {
+            writeln!(self.wtr, "{} open {account}", self.config.date)?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, description: &str, postings: &[(String, Decimal)]) -> io::Result<()> {
+        for (account, _) in postings {
+            self.open_account(account)?;
+        }
+        match self.config.format {
+            LedgerFormat::Beancount => {
+                writeln!(self.wtr, "{} * \"{description}\"", self.config.date)?
+            }
+            LedgerFormat::LedgerCli => writeln!(self.wtr, "{} * {description}", self.config.date)?,
+        }
+        for (account, amount) in postings {
+            writeln!(self.wtr, "  {account}  {amount} {}", self.config.commodity)?;
+        }
+        writeln!(self.wtr)?;
+        Ok(())
+    }
+}
+
+impl<W: io::Write + Send> TransactionObserver for LedgerExporter<W> {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        let available_delta = after.available() - before.available();
+        let held_delta = after.held() - before.held();
+        let clearing_delta = -(available_delta + held_delta); // postings always balance to zero
+
+        let postings: Vec<(String, Decimal)> = [
+            (
+                format!("Assets:Clients:C{client}:Available"),
+                available_delta,
+            ),
+            (format!("Assets:Clients:C{client}:Held"), held_delta),
+            (CLEARING_ACCOUNT.to_string(), clearing_delta),
+        ]
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(account, amount)| (account, amount.normalize()))
+        .collect();
+
+        if postings.is_empty() {
+            return; // transaction was ignored, nothing to book
+        }
+        let description = format!(
+            "{} tx {} client {client}",
+            transaction.type_name(),
+            transaction.tx_id()
+        );
+        if let Err(err) = self.write_entry(&description, &postings) {
+            error!(%err, %client, ?transaction, "failed to write ledger entry");
+        }
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        let postings: Vec<(String, Decimal)> = [
+            (
+                format!("Assets:Clients:C{client}:Available"),
+                account.available(),
+            ),
+            (format!("Assets:Clients:C{client}:Held"), account.held()),
+            (OPENING_ACCOUNT.to_string(), -account.total()),
+        ]
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(account, amount)| (account, amount.normalize()))
+        .collect();
+        if postings.is_empty() {
+            return;
+        }
+        let description = format!("opening balance client {client}");
+        if let Err(err) = self.write_entry(&description, &postings) {
+            error!(%err, %client, "failed to write ledger entry");
+        }
+    }
+}
@@ -1,16 +1,82 @@
 use std::{
     io,
-    sync::mpsc::Receiver,
     thread::{self, JoinHandle},
 };
 
-use csv::Writer;
-use model::{Account, ClientId, CsvOutputAccount};
-use tracing::error;
+use model::{Account, ClientId};
+use output::{
+    AccountWriter, OutputOptions, OutputWriter, SerializationFailurePolicy, WriterError,
+    WriterSummary,
+};
 use tracing_subscriber::EnvFilter;
 
+pub mod alerts;
+#[cfg(feature = "async")]
+pub mod async_engine;
+pub mod audit;
+pub mod cancel;
+pub mod cardinality;
+pub mod case_management;
+pub mod channel;
+pub mod checkpoint;
+pub mod cli;
 pub mod csv_input;
+pub mod custom_types;
+#[cfg(feature = "dense-accounts")]
+pub mod dense_accounts;
+pub mod encoding;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+pub mod filter;
+#[cfg(feature = "fix")]
+pub mod fix_input;
+pub mod input_registry;
+pub mod invariants;
+pub mod iso20022_input;
+pub mod json_input;
+#[cfg(feature = "kafka")]
+pub mod kafka_input;
+pub mod ledger_export;
+pub mod manifest;
+pub mod merge;
+pub mod metrics;
 pub mod model;
+mod money;
+#[cfg(feature = "msgpack")]
+pub mod msgpack_input;
+pub mod normalize;
+pub mod notify;
+pub mod observer;
+pub mod opening_balances;
+pub mod output;
+#[cfg(feature = "parquet")]
+pub mod parquet_input;
+#[cfg(feature = "parquet")]
+pub mod parquet_output;
+pub mod partition;
+pub mod query;
+#[cfg(feature = "remote")]
+pub mod remote_input;
+pub mod reports;
+mod rng;
+pub mod rules;
+pub mod run_metadata;
+#[cfg(feature = "s3")]
+pub mod s3_input;
+pub mod simulation;
+pub mod sink;
+pub mod snapshot;
+pub mod statement_export;
+pub mod table_output;
+pub mod tenants;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod trailer;
+pub mod transform;
+pub mod trust;
+pub mod tuning;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring_input;
 
 pub fn setup_tracing_logs() {
     tracing_subscriber::fmt()
@@ -24,28 +90,48 @@ pub fn setup_tracing_logs() {
         .init();
 }
 
-pub fn spawn_writer_thread<W: io::Write + Send + 'static>(
+/// Writes the accounts received from a channel, e.g. the `Receiver` of the `Sender` given to
+/// `Clients::new` or of an `account_channel`, until it is closed
+pub fn spawn_writer_thread<W, R>(wtr: W, rx: R) -> JoinHandle<W>
+where
+    W: io::Write + Send + 'static,
+    R: IntoIterator<Item = (ClientId, Account)> + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut account_writer = AccountWriter::new(wtr, SerializationFailurePolicy::Skip);
+        //channel is closed when nothing else needs to be written
+        for (client, account) in rx {
+            account_writer.write(client, &account);
+        }
+        let (wtr, _summary) = account_writer.finish().expect("failed to flush");
+        wtr
+    })
+}
+
+/// Like `spawn_writer_thread`, with a configurable behavior when an account fails to serialize.
+/// With `SerializationFailurePolicy::Abort` the remaining accounts are drained but not written,
+/// and joining the thread returns the error. The accounts are written in the given format.
+pub fn spawn_writer_thread_with_policy<W, R>(
     wtr: W,
-    rx: Receiver<(ClientId, Account)>,
-) -> JoinHandle<Writer<W>> {
+    rx: R,
+    policy: SerializationFailurePolicy,
+    options: OutputOptions,
+) -> JoinHandle<Result<(W, WriterSummary), WriterError>>
+where
+    W: io::Write + Send + 'static,
+    R: IntoIterator<Item = (ClientId, Account)> + Send + 'static,
+{
     thread::spawn(move || {
-        let mut csv_writer = csv::WriterBuilder::new().from_writer(wtr);
-        loop {
-            match rx.recv() {
-                Ok((client, account)) => {
-                    if let Err(err) =
-                        csv_writer.serialize(CsvOutputAccount::from((&client, &account)))
-                    {
-                        error!(%err, %client, ?account, "failed to serialize account");
-                    }
-                }
-                Err(_err) => {
-                    //channel was closed indicating nothing else needs to be written
-                    csv_writer.flush().expect("failed to flush");
-                    break;
-                }
+        let mut output_writer = match OutputWriter::new(wtr, policy, options) {
+            Ok(output_writer) => output_writer,
+            Err(err) => {
+                rx.into_iter().for_each(drop); // the accounts are still drained so that senders do not fail
+                return Err(err);
             }
+        };
+        for (client, account) in rx {
+            output_writer.write(client, &account);
         }
-        csv_writer
+        output_writer.finish()
     })
 }
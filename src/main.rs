@@ -1,36 +1,1070 @@
-use std::{env, io, path::Path};
-use tracing::info;
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, LineWriter},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::SystemTime,
+};
+use tracing::{error, info, warn};
+#[cfg(feature = "fault-injection")]
+use tx_engine::fault_injection::{FaultyReader, FaultyWriter};
 use tx_engine::{
-    csv_input::read_transactions_from_csv, model::Clients, setup_tracing_logs, spawn_writer_thread,
+    alerts::{BalanceAlert, BalanceThresholds, Threshold, ThresholdMonitor},
+    audit::AuditJournal,
+    cancel::{CancellationToken, IngestionProgress, cancellable},
+    cardinality::estimate_cardinality,
+    case_management::{
+        apply_case_decisions, preview_case_decisions, read_case_decisions, write_decision_preview,
+        write_dispute_cases,
+    },
+    checkpoint::{DEFAULT_CHECKPOINT_EVERY, Snapshot},
+    cli::{Args, Command, InputFormat, NormalizeConfig, USAGE},
+    csv_input::{
+        ConversionError, follow_transactions_from_csv, read_transactions_from_csv_with_options,
+        read_transactions_from_csv_with_quarantine,
+    },
+    filter::filter_transactions,
+    input_registry::{DuplicatePolicy, InputRegistry},
+    invariants::{BalanceFloorCheck, ConservationCheck, InvariantMode},
+    iso20022_input::read_transactions_from_iso20022,
+    json_input::read_transactions_from_json,
+    ledger_export::{LedgerExportConfig, LedgerExporter},
+    manifest::{CompletionManifest, RunManifest, RunStatus},
+    merge::{TxIdNamespaces, merge_sources},
+    metrics::Metrics,
+    model::{Clients, ProcessingStats, Transaction},
+    normalize::normalize,
+    notify::{NotificationSummary, OverflowPolicy, spawn_notification_sink},
+    opening_balances::{open_accounts, read_opening_balances},
+    output::{AtomicOutput, OutputOptions},
+    partition::{
+        PartWriters, PartitionPlan, count_client_transactions, spawn_partitioned_writer_thread,
+    },
+    query::run_query,
+    reports::{
+        ActivityRecorder, CostRecorder, SegmentMap, load_segments, write_exposure_report,
+        write_segment_report,
+    },
+    rules::RuleSet,
+    run_metadata::{RunMetadata, file_sha256},
+    setup_tracing_logs,
+    simulation::run_simulation,
+    sink::account_channel,
+    snapshot::{AccountUpdateFeed, DEFAULT_DELTA_EVERY, DeltaEmitter},
+    spawn_writer_thread, spawn_writer_thread_with_policy,
+    statement_export::StatementRecorder,
+    tenants::{Tenants, tenant_transactions_from_reader},
+    trailer::{OutputTrailer, verify_output},
+    transform::{
+        RemapClients, ScaleAmounts, TransactionTransform, minor_unit_amounts,
+        transform_transactions,
+    },
+    trust::{TrustLevel, validate_source},
+    tuning::{SystemResources, Tuning},
 };
 
 fn main() -> io::Result<()> {
     setup_tracing_logs(); // initialize logging to stderr
     info!("Starting the transactions processing application...");
 
-    let mut args = env::args();
+    let args = match Command::parse(env::args().skip(1)) {
+        Ok(Command::Process(args)) => *args,
+        Ok(Command::Simulate(config)) => {
+            // accounts are discarded, only the performance report is printed
+            let (tx, rx) = tx_engine::channel::unbounded();
+            let thread_id = spawn_writer_thread(io::sink(), rx);
+            let mut clients = Clients::new(tx);
+            let report = run_simulation(&config, &mut clients);
+            drop(clients);
+            thread_id.join().expect("failed to join writer thread");
+            print!("{report}");
+            return Ok(());
+        }
+        Ok(Command::Query(config)) => {
+            let transactions =
+                read_transactions_from_csv_with_options(&config.input, &config.csv_options)
+                    .expect("failed to load the csv");
+            let summary = run_query(&config.query, transactions, io::stdout().lock())
+                .expect("failed to write the matching transactions");
+            info!(?summary, "Finished query");
+            return Ok(());
+        }
+        Ok(Command::Normalize(config)) => return run_normalize(&config),
+        Ok(Command::Plan(config)) => {
+            let transactions =
+                read_transactions_from_csv_with_options(&config.input, &config.csv_options)
+                    .expect("failed to load the csv");
+            let plan =
+                PartitionPlan::balance(&count_client_transactions(transactions), config.partitions);
+            for partition in &plan.partitions {
+                info!(?partition, "Planned partition");
+            }
+            let plan = serde_json::to_string_pretty(&plan)? + "\n";
+            match &config.output {
+                Some(path) => fs::write(path, plan)?,
+                None => print!("{plan}"),
+            }
+            return Ok(());
+        }
+        Ok(Command::Run(manifest)) => return run_manifest(&manifest),
+        Ok(Command::Verify(output)) => match verify_output(&output) {
+            Ok(trailer) => {
+                println!("{}: ok, {} rows", output.display(), trailer.rows);
+                return Ok(());
+            }
+            Err(err) => {
+                eprintln!("{}: {err}", output.display());
+                std::process::exit(1)
+            }
+        },
+        Err(err) => {
+            eprintln!("{err}");
+            eprintln!("{USAGE}");
+            std::process::exit(2)
+        }
+    };
+
+    run_process(args).map(drop)
+}
+
+// Runs a manifest through the process options, the completion manifest is written even when the
+// run failed (but not if it panicked)
+fn run_manifest(path: &Path) -> io::Result<()> {
+    let started = SystemTime::now();
+    let manifest = RunManifest::read(path).map_err(io::Error::other)?;
+    let result = manifest
+        .to_args()
+        .map_err(|err| err.to_string())
+        .and_then(|args| Args::parse(args).map_err(|err| err.to_string()))
+        .and_then(|args| run_process(args).map_err(|err| err.to_string()));
+    if let Err(err) = &result {
+        error!(%err, ?path, "Manifest run failed");
+    }
+    let completion = CompletionManifest::new(&manifest, started, result)?;
+    completion.write(&manifest.completion_path(path))?;
+    if completion.status == RunStatus::Failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// Applies an input and writes the accounts and every requested report, the stats are `None` when
+// the input was skipped (already processed) or split by tenant
+fn run_process(mut args: Args) -> io::Result<Option<ProcessingStats>> {
+    // refuse (or skip) an input that an earlier run already processed
+    let registry = match &args.processed_registry {
+        Some(registry_path) => {
+            let registry = InputRegistry::open(registry_path)
+                .expect("failed to load the processed inputs registry");
+            let sha256 = file_sha256(&args.input)?;
+            if let Some(earlier) = registry.find(&sha256) {
+                let message = format!(
+                    "{} was already processed (as {} at {})",
+                    args.input.display(),
+                    earlier.path.display(),
+                    earlier.processed_at
+                );
+                match args.duplicate_policy {
+                    DuplicatePolicy::Refuse => {
+                        return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+                    }
+                    DuplicatePolicy::Skip => {
+                        warn!(message, "Skipping duplicate input");
+                        return Ok(None);
+                    }
+                }
+            }
+            Some((registry, sha256))
+        }
+        None => None,
+    };
 
-    // load input csv
-    info!("Loading input csv...");
-    let file_path = args.nth(1).expect("No command line argument was provided");
-    let file_path = Path::new(&file_path);
-    let transactions_iter = read_transactions_from_csv(file_path).expect("failed to load the csv");
+    if args.reproducible {
+        if args.auto_tune {
+            warn!(
+                "Reproducible run: ignoring --auto-tune, the configured partitions and buffers are kept"
+            );
+            args.auto_tune = false;
+        }
+        if args.alerts_buffer.overflow == OverflowPolicy::DropOldest {
+            warn!("Reproducible run: alerts are never dropped, blocking on overflow instead");
+            args.alerts_buffer.overflow = OverflowPolicy::Block;
+        }
+        args.run_metadata = true;
+    }
+    let run_metadata = args
+        .run_metadata
+        .then(|| {
+            let config = format!("{args:?}");
+            if args.reproducible {
+                RunMetadata::reproducible(&[&args.input], &config)
+            } else {
+                RunMetadata::new(&[&args.input], &config)
+            }
+        })
+        .transpose()?;
 
-    let (tx, rx) = std::sync::mpsc::channel();
-    let thread_id = spawn_writer_thread(io::stdout(), rx);
+    // multi-tenant mode: one ledger (and one output file) per tenant
+    if let Some(output_dir) = &args.tenant_output_dir {
+        run_tenants(&args.input, output_dir, args.reproducible)?;
+        record_input(registry, &args.input);
+        write_sidecars(run_metadata.as_ref(), &[output_dir])?;
+        return Ok(None);
+    }
+
+    if args.auto_tune {
+        let tuning = Tuning::auto(&SystemResources::detect(&args.input));
+        args.partitioner.partitions = tuning.partitions;
+        args.partitioner.channel_bound = tuning.channel_bound;
+        args.csv_options.buffer_capacity = Some(tuning.reader_buffer_bytes);
+    }
+
+    // optional pre-pass to size the maps and check the input is not larger than expected
+    let estimate = match (args.estimate_cardinality, args.input_format) {
+        (true, InputFormat::Csv) => {
+            info!("Estimating input cardinality...");
+            let estimate = estimate_cardinality(
+                read_transactions_from_csv_with_options(&args.input, &args.csv_options)
+                    .expect("failed to load the csv"),
+            );
+            estimate.check(&args.cardinality_limits);
+            Some(estimate)
+        }
+        (true, input_format) => {
+            warn!(
+                ?input_format,
+                "Cardinality estimation is only supported for csv inputs"
+            );
+            None
+        }
+        (false, _) => None,
+    };
+
+    // Ctrl-C / SIGTERM stop the ingestion, what was read is still applied and written out
+    let cancellation = CancellationToken::on_signals().unwrap_or_else(|err| {
+        warn!(%err, "failed to install the signal handler, interrupting will kill the run");
+        CancellationToken::new()
+    });
+
+    // load input file
+    info!(input_format = ?args.input_format, "Loading input file...");
+    let transactions_iter: Box<dyn Iterator<Item = Result<Transaction, ConversionError>>> =
+        match args.input_format {
+            #[cfg(feature = "fault-injection")]
+            InputFormat::Csv if args.inject_faults.is_some() => {
+                let faults = args.inject_faults.clone().unwrap_or_default();
+                let csv_reader = args
+                    .csv_options
+                    .reader_builder()
+                    .from_reader(FaultyReader::new(File::open(&args.input)?, faults));
+                Box::new(tx_engine::csv_input::transactions_from_reader(csv_reader))
+            }
+            InputFormat::Csv if args.follow.is_some() => Box::new(
+                follow_transactions_from_csv(
+                    &args.input,
+                    &args.csv_options,
+                    args.follow.unwrap_or_default(),
+                    &cancellation,
+                )
+                .expect("failed to load the csv"),
+            ),
+            InputFormat::Csv => match &args.quarantine {
+                Some(quarantine_path) => Box::new(
+                    read_transactions_from_csv_with_quarantine(
+                        &args.input,
+                        quarantine_path,
+                        &args.csv_options,
+                    )
+                    .expect("failed to load the csv"),
+                ),
+                None => Box::new(
+                    read_transactions_from_csv_with_options(&args.input, &args.csv_options)
+                        .expect("failed to load the csv"),
+                ),
+            },
+            InputFormat::Json => {
+                Box::new(read_transactions_from_json(&args.input).expect("failed to load the json"))
+            }
+            InputFormat::Iso20022 => Box::new(
+                read_transactions_from_iso20022(&args.input).expect("failed to load the xml"),
+            ),
+            #[cfg(feature = "parquet")]
+            InputFormat::Parquet => Box::new(
+                tx_engine::parquet_input::read_transactions_from_parquet(&args.input)
+                    .expect("failed to load the parquet file"),
+            ),
+            #[cfg(feature = "msgpack")]
+            InputFormat::Msgpack => Box::new(
+                tx_engine::msgpack_input::read_transactions_from_msgpack(&args.input)
+                    .expect("failed to load the msgpack file"),
+            ),
+            #[cfg(feature = "kafka")]
+            InputFormat::Kafka => {
+                use tx_engine::kafka_input::{KafkaConfig, consume_transactions_from_kafka};
+                let source = args.input.to_string_lossy();
+                let Some(mut config) = KafkaConfig::from_source(&source) else {
+                    error!(%source, "Kafka input must be <brokers>/<topic>");
+                    std::process::exit(2)
+                };
+                if let Some(group_id) = &args.kafka_group {
+                    config.group_id = group_id.clone();
+                }
+                config.payload = args.kafka_payload;
+                config.exit_at_end = args.kafka_exit_at_end;
+                Box::new(
+                    consume_transactions_from_kafka(config)
+                        .expect("failed to subscribe to the kafka topic")
+                        .with_cancellation(cancellation.clone()),
+                )
+            }
+            #[cfg(feature = "fix")]
+            InputFormat::Fix => Box::new(tx_engine::fix_input::transactions_from_fix_reader(
+                File::open(&args.input)?,
+            )),
+        };
+    let transactions_iter = validated(transactions_iter, &args.input, &args);
+    let transactions_iter: Box<dyn Iterator<Item = Result<Transaction, ConversionError>>> =
+        if args.merge_inputs.is_empty() {
+            transactions_iter
+        } else {
+            let mut sources = vec![transactions_iter];
+            for merge_input in &args.merge_inputs {
+                info!(?merge_input, "Merging input file...");
+                let transactions =
+                    read_transactions_from_csv_with_options(merge_input, &args.csv_options)
+                        .expect("failed to load the csv");
+                sources.push(validated(transactions, merge_input, &args));
+            }
+            let names = std::iter::once(&args.input)
+                .chain(&args.merge_inputs)
+                .map(|path| path.display().to_string())
+                .collect();
+            let mapping = match &args.tx_id_map {
+                Some(path) => Some(File::create(path)?),
+                None => None,
+            };
+            Box::new(merge_sources(sources, TxIdNamespaces::new(names, mapping)))
+        };
+    let resumed = match &args.resume {
+        Some(path) => {
+            let snapshot = Snapshot::read(path)?;
+            info!(
+                ?path,
+                input_offset = snapshot.input_offset,
+                "Resuming from checkpoint"
+            );
+            Some(snapshot)
+        }
+        None => None,
+    };
+    let (transactions_iter, ingestion) = cancellable(&cancellation, transactions_iter);
+    // the records of the checkpoint were applied by the interrupted run
+    let transactions_iter = transactions_iter.skip(
+        resumed
+            .as_ref()
+            .map_or(0, |snapshot| snapshot.input_offset as usize),
+    );
+    let metrics = args.metrics_every.map(|period| {
+        let metrics = Metrics::new();
+        (metrics.spawn_reporter(period), metrics)
+    });
+    let transactions_iter: Box<dyn Iterator<Item = Result<Transaction, ConversionError>>> =
+        match &metrics {
+            Some((_, metrics)) => Box::new(metrics.meter(transactions_iter)),
+            None => Box::new(transactions_iter),
+        };
+
+    // the file output is renamed over its path once every account was written
+    let (atomic_output, output): (Option<AtomicOutput>, Box<dyn io::Write + Send>) = match &args
+        .output
+    {
+        Some(path) if args.partition_dir.is_none() && args.parts_dir.is_none() => {
+            let (atomic_output, file) = AtomicOutput::create(path)?;
+            (Some(atomic_output), Box::new(file))
+        }
+        Some(path) => {
+            warn!(
+                ?path,
+                "Ignoring --output, the accounts are written to the partitions"
+            );
+            (None, Box::new(io::stdout()))
+        }
+        None => {
+            if args.output_trailer {
+                warn!(
+                    "No trailer for the accounts written to stdout, --output-trailer needs --output"
+                );
+            }
+            (None, Box::new(io::stdout()))
+        }
+    };
+    #[cfg(feature = "fault-injection")]
+    let output: Box<dyn io::Write + Send> = match &args.inject_faults {
+        Some(faults) => Box::new(FaultyWriter::new(output, faults.clone())),
+        None => output,
+    };
+
+    let (mut tx, rx) = match &metrics {
+        Some((_, metrics)) => metrics.meter_output(account_channel(args.output_batch)),
+        None => account_channel(args.output_batch),
+    };
+    let mut part_sinks = None; // one per part, fed concurrently with the remaining accounts
+    // joins the writer thread once every account was sent
+    let join_output: Box<dyn FnOnce() -> io::Result<()>> = match (
+        &args.parts_dir,
+        &args.partition_dir,
+    ) {
+        (Some(dir), partition_dir) => {
+            if partition_dir.is_some() {
+                warn!(
+                    "Ignoring --partition-dir, the accounts are written to the parts of --parts-dir"
+                );
+            }
+            let parts = PartWriters::create(
+                dir,
+                args.threads.unwrap_or(NonZeroUsize::MIN),
+                args.partitioner.channel_bound,
+                args.write_failure_policy,
+                args.output_options.clone(),
+            )?;
+            tx = Box::new(parts.router()); // the accounts locked while applying go to their part
+            part_sinks = Some(parts.sinks());
+            Box::new(move || {
+                let manifest = parts.finish()?;
+                info!(parts = manifest.partitions.len(), "Wrote output parts");
+                Ok(())
+            })
+        }
+        (None, Some(dir)) => {
+            let thread_id = spawn_partitioned_writer_thread(
+                dir,
+                args.partitioner,
+                rx,
+                args.write_failure_policy,
+                args.output_options.clone(),
+            )?;
+            Box::new(move || {
+                let manifest = thread_id.join().expect("failed to join writer thread")?;
+                info!(
+                    partitions = manifest.partitions.len(),
+                    "Wrote partitioned output"
+                );
+                Ok(())
+            })
+        }
+        (None, None) => {
+            let thread_id = spawn_writer_thread_with_policy(
+                output,
+                rx,
+                args.write_failure_policy,
+                args.output_options.clone(),
+            );
+            Box::new(move || {
+                let (output, summary) = thread_id
+                    .join()
+                    .expect("failed to join writer thread")
+                    .map_err(io::Error::other)?;
+                drop(output); // closes the file before it is renamed
+                if let Some(atomic_output) = atomic_output {
+                    let path = atomic_output.path().to_path_buf();
+                    atomic_output.commit()?;
+                    info!(?path, "Wrote output file");
+                }
+                info!(%summary, "Wrote output");
+                Ok(())
+            })
+        }
+    };
 
     // apply the transactions
     info!("Applying transactions...");
-    let mut clients = Clients::new(tx);
-    clients.load_transactions(transactions_iter); //will early write accounts that become locked
+    let (estimated_clients, estimated_transactions) = estimate.map_or((0, 0), |estimate| {
+        (estimate.clients as usize, estimate.transactions as usize)
+    });
+    let mut clients = match resumed {
+        Some(snapshot) => Clients::from_snapshot(snapshot, tx),
+        None => Clients::new(tx),
+    };
+    clients.reserve(
+        args.expected_clients.unwrap_or(estimated_clients),
+        args.expected_transactions.unwrap_or(estimated_transactions),
+    );
+    clients.sorted_output = args.reproducible;
+    clients.sparse_accounts = args.sparse_accounts;
+    clients.settle_horizon = args.settle_horizon;
+    clients.rounding = args.rounding;
+    // every extra output gets the same accounts from its own writer thread
+    let extra_output_paths = match &args.parts_dir {
+        Some(_) if !args.extra_outputs.is_empty() => {
+            warn!("Ignoring --extra-output, the accounts are written to the parts of --parts-dir");
+            &[][..]
+        }
+        _ => &args.extra_outputs[..],
+    };
+    let mut extra_outputs = Vec::new();
+    for (format, path) in extra_output_paths {
+        let (atomic_output, file) = AtomicOutput::create(path)?;
+        let (extra_tx, extra_rx) = account_channel(args.output_batch);
+        let options = OutputOptions {
+            format: *format,
+            ..args.output_options.clone()
+        };
+        let thread_id = spawn_writer_thread_with_policy(
+            file,
+            extra_rx,
+            args.write_failure_policy,
+            options.clone(),
+        );
+        clients.add_sink(extra_tx);
+        extra_outputs.push((atomic_output, thread_id, options.clone()));
+    }
+    if args.resume.is_some() {
+        // the outputs of the interrupted run, which had the accounts locked before the checkpoint, are lost
+        clients.emit_locked_accounts();
+    }
+    if let Some(ledger_path) = &args.ledger_export {
+        let config = LedgerExportConfig {
+            format: args.ledger_format,
+            ..Default::default()
+        };
+        clients.add_observer(LedgerExporter::new(
+            BufWriter::new(File::create(ledger_path)?),
+            config,
+        ));
+    }
+    let (monitor, alerts_delivery) = threshold_monitor(&args)?;
+    if let Some(monitor) = monitor {
+        clients.add_observer(monitor);
+    }
+    let statements = args.statements_dir.as_ref().map(|dir| {
+        let recorder = Arc::new(Mutex::new(StatementRecorder::default()));
+        clients.add_observer(recorder.clone());
+        (dir, recorder)
+    });
+    let conservation = args.conservation_check.map(|mode| {
+        let check = Arc::new(Mutex::new(ConservationCheck::default()));
+        clients.add_observer(check.clone());
+        (mode, check)
+    });
+    let balance_floor = (args.balance_floor_check)
+        .or(args
+            .balance_floor_report
+            .is_some()
+            .then_some(InvariantMode::Warn))
+        .map(|mode| {
+            let check = Arc::new(Mutex::new(BalanceFloorCheck::new(args.balance_floor)));
+            clients.add_observer(check.clone());
+            (mode, check)
+        });
+    let activity = args.activity_report.as_ref().map(|path| {
+        let recorder = Arc::new(Mutex::new(ActivityRecorder::default()));
+        clients.add_observer(recorder.clone());
+        (path, recorder)
+    });
+    let deltas = match &args.delta_output {
+        Some(path) => {
+            let emitter = Arc::new(Mutex::new(DeltaEmitter::new(
+                args.delta_every.unwrap_or(DEFAULT_DELTA_EVERY),
+                BufWriter::new(File::create(path)?),
+            )));
+            clients.add_observer(emitter.clone());
+            Some(emitter)
+        }
+        None => None,
+    };
+    if let Some(path) = &args.audit_journal {
+        clients.add_observer(AuditJournal::open(path)?);
+    }
+    if let Some(path) = &args.update_feed {
+        // line buffered so that each update is visible as soon as it is written
+        let feed = AccountUpdateFeed::new(LineWriter::new(File::create(path)?));
+        clients.add_observer(feed);
+    }
+    let costs = args.cost_report.as_ref().map(|path| {
+        let recorder = Arc::new(Mutex::new(CostRecorder::default()));
+        clients.add_observer(recorder.clone());
+        (path, recorder)
+    });
+    if let Some(path) = &args.opening_balances {
+        info!(?path, "Loading opening balances...");
+        let csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_path(path)?;
+        read_opening_balances(csv_reader)
+            .and_then(|balances| open_accounts(&mut clients, &balances))
+            .map_err(io::Error::other)?;
+    }
+    //will early write accounts that become locked
+    let transactions_iter = match args.minor_units {
+        Some(decimals) => Box::new(minor_unit_amounts(decimals, transactions_iter)),
+        None => transactions_iter,
+    };
+    let transactions_iter = match transforms(&args) {
+        Some(transforms) => Box::new(transform_transactions(transforms, transactions_iter)),
+        None => transactions_iter,
+    };
+    let stats =
+        if args.client_filters.is_empty() && args.disabled_types.is_empty() && args.rules.is_none()
+        {
+            load_transactions(&mut clients, transactions_iter, &args, &ingestion)
+        } else {
+            let mut filtered = filter_transactions(args.client_filters.clone(), transactions_iter)
+                .with_disabled_types(args.disabled_types.iter().copied().collect())
+                .with_rules(rules(&args));
+            let mut stats = load_transactions(&mut clients, &mut filtered, &args, &ingestion);
+            info!(
+                skipped = filtered.skipped(),
+                "Skipped transactions of filtered out clients"
+            );
+            for (transaction_type, skipped) in filtered.skipped_types() {
+                info!(
+                    transaction_type,
+                    skipped, "Skipped transactions of a disabled type"
+                );
+            }
+            for (rule, rejected) in filtered.rejected() {
+                info!(%rule, rejected, "Rejected transactions");
+                stats.rejected += rejected; // the rules reject valid transactions too
+            }
+            stats
+        };
+    info!(?stats, "Loaded transactions");
+    match &args.stats_output {
+        Some(path) if path.as_os_str() == "-" => {
+            eprintln!("{}", serde_json::to_string(&stats)?);
+        }
+        Some(path) => fs::write(path, serde_json::to_string_pretty(&stats)? + "\n")?,
+        None => {}
+    }
+
+    if let Some(decisions_path) = &args.case_decisions {
+        info!(?decisions_path, "Applying case decisions...");
+        let csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_path(decisions_path)
+            .expect("failed to load the case decisions");
+        apply_case_decisions(&mut clients, csv_reader);
+    }
 
-    // output to stdout
-    info!("Writing remaining clients to stdout...");
-    clients // write the remaining (non locked) clients to stdout
-        .send_to_output(tx_engine::model::OutputMode::SkipLocked)
-        .expect("failed to write to output");
+    for (from, into) in &args.merge_clients {
+        // logged as the merge audit record
+        if let Err(err) = clients.merge_clients(*from, *into) {
+            error!(%err, %from, %into, "Refused client merge");
+        }
+    }
 
-    thread_id.join().expect("failed to join writer thread");
+    for client in &args.erase_clients {
+        clients.erase_client(*client, args.erasure_policy); // logged as the erasure audit record
+    }
+
+    if let Some(decisions_path) = &args.preview_decisions {
+        info!(?decisions_path, "Previewing case decisions...");
+        let csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_path(decisions_path)
+            .expect("failed to load the previewed decisions");
+        let preview = preview_case_decisions(&clients, &read_case_decisions(csv_reader));
+        for impact in &preview.impacts {
+            info!(?impact, "Previewed impact");
+        }
+        if let Some(report_path) = &args.preview_report {
+            write_decision_preview(&preview, File::create(report_path)?)
+                .expect("failed to write the decisions preview");
+        }
+    }
+
+    if let Some(deltas) = &deltas {
+        // the changes since the last emission, including erasures and case decisions
+        deltas
+            .lock()
+            .expect("delta emitter poisoned")
+            .emit()
+            .expect("failed to write the account deltas");
+    }
+
+    if let Some((dir, statements)) = &statements {
+        info!(?dir, "Writing client statements...");
+        statements
+            .lock()
+            .expect("statement recorder poisoned")
+            .write_qif_statements(dir, "01/01/1970")?;
+    }
+
+    if let Some((report_path, activity)) = &activity {
+        info!(?report_path, "Writing client activity report...");
+        activity
+            .lock()
+            .expect("activity recorder poisoned")
+            .write_activity_report(File::create(report_path)?)
+            .expect("failed to write the activity report");
+    }
+
+    if let Some(report_path) = &args.segment_report {
+        info!(?report_path, "Writing segment report...");
+        write_segment_report(&clients, &segments(&args), File::create(report_path)?)
+            .expect("failed to write the segment report");
+    }
+
+    if let Some((report_path, costs)) = &costs {
+        info!(?report_path, "Writing processing cost report...");
+        costs
+            .lock()
+            .expect("cost recorder poisoned")
+            .write_cost_report(&segments(&args), File::create(report_path)?)
+            .expect("failed to write the cost report");
+    }
+
+    if let Some(cases_path) = &args.dispute_cases {
+        info!(?cases_path, "Writing dispute cases...");
+        write_dispute_cases(&clients, File::create(cases_path)?)
+            .expect("failed to write the dispute cases");
+    }
+
+    if let Some(report_path) = &args.exposure_report {
+        info!(?report_path, "Writing held-funds exposure report...");
+        write_exposure_report(&clients, File::create(report_path)?)
+            .expect("failed to write the exposure report");
+    }
+
+    if let Some((mode, check)) = &conservation {
+        let verified = check
+            .lock()
+            .expect("conservation check poisoned")
+            .verify(&clients);
+        if let (InvariantMode::Strict, Err(err)) = (mode, verified) {
+            return Err(io::Error::other(err));
+        }
+    }
+
+    if let Some((mode, check)) = &balance_floor {
+        let check = check.lock().expect("balance floor check poisoned");
+        if let Some(report_path) = &args.balance_floor_report {
+            info!(?report_path, "Writing balance floor report...");
+            check
+                .write_report(File::create(report_path)?)
+                .expect("failed to write the balance floor report");
+        }
+        if let (InvariantMode::Strict, Err(err)) = (mode, check.verify()) {
+            return Err(io::Error::other(err));
+        }
+    }
+
+    // output to stdout (or the partitions)
+    info!("Writing remaining clients to the output...");
+    // write the remaining (non locked) clients
+    match part_sinks {
+        Some(sinks) => {
+            clients.send_to_output_sharded(tx_engine::model::OutputMode::SkipLocked, sinks)
+        }
+        None => clients.send_to_output(tx_engine::model::OutputMode::SkipLocked),
+    }
+
+    join_output()?;
+    if let Some((reporter, metrics)) = metrics {
+        reporter.stop();
+        eprintln!("metrics: {}", metrics.snapshot());
+    }
+    if args.output_trailer
+        && let Some(path) = &args.output
+        && args.partition_dir.is_none()
+        && args.parts_dir.is_none()
+    {
+        write_trailer(path, &args.output_options)?;
+    }
+    for (atomic_output, thread_id, options) in extra_outputs {
+        let (file, summary) = thread_id
+            .join()
+            .expect("failed to join writer thread")
+            .map_err(io::Error::other)?;
+        drop(file); // closes the file before it is renamed
+        let path = atomic_output.path().to_path_buf();
+        atomic_output.commit()?;
+        info!(?path, %summary, "Wrote extra output");
+        if args.output_trailer {
+            write_trailer(&path, &options)?;
+        }
+    }
+    if let Some(thread_id) = alerts_delivery {
+        // the monitor, and so the sender, went away with the clients
+        let summary = thread_id.join().expect("failed to join alerts thread");
+        info!(%summary, "Delivered alerts");
+    }
+    if ingestion.interrupted() {
+        // the input was not fully processed, it must not be recorded as such
+        warn!(
+            input = ?args.input,
+            records = ingestion.records(),
+            "Checkpoint: interrupted before the end of the input, the outputs only cover the records read"
+        );
+    } else {
+        record_input(registry, &args.input);
+    }
+    let artifacts = [
+        &args.output,
+        &args.partition_dir,
+        &args.parts_dir,
+        &args.ledger_export,
+        &args.statements_dir,
+        &args.activity_report,
+        &args.exposure_report,
+        &args.dispute_cases,
+        &args.preview_report,
+        &args.alerts_output,
+        &args.segment_report,
+        &args.cost_report,
+        &args.delta_output,
+        &args.update_feed,
+        &args.quarantine,
+    ];
+    write_sidecars(
+        run_metadata.as_ref(),
+        &(artifacts.into_iter().flatten())
+            .chain(extra_output_paths.iter().map(|(_, path)| path))
+            .collect::<Vec<_>>(),
+    )?;
+    if ingestion.interrupted() {
+        error!("Interrupted, the outputs are partial");
+        std::process::exit(130);
+    }
     info!("Finished processing transactions");
+    Ok(Some(stats))
+}
+
+// Marks the input as processed once the run succeeded
+fn record_input(registry: Option<(InputRegistry, String)>, input: &Path) {
+    if let Some((mut registry, sha256)) = registry {
+        registry
+            .record(&sha256, input)
+            .expect("failed to update the processed inputs registry");
+    }
+}
+
+// Checks the transactions of an input against the validation profile of its trust level
+fn validated<'a>(
+    transactions: Box<dyn Iterator<Item = Result<Transaction, ConversionError>> + 'a>,
+    source: &Path,
+    args: &Args,
+) -> Box<dyn Iterator<Item = Result<Transaction, ConversionError>> + 'a> {
+    match args.trust.level(source) {
+        TrustLevel::Trusted => transactions,
+        level => Box::new(validate_source(
+            transactions,
+            source.display().to_string(),
+            level,
+        )),
+    }
+}
+
+// Applies the transactions on this thread, or sharded by client on worker threads, writing the
+// checkpoints along the way
+fn load_transactions<T: Iterator<Item = Result<Transaction, ConversionError>>>(
+    clients: &mut Clients,
+    transactions: T,
+    args: &Args,
+    ingestion: &IngestionProgress,
+) -> ProcessingStats {
+    if let Some(path) = &args.checkpoint {
+        let every = args.checkpoint_every.unwrap_or(DEFAULT_CHECKPOINT_EVERY);
+        return clients.load_transactions_with_checkpoints(
+            transactions,
+            every,
+            args.threads,
+            |clients| {
+                let mut snapshot = clients.snapshot();
+                snapshot.input_offset = ingestion.records();
+                if let Err(err) = snapshot.write(path) {
+                    error!(%err, ?path, "Failed to write the checkpoint, the run goes on");
+                }
+            },
+        );
+    }
+    match args.threads {
+        Some(threads) if threads.get() > 1 => {
+            clients.load_transactions_parallel(transactions, threads)
+        }
+        _ => clients.load_transactions(transactions),
+    }
+}
+
+// Trailer of a committed output file, for the consumers to verify it before loading
+fn write_trailer(path: &Path, options: &OutputOptions) -> io::Result<()> {
+    let trailer = OutputTrailer::create(path, options).map_err(io::Error::other)?;
+    trailer.write(path)?;
+    info!(?path, rows = trailer.rows, "Wrote output trailer");
+    Ok(())
+}
+
+type AlertsDelivery = Option<JoinHandle<NotificationSummary>>;
+
+// Client segments sidecar, everyone is unsegmented without it
+fn segments(args: &Args) -> SegmentMap {
+    match &args.segments {
+        Some(segments_path) => load_segments(
+            csv::ReaderBuilder::new()
+                .trim(csv::Trim::All) //trim whitespace around fields
+                .from_path(segments_path)
+                .expect("failed to load the segments"),
+        )
+        .expect("invalid segments file"),
+        None => SegmentMap::new(),
+    }
+}
+
+// Rejection rules, compiled before anything is processed so that an invalid rule fails the run early
+fn rules(args: &Args) -> RuleSet {
+    match &args.rules {
+        Some(rules_path) => {
+            RuleSet::parse(&fs::read_to_string(rules_path).expect("failed to load the rules"))
+                .expect("invalid rules")
+                .with_segments(segments(args))
+        }
+        None => RuleSet::default(),
+    }
+}
+
+// Rewrites applied to the transactions before the filters, None if there are none
+fn transforms(args: &Args) -> Option<Vec<Box<dyn TransactionTransform>>> {
+    let mut transforms: Vec<Box<dyn TransactionTransform>> = Vec::new();
+    if let Some(path) = &args.remap_clients {
+        let csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_path(path)
+            .expect("failed to load the client mapping");
+        transforms.push(Box::new(
+            RemapClients::from_reader(csv_reader).expect("invalid client mapping"),
+        ));
+    }
+    if let Some(factor) = args.amount_scale {
+        transforms.push(Box::new(ScaleAmounts(factor)));
+    }
+    (!transforms.is_empty()).then_some(transforms)
+}
+
+// Balance alerts raised during processing, None if no threshold is configured,
+// with the thread delivering them to --alerts-output
+fn threshold_monitor(args: &Args) -> io::Result<(Option<ThresholdMonitor>, AlertsDelivery)> {
+    if args.alert_threshold == Threshold::default() && args.alert_thresholds_file.is_none() {
+        return Ok((None, None));
+    }
+    let mut thresholds = BalanceThresholds {
+        global: args.alert_threshold,
+        ..Default::default()
+    };
+    if let Some(path) = &args.alert_thresholds_file {
+        let csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_path(path)
+            .expect("failed to load the alert thresholds");
+        thresholds
+            .load_per_client(csv_reader)
+            .expect("invalid alert thresholds");
+    }
+    // a slow alerts consumer must not stall the processing, they are delivered on their own thread
+    let (alerts, delivery) = match &args.alerts_output {
+        Some(path) => {
+            let mut wtr = csv::Writer::from_writer(File::create(path)?);
+            let (sender, thread_id) =
+                spawn_notification_sink(args.alerts_buffer.clone(), move |alert: BalanceAlert| {
+                    // flushed on every alert so that they can be followed while the input is processed
+                    let written = wtr.serialize(&alert).and_then(|()| Ok(wtr.flush()?));
+                    if let Err(err) = written {
+                        error!(%err, %alert, "failed to write alert");
+                    }
+                })?;
+            (Some(sender), Some(thread_id))
+        }
+        None => (None, None),
+    };
+    let monitor = ThresholdMonitor::new(thresholds, move |alert| {
+        if let Some(alerts) = &alerts {
+            alerts.send(alert.clone());
+        }
+    });
+    Ok((Some(monitor), delivery))
+}
+
+// Provenance next to every output file or directory, the stdout output is traced by the logged run id
+fn write_sidecars(metadata: Option<&RunMetadata>, artifacts: &[&PathBuf]) -> io::Result<()> {
+    if let Some(metadata) = metadata {
+        for artifact in artifacts {
+            metadata.write_sidecar(artifact)?;
+        }
+    }
+    Ok(())
+}
+
+// Routes every row by its tenant column, each tenant is written to <output_dir>/<tenant>.csv
+fn run_tenants(file_path: &Path, output_dir: &Path, sorted_output: bool) -> io::Result<()> {
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_path(file_path)
+        .expect("failed to load the csv");
+
+    info!("Applying transactions per tenant...");
+    let mut tenants = Tenants::new(output_dir);
+    tenants.sorted_output = sorted_output;
+    tenants.load_transactions(tenant_transactions_from_reader(csv_reader))?;
+
+    info!("Writing remaining clients of every tenant...");
+    tenants.finish()?;
+    info!("Finished processing transactions");
+    Ok(())
+}
+
+// Parses and validates an input, writing it in the canonical format without applying anything
+fn run_normalize(config: &NormalizeConfig) -> io::Result<()> {
+    info!(input_format = ?config.input_format, "Normalizing input file...");
+    let transactions: Box<dyn Iterator<Item = Result<Transaction, ConversionError>>> =
+        match config.input_format {
+            InputFormat::Csv => Box::new(
+                read_transactions_from_csv_with_options(&config.input, &config.csv_options)
+                    .expect("failed to load the csv"),
+            ),
+            InputFormat::Json => Box::new(
+                read_transactions_from_json(&config.input).expect("failed to load the json"),
+            ),
+            InputFormat::Iso20022 => Box::new(
+                read_transactions_from_iso20022(&config.input).expect("failed to load the xml"),
+            ),
+            #[cfg(feature = "parquet")]
+            InputFormat::Parquet => Box::new(
+                tx_engine::parquet_input::read_transactions_from_parquet(&config.input)
+                    .expect("failed to load the parquet file"),
+            ),
+            #[cfg(feature = "msgpack")]
+            InputFormat::Msgpack => Box::new(
+                tx_engine::msgpack_input::read_transactions_from_msgpack(&config.input)
+                    .expect("failed to load the msgpack file"),
+            ),
+            #[cfg(feature = "kafka")]
+            InputFormat::Kafka => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "kafka topics cannot be normalized",
+                ));
+            }
+            #[cfg(feature = "fix")]
+            InputFormat::Fix => Box::new(tx_engine::fix_input::transactions_from_fix_reader(
+                File::open(&config.input)?,
+            )),
+        };
+    let summary = match &config.output {
+        Some(path) => {
+            let (atomic_output, file) = AtomicOutput::create(path)?;
+            let (_, summary) = normalize(transactions, config.format, BufWriter::new(file))
+                .expect("failed to write the canonical transactions");
+            atomic_output.commit()?;
+            summary
+        }
+        None => {
+            normalize(transactions, config.format, io::stdout().lock())
+                .expect("failed to write the canonical transactions")
+                .1
+        }
+    };
+    info!(?summary, "Finished normalizing");
     Ok(())
 }
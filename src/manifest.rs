@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, instrument};
+
+use crate::{model::ProcessingStats, run_metadata::file_sha256};
+
+/// Process options writing an artifact that a manifest can list in its `outputs`, a directory
+/// for the last three
+pub const MANIFEST_OUTPUTS: [&str; 18] = [
+    "output",
+    "stats-output",
+    "audit-journal",
+    "update-feed",
+    "delta-output",
+    "exposure-report",
+    "alerts-output",
+    "segment-report",
+    "cost-report",
+    "dispute-cases",
+    "preview-report",
+    "activity-report",
+    "ledger-export",
+    "quarantine",
+    "tx-id-map",
+    "statements-dir",
+    "partition-dir",
+    "tenant-output-dir",
+];
+
+/// File modification times are coarser than the clock, an artifact modified this long before the
+/// run started still counts as written by it
+const MTIME_TOLERANCE: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    #[error("failed to read the manifest: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid manifest: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("the manifest has no input")]
+    NoInput,
+    #[error("unknown output {0}, expected one of {MANIFEST_OUTPUTS:?}")]
+    UnknownOutput(String),
+    #[error("{0} is not supported")]
+    Unsupported(&'static str),
+}
+
+/// Declarative contract of a run for orchestrators (`tx_engine run <manifest.json>`), instead of
+/// command line flags. It is translated to the process options, so everything they support can be
+/// given, and the run ends with a `CompletionManifest`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RunManifest {
+    pub inputs: Vec<PathBuf>, // the first one is the input, the others are merged after it
+    #[serde(default)]
+    pub outputs: BTreeMap<String, PathBuf>, // option without the dashes, e.g. "output" or "stats-output"
+    #[serde(default)]
+    pub options: Vec<String>, // any other process options, e.g. ["--sparse-accounts"]
+    #[serde(default)]
+    pub expected: ExpectedCounts,
+    #[serde(default)]
+    pub snapshot: Option<PathBuf>, // account state to restore before the inputs
+    #[serde(default)]
+    pub completion: Option<PathBuf>, // `<manifest>.completion.json` by default
+}
+
+/// Counts the run must match to succeed, unset ones are not checked
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedCounts {
+    pub transactions: Option<u64>, // valid transactions, applied or not
+    pub invalid: Option<u64>,
+    pub rejected: Option<u64>,
+    pub locked_accounts: Option<u64>,
+}
+
+impl RunManifest {
+    pub fn read(path: &Path) -> Result<RunManifest, ManifestError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// The equivalent process command line
+    pub fn to_args(&self) -> Result<Vec<String>, ManifestError> {
+        if self.snapshot.is_some() {
+            // a snapshot is engine state (disputable deposits included), accounts can only be
+            // seeded from opening balances (`--opening-balances` in the options)
+            return Err(ManifestError::Unsupported("restoring a snapshot"));
+        }
+        let (input, merged) = self.inputs.split_first().ok_or(ManifestError::NoInput)?;
+        let mut args = vec![input.display().to_string()];
+        for input in merged {
+            args.extend(["--merge-input".to_string(), input.display().to_string()]);
+        }
+        for (name, path) in &self.outputs {
+            if !MANIFEST_OUTPUTS.contains(&name.as_str()) {
+                return Err(ManifestError::UnknownOutput(name.clone()));
+            }
+            args.extend([format!("--{name}"), path.display().to_string()]);
+        }
+        args.extend(self.options.iter().cloned());
+        Ok(args)
+    }
+
+    /// Where the completion manifest of the run is written
+    pub fn completion_path(&self, manifest: &Path) -> PathBuf {
+        self.completion.clone().unwrap_or_else(|| {
+            let mut file_name = manifest.file_stem().unwrap_or_default().to_os_string();
+            file_name.push(".completion.json");
+            manifest.with_file_name(file_name)
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,  // the run failed or did not match the expected counts
+    Skipped, // the input was already processed (`--processed-registry`)
+}
+
+/// An expected count next to the actual one
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CountCheck {
+    pub count: String,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// An output of the run, the files of a directory output are listed one by one. A file left from
+/// an earlier run (modified before this one started) is not `written`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub output: String,
+    pub path: PathBuf,
+    pub written: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Written at the end of a manifest run, whatever its outcome, for the orchestrator to pick up
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CompletionManifest {
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ProcessingStats>,
+    pub mismatches: Vec<CountCheck>, // expected counts that were not met
+    pub artifacts: Vec<Artifact>,
+}
+
+impl CompletionManifest {
+    /// Completion of a run started at `started` that returned its stats (`None` when skipped) or
+    /// failed with an error
+    #[instrument(skip_all)]
+    pub fn new(
+        manifest: &RunManifest,
+        started: SystemTime,
+        result: Result<Option<ProcessingStats>, String>,
+    ) -> io::Result<CompletionManifest> {
+        let mut completion = CompletionManifest {
+            status: RunStatus::Succeeded,
+            error: None,
+            stats: None,
+            mismatches: Vec::new(),
+            artifacts: Vec::new(),
+        };
+        match result {
+            Ok(Some(stats)) => {
+                let expected = manifest.expected;
+                let valid = stats.transactions.values().sum();
+                for (count, expected, actual) in [
+                    ("transactions", expected.transactions, valid),
+                    ("invalid", expected.invalid, stats.invalid),
+                    ("rejected", expected.rejected, stats.rejected),
+                    (
+                        "locked_accounts",
+                        expected.locked_accounts,
+                        stats.locked_accounts,
+                    ),
+                ] {
+                    if let Some(expected) = expected
+                        && expected != actual
+                    {
+                        completion.mismatches.push(CountCheck {
+                            count: count.to_string(),
+                            expected,
+                            actual,
+                        });
+                    }
+                }
+                if !completion.mismatches.is_empty() {
+                    completion.status = RunStatus::Failed;
+                    completion.error = Some("the expected counts were not met".to_string());
+                }
+                completion.stats = Some(stats);
+            }
+            Ok(None) => completion.status = RunStatus::Skipped,
+            Err(err) => {
+                completion.status = RunStatus::Failed;
+                completion.error = Some(err);
+            }
+        }
+        for (output, path) in &manifest.outputs {
+            completion
+                .artifacts
+                .extend(artifacts(output, path, started)?);
+        }
+        Ok(completion)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut wtr = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut wtr, self)?;
+        writeln!(wtr)?;
+        wtr.flush()?;
+        info!(?path, status = ?self.status, "Wrote completion manifest");
+        Ok(())
+    }
+}
+
+// the file, or the files of the directory, written for an output
+fn artifacts(output: &str, path: &Path, started: SystemTime) -> io::Result<Vec<Artifact>> {
+    let artifact = |path: PathBuf| -> io::Result<Artifact> {
+        let metadata = fs::metadata(&path)?;
+        Ok(Artifact {
+            output: output.to_string(),
+            written: metadata.modified()? + MTIME_TOLERANCE >= started,
+            bytes: Some(metadata.len()),
+            sha256: Some(file_sha256(&path)?),
+            path,
+        })
+    };
+    if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .filter(|path| path.as_ref().map_or(true, |path| path.is_file()))
+            .collect::<io::Result<_>>()?;
+        files.sort();
+        return files.into_iter().map(artifact).collect();
+    }
+    if path.is_file() {
+        return Ok(vec![artifact(path.to_path_buf())?]);
+    }
+    Ok(vec![Artifact {
+        output: output.to_string(),
+        path: path.to_path_buf(),
+        written: false,
+        bytes: None,
+        sha256: None,
+    }])
+}
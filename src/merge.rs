@@ -0,0 +1,110 @@
+use std::{collections::HashMap, io};
+
+use serde::Serialize;
+use tracing::{debug, error, instrument};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{Transaction, TransactionId},
+};
+
+/// One line of the tx id mapping: which engine tx id a source tx id was given
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvTxIdMapping {
+    pub source: String,
+    pub source_tx: TransactionId,
+    pub engine_tx: TransactionId,
+}
+
+/// Gives every (source, tx) its own engine tx id so that sources with overlapping tx id spaces can be
+/// merged into one run. Disputes, resolves and chargebacks are resolved within their own source only.
+#[derive(Debug)]
+pub struct TxIdNamespaces<W: io::Write> {
+    sources: Vec<String>,
+    ids: HashMap<(usize, TransactionId), TransactionId>,
+    next: u32,
+    mapping: Option<csv::Writer<W>>, // where the allocations are recorded
+}
+
+impl<W: io::Write> TxIdNamespaces<W> {
+    pub fn new(sources: Vec<String>, mapping: Option<W>) -> TxIdNamespaces<W> {
+        TxIdNamespaces {
+            sources,
+            ids: HashMap::new(),
+            next: 0,
+            mapping: mapping.map(csv::Writer::from_writer),
+        }
+    }
+
+    /// Engine tx id of a source tx id, allocated on first use
+    fn engine_tx(
+        &mut self,
+        source: usize,
+        source_tx: TransactionId,
+    ) -> Result<TransactionId, ConversionError> {
+        if let Some(engine_tx) = self.ids.get(&(source, source_tx)) {
+            return Ok(*engine_tx);
+        }
+        let engine_tx = TransactionId(self.next);
+        self.next = self.next.checked_add(1).ok_or_else(|| {
+            ConversionError::Unexpected("no tx id left for the merged sources".to_string())
+        })?;
+        self.ids.insert((source, source_tx), engine_tx);
+        let record = CsvTxIdMapping {
+            source: self.sources[source].clone(),
+            source_tx,
+            engine_tx,
+        };
+        debug!(?record, "Allocated engine tx id");
+        if let Some(mapping) = &mut self.mapping
+            && let Err(err) = mapping
+                .serialize(&record)
+                .and_then(|_| Ok(mapping.flush()?))
+        {
+            error!(%err, ?record, "failed to record the tx id mapping");
+        }
+        Ok(engine_tx)
+    }
+
+    /// Rewrites the tx id of a transaction of the given source.
+    /// A reference to an unknown tx gets a fresh id so it can never match another source's transaction.
+    pub fn remap(
+        &mut self,
+        source: usize,
+        transaction: Transaction,
+    ) -> Result<Transaction, ConversionError> {
+        let tx = self.engine_tx(source, transaction.tx_id())?;
+        Ok(match transaction {
+            Transaction::Deposit { client, amount, .. } => {
+                Transaction::Deposit { client, tx, amount }
+            }
+            Transaction::Withdrawal { client, amount, .. } => {
+                Transaction::Withdrawal { client, tx, amount }
+            }
+            Transaction::Dispute { client, .. } => Transaction::Dispute { client, tx },
+            Transaction::Resolve { client, .. } => Transaction::Resolve { client, tx },
+            Transaction::Chargeback { client, .. } => Transaction::Chargeback { client, tx },
+            Transaction::Custom(mut custom) => {
+                custom.tx = tx;
+                Transaction::Custom(custom)
+            }
+        })
+    }
+}
+
+/// Chains the transactions of several sources, one after the other, with their tx ids remapped
+#[instrument(skip(sources, namespaces))]
+pub fn merge_sources<'a, W: io::Write + 'a>(
+    sources: Vec<Box<dyn Iterator<Item = Result<Transaction, ConversionError>> + 'a>>,
+    mut namespaces: TxIdNamespaces<W>,
+) -> impl Iterator<Item = Result<Transaction, ConversionError>> + 'a {
+    sources
+        .into_iter()
+        .enumerate()
+        .flat_map(|(source, transactions)| {
+            transactions.map(move |transaction| (source, transaction))
+        })
+        .map(move |(source, transaction)| {
+            transaction.and_then(|transaction| namespaces.remap(source, transaction))
+        })
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    ops::Not,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    channel::{self, Sender},
+    csv_input::ConversionError,
+    model::{Account, ClientId, Transaction},
+    sink::{AccountReceiver, AccountSink},
+};
+
+// the builtin transaction types have their own counter, the custom ones share the last
+const TYPES: [&str; 6] = [
+    "deposit",
+    "withdrawal",
+    "dispute",
+    "resolve",
+    "chargeback",
+    "custom",
+];
+
+#[derive(Debug)]
+struct Counters {
+    started: Instant,
+    transactions: [AtomicU64; TYPES.len()], // valid transactions read, by type
+    invalid: AtomicU64,                     // rows that failed conversion
+    queued: AtomicU64,                      // accounts sent to the writer threads
+    dequeued: AtomicU64,                    // accounts taken by the writer threads
+    max_queue_depth: AtomicU64,
+}
+
+/// Throughput counters of a run, shared by the stages they meter: the transactions read
+/// (`Metrics::meter`) and the accounts queued to the writer threads (`Metrics::meter_output`).
+/// Counting is a relaxed atomic increment, so the metrics cost about nothing when no one reads
+/// them; `snapshot` reads them from any thread while the run goes on.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            counters: Arc::new(Counters {
+                started: Instant::now(),
+                transactions: Default::default(),
+                invalid: AtomicU64::new(0),
+                queued: AtomicU64::new(0),
+                dequeued: AtomicU64::new(0),
+                max_queue_depth: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+/// The metrics of a run at some point, see `Metrics::snapshot`
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ProcessingMetrics {
+    pub elapsed_seconds: f64, // since the metrics were created
+    pub transactions: u64,    // valid transactions read so far
+    pub invalid: u64,
+    pub transactions_per_second: f64,
+    pub by_type: BTreeMap<&'static str, u64>, // the custom types are counted together as `custom`
+    pub output_queue_depth: u64, // accounts sent to the writer threads and not taken yet
+    pub max_output_queue_depth: u64,
+}
+
+impl Display for ProcessingMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} transactions ({} invalid) in {:.1}s, {:.0} tx/s",
+            self.transactions, self.invalid, self.elapsed_seconds, self.transactions_per_second
+        )?;
+        for (transaction_type, count) in &self.by_type {
+            write!(f, ", {transaction_type} {count}")?;
+        }
+        write!(
+            f,
+            ", output queue {} (max {})",
+            self.output_queue_depth, self.max_output_queue_depth
+        )
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// The counters so far
+    pub fn snapshot(&self) -> ProcessingMetrics {
+        let counters = &self.counters;
+        let elapsed_seconds = counters.started.elapsed().as_secs_f64();
+        let by_type: BTreeMap<&'static str, u64> = TYPES
+            .iter()
+            .zip(&counters.transactions)
+            .map(|(name, count)| (*name, count.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        let transactions = by_type.values().sum();
+        let queued = counters.queued.load(Ordering::Relaxed);
+        let dequeued = counters.dequeued.load(Ordering::Relaxed);
+        ProcessingMetrics {
+            elapsed_seconds,
+            transactions,
+            invalid: counters.invalid.load(Ordering::Relaxed),
+            transactions_per_second: match elapsed_seconds > 0.0 {
+                true => transactions as f64 / elapsed_seconds,
+                false => 0.0,
+            },
+            by_type,
+            output_queue_depth: queued.saturating_sub(dequeued),
+            max_output_queue_depth: counters.max_queue_depth.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Counts the transactions of an input as they are read
+    pub fn meter<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+        &self,
+        transactions: I,
+    ) -> Metered<I> {
+        Metered {
+            inner: transactions,
+            metrics: self.clone(),
+        }
+    }
+
+    /// Counts the accounts going through a writer channel, e.g. the one of `sink::account_channel`,
+    /// for the queue depth
+    pub fn meter_output(
+        &self,
+        (sink, receiver): (Box<dyn AccountSink>, AccountReceiver),
+    ) -> (Box<dyn AccountSink>, AccountReceiver) {
+        let metered_sink = MeteredSink {
+            inner: sink,
+            metrics: self.clone(),
+        };
+        let metrics = self.clone();
+        let metered_receiver = receiver.inspect(move |_| {
+            metrics.counters.dequeued.fetch_add(1, Ordering::Relaxed);
+        });
+        (Box::new(metered_sink), Box::new(metered_receiver))
+    }
+
+    /// Prints a snapshot to stderr every `period` until the returned reporter is stopped or dropped,
+    /// whatever the log level
+    pub fn spawn_reporter(&self, period: Duration) -> MetricsReporter {
+        let (stop, stopped) = channel::unbounded::<()>();
+        let metrics = self.clone();
+        let thread_id = thread::spawn(move || {
+            while channel::wait_for_shutdown(&stopped, period).not() {
+                eprintln!("metrics: {}", metrics.snapshot());
+            }
+        });
+        MetricsReporter {
+            stop: Some(stop),
+            thread_id: Some(thread_id),
+        }
+    }
+
+    fn record(&self, transaction: &Transaction) {
+        let index = match transaction {
+            Transaction::Deposit { .. } => 0,
+            Transaction::Withdrawal { .. } => 1,
+            Transaction::Dispute { .. } => 2,
+            Transaction::Resolve { .. } => 3,
+            Transaction::Chargeback { .. } => 4,
+            Transaction::Custom(_) => 5,
+        };
+        self.counters.transactions[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Transactions of an input counted by `Metrics`
+#[derive(Debug)]
+pub struct Metered<I> {
+    inner: I,
+    metrics: Metrics,
+}
+
+impl<I: Iterator<Item = Result<Transaction, ConversionError>>> Iterator for Metered<I> {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.inner.next();
+        match &next {
+            Some(Ok(transaction)) => self.metrics.record(transaction),
+            Some(Err(_)) => {
+                self.metrics
+                    .counters
+                    .invalid
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            None => {}
+        }
+        next
+    }
+}
+
+// counts the accounts queued to a writer thread
+struct MeteredSink {
+    inner: Box<dyn AccountSink>,
+    metrics: Metrics,
+}
+
+impl AccountSink for MeteredSink {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        let counters = &self.metrics.counters;
+        let queued = counters.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        let depth = queued.saturating_sub(counters.dequeued.load(Ordering::Relaxed));
+        counters.max_queue_depth.fetch_max(depth, Ordering::Relaxed);
+        self.inner.emit(client, account);
+    }
+}
+
+/// Periodic logging of the metrics, see `Metrics::spawn_reporter`
+#[derive(Debug)]
+pub struct MetricsReporter {
+    stop: Option<Sender<()>>,
+    thread_id: Option<JoinHandle<()>>,
+}
+
+impl MetricsReporter {
+    /// Stops the logging and waits for the reporter thread
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        drop(self.stop.take()); // wakes the reporter up
+        if let Some(thread_id) = self.thread_id.take() {
+            thread_id
+                .join()
+                .expect("failed to join metrics reporter thread");
+        }
+    }
+}
+
+impl Drop for MetricsReporter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
@@ -1,30 +1,178 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque, hash_map::Entry},
     fmt::Display,
+    io, mem,
+    num::{NonZeroU64, NonZeroUsize},
     ops::Not,
-    sync::mpsc::{SendError, Sender},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
 };
 
-use rust_decimal::{Decimal, dec};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use tracing::{Level, error, instrument, span, trace, warn};
+use thiserror::Error;
+use tracing::{Level, error, info, instrument, span, trace, warn};
 
-use crate::csv_input::ConversionError;
+use crate::{
+    channel,
+    checkpoint::{SNAPSHOT_VERSION, Snapshot, SnapshotAccount, SnapshotDeposit},
+    csv_input::{ConversionError, transactions_from_reader},
+    custom_types::custom_handler,
+    money::Money,
+    observer::TransactionObserver,
+    sink::AccountSink,
+};
 
 /// Clients contains the mapping between the ClientId's and the Client Accounts
 #[derive(Debug)]
 pub struct Clients {
-    pub accounts: HashMap<ClientId, Account>, // Client accounts
-    pub disputable_transactions: HashMap<TransactionId, DisputableTransactionStatus>, // Transactions that can be disputed or resolved or chargedback (shared since TransactionIds are globally unique)
-    pub output_sender: Sender<(ClientId, Account)>, // sender to early print accounts that are in a final state (locked)
+    pub accounts: Accounts,                              // Client accounts
+    pub disputable_transactions: DisputableTransactions, // Transactions that can be disputed or resolved or chargedback (shared since TransactionIds are globally unique)
+    disputes: HashMap<ClientId, BTreeSet<TransactionId>, MapHasher>, // open disputes by owner, kept in sync with disputable_transactions by the methods below
+    outputs: Vec<Box<dyn AccountSink>>, // receive the accounts, early for the ones in a final state (locked)
+    pub erased: HashSet<ClientId>, // tombstones of anonymized clients, their transactions are rejected
+    pub sorted_output: bool, // send_to_output emits the accounts by client id, for reproducible outputs
+    pub sparse_accounts: bool, // a client's account only exists once a transaction changed it
+    pub rounding: RoundingPolicy, // of the balances emitted to the outputs
+    pub settle_horizon: Option<NonZeroU64>, // an undisputed deposit can only be disputed by this many following transactions
+    settling: VecDeque<(u64, TransactionId)>, // deposits by age, dropped once past the settle horizon
+    applied: u64, // transactions given to apply_transaction, the clock of the settle horizon
+    observers: Vec<Box<dyn TransactionObserver>>, // notified after each transaction (exporters, reports)
 }
 
 impl Clients {
-    pub fn new(tx: Sender<(ClientId, Account)>) -> Clients {
+    /// Clients writing their accounts to `output`, e.g. the `Sender` of a writer thread
+    pub fn new<S: AccountSink + 'static>(output: S) -> Clients {
         Clients {
-            accounts: HashMap::new(),
-            disputable_transactions: HashMap::new(),
-            output_sender: tx,
+            accounts: Accounts::default(),
+            disputable_transactions: DisputableTransactions::default(),
+            disputes: HashMap::default(),
+            outputs: vec![Box::new(output)],
+            erased: HashSet::new(),
+            sorted_output: false,
+            sparse_accounts: false,
+            rounding: RoundingPolicy::default(),
+            settle_horizon: None,
+            settling: VecDeque::new(),
+            applied: 0,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Like `new` with the maps pre-sized for the expected clients and disputable deposits (at most
+    /// the transactions), e.g. from a cardinality estimate, to avoid rehashing while loading
+    pub fn with_capacity<S: AccountSink + 'static>(
+        output: S,
+        clients: usize,
+        disputables: usize,
+    ) -> Clients {
+        let mut sized = Clients::new(output);
+        sized.reserve(clients, disputables);
+        sized
+    }
+
+    /// Makes room for at least `clients` more accounts and `disputables` more disputable deposits,
+    /// e.g. before loading a large input into `Clients` that were configured after `new`
+    pub fn reserve(&mut self, clients: usize, disputables: usize) {
+        self.accounts.reserve(clients);
+        self.disputable_transactions.reserve(disputables);
+    }
+
+    /// The state of the accounts and deposits, to resume from it with `from_snapshot`. The
+    /// `input_offset` is left to the caller, who knows how much of the input was consumed.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut accounts: Vec<SnapshotAccount> = (self.accounts.iter())
+            .map(|(client, account)| SnapshotAccount {
+                client: *client,
+                available: account.available.to_decimal(),
+                held: account.held.to_decimal(),
+                locked: account.locked,
+                fields: account.fields.clone(),
+            })
+            .collect();
+        accounts.sort_unstable_by_key(|account| account.client);
+        let mut disputable_transactions: Vec<SnapshotDeposit> = (self.disputable_transactions)
+            .iter()
+            .map(|(tx, disputable)| {
+                let (amount, disputed) = match disputable.status {
+                    DisputableTransactionStatus::NotDisputedAmount(amount) => (amount, false),
+                    DisputableTransactionStatus::DisputedAmount(amount) => (amount, true),
+                };
+                SnapshotDeposit {
+                    tx: *tx,
+                    client: disputable.client,
+                    amount,
+                    disputed,
+                }
+            })
+            .collect();
+        disputable_transactions.sort_unstable_by_key(|deposit| deposit.tx);
+        let mut erased: Vec<ClientId> = self.erased.iter().copied().collect();
+        erased.sort_unstable();
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            input_offset: 0,
+            applied: self.applied,
+            accounts,
+            disputable_transactions,
+            settling: self.settling.iter().copied().collect(),
+            erased,
+        }
+    }
+
+    /// Clients in the state of a snapshot, writing their accounts to `output`. The accounts locked
+    /// before the snapshot are not emitted again by the transactions, see `emit_locked_accounts`.
+    pub fn from_snapshot<S: AccountSink + 'static>(snapshot: Snapshot, output: S) -> Clients {
+        let mut clients = Clients::with_capacity(
+            output,
+            snapshot.accounts.len(),
+            snapshot.disputable_transactions.len(),
+        );
+        for restored in snapshot.accounts {
+            let mut account = Account::new(restored.available, restored.held, restored.locked);
+            account.fields = restored.fields;
+            clients.accounts.insert(restored.client, account);
+        }
+        for deposit in snapshot.disputable_transactions {
+            let status = match deposit.disputed {
+                true => {
+                    (clients.disputes.entry(deposit.client).or_default()).insert(deposit.tx);
+                    DisputableTransactionStatus::DisputedAmount(deposit.amount)
+                }
+                false => DisputableTransactionStatus::NotDisputedAmount(deposit.amount),
+            };
+            clients.disputable_transactions.insert(
+                deposit.tx,
+                DisputableTransaction {
+                    client: deposit.client,
+                    status,
+                },
+            );
+        }
+        clients.settling = snapshot.settling.into();
+        clients.applied = snapshot.applied;
+        clients.erased = snapshot.erased.into_iter().collect();
+        clients
+    }
+
+    /// Emits every locked account to the outputs, e.g. after `from_snapshot`: the accounts locked
+    /// before the snapshot were only written to the outputs of the run that took it
+    pub fn emit_locked_accounts(&mut self) {
+        let mut locked: Vec<(&ClientId, &Account)> = (self.accounts.iter())
+            .filter(|(_, account)| account.locked)
+            .collect();
+        if self.sorted_output {
+            locked.sort_unstable_by_key(|(client, _)| client.0);
+        }
+        for (client, account) in locked {
+            let account = self.rounding.apply(account);
+            for output in self.outputs.iter_mut() {
+                output.emit(*client, &account);
+            }
         }
     }
 
@@ -33,72 +181,794 @@ impl Clients {
     pub fn load_transactions<T: Iterator<Item = Result<Transaction, ConversionError>>>(
         &mut self,
         transactions: T,
-    ) {
+    ) -> ProcessingStats {
+        let started = Instant::now();
+        let mut stats = ProcessingStats::default();
         for transaction in transactions {
-            match transaction {
-                Err(err) => error!(error=%err, "Skipping invalid transaction in file"),
-                Ok(transaction) => {
-                    let client_id = transaction.client_id();
-                    let span = span!(Level::TRACE, "applying transaction");
-                    let _enter = span.enter();
-                    self.accounts
-                        .entry(client_id)
-                        .and_modify(|account| {
-                            if account.locked().not() {
-                                //if not locked
-                                account.apply(&transaction, &mut self.disputable_transactions);
-                                if account.locked() { // became locked, we can send this account to the output imediately
-                                    self.output_sender
-                                        .send((client_id, account.clone()))
-                                        .expect("failed to send");
-                                }
-                            }
-                            else{
-                                warn!(%client_id, ?transaction, "Tried to apply transction to a locked account");
-                            }
-                        })
-                        .or_insert_with(|| {
-                            let mut account = Account::default();
-                            account.apply(&transaction, &mut self.disputable_transactions);
-                            if account.locked() { // became locked, we can send this account to the output imediately
-                                self.output_sender
-                                    .send((client_id, account.clone()))
-                                    .expect("failed to send");
-                            }
-                            account
-                        });
+            self.load_transaction(transaction, &mut stats);
+        }
+        self.finish_loading(&mut stats, started);
+        stats
+    }
+
+    // applies one transaction of `load_transactions`, or counts it as invalid
+    pub(crate) fn load_transaction<E: Display>(
+        &mut self,
+        transaction: Result<Transaction, E>,
+        stats: &mut ProcessingStats,
+    ) {
+        match transaction {
+            Err(err) => {
+                error!(error=%err, "Skipping invalid transaction in file");
+                stats.invalid += 1;
+            }
+            Ok(transaction) => {
+                *stats
+                    .transactions
+                    .entry(transaction.type_name())
+                    .or_default() += 1;
+                let volume = match &transaction {
+                    Transaction::Deposit { amount, .. } => Some((&mut stats.deposits, *amount)),
+                    Transaction::Withdrawal { amount, .. } => {
+                        Some((&mut stats.withdrawals, *amount))
+                    }
+                    _ => None,
+                };
+                match (self.apply_transaction(transaction), volume) {
+                    (false, _) => stats.rejected += 1,
+                    (true, Some((volume, amount))) => *volume += amount,
+                    (true, None) => {}
+                }
+            }
+        }
+    }
+
+    // the counts of a loading that are only known once it is over
+    pub(crate) fn finish_loading(&self, stats: &mut ProcessingStats, started: Instant) {
+        stats.locked_accounts = self
+            .accounts
+            .values()
+            .filter(|account| account.locked)
+            .count() as u64;
+        stats.elapsed_seconds = started.elapsed().as_secs_f64();
+    }
+
+    /// Like `load_transactions` with the accounts sharded by client id over `threads` worker threads,
+    /// each owning its clients' accounts and disputable deposits, while this thread parses and routes
+    /// the transactions. The transactions of a client are applied in input order, so the final
+    /// accounts are the same as with `load_transactions` as long as a dispute, resolve or chargeback
+    /// only references a deposit of its own client (one of another client is ignored).
+    /// Observers are shared by the workers behind a lock, they see each client's transactions in
+    /// order but the clients interleaved; locked accounts are emitted as the workers report them.
+    #[instrument(skip(self, transactions))]
+    pub fn load_transactions_parallel<T: Iterator<Item = Result<Transaction, ConversionError>>>(
+        &mut self,
+        transactions: T,
+        threads: NonZeroUsize,
+    ) -> ProcessingStats {
+        let started = Instant::now();
+        let threads = threads.get();
+        let shard_of = |client: ClientId| client.0 as usize % threads;
+        let observers = mem::take(&mut self.observers);
+        let observers = observers
+            .is_empty()
+            .not()
+            .then(|| Arc::new(Mutex::new(observers)));
+        let (locked_tx, locked_rx) = channel::unbounded();
+        // the shards share the capacity of the maps, which keep it to collect the shards at the end
+        let clients_share = self.accounts.capacity().div_ceil(threads);
+        let disputables_share = self.disputable_transactions.capacity().div_ceil(threads);
+        let mut shards: Vec<Clients> = (0..threads)
+            .map(|_| {
+                let mut shard =
+                    Clients::with_capacity(locked_tx.clone(), clients_share, disputables_share);
+                shard.erased = self.erased.clone();
+                shard.sparse_accounts = self.sparse_accounts;
+                shard.rounding = self.rounding;
+                shard.settle_horizon = self.settle_horizon;
+                shard.applied = self.applied; // the shard clocks start at the current one
+                if let Some(observers) = &observers {
+                    shard.add_observer(observers.clone());
+                }
+                shard
+            })
+            .collect();
+        drop(locked_tx);
+        for (age, tx) in self.settling.drain(..) {
+            if let Some(disputable) = self.disputable_transactions.get(&tx) {
+                shards[shard_of(disputable.client)]
+                    .settling
+                    .push_back((age, tx));
+            }
+        }
+        for (client, account) in self.accounts.drain() {
+            shards[shard_of(client)].accounts.insert(client, account);
+        }
+        for (tx, disputable) in self.disputable_transactions.drain() {
+            let shard = &mut shards[shard_of(disputable.client)];
+            shard.disputable_transactions.insert(tx, disputable);
+        }
+        for (client, disputes) in self.disputes.drain() {
+            shards[shard_of(client)].disputes.insert(client, disputes);
+        }
+
+        let mut invalid = 0;
+        let results = thread::scope(|scope| {
+            let mut senders = Vec::with_capacity(threads);
+            let mut workers = Vec::with_capacity(threads);
+            for mut shard in shards {
+                let (batch_tx, batch_rx) = channel::bounded::<Vec<Transaction>>(BATCH_QUEUE);
+                senders.push(batch_tx);
+                workers.push(scope.spawn(move || {
+                    let stats = shard.load_transactions(batch_rx.into_iter().flatten().map(Ok));
+                    (shard, stats)
+                }));
+            }
+            let mut batches: Vec<Vec<Transaction>> =
+                (0..threads).map(|_| Vec::with_capacity(BATCH)).collect();
+            for transaction in transactions {
+                let transaction = match transaction {
+                    Ok(transaction) => transaction,
+                    Err(err) => {
+                        error!(error=%err, "Skipping invalid transaction in file");
+                        invalid += 1;
+                        continue;
+                    }
+                };
+                let shard = shard_of(transaction.client_id());
+                batches[shard].push(transaction);
+                if batches[shard].len() == BATCH {
+                    let batch = mem::replace(&mut batches[shard], Vec::with_capacity(BATCH));
+                    if senders[shard].send(batch).is_err() {
+                        error!(shard, "worker thread stopped, dropping its transactions");
+                    }
+                    self.emit_locked(&locked_rx);
+                }
+            }
+            for (sender, batch) in senders.into_iter().zip(batches) {
+                if batch.is_empty().not() && sender.send(batch).is_err() {
+                    error!("worker thread stopped, dropping its transactions");
+                }
+            } // the senders are dropped, the workers finish
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("worker thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut stats = ProcessingStats {
+            invalid,
+            ..ProcessingStats::default()
+        };
+        let started_at = self.applied;
+        let mut settling = Vec::new(); // (transactions of its shard since the deposit, tx)
+        for (shard, shard_stats) in results {
+            self.applied += shard.applied - started_at;
+            settling.extend(
+                shard
+                    .settling
+                    .into_iter()
+                    .map(|(age, tx)| (shard.applied - age, tx)),
+            );
+            self.accounts.extend(shard.accounts);
+            self.disputable_transactions
+                .extend(shard.disputable_transactions);
+            self.disputes.extend(shard.disputes);
+            stats.add(shard_stats);
+        } // the shards are dropped, closing the locked accounts channel
+        settling.sort_unstable_by_key(|(since, _)| Reverse(*since));
+        self.settling.extend(
+            settling
+                .into_iter()
+                .map(|(since, tx)| (self.applied - since, tx)),
+        );
+        self.emit_locked(&locked_rx);
+        if let Some(observers) = observers {
+            self.observers = Arc::into_inner(observers)
+                .expect("the workers released the observers")
+                .into_inner()
+                .expect("observer mutex poisoned");
+        }
+        stats.locked_accounts = self
+            .accounts
+            .values()
+            .filter(|account| account.locked)
+            .count() as u64;
+        stats.elapsed_seconds = started.elapsed().as_secs_f64();
+        stats
+    }
+
+    /// Like `load_transactions` over a csv reader, parsed on its own thread: reading and parsing the
+    /// next rows overlaps with applying the previous ones on this thread. The parsed transactions
+    /// are handed over in batches through a bounded queue, so at most a few thousand are buffered.
+    #[instrument(skip(self, csv_reader))]
+    pub fn load_transactions_pipelined<R: io::Read + Send>(
+        &mut self,
+        csv_reader: csv::Reader<R>,
+    ) -> ProcessingStats {
+        thread::scope(|scope| {
+            let (batch_tx, batch_rx) = channel::bounded(BATCH_QUEUE);
+            let parser = scope.spawn(move || {
+                let mut batch = Vec::with_capacity(BATCH);
+                for transaction in transactions_from_reader(csv_reader) {
+                    batch.push(transaction);
+                    if batch.len() == BATCH {
+                        let full = mem::replace(&mut batch, Vec::with_capacity(BATCH));
+                        if batch_tx.send(full).is_err() {
+                            return; // the applying thread stopped
+                        }
+                    }
+                }
+                let _ = batch_tx.send(batch);
+            });
+            let stats = self.load_transactions(batch_rx.into_iter().flatten());
+            parser.join().expect("parser thread panicked");
+            stats
+        })
+    }
+
+    /// Like `load_transactions`, on `threads` workers like `load_transactions_parallel` when more
+    /// than one, calling `checkpoint` with the clients after every `every` transactions and after
+    /// the last ones, e.g. to write a `snapshot` a crashed run resumes from
+    #[instrument(skip(self, transactions, checkpoint))]
+    pub fn load_transactions_with_checkpoints<T, F>(
+        &mut self,
+        transactions: T,
+        every: NonZeroU64,
+        threads: Option<NonZeroUsize>,
+        mut checkpoint: F,
+    ) -> ProcessingStats
+    where
+        T: Iterator<Item = Result<Transaction, ConversionError>>,
+        F: FnMut(&Clients),
+    {
+        let started = Instant::now();
+        let mut stats = ProcessingStats::default();
+        let mut transactions = transactions.peekable();
+        while transactions.peek().is_some() {
+            let chunk = transactions.by_ref().take(every.get() as usize);
+            stats.add(match threads {
+                Some(threads) if threads.get() > 1 => {
+                    self.load_transactions_parallel(chunk, threads)
+                }
+                _ => self.load_transactions(chunk),
+            });
+            checkpoint(self);
+        }
+        self.finish_loading(&mut stats, started);
+        stats
+    }
+
+    // forwards the accounts locked by the workers of `load_transactions_parallel`, already rounded
+    fn emit_locked(&mut self, locked: &channel::Receiver<(ClientId, Account)>) {
+        for (client, account) in locked.try_iter() {
+            for output in self.outputs.iter_mut() {
+                output.emit(client, &account);
+            }
+        }
+    }
+
+    /// Register another sink, every account is emitted to each of them in registration order
+    pub fn add_sink<S: AccountSink + 'static>(&mut self, sink: S) {
+        self.outputs.push(Box::new(sink));
+    }
+
+    /// Register an observer that is notified of every transaction applied to the accounts
+    pub fn add_observer<O: TransactionObserver + 'static>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Mutate the client Account targeted by a single Transaction, returns whether it changed the
+    /// account (false when it was ignored: not enough funds, unknown tx, locked account...)
+    pub fn apply_transaction(&mut self, transaction: Transaction) -> bool {
+        let client_id = transaction.client_id();
+        let span = span!(Level::TRACE, "applying transaction");
+        let _enter = span.enter();
+        self.applied += 1;
+        if let Some(horizon) = self.settle_horizon {
+            self.settle(horizon);
+        }
+        if self.erased.contains(&client_id) {
+            warn!(%client_id, tx = %transaction.tx_id(), "Rejected transaction of an erased client");
+            return false;
+        }
+        let new = self.sparse_accounts && self.accounts.contains_key(&client_id).not();
+        let tx = transaction.tx_id();
+        let disputed_by = dispute_owner(&self.disputable_transactions, tx);
+        let account = self.accounts.entry(client_id).or_default();
+        // only pay for the copy of the previous state if someone is observing
+        let before = self.observers.is_empty().not().then(|| account.clone());
+        let was_locked = account.locked();
+        let balances = (account.available, account.held);
+        if was_locked.not() {
+            //if not locked
+            account.apply(&transaction, &mut self.disputable_transactions);
+            index_dispute(
+                &mut self.disputes,
+                &self.disputable_transactions,
+                tx,
+                disputed_by,
+            );
+        } else {
+            warn!(%client_id, ?transaction, "Tried to apply transction to a locked account");
+        }
+        if new && *account == Account::default() {
+            // e.g. a dispute of an unknown tx, the client would only be an empty placeholder
+            trace!(%client_id, "Not materializing an account left untouched");
+            self.accounts.remove(&client_id);
+            return false;
+        }
+        let changed =
+            account.locked() != was_locked || (account.available, account.held) != balances;
+        if self.settle_horizon.is_some()
+            && changed
+            && matches!(transaction, Transaction::Deposit { .. })
+        {
+            self.settling.push_back((self.applied, tx));
+        }
+        if let Some(before) = before {
+            for observer in self.observers.iter_mut() {
+                observer.on_transaction(client_id, &transaction, &before, account);
+            }
+            for observer in self.observers.iter_mut() {
+                observer.annotate(client_id, account);
+            }
+        }
+        if was_locked.not() && account.locked() {
+            // became locked, we can send this account (with its custom fields) to the output imediately
+            let account = self.rounding.apply(account);
+            for output in self.outputs.iter_mut() {
+                output.emit(client_id, &account);
+            }
+        }
+        changed
+    }
+
+    // drops the undisputed deposits older than the horizon, disputed ones are checked again a horizon later
+    fn settle(&mut self, horizon: NonZeroU64) {
+        while let Some((age, tx)) = self.settling.front().copied() {
+            if self.applied - age <= horizon.get() {
+                break;
+            }
+            self.settling.pop_front();
+            match self
+                .disputable_transactions
+                .get(&tx)
+                .map(|disputable| &disputable.status)
+            {
+                Some(DisputableTransactionStatus::NotDisputedAmount(_)) => {
+                    self.disputable_transactions.remove(&tx);
+                    trace!(%tx, "Settled deposit, it can no longer be disputed");
+                }
+                Some(DisputableTransactionStatus::DisputedAmount(_)) => {
+                    self.settling.push_back((self.applied, tx))
                 }
+                None => {} // charged back or erased
             }
         }
     }
 
-    /// Send accounts to the output channel
+    /// Anonymizes a client (GDPR erasure): its account and disputable deposits are dropped, observers
+    /// forget its history and a tombstone rejects any later transaction with the same id.
+    /// Accounts that were already written because they became locked cannot be recalled.
+    #[instrument(skip(self))]
+    pub fn erase_client(&mut self, client: ClientId, policy: ErasurePolicy) -> Erasure {
+        let account = self.accounts.remove(&client).unwrap_or_default();
+        self.disputable_transactions
+            .retain(|_, disputable| disputable.client != client);
+        self.disputes.remove(&client);
+        for observer in self.observers.iter_mut() {
+            observer.on_erasure(client);
+        }
+        self.erased.insert(client);
+        let erasure = Erasure {
+            client,
+            policy,
+            amount: account.total(),
+        };
+        info!(%client, ?policy, amount = %erasure.amount, "Erased client");
+        erasure
+    }
+
+    /// Seeds the account of a client before any of its transactions, e.g. an opening balance
+    /// migrated from another ledger. Observers see it as an opening entry, and a locked account is
+    /// emitted right away like one locked by a chargeback.
+    #[instrument(skip(self))]
+    pub fn open_account(&mut self, client: ClientId, account: Account) -> Result<(), OpeningError> {
+        if self.erased.contains(&client) {
+            return Err(OpeningError::Erased(client));
+        }
+        if self.accounts.contains_key(&client) {
+            return Err(OpeningError::Existing(client));
+        }
+        let account = self.accounts.entry(client).or_insert(account);
+        for observer in self.observers.iter_mut() {
+            observer.on_opening(client, account);
+        }
+        for observer in self.observers.iter_mut() {
+            observer.annotate(client, account);
+        }
+        if account.locked() {
+            let account = self.rounding.apply(account);
+            for output in self.outputs.iter_mut() {
+                output.emit(client, &account);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the account of `from` into the one of `into` (account consolidation after identity
+    /// matching): balances are summed and the deposits of `from`, disputed or not, now belong to `into`.
+    /// The account of `from` is dropped, locked or erased accounts cannot be merged.
+    #[instrument(skip(self))]
+    pub fn merge_clients(
+        &mut self,
+        from: ClientId,
+        into: ClientId,
+    ) -> Result<ClientMerge, MergeError> {
+        if from == into {
+            return Err(MergeError::SameClient(from));
+        }
+        for client in [from, into] {
+            if self.erased.contains(&client) {
+                return Err(MergeError::Erased(client));
+            }
+            if self.accounts.get(&client).is_some_and(Account::locked) {
+                return Err(MergeError::Locked(client));
+            }
+        }
+        let source = self
+            .accounts
+            .get(&from)
+            .ok_or(MergeError::UnknownClient(from))?;
+        let into_account = self.accounts.get(&into).cloned().unwrap_or_default();
+        let (Some(available), Some(held)) = (
+            into_account.available.checked_add(source.available),
+            into_account.held.checked_add(source.held),
+        ) else {
+            return Err(MergeError::Overflow(from, into));
+        };
+        let source = self
+            .accounts
+            .remove(&from)
+            .expect("the source account exists");
+        for disputable in self.disputable_transactions.values_mut() {
+            if disputable.client == from {
+                disputable.client = into;
+            }
+        }
+        let disputed = self.disputes.remove(&from).unwrap_or_default();
+        let disputes = disputed.len();
+        if disputed.is_empty().not() {
+            self.disputes.entry(into).or_default().extend(disputed);
+        }
+        let account = self.accounts.entry(into).or_default();
+        account.available = available;
+        account.held = held;
+        for observer in self.observers.iter_mut() {
+            observer.on_merge(from, into, account);
+        }
+        let merge = ClientMerge {
+            from,
+            into,
+            available: source.available.to_decimal(),
+            held: source.held.to_decimal(),
+            disputes,
+        };
+        info!(%from, %into, available = %merge.available, held = %merge.held, disputes, "Merged client");
+        Ok(merge)
+    }
+
+    /// Open disputes of a client by tx id: the deposits it owns whose amounts are held
+    pub fn client_disputes(
+        &self,
+        client: ClientId,
+    ) -> impl Iterator<Item = (TransactionId, Decimal)> + '_ {
+        self.disputes
+            .get(&client)
+            .into_iter()
+            .flatten()
+            .filter_map(|tx| match self.disputable_transactions.get(tx)?.status {
+                DisputableTransactionStatus::DisputedAmount(amount) => Some((*tx, amount)),
+                DisputableTransactionStatus::NotDisputedAmount(_) => None,
+            })
+    }
+
+    /// Clients owning at least one open dispute, in no particular order
+    pub fn disputed_clients(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.disputes.keys().copied()
+    }
+
+    /// Emit the accounts to the outputs, which are dropped afterwards (closing the writer channels)
     pub fn send_to_output(
+        mut self,
+        output_mode: OutputMode, // Send All the accounts or skip the locked ones
+    ) {
+        let mut accounts: Vec<(&ClientId, &Account)> = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| matches!(output_mode, OutputMode::All) || account.locked().not())
+            .collect();
+        if self.sorted_output {
+            accounts.sort_unstable_by_key(|(client, _)| client.0);
+        }
+        for (client, account) in accounts {
+            let account = self.rounding.apply(account);
+            for output in self.outputs.iter_mut() {
+                output.emit(*client, &account);
+            }
+        }
+    }
+
+    /// Like `send_to_output` but the accounts are split over `sinks` by client id modulo their
+    /// number, as in `load_transactions_parallel`, and each sink is fed by its own thread, e.g. one
+    /// writer per part (see `partition::PartWriters`). The registered outputs do not get them.
+    pub fn send_to_output_sharded<S: AccountSink>(
         self,
         output_mode: OutputMode, // Send All the accounts or skip the locked ones
-    ) -> Result<(), SendError<(ClientId, Account)>> {
+        sinks: Vec<S>,
+    ) {
+        let shards = sinks.len().max(1);
+        let mut shard_accounts: Vec<Vec<(&ClientId, &Account)>> = vec![Vec::new(); shards];
         for (client, account) in self
             .accounts
-            .into_iter()
+            .iter()
             .filter(|(_, account)| matches!(output_mode, OutputMode::All) || account.locked().not())
         {
-            self.output_sender.send((client, account))?;
+            shard_accounts[client.0 as usize % shards].push((client, account));
+        }
+        let (rounding, sorted_output) = (self.rounding, self.sorted_output);
+        thread::scope(|scope| {
+            for (mut sink, mut accounts) in sinks.into_iter().zip(shard_accounts) {
+                scope.spawn(move || {
+                    if sorted_output {
+                        accounts.sort_unstable_by_key(|(client, _)| client.0);
+                    }
+                    for (client, account) in accounts {
+                        sink.emit(*client, &rounding.apply(account));
+                    }
+                });
+            }
+        });
+    }
+}
+
+// owner of tx when it is an open dispute
+fn dispute_owner(
+    disputable_transactions: &DisputableTransactions,
+    tx: TransactionId,
+) -> Option<ClientId> {
+    disputable_transactions
+        .get(&tx)
+        .filter(|disputable| {
+            matches!(
+                disputable.status,
+                DisputableTransactionStatus::DisputedAmount(_)
+            )
+        })
+        .map(|disputable| disputable.client)
+}
+
+// moves tx in the dispute index from the owner of its open dispute before a transaction to the one after
+fn index_dispute(
+    disputes: &mut HashMap<ClientId, BTreeSet<TransactionId>, MapHasher>,
+    disputable_transactions: &DisputableTransactions,
+    tx: TransactionId,
+    disputed_by: Option<ClientId>,
+) {
+    let owner = dispute_owner(disputable_transactions, tx);
+    if owner == disputed_by {
+        return;
+    }
+    if let Some(client) = disputed_by
+        && let Entry::Occupied(mut txs) = disputes.entry(client)
+    {
+        txs.get_mut().remove(&tx);
+        if txs.get().is_empty() {
+            txs.remove();
+        }
+    }
+    if let Some(client) = owner {
+        disputes.entry(client).or_default().insert(tx);
+    }
+}
+
+/// Decimal places and rounding of the balances written to the outputs. The default is the
+/// precision of the input, 4 places with banker's rounding, like the `Account` accessors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundingPolicy {
+    pub scale: u32, // 0 to 4, amounts never have more than 4 decimal places
+    pub strategy: RoundingStrategy,
+}
+
+impl Default for RoundingPolicy {
+    fn default() -> Self {
+        RoundingPolicy {
+            scale: 4,
+            strategy: RoundingStrategy::Bankers,
         }
-        Ok(())
     }
 }
 
+impl RoundingPolicy {
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        amount.round_dp_with_strategy(self.scale, self.strategy.into())
+    }
+
+    // rounding to fewer places cannot overflow
+    fn round_money(&self, amount: Money) -> Money {
+        Money::from_decimal(self.round(amount.to_decimal())).unwrap_or(amount)
+    }
+
+    /// The account with its balances rounded, borrowed as is with the default policy
+    pub fn apply<'a>(&self, account: &'a Account) -> Cow<'a, Account> {
+        if *self == RoundingPolicy::default() {
+            return Cow::Borrowed(account);
+        }
+        Cow::Owned(Account {
+            available: self.round_money(account.available),
+            held: self.round_money(account.held),
+            ..account.clone()
+        })
+    }
+}
+
+/// How a balance is rounded to the scale of the output
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingStrategy {
+    #[default]
+    Bankers, // half to even: 0.125 -> 0.12, 0.135 -> 0.14
+    HalfUp,   // half away from zero: 0.125 -> 0.13
+    HalfDown, // half toward zero: 0.125 -> 0.12, 0.135 -> 0.13
+    Down,     // truncated toward zero
+    Up,       // away from zero
+}
+
+impl FromStr for RoundingStrategy {
+    type Err = String;
+
+    /// `bankers`, `half-up`, `half-down`, `down` or `up`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bankers" => Ok(RoundingStrategy::Bankers),
+            "half-up" => Ok(RoundingStrategy::HalfUp),
+            "half-down" => Ok(RoundingStrategy::HalfDown),
+            "down" => Ok(RoundingStrategy::Down),
+            "up" => Ok(RoundingStrategy::Up),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+impl From<RoundingStrategy> for rust_decimal::RoundingStrategy {
+    fn from(strategy: RoundingStrategy) -> Self {
+        match strategy {
+            RoundingStrategy::Bankers => rust_decimal::RoundingStrategy::MidpointNearestEven,
+            RoundingStrategy::HalfUp => rust_decimal::RoundingStrategy::MidpointAwayFromZero,
+            RoundingStrategy::HalfDown => rust_decimal::RoundingStrategy::MidpointTowardZero,
+            RoundingStrategy::Down => rust_decimal::RoundingStrategy::ToZero,
+            RoundingStrategy::Up => rust_decimal::RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+/// What happens to the remaining funds of an erased client
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErasurePolicy {
+    #[default]
+    Zero, // the balance is written off
+    TransferOut, // the balance is paid out to the client before the account is dropped
+}
+
+/// Record of an erasure, the only trace left of the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Erasure {
+    pub client: ClientId,
+    pub policy: ErasurePolicy,
+    pub amount: Decimal, // total funds written off or transferred out (held funds included)
+}
+
+/// Transactions sent to another thread at once (the workers of `Clients::load_transactions_parallel`,
+/// the applying thread of `Clients::load_transactions_pipelined`), and batches queued per receiving
+/// thread before the sender waits
+const BATCH: usize = 1024;
+const BATCH_QUEUE: usize = 16;
+
+/// What `load_transactions` did, written as the machine-readable summary of a run
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ProcessingStats {
+    pub transactions: BTreeMap<&'static str, u64>, // valid transactions by type, applied or not
+    pub invalid: u64,                              // rows that failed conversion
+    pub rejected: u64, // valid transactions that left their account unchanged
+    pub locked_accounts: u64, // once the transactions were loaded
+    pub deposits: Decimal, // volume of the applied deposits
+    pub withdrawals: Decimal,
+    pub elapsed_seconds: f64, // wall-clock time of the loading
+}
+
+impl ProcessingStats {
+    // adds the counts and volumes of a shard, the locked accounts and time are the caller's
+    fn add(&mut self, other: ProcessingStats) {
+        for (transaction_type, count) in other.transactions {
+            *self.transactions.entry(transaction_type).or_default() += count;
+        }
+        self.invalid += other.invalid;
+        self.rejected += other.rejected;
+        self.deposits += other.deposits;
+        self.withdrawals += other.withdrawals;
+    }
+}
+
+/// Record of a client merge, kept in the audit trail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientMerge {
+    pub from: ClientId,
+    pub into: ClientId,
+    pub available: Decimal, // funds moved from `from` to `into`
+    pub held: Decimal,
+    pub disputes: usize, // open disputes reassigned to `into`
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    #[error("client {0} cannot be merged into itself")]
+    SameClient(ClientId),
+    #[error("client {0} has no account")]
+    UnknownClient(ClientId),
+    #[error("client {0} was erased")]
+    Erased(ClientId),
+    #[error("client {0} is locked")]
+    Locked(ClientId),
+    #[error("the balances of client {0} and client {1} overflow once summed")]
+    Overflow(ClientId, ClientId),
+}
+
+/// Tx id of the synthetic entries of opening balances (journal, statements, ledger), their
+/// `opening` type tells them apart from a transaction with the same id
+pub const OPENING_TX: TransactionId = TransactionId(0);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum OpeningError {
+    #[error("client {0} already has an account")]
+    Existing(ClientId),
+    #[error("client {0} was erased")]
+    Erased(ClientId),
+}
+
 // Output all accounts or skip the locked ones
 pub enum OutputMode {
     SkipLocked,
     All,
 }
 
+/// Hasher of the account and dispute maps: SipHash, which inputs crafted to collide cannot slow
+/// down, unless the `fast-hash` feature swaps in the much cheaper FxHash for trusted inputs
+#[cfg(not(feature = "fast-hash"))]
+pub type MapHasher = std::collections::hash_map::RandomState;
+#[cfg(feature = "fast-hash")]
+pub type MapHasher = rustc_hash::FxBuildHasher;
+
+/// Client accounts indexed by their ClientId, in a table of every id with the `dense-accounts` feature
+#[cfg(not(feature = "dense-accounts"))]
+pub type Accounts = HashMap<ClientId, Account, MapHasher>;
+#[cfg(feature = "dense-accounts")]
+pub type Accounts = crate::dense_accounts::DenseAccounts;
+
+/// Disputable transactions indexed by their (globally unique) TransactionId
+pub type DisputableTransactions = HashMap<TransactionId, DisputableTransaction, MapHasher>;
+
+/// A deposit that can still be disputed, together with the client that owns it
+#[derive(Debug, Clone)]
+pub struct DisputableTransaction {
+    pub client: ClientId,
+    pub status: DisputableTransactionStatus,
+}
+
 // Possible states of a disputable transaction (deposit)
 // Criterion shows that there is a performance gain (6%) in not having a ChargedBack variant and simply
 // removing transactions that were charged back
 // the Decimal is the ammount involved in the deposit
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DisputableTransactionStatus {
     NotDisputedAmount(Decimal),
     DisputedAmount(Decimal),
@@ -107,37 +977,59 @@ pub enum DisputableTransactionStatus {
 impl Account {
     fn apply_deposit(
         &mut self,
+        client: ClientId,
         tx: TransactionId,
         amount: Decimal,
-        disputable_transactions: &mut HashMap<TransactionId, DisputableTransactionStatus>,
+        disputable_transactions: &mut DisputableTransactions,
     ) {
-        self.available += amount;
-        disputable_transactions.insert(tx, DisputableTransactionStatus::NotDisputedAmount(amount));
-        trace!("Applied deposit");
+        let balances = Money::from_decimal(amount)
+            .and_then(|amount| Some((self.available.checked_add(amount)?, self.held)));
+        if self.set_balances(balances) {
+            disputable_transactions.insert(
+                tx,
+                DisputableTransaction {
+                    client,
+                    status: DisputableTransactionStatus::NotDisputedAmount(amount),
+                },
+            );
+            trace!("Applied deposit");
+        }
     }
 
     fn apply_whithdrawal(&mut self, amount: Decimal) {
-        if self.available >= amount {
-            self.available -= amount;
-            trace!(%amount, "Applied whitdrawal");
-        } else {
-            warn!(%amount, %self.available, "not enough funds available for whithdrawal")
+        match Money::from_decimal(amount) {
+            Some(money) if self.available < money => {
+                warn!(%amount, %self.available, "not enough funds available for whithdrawal")
+            }
+            money => {
+                let balances =
+                    money.and_then(|money| Some((self.available.checked_sub(money)?, self.held)));
+                if self.set_balances(balances) {
+                    trace!(%amount, "Applied whitdrawal");
+                }
+            }
         }
     }
     fn apply_dispute(
         &mut self,
         tx: &TransactionId,
-        disputable_transactions: &mut HashMap<TransactionId, DisputableTransactionStatus>,
+        disputable_transactions: &mut DisputableTransactions,
     ) {
         match disputable_transactions.get_mut(tx) {
             // Transaction exists
-            Some(status) => match status {
+            Some(DisputableTransaction { status, .. }) => match status {
                 // It's currently not disputed, so we can dispute it
                 DisputableTransactionStatus::NotDisputedAmount(amount) => {
-                    self.held += *amount;
-                    self.available -= *amount;
-                    *status = DisputableTransactionStatus::DisputedAmount(*amount);
-                    trace!(%tx, "Disputed transaction");
+                    let balances = Money::from_decimal(*amount).and_then(|amount| {
+                        Some((
+                            self.available.checked_sub(amount)?,
+                            self.held.checked_add(amount)?,
+                        ))
+                    });
+                    if self.set_balances(balances) {
+                        *status = DisputableTransactionStatus::DisputedAmount(*amount);
+                        trace!(%tx, "Disputed transaction");
+                    }
                 }
                 // It's already disputed or in another invalid state
                 DisputableTransactionStatus::DisputedAmount(_) => {
@@ -154,16 +1046,22 @@ impl Account {
     fn apply_resolve(
         &mut self,
         tx: &TransactionId,
-        disputable_transactions: &mut HashMap<TransactionId, DisputableTransactionStatus>,
+        disputable_transactions: &mut DisputableTransactions,
     ) {
         match disputable_transactions.get_mut(tx) {
             // Transaction exists
-            Some(status) => match status {
+            Some(DisputableTransaction { status, .. }) => match status {
                 DisputableTransactionStatus::DisputedAmount(amount) => {
-                    self.held -= *amount;
-                    self.available += *amount;
-                    *status = DisputableTransactionStatus::NotDisputedAmount(*amount);
-                    trace!(%tx, "Resolved transaction");
+                    let balances = Money::from_decimal(*amount).and_then(|amount| {
+                        Some((
+                            self.available.checked_add(amount)?,
+                            self.held.checked_sub(amount)?,
+                        ))
+                    });
+                    if self.set_balances(balances) {
+                        *status = DisputableTransactionStatus::NotDisputedAmount(*amount);
+                        trace!(%tx, "Resolved transaction");
+                    }
                 }
                 DisputableTransactionStatus::NotDisputedAmount(_) => {
                     warn!(%tx, ?status, "Transaction is not disputed: it cannot be resolved");
@@ -178,12 +1076,16 @@ impl Account {
     fn apply_chargeback(
         &mut self,
         tx: &TransactionId,
-        disputable_transactions: &mut HashMap<TransactionId, DisputableTransactionStatus>,
+        disputable_transactions: &mut DisputableTransactions,
     ) {
         match disputable_transactions.get_mut(tx) {
-            Some(status) => match status {
+            Some(DisputableTransaction { status, .. }) => match status {
                 DisputableTransactionStatus::DisputedAmount(amount) => {
-                    self.held -= *amount;
+                    let balances = Money::from_decimal(*amount)
+                        .and_then(|amount| Some((self.available, self.held.checked_sub(amount)?)));
+                    if self.set_balances(balances).not() {
+                        return;
+                    }
                     disputable_transactions.remove(tx); // if a transaction was charged back then it cannot be disputed again
                     trace!(%tx, "Transaction was chargedback");
 
@@ -200,22 +1102,34 @@ impl Account {
         }
     }
 
+    // sets the new (available, held) balances, None when computing them overflowed: the
+    // transaction is ignored
+    fn set_balances(&mut self, balances: Option<(Money, Money)>) -> bool {
+        match balances {
+            Some((available, held)) => {
+                self.available = available;
+                self.held = held;
+                true
+            }
+            None => {
+                warn!(%self.available, %self.held, "Amount overflows the balances, transaction ignored");
+                false
+            }
+        }
+    }
+
     #[instrument]
     /// Mutate this account with a transaction
     pub fn apply(
         &mut self,
         transaction: &Transaction,
-        disputable_transactions: &mut HashMap<TransactionId, DisputableTransactionStatus>, // map that keeps the transactions that are disputable or in dispute
+        disputable_transactions: &mut DisputableTransactions, // map that keeps the transactions that are disputable or in dispute
     ) {
         if self.locked.not() {
             // if account is not locked
             match transaction {
-                Transaction::Deposit {
-                    client: _,
-                    tx,
-                    amount,
-                } => {
-                    self.apply_deposit(*tx, *amount, disputable_transactions);
+                Transaction::Deposit { client, tx, amount } => {
+                    self.apply_deposit(*client, *tx, *amount, disputable_transactions);
                 }
                 Transaction::Withdrawal {
                     client: _,
@@ -233,6 +1147,13 @@ impl Account {
                 Transaction::Chargeback { client: _, tx } => {
                     self.apply_chargeback(tx, disputable_transactions);
                 }
+                Transaction::Custom(custom) => match custom_handler(custom.type_name) {
+                    Some((_, handler)) => handler.apply(custom, self, disputable_transactions),
+                    None => warn!(
+                        type_name = custom.type_name,
+                        "Unregistered transaction type"
+                    ),
+                },
             }
         }
     }
@@ -240,9 +1161,11 @@ impl Account {
 
 #[derive(Debug, Serialize, PartialEq, Eq, Clone)]
 pub struct Account {
-    available: Decimal, // The total funds that are available for trading, staking, withdrawal, etc. This should be equal to the total - held amount
-    held: Decimal, // The total funds that are held for dispute. This should be equal to total - available amounts
-    locked: bool,  // Whether the account is locked. An account is locked if a charge back occurs
+    available: Money, // The total funds that are available for trading, staking, withdrawal, etc. This should be equal to the total - held amount
+    held: Money, // The total funds that are held for dispute. This should be equal to total - available amounts
+    locked: bool, // Whether the account is locked. An account is locked if a charge back occurs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: BTreeMap<String, String>, // custom fields (e.g. a risk score) attached by observers
 }
 
 #[derive(Debug, Serialize, PartialEq, Eq, Clone)]
@@ -269,19 +1192,22 @@ impl From<(&ClientId, &Account)> for CsvOutputAccount {
 impl Default for Account {
     fn default() -> Self {
         Account {
-            held: dec!(0),
-            available: dec!(0),
+            held: Money::ZERO,
+            available: Money::ZERO,
             locked: false,
+            fields: BTreeMap::new(),
         }
     }
 }
 
 impl Account {
+    /// Panics when a balance does not fit in the `fast-money` representation (beyond ±922 trillion)
     pub fn new(available: Decimal, held: Decimal, locked: bool) -> Account {
         Account {
-            available,
-            held,
+            available: Money::from_decimal(available).expect("available funds overflow"),
+            held: Money::from_decimal(held).expect("held funds overflow"),
             locked,
+            fields: BTreeMap::new(),
         }
     }
 
@@ -289,23 +1215,52 @@ impl Account {
     /// from two integers are rounded to the nearest even integer.
     /// This method is particularly useful in financial and statistical calculations to minimize bias and cumulative errors
     pub fn available(&self) -> Decimal {
-        self.available.round_dp(4) // bankers rounding 0.00025 -> 0.0002  and 0.00015 -> 0.0002
+        self.available.to_decimal().round_dp(4) // bankers rounding 0.00025 -> 0.0002  and 0.00015 -> 0.0002
     }
 
     pub fn held(&self) -> Decimal {
-        self.held.round_dp(4) // bankers rounding 0.00025 -> 0.0002  and 0.00015 -> 0.0002
+        self.held.to_decimal().round_dp(4) // bankers rounding 0.00025 -> 0.0002  and 0.00015 -> 0.0002
     }
 
     pub fn total(&self) -> Decimal {
-        self.available.round_dp(4) + self.held.round_dp(4) // bankers rounding 0.00025 -> 0.0002  and 0.00015 -> 0.0002
+        self.available() + self.held() // bankers rounding 0.00025 -> 0.0002  and 0.00015 -> 0.0002
     }
 
     pub fn locked(&self) -> bool {
         self.locked
     }
+
+    /// Adds signed amounts to the available and held funds, for custom transaction types.
+    /// Returns false, leaving the account untouched, when a balance would overflow.
+    pub fn adjust(&mut self, available: Decimal, held: Decimal) -> bool {
+        let balances = Money::from_decimal(available)
+            .zip(Money::from_decimal(held))
+            .and_then(|(available, held)| {
+                Some((
+                    self.available.checked_add(available)?,
+                    self.held.checked_add(held)?,
+                ))
+            });
+        self.set_balances(balances)
+    }
+
+    /// Freezes the account, like a chargeback does
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    /// Attaches a custom field (e.g. a risk score or flag reasons), written to the output when its
+    /// name is one of the `OutputOptions::columns`
+    pub fn set_field(&mut self, name: &str, value: impl Into<String>) {
+        self.fields.insert(name.to_string(), value.into());
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Serialize, Copy)]
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Serialize, Copy)]
 pub struct ClientId(pub u16);
 
 impl Display for ClientId {
@@ -314,7 +1269,7 @@ impl Display for ClientId {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Serialize, Copy)]
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Serialize, Copy)]
 pub struct TransactionId(pub u32);
 
 impl Display for TransactionId {
@@ -323,7 +1278,7 @@ impl Display for TransactionId {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Transaction {
     /// A deposit is a credit to the client's asset account, meaning it should increase the available
     /// and total funds of the client account
@@ -366,9 +1321,44 @@ pub enum Transaction {
     /// specify an amount. Like a resolve, if the tx specified doesn't exist, or the tx isn't under
     /// dispute, you can ignore chargeback and assume this is an error on our partner's side.
     Chargeback { client: ClientId, tx: TransactionId },
+    /// A transaction of a type registered by the embedder, see `custom_types`.
+    /// Boxed so that the built-in transactions stay small.
+    Custom(Box<CustomTransaction>),
+}
+
+/// A transaction of a type registered with `custom_types::register_transaction_type`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomTransaction {
+    pub type_name: &'static str, // registered name, as in the input `type` column
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub amount: Option<Decimal>,
 }
 
 impl Transaction {
+    pub fn tx_id(&self) -> TransactionId {
+        match self {
+            Transaction::Deposit { tx, .. }
+            | Transaction::Withdrawal { tx, .. }
+            | Transaction::Dispute { tx, .. }
+            | Transaction::Resolve { tx, .. }
+            | Transaction::Chargeback { tx, .. } => *tx,
+            Transaction::Custom(custom) => custom.tx,
+        }
+    }
+
+    /// Name of the transaction type as written in the input csv
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Transaction::Deposit { .. } => "deposit",
+            Transaction::Withdrawal { .. } => "withdrawal",
+            Transaction::Dispute { .. } => "dispute",
+            Transaction::Resolve { .. } => "resolve",
+            Transaction::Chargeback { .. } => "chargeback",
+            Transaction::Custom(custom) => custom.type_name,
+        }
+    }
+
     pub fn client_id(&self) -> ClientId {
         match self {
             Transaction::Deposit {
@@ -384,21 +1374,126 @@ impl Transaction {
             Transaction::Dispute { client, tx: _ } => client,
             Transaction::Resolve { client, tx: _ } => client,
             Transaction::Chargeback { client, tx: _ } => client,
+            Transaction::Custom(custom) => &custom.client,
         }
         .to_owned()
     }
 }
 
+/// Type of a transaction, the `type` column of the input. Deserialized from the borrowed field, so
+/// reading a row does not allocate: registered custom types resolve to their registered name and
+/// only an unknown type, which fails the conversion, is copied for the error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionKind {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Custom(&'static str), // registered with `custom_types::register_transaction_type`
+    Unknown(String),
+}
+
+impl TransactionKind {
+    pub fn from_name(name: &str) -> TransactionKind {
+        match name {
+            "deposit" => TransactionKind::Deposit,
+            "withdrawal" => TransactionKind::Withdrawal,
+            "dispute" => TransactionKind::Dispute,
+            "resolve" => TransactionKind::Resolve,
+            "chargeback" => TransactionKind::Chargeback,
+            other => match custom_handler(other) {
+                Some((type_name, _)) => TransactionKind::Custom(type_name),
+                None => TransactionKind::Unknown(other.to_string()),
+            },
+        }
+    }
+
+    /// Name of the type as written in the input csv
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransactionKind::Deposit => "deposit",
+            TransactionKind::Withdrawal => "withdrawal",
+            TransactionKind::Dispute => "dispute",
+            TransactionKind::Resolve => "resolve",
+            TransactionKind::Chargeback => "chargeback",
+            TransactionKind::Custom(type_name) => type_name,
+            TransactionKind::Unknown(type_name) => type_name,
+        }
+    }
+}
+
+impl Display for TransactionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for TransactionKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KindVisitor;
+
+        impl serde::de::Visitor<'_> for KindVisitor {
+            type Value = TransactionKind;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a transaction type")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<TransactionKind, E> {
+                Ok(TransactionKind::from_name(name))
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, name: &[u8]) -> Result<TransactionKind, E> {
+                match std::str::from_utf8(name) {
+                    Ok(name) => Ok(TransactionKind::from_name(name)),
+                    Err(_) => Err(E::invalid_value(serde::de::Unexpected::Bytes(name), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(KindVisitor)
+    }
+}
+
 /// Type used to deserialize input csv lines
 #[derive(Debug, Deserialize, Serialize)]
 pub struct InputCsvRecord {
     #[serde(rename = "type")]
-    pub transaction_type: String,
+    pub transaction_type: TransactionKind,
     pub client: ClientId,
     pub tx: TransactionId,
     pub amount: Option<Decimal>,
 }
 
+/// Converts a Transaction back to its input csv line
+impl From<&Transaction> for InputCsvRecord {
+    fn from(transaction: &Transaction) -> Self {
+        let (transaction_type, amount) = match transaction {
+            Transaction::Deposit { amount, .. } => (TransactionKind::Deposit, Some(*amount)),
+            Transaction::Withdrawal { amount, .. } => (TransactionKind::Withdrawal, Some(*amount)),
+            Transaction::Dispute { .. } => (TransactionKind::Dispute, None),
+            Transaction::Resolve { .. } => (TransactionKind::Resolve, None),
+            Transaction::Chargeback { .. } => (TransactionKind::Chargeback, None),
+            Transaction::Custom(custom) => {
+                (TransactionKind::Custom(custom.type_name), custom.amount)
+            }
+        };
+        InputCsvRecord {
+            transaction_type,
+            client: transaction.client_id(),
+            tx: transaction.tx_id(),
+            amount,
+        }
+    }
+}
+
 /// Converts from an InputCsvRecord to a Transaction
 impl TryFrom<InputCsvRecord> for Transaction {
     fn try_from(csv_record: InputCsvRecord) -> Result<Self, ConversionError> {
@@ -408,8 +1503,8 @@ impl TryFrom<InputCsvRecord> for Transaction {
             tx,
             amount,
         } = csv_record;
-        Ok(match transaction_type.as_str() {
-            "deposit" => {
+        Ok(match transaction_type {
+            TransactionKind::Deposit => {
                 let amount =
                     amount.ok_or(ConversionError::MissingAmount(transaction_type.to_string()))?;
 
@@ -421,7 +1516,7 @@ impl TryFrom<InputCsvRecord> for Transaction {
                 }
                 Transaction::Deposit { client, tx, amount }
             }
-            "withdrawal" => {
+            TransactionKind::Withdrawal => {
                 let amount =
                     amount.ok_or(ConversionError::MissingAmount(transaction_type.to_string()))?;
 
@@ -433,12 +1528,27 @@ impl TryFrom<InputCsvRecord> for Transaction {
                 }
                 Transaction::Withdrawal { client, tx, amount }
             }
-            "dispute" => Transaction::Dispute { client, tx },
-            "resolve" => Transaction::Resolve { client, tx },
-            "chargeback" => Transaction::Chargeback { client, tx },
-            _ => Err(ConversionError::InvalidTransactionType(
-                transaction_type.to_string(),
-            ))?,
+            TransactionKind::Dispute => Transaction::Dispute { client, tx },
+            TransactionKind::Resolve => Transaction::Resolve { client, tx },
+            TransactionKind::Chargeback => Transaction::Chargeback { client, tx },
+            TransactionKind::Custom(type_name) => match custom_handler(type_name) {
+                Some((type_name, handler)) => {
+                    let custom = CustomTransaction {
+                        type_name,
+                        client,
+                        tx,
+                        amount,
+                    };
+                    handler.validate(&custom)?;
+                    Transaction::Custom(Box::new(custom))
+                }
+                None => Err(ConversionError::InvalidTransactionType(
+                    type_name.to_string(),
+                ))?,
+            },
+            TransactionKind::Unknown(type_name) => {
+                Err(ConversionError::InvalidTransactionType(type_name))?
+            }
         })
     }
 
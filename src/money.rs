@@ -0,0 +1,126 @@
+use std::fmt::{self, Display};
+
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer};
+
+/// Balance of an account, a `Decimal` unless the `fast-money` feature is enabled
+#[cfg(not(feature = "fast-money"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Money(Decimal);
+
+#[cfg(not(feature = "fast-money"))]
+impl Money {
+    pub(crate) const ZERO: Money = Money(Decimal::ZERO);
+
+    pub(crate) fn from_decimal(amount: Decimal) -> Option<Money> {
+        Some(Money(amount))
+    }
+
+    pub(crate) fn to_decimal(self) -> Decimal {
+        self.0
+    }
+
+    pub(crate) fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub(crate) fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+}
+
+/// Decimal places the engine keeps
+#[cfg(feature = "fast-money")]
+const SCALE: u32 = 4;
+/// Ten-thousandths in one unit of each scale
+#[cfg(feature = "fast-money")]
+const SCALE_FACTORS: [i64; SCALE as usize + 1] = [10_000, 1_000, 100, 10, 1];
+
+/// Balance of an account as an `i64` of ten-thousandths (4 implied decimal places), which is much
+/// cheaper to add and compare than a `Decimal`. Amounts with more decimal places are rounded
+/// (banker's rounding) when applied, and an amount or balance beyond ±922 trillion is an overflow.
+/// The decimal places of the amounts are kept so that the output is written like a `Decimal`
+/// would be (`1.5` stays `1.5`, `2.00` stays `2.00`).
+#[cfg(feature = "fast-money")]
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Money {
+    units: i64,
+    scale: u32, // decimal places written to the output, at most `SCALE`
+}
+
+#[cfg(feature = "fast-money")]
+impl Money {
+    pub(crate) const ZERO: Money = Money { units: 0, scale: 0 };
+
+    /// None when the amount does not fit in an `i64` of ten-thousandths
+    pub(crate) fn from_decimal(amount: Decimal) -> Option<Money> {
+        let amount = match amount.scale() > SCALE {
+            true => amount.round_dp(SCALE),
+            false => amount, // the usual case, no rounding to pay for
+        };
+        let units = i64::try_from(amount.mantissa())
+            .ok()?
+            .checked_mul(SCALE_FACTORS[amount.scale() as usize])?;
+        Some(Money {
+            units,
+            scale: amount.scale(),
+        })
+    }
+
+    pub(crate) fn to_decimal(self) -> Decimal {
+        let mut amount = Decimal::new(self.units, SCALE);
+        amount.rescale(self.scale); // exact, the amounts never had more decimal places
+        amount
+    }
+
+    pub(crate) fn checked_add(self, other: Money) -> Option<Money> {
+        Some(Money {
+            units: self.units.checked_add(other.units)?,
+            scale: self.scale.max(other.scale),
+        })
+    }
+
+    pub(crate) fn checked_sub(self, other: Money) -> Option<Money> {
+        Some(Money {
+            units: self.units.checked_sub(other.units)?,
+            scale: self.scale.max(other.scale),
+        })
+    }
+}
+
+// the decimal places only matter for the output, like for a `Decimal`
+#[cfg(feature = "fast-money")]
+impl PartialEq for Money {
+    fn eq(&self, other: &Money) -> bool {
+        self.units == other.units
+    }
+}
+
+#[cfg(feature = "fast-money")]
+impl Eq for Money {}
+
+#[cfg(feature = "fast-money")]
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Money) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "fast-money")]
+impl Ord for Money {
+    fn cmp(&self, other: &Money) -> std::cmp::Ordering {
+        self.units.cmp(&other.units)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_decimal().fmt(f)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Serialize::serialize(&self.to_decimal(), serializer)
+    }
+}
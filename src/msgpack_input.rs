@@ -0,0 +1,73 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use rmp_serde::decode::{Deserializer, ReadReader};
+use serde::Deserialize;
+use tracing::{error, instrument};
+
+use crate::{
+    csv_input::{ConversionError, open_input},
+    json_input::JsonRecord,
+    model::{InputCsvRecord, Transaction},
+};
+
+/// Transactions of a stream of MessagePack records, one after the other without framing.
+/// A record is a map `{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}` or an array
+/// `["deposit", 1, 1, "1.5"]` (nil amount for disputes, resolves and chargebacks); the amount is a
+/// string (exact), an integer or a float.
+pub struct MsgpackTransactions<R: BufRead> {
+    deserializer: Deserializer<ReadReader<R>>,
+    stopped: bool, // a corrupt stream cannot be resynchronized
+}
+
+// Loads the MessagePack file in path as a Iterator over transactions
+#[instrument]
+pub fn read_transactions_from_msgpack(
+    msgpack_path: &Path,
+) -> Result<MsgpackTransactions<BufReader<Box<dyn Read + Send>>>, ConversionError> {
+    let file = open_input(msgpack_path).map_err(|err| ConversionError::Msgpack(err.to_string()))?;
+    Ok(transactions_from_msgpack_reader(BufReader::new(file)))
+}
+
+// Transforms a reader over MessagePack records into a iterator over transactions
+pub fn transactions_from_msgpack_reader<R: BufRead>(reader: R) -> MsgpackTransactions<R> {
+    MsgpackTransactions {
+        deserializer: Deserializer::new(reader),
+        stopped: false,
+    }
+}
+
+impl<R: BufRead> Iterator for MsgpackTransactions<R> {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
+        match self.deserializer.get_mut().fill_buf() {
+            Ok([]) => return None, // end of the stream, between two records
+            Ok(_) => {}
+            Err(err) => {
+                self.stopped = true;
+                return Some(Err(ConversionError::Msgpack(err.to_string())));
+            }
+        }
+        // the whole record is decoded first so that a record of the wrong shape is skipped cleanly
+        match serde_json::Value::deserialize(&mut self.deserializer) {
+            Ok(value) => Some(transaction_from_value(value)),
+            Err(err) => {
+                error!(%err, "Corrupt MessagePack stream, stopping");
+                self.stopped = true;
+                Some(Err(ConversionError::Msgpack(err.to_string())))
+            }
+        }
+    }
+}
+
+fn transaction_from_value(value: serde_json::Value) -> Result<Transaction, ConversionError> {
+    let record: JsonRecord =
+        serde_json::from_value(value).map_err(|err| ConversionError::Msgpack(err.to_string()))?;
+    Transaction::try_from(InputCsvRecord::try_from(record)?)
+}
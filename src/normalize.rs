@@ -0,0 +1,122 @@
+use std::{io, str::FromStr};
+
+use thiserror::Error;
+use tracing::{instrument, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{InputCsvRecord, Transaction},
+};
+
+/// Format written by `normalize`, both are read back by the engine
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CanonicalFormat {
+    #[default]
+    Csv, // type,client,tx,amount with a header
+    #[cfg(feature = "msgpack")]
+    Msgpack, // stream of MessagePack maps with the csv columns, the amount as a string
+}
+
+impl FromStr for CanonicalFormat {
+    type Err = String;
+
+    /// `csv` or `msgpack` (`msgpack` feature)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(CanonicalFormat::Csv),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(CanonicalFormat::Msgpack),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NormalizeError {
+    #[error("failed to write the csv: {0}")]
+    Csv(#[from] csv::Error),
+    #[cfg(feature = "msgpack")]
+    #[error("failed to write the msgpack: {0}")]
+    Msgpack(#[from] rmp_serde::encode::Error),
+    #[error("failed to write: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Number of transactions read and written by `normalize`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeSummary {
+    pub read: u64,
+    pub written: u64,
+    pub invalid: u64, // rows that failed parsing or validation, logged and skipped
+}
+
+// destination of the canonical records
+enum CanonicalWriter<W: io::Write> {
+    Csv(Box<csv::Writer<W>>),
+    #[cfg(feature = "msgpack")]
+    Msgpack(io::BufWriter<W>),
+}
+
+impl<W: io::Write> CanonicalWriter<W> {
+    fn new(wtr: W, format: CanonicalFormat) -> Result<CanonicalWriter<W>, NormalizeError> {
+        Ok(match format {
+            CanonicalFormat::Csv => {
+                let mut csv_writer = csv::WriterBuilder::new()
+                    .has_headers(false) // header is written explicitly so that an empty output still has it
+                    .from_writer(wtr);
+                csv_writer.write_record(["type", "client", "tx", "amount"])?;
+                CanonicalWriter::Csv(Box::new(csv_writer))
+            }
+            #[cfg(feature = "msgpack")]
+            CanonicalFormat::Msgpack => CanonicalWriter::Msgpack(io::BufWriter::new(wtr)),
+        })
+    }
+
+    fn write(&mut self, record: &InputCsvRecord) -> Result<(), NormalizeError> {
+        match self {
+            CanonicalWriter::Csv(csv_writer) => csv_writer.serialize(record)?,
+            #[cfg(feature = "msgpack")]
+            CanonicalWriter::Msgpack(wtr) => rmp_serde::encode::write_named(wtr, record)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<W, NormalizeError> {
+        let mut wtr = match self {
+            CanonicalWriter::Csv(csv_writer) => {
+                csv_writer.into_inner().map_err(|err| err.into_error())?
+            }
+            #[cfg(feature = "msgpack")]
+            CanonicalWriter::Msgpack(wtr) => wtr.into_inner().map_err(|err| err.into_error())?,
+        };
+        wtr.flush()?;
+        Ok(wtr)
+    }
+}
+
+/// Writes the valid transactions of any input in the canonical format, without applying them.
+/// Every check of the parsers applies (types, missing or negative amounts, registered custom
+/// types...), so the output is exactly what the engine would process.
+#[instrument(skip(transactions, wtr))]
+pub fn normalize<I: Iterator<Item = Result<Transaction, ConversionError>>, W: io::Write>(
+    transactions: I,
+    format: CanonicalFormat,
+    wtr: W,
+) -> Result<(W, NormalizeSummary), NormalizeError> {
+    let mut summary = NormalizeSummary::default();
+    let mut writer = CanonicalWriter::new(wtr, format)?;
+    for transaction in transactions {
+        summary.read += 1;
+        match transaction {
+            Ok(transaction) => {
+                writer.write(&InputCsvRecord::from(&transaction))?;
+                summary.written += 1;
+            }
+            Err(err) => {
+                warn!(%err, "Skipping invalid row");
+                summary.invalid += 1;
+            }
+        }
+    }
+    Ok((writer.finish()?, summary))
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{error, info, instrument, warn};
+
+/// What a notification sink does when its consumer is too slow and the buffer is full
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    DropOldest, // make room by discarding the oldest pending notification
+    #[default]
+    Block, // wait for the consumer, every notification is delivered but processing may stall
+    Spill(PathBuf), // append the overflow to a file, delivered in order once the consumer catches up
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    /// `drop-oldest`, `block` or `spill:<file>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop-oldest" => Ok(OverflowPolicy::DropOldest),
+            "block" => Ok(OverflowPolicy::Block),
+            _ => match s.strip_prefix("spill:") {
+                Some(path) if !path.is_empty() => Ok(OverflowPolicy::Spill(path.into())),
+                _ => Err(format!("unknown overflow policy: {s}")),
+            },
+        }
+    }
+}
+
+/// Pending notifications kept in memory, and what happens beyond
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        BufferConfig {
+            capacity: 1024,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// Counters of a notification sink, once it is closed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationSummary {
+    pub delivered: u64,
+    pub dropped: u64, // discarded by drop-oldest
+    pub spilled: u64, // went through the spill file
+}
+
+impl Display for NotificationSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} delivered, {} dropped, {} spilled",
+            self.delivered, self.dropped, self.spilled
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Spill {
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    pending: u64, // written but not read back yet
+}
+
+#[derive(Debug)]
+struct State<T> {
+    queue: VecDeque<T>,
+    spill: Option<Spill>,
+    closed: bool,
+    summary: NotificationSummary,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    changed: Condvar, // signaled on every push, pop and close
+}
+
+/// Producer side of a buffered notification sink. Dropping it closes the sink, the pending
+/// notifications are still delivered before the consumer thread ends.
+#[derive(Debug)]
+pub struct NotificationSender<T> {
+    shared: Arc<Shared<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl<T: Serialize> NotificationSender<T> {
+    /// Queues a notification, only ever waits for the consumer with the block policy
+    #[instrument(level = "trace", skip_all)]
+    pub fn send(&self, notification: T) {
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .expect("notification mutex poisoned");
+        let spilling = state.spill.as_ref().is_some_and(|spill| spill.pending > 0);
+        if state.queue.len() >= self.capacity || spilling {
+            match &self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.summary.dropped += 1;
+                    warn!(
+                        capacity = self.capacity,
+                        "Notification buffer full, dropped the oldest"
+                    );
+                }
+                OverflowPolicy::Block => {
+                    state = self
+                        .shared
+                        .changed
+                        .wait_while(state, |state| state.queue.len() >= self.capacity)
+                        .expect("notification mutex poisoned");
+                }
+                OverflowPolicy::Spill(_) => {
+                    // once spilling, everything goes through the file until it is read back, to keep the order
+                    if let Some(spill) = &mut state.spill {
+                        let written = serde_json::to_writer(&mut spill.writer, &notification)
+                            .map_err(io::Error::from)
+                            .and_then(|()| spill.writer.write_all(b"\n"))
+                            .and_then(|()| spill.writer.flush());
+                        match written {
+                            Ok(()) => {
+                                spill.pending += 1;
+                                state.summary.spilled += 1;
+                                self.shared.changed.notify_all();
+                                return;
+                            }
+                            Err(err) => error!(%err, "failed to spill notification, buffering it"),
+                        }
+                    }
+                }
+            }
+        }
+        state.queue.push_back(notification);
+        self.shared.changed.notify_all();
+    }
+}
+
+impl<T> Drop for NotificationSender<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.closed = true;
+        }
+        self.shared.changed.notify_all();
+    }
+}
+
+// Next notification in order: the in memory queue first, then the spill file
+fn next_notification<T: DeserializeOwned>(state: &mut State<T>) -> Option<T> {
+    if let Some(notification) = state.queue.pop_front() {
+        return Some(notification);
+    }
+    let spill = state.spill.as_mut().filter(|spill| spill.pending > 0)?;
+    spill.pending -= 1;
+    let mut line = String::new();
+    match spill.reader.read_line(&mut line) {
+        Ok(_) => serde_json::from_str(&line)
+            .inspect_err(|err| error!(%err, "failed to read back spilled notification"))
+            .ok(),
+        Err(err) => {
+            error!(%err, "failed to read back spilled notification");
+            None
+        }
+    }
+}
+
+/// Delivers notifications to `sink` on a dedicated thread, so that a slow external system
+/// can't stall transaction processing beyond what the overflow policy allows.
+#[instrument(skip(sink))]
+pub fn spawn_notification_sink<T, F>(
+    config: BufferConfig,
+    mut sink: F,
+) -> io::Result<(NotificationSender<T>, JoinHandle<NotificationSummary>)>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+    F: FnMut(T) + Send + 'static,
+{
+    let BufferConfig { capacity, overflow } = config;
+    let capacity = capacity.max(1);
+    let spill = match &overflow {
+        OverflowPolicy::Spill(path) => Some(Spill {
+            writer: BufWriter::new(File::create(path)?),
+            reader: BufReader::new(File::open(path)?),
+            pending: 0,
+        }),
+        _ => None,
+    };
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            spill,
+            closed: false,
+            summary: NotificationSummary::default(),
+        }),
+        changed: Condvar::new(),
+    });
+    let consumer = shared.clone();
+    let thread_id = thread::spawn(move || {
+        loop {
+            let mut state = consumer
+                .changed
+                .wait_while(
+                    consumer.state.lock().expect("notification mutex poisoned"),
+                    |state| {
+                        !state.closed
+                            && state.queue.is_empty()
+                            && state.spill.as_ref().is_none_or(|spill| spill.pending == 0)
+                    },
+                )
+                .expect("notification mutex poisoned");
+            let Some(notification) = next_notification(&mut state) else {
+                if state.closed
+                    && state.queue.is_empty()
+                    && state.spill.as_ref().is_none_or(|spill| spill.pending == 0)
+                {
+                    let summary = state.summary;
+                    info!(%summary, "Notification sink closed");
+                    return summary;
+                }
+                continue; // unreadable spilled notification
+            };
+            state.summary.delivered += 1;
+            consumer.changed.notify_all();
+            drop(state); // the sink runs without holding the lock
+            sink(notification);
+        }
+    });
+    let sender = NotificationSender {
+        shared,
+        capacity,
+        policy: overflow,
+    };
+    Ok((sender, thread_id))
+}
@@ -0,0 +1,116 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use crate::model::{Account, ClientId, Transaction};
+
+/// Hook called by `Clients` for every valid transaction, with the account state before and after it.
+/// A transaction that was ignored (not enough funds, unknown tx, locked account...) leaves `before == after`.
+pub trait TransactionObserver: Send {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    );
+
+    /// Called when a client is erased, observers must drop whatever they retained about it
+    fn on_erasure(&mut self, _client: ClientId) {}
+
+    /// Called when the account of `from` was merged into `into`, with the merged account
+    fn on_merge(&mut self, _from: ClientId, _into: ClientId, _account: &Account) {}
+
+    /// Called when a client's account was seeded with an opening balance, before its transactions
+    fn on_opening(&mut self, _client: ClientId, _account: &Account) {}
+
+    /// Called after every observer saw the transaction, to attach custom fields to the account
+    /// (`Account::set_field`) that the output writes as extra columns
+    fn annotate(&mut self, _client: ClientId, _account: &mut Account) {}
+}
+
+impl Debug for dyn TransactionObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TransactionObserver")
+    }
+}
+
+/// Shared observers let the caller keep a handle to read the observer state after processing
+impl<O: TransactionObserver> TransactionObserver for Arc<Mutex<O>> {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        self.lock()
+            .expect("observer mutex poisoned")
+            .on_transaction(client, transaction, before, after);
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.lock()
+            .expect("observer mutex poisoned")
+            .on_erasure(client);
+    }
+
+    fn on_merge(&mut self, from: ClientId, into: ClientId, account: &Account) {
+        self.lock()
+            .expect("observer mutex poisoned")
+            .on_merge(from, into, account);
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        self.lock()
+            .expect("observer mutex poisoned")
+            .on_opening(client, account);
+    }
+
+    fn annotate(&mut self, client: ClientId, account: &mut Account) {
+        self.lock()
+            .expect("observer mutex poisoned")
+            .annotate(client, account);
+    }
+}
+
+/// A list of observers is notified in order, e.g. to share all the observers of `Clients` with
+/// the worker threads of `Clients::load_transactions_parallel`
+impl TransactionObserver for Vec<Box<dyn TransactionObserver>> {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        for observer in self.iter_mut() {
+            observer.on_transaction(client, transaction, before, after);
+        }
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        for observer in self.iter_mut() {
+            observer.on_erasure(client);
+        }
+    }
+
+    fn on_merge(&mut self, from: ClientId, into: ClientId, account: &Account) {
+        for observer in self.iter_mut() {
+            observer.on_merge(from, into, account);
+        }
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        for observer in self.iter_mut() {
+            observer.on_opening(client, account);
+        }
+    }
+
+    fn annotate(&mut self, client: ClientId, account: &mut Account) {
+        for observer in self.iter_mut() {
+            observer.annotate(client, account);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::{collections::HashSet, io};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{info, instrument};
+
+use crate::{
+    model::{Account, ClientId, Clients, OpeningError},
+    money::Money,
+};
+
+/// Decimal places of the amounts the engine keeps
+const AMOUNT_DECIMALS: u32 = 4;
+
+/// One row of an opening balances file: the state of a client's account migrated from another
+/// ledger, seeded before the transactions are processed
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct OpeningBalance {
+    pub client: ClientId,
+    pub available: Decimal, // can be negative, e.g. a dispute of funds already withdrawn
+    pub held: Decimal,
+    pub locked: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum OpeningBalanceError {
+    #[error("failed to read the opening balances: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("invalid opening balance on line {line}: {reason}")]
+    Invalid { line: u64, reason: String },
+    #[error("failed to open an account: {0}")]
+    Opening(#[from] OpeningError),
+}
+
+/// Reads and validates a whole opening balances file (client, available, held, locked), nothing
+/// is returned unless every row is valid: held funds cannot be negative, amounts have at most 4
+/// decimal places (and fit in an `i64` of them with `fast-money`) and a client appears once
+#[instrument(skip(csv_reader))]
+pub fn read_opening_balances<R: io::Read>(
+    mut csv_reader: csv::Reader<R>,
+) -> Result<Vec<OpeningBalance>, OpeningBalanceError> {
+    let headers = csv_reader.headers()?.clone();
+    let mut balances = Vec::new();
+    let mut clients = HashSet::new();
+    for record in csv_reader.into_records() {
+        let record = record?;
+        let line = record.position().map_or(0, |position| position.line());
+        let invalid = |reason: String| OpeningBalanceError::Invalid { line, reason };
+        let balance: OpeningBalance = record.deserialize(Some(&headers))?;
+        if balance.held < Decimal::ZERO {
+            return Err(invalid(format!("negative held funds {}", balance.held)));
+        }
+        for amount in [balance.available, balance.held] {
+            if amount.normalize().scale() > AMOUNT_DECIMALS {
+                return Err(invalid(format!(
+                    "amount {amount} has more than {AMOUNT_DECIMALS} decimal places"
+                )));
+            }
+            if Money::from_decimal(amount).is_none() {
+                return Err(invalid(format!("amount {amount} is out of range")));
+            }
+        }
+        if !clients.insert(balance.client) {
+            return Err(invalid(format!("client {} appears twice", balance.client)));
+        }
+        balances.push(balance);
+    }
+    Ok(balances)
+}
+
+/// Seeds the accounts with the opening balances, before any transaction is loaded
+#[instrument(skip_all)]
+pub fn open_accounts(
+    clients: &mut Clients,
+    balances: &[OpeningBalance],
+) -> Result<(), OpeningBalanceError> {
+    for balance in balances {
+        clients.open_account(
+            balance.client,
+            Account::new(balance.available, balance.held, balance.locked),
+        )?;
+    }
+    info!(accounts = balances.len(), "Opened accounts");
+    Ok(())
+}
@@ -0,0 +1,588 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    mem,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+use serde::{Serialize, Serializer, ser::SerializeMap};
+use thiserror::Error;
+use tracing::{error, warn};
+
+use rust_decimal::Decimal;
+
+use crate::model::{Account, ClientId, CsvOutputAccount};
+
+const HEADER: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+/// Row written in place of an account that failed to serialize: the client id with empty balances
+#[derive(Debug, Serialize)]
+struct PlaceholderAccount {
+    client: ClientId,
+    available: Option<Decimal>,
+    held: Option<Decimal>,
+    total: Option<Decimal>,
+    locked: Option<bool>,
+}
+
+/// What the writer does when an account cannot be serialized
+// json object of a record followed by the custom columns, in the order of the options
+#[derive(Serialize)]
+struct WithFields<'a, S> {
+    #[serde(flatten)]
+    record: S,
+    #[serde(flatten)]
+    fields: Fields<'a>,
+}
+
+struct Fields<'a>(Vec<(&'a String, &'a Option<&'a str>)>);
+
+impl Serialize for Fields<'_> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (column, field) in &self.0 {
+            map.serialize_entry(column, field)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SerializationFailurePolicy {
+    #[default]
+    Skip, // log it, the account is missing from the output
+    Retry(u32),  // try again up to n times, then skip
+    Placeholder, // write a row with the client id and empty balances so the gap is visible downstream
+    Abort,       // stop writing, the run fails once the output is joined
+}
+
+impl FromStr for SerializationFailurePolicy {
+    type Err = String;
+
+    /// `skip`, `retry` (3 attempts), `retry:<n>`, `placeholder` or `abort`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("retry", retries)) => retries
+                .parse()
+                .map(SerializationFailurePolicy::Retry)
+                .map_err(|_| s.to_string()),
+            None => match s {
+                "skip" => Ok(SerializationFailurePolicy::Skip),
+                "retry" => Ok(SerializationFailurePolicy::Retry(3)),
+                "placeholder" => Ok(SerializationFailurePolicy::Placeholder),
+                "abort" => Ok(SerializationFailurePolicy::Abort),
+                _ => Err(s.to_string()),
+            },
+            Some(_) => Err(s.to_string()),
+        }
+    }
+}
+
+/// When fields of the output are quoted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Quoting {
+    #[default]
+    Necessary, // only fields containing the delimiter, the quote or a line break
+    Always,
+    NonNumeric,
+    Never, // the consumer must not expect delimiters inside fields
+}
+
+impl FromStr for Quoting {
+    type Err = String;
+
+    /// `necessary`, `always`, `non-numeric` or `never`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "necessary" => Ok(Quoting::Necessary),
+            "always" => Ok(Quoting::Always),
+            "non-numeric" => Ok(Quoting::NonNumeric),
+            "never" => Ok(Quoting::Never),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// Record terminator of the output
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,       // e.g. for legacy Windows consumers
+    Custom(u8), // any single byte
+}
+
+/// Csv dialect of the account output, the defaults match the documented output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvWriterOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub quoting: Quoting,
+    pub line_ending: LineEnding,
+}
+
+impl Default for CsvWriterOptions {
+    fn default() -> Self {
+        CsvWriterOptions {
+            delimiter: b',',
+            quote: b'"',
+            quoting: Quoting::default(),
+            line_ending: LineEnding::default(),
+        }
+    }
+}
+
+impl CsvWriterOptions {
+    /// Builder of the writers of the output rows
+    pub fn writer_builder(&self) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quote_style(match self.quoting {
+                Quoting::Necessary => csv::QuoteStyle::Necessary,
+                Quoting::Always => csv::QuoteStyle::Always,
+                Quoting::NonNumeric => csv::QuoteStyle::NonNumeric,
+                Quoting::Never => csv::QuoteStyle::Never,
+            })
+            .terminator(match self.line_ending {
+                LineEnding::Lf => csv::Terminator::Any(b'\n'),
+                LineEnding::Crlf => csv::Terminator::CRLF,
+                LineEnding::Custom(byte) => csv::Terminator::Any(byte),
+            });
+        builder
+    }
+}
+
+/// Encoding of the account output
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Json,   // a single array of account objects
+    NdJson, // one account object per line
+    Table,  // aligned columns for humans, see `TableAccountWriter`
+    #[cfg(feature = "parquet")]
+    Parquet, // columnar file for the analytics warehouse, see `OutputWriter`
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    /// `csv`, `json`, `ndjson`, `table` or `parquet` (`parquet` feature)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::NdJson),
+            "table" => Ok(OutputFormat::Table),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// The name parsed by `FromStr`
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::NdJson => "ndjson",
+            OutputFormat::Table => "table",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => "parquet",
+        })
+    }
+}
+
+impl OutputFormat {
+    /// Extension of the files written in this format, e.g. the partitions
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::NdJson => "ndjson",
+            OutputFormat::Table => "txt",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// How the accounts are written: the format, the dialect when it is csv, and the custom fields
+/// (`Account::set_field`) written as extra columns after the standard ones, empty when unset
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub dialect: CsvWriterOptions,
+    pub columns: Vec<String>,
+}
+
+/// Counts of what the writer did, logged at the end of the run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriterSummary {
+    pub written: u64,
+    pub failed: u64, // accounts that could not be serialized, including the placeholders
+}
+
+impl Display for WriterSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "written={} failed={}", self.written, self.failed)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum WriterError {
+    #[error("aborted after failing to serialize the account of client {client}: {source}")]
+    Aborted {
+        client: ClientId,
+        source: Box<WriterError>,
+    },
+    #[error("failed to serialize: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("failed to serialize: {0}")]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "parquet")]
+    #[error("failed to write the parquet output: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("the {0:?} output is not written row by row")]
+    Unsupported(OutputFormat),
+    #[error("failed to write the output: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Csv (or json) writer of the output accounts applying a `SerializationFailurePolicy`.
+/// Every row is serialized on its own before being written, so a failed row never leaves a partial
+/// record in the output and can be retried as a whole.
+#[derive(Debug)]
+pub struct AccountWriter<W: io::Write> {
+    wtr: BufWriter<W>,
+    row: Vec<u8>, // serialized row not yet written to the output
+    policy: SerializationFailurePolicy,
+    options: OutputOptions,
+    wrote_header: bool, // the csv header or the opening of the json array
+    wrote_record: bool, // json array elements are separated by commas
+    aborted: Option<(ClientId, WriterError)>,
+    summary: WriterSummary,
+}
+
+// serializes a single csv row into `row`
+fn serialize_row<S: Serialize>(
+    row: &mut Vec<u8>,
+    dialect: &CsvWriterOptions,
+    record: S,
+) -> Result<(), WriterError> {
+    let mut csv_writer = dialect
+        .writer_builder()
+        .has_headers(false) // header is handled by the AccountWriter, it must precede placeholders
+        .buffer_capacity(128) // one row at a time
+        .from_writer(mem::take(row));
+    csv_writer.serialize(record)?;
+    *row = csv_writer
+        .into_inner()
+        .map_err(|err| WriterError::Io(err.into_error()))?;
+    Ok(())
+}
+
+impl<W: io::Write> AccountWriter<W> {
+    pub fn new(wtr: W, policy: SerializationFailurePolicy) -> AccountWriter<W> {
+        AccountWriter {
+            wtr: BufWriter::new(wtr),
+            row: Vec::new(),
+            policy,
+            options: OutputOptions::default(),
+            wrote_header: false,
+            wrote_record: false,
+            aborted: None,
+            summary: WriterSummary::default(),
+        }
+    }
+
+    /// Writes the accounts in this format and csv dialect instead of the default csv
+    pub fn with_options(mut self, options: OutputOptions) -> AccountWriter<W> {
+        self.options = options;
+        self
+    }
+
+    /// Writes the header now, by default it is written with the first account
+    pub fn write_header(&mut self) -> Result<(), WriterError> {
+        if !self.wrote_header {
+            if self.row.is_empty() {
+                match self.options.format {
+                    OutputFormat::Csv => serialize_row(
+                        &mut self.row,
+                        &self.options.dialect,
+                        (HEADER, &self.options.columns),
+                    )?,
+                    OutputFormat::Json => self.row.push(b'['),
+                    OutputFormat::NdJson => {}
+                    OutputFormat::Table => {
+                        return Err(WriterError::Unsupported(self.options.format));
+                    }
+                    #[cfg(feature = "parquet")]
+                    OutputFormat::Parquet => {
+                        return Err(WriterError::Unsupported(self.options.format));
+                    }
+                }
+            }
+            self.write_row()?;
+            self.wrote_header = true;
+        }
+        Ok(())
+    }
+
+    pub fn summary(&self) -> WriterSummary {
+        self.summary
+    }
+
+    // moves the serialized row to the output, the row is kept if that fails
+    fn write_row(&mut self) -> Result<(), WriterError> {
+        self.wtr.write_all(&self.row)?;
+        self.row.clear();
+        Ok(())
+    }
+
+    // serializes a single record into `row` in the output format, `row` stays empty if that fails
+    fn serialize_record<S: Serialize>(&mut self, record: S) -> Result<(), WriterError> {
+        match self.options.format {
+            OutputFormat::Csv => serialize_row(&mut self.row, &self.options.dialect, record),
+            OutputFormat::Json | OutputFormat::NdJson => {
+                let json = serde_json::to_vec(&record)?;
+                if self.options.format == OutputFormat::Json {
+                    self.row
+                        .extend_from_slice(if self.wrote_record { b",\n" } else { b"\n" });
+                }
+                self.row.extend_from_slice(&json);
+                if self.options.format == OutputFormat::NdJson {
+                    self.row.push(b'\n');
+                }
+                Ok(())
+            }
+            OutputFormat::Table => Err(WriterError::Unsupported(self.options.format)),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => Err(WriterError::Unsupported(self.options.format)),
+        }
+    }
+
+    // serializes a record followed by the custom fields of the extra columns
+    fn serialize_with_fields<S: Serialize>(
+        &mut self,
+        record: S,
+        fields: &[Option<&str>],
+    ) -> Result<(), WriterError> {
+        if self.options.columns.is_empty() {
+            return self.serialize_record(record);
+        }
+        if self.options.format == OutputFormat::Csv {
+            return self.serialize_record((record, fields));
+        }
+        // the columns are moved out while the record borrows them
+        let columns = mem::take(&mut self.options.columns);
+        let result = self.serialize_record(WithFields {
+            record,
+            fields: Fields(columns.iter().zip(fields).collect()),
+        });
+        self.options.columns = columns;
+        result
+    }
+
+    // writes the serialized record, after the header
+    fn write_record(&mut self) -> Result<(), WriterError> {
+        self.write_row()?;
+        self.wrote_record = true;
+        Ok(())
+    }
+
+    fn write_account(&mut self, client: ClientId, account: &Account) -> Result<(), WriterError> {
+        self.write_header()?;
+        if self.row.is_empty() {
+            let fields: Vec<Option<&str>> = (self.options.columns.iter())
+                .map(|column| account.field(column))
+                .collect();
+            self.serialize_with_fields(CsvOutputAccount::from((&client, account)), &fields)?;
+        }
+        self.write_record()
+    }
+
+    /// Writes one account, once aborted every following account is dropped
+    pub fn write(&mut self, client: ClientId, account: &Account) {
+        if self.aborted.is_some() {
+            return;
+        }
+        let attempts = match self.policy {
+            SerializationFailurePolicy::Retry(retries) => retries + 1,
+            _ => 1,
+        };
+        let mut result = Ok(());
+        for attempt in 1..=attempts {
+            result = self.write_account(client, account);
+            match &result {
+                Ok(()) => break,
+                Err(err) if attempt < attempts => {
+                    warn!(%err, %client, attempt, "failed to serialize account, retrying")
+                }
+                Err(_) => {}
+            }
+        }
+        let Err(err) = result else {
+            self.summary.written += 1;
+            return;
+        };
+        self.row.clear(); // a failed row is not carried over to the next account
+        error!(%err, %client, ?account, policy = ?self.policy, "failed to serialize account");
+        self.summary.failed += 1;
+        match self.policy {
+            SerializationFailurePolicy::Skip | SerializationFailurePolicy::Retry(_) => {}
+            SerializationFailurePolicy::Placeholder => {
+                let placeholder = PlaceholderAccount {
+                    client,
+                    available: None,
+                    held: None,
+                    total: None,
+                    locked: None,
+                };
+                let fields = vec![None; self.options.columns.len()];
+                let written = self
+                    .serialize_with_fields(placeholder, &fields)
+                    .and_then(|_| self.write_record());
+                if let Err(err) = written {
+                    error!(%err, %client, "failed to write placeholder");
+                    self.row.clear();
+                }
+            }
+            SerializationFailurePolicy::Abort => self.aborted = Some((client, err)),
+        }
+    }
+
+    /// Flushes the output (closing the json array), fails if the writer aborted
+    pub fn finish(mut self) -> Result<(W, WriterSummary), WriterError> {
+        if let Some((client, source)) = self.aborted {
+            return Err(WriterError::Aborted {
+                client,
+                source: Box::new(source),
+            });
+        }
+        if self.options.format == OutputFormat::Json {
+            self.write_header()?; // an empty output is still an array
+            self.wtr
+                .write_all(if self.wrote_record { b"\n]\n" } else { b"]\n" })?;
+        }
+        let wtr = self
+            .wtr
+            .into_inner()
+            .map_err(|err| WriterError::Io(err.into_error()))?;
+        Ok((wtr, self.summary))
+    }
+}
+
+/// Writer of the output accounts in any `OutputFormat`
+pub enum OutputWriter<W: io::Write + Send> {
+    Text(AccountWriter<W>), // csv, json and ndjson
+    Table(crate::table_output::TableAccountWriter<W>),
+    #[cfg(feature = "parquet")]
+    Parquet(crate::parquet_output::ParquetAccountWriter<W>),
+}
+
+impl<W: io::Write + Send> OutputWriter<W> {
+    /// The failure policy only applies to the text formats, a table or parquet row cannot fail on its own
+    pub fn new(
+        wtr: W,
+        policy: SerializationFailurePolicy,
+        options: OutputOptions,
+    ) -> Result<OutputWriter<W>, WriterError> {
+        Ok(match options.format {
+            OutputFormat::Table => OutputWriter::Table(
+                crate::table_output::TableAccountWriter::new(wtr, &options.columns),
+            ),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => OutputWriter::Parquet(
+                crate::parquet_output::ParquetAccountWriter::new(wtr, &options.columns)?,
+            ),
+            _ => OutputWriter::Text(AccountWriter::new(wtr, policy).with_options(options)),
+        })
+    }
+
+    /// Writes the csv header or opens the json array now, so that an empty output still has it
+    pub fn write_header(&mut self) -> Result<(), WriterError> {
+        match self {
+            OutputWriter::Text(account_writer) => account_writer.write_header(),
+            OutputWriter::Table(_) => Ok(()), // rendered with the rows
+            #[cfg(feature = "parquet")]
+            OutputWriter::Parquet(_) => Ok(()), // the schema is in the footer
+        }
+    }
+
+    pub fn write(&mut self, client: ClientId, account: &Account) {
+        match self {
+            OutputWriter::Text(account_writer) => account_writer.write(client, account),
+            OutputWriter::Table(table_writer) => table_writer.write(client, account),
+            #[cfg(feature = "parquet")]
+            OutputWriter::Parquet(parquet_writer) => parquet_writer.write(client, account),
+        }
+    }
+
+    pub fn finish(self) -> Result<(W, WriterSummary), WriterError> {
+        match self {
+            OutputWriter::Text(account_writer) => account_writer.finish(),
+            OutputWriter::Table(table_writer) => Ok(table_writer.finish()?),
+            #[cfg(feature = "parquet")]
+            OutputWriter::Parquet(parquet_writer) => Ok(parquet_writer.finish()?),
+        }
+    }
+}
+
+/// Output file written under a temporary name in its directory and renamed over `path` once
+/// complete, so that readers never see a partial file and a failed run keeps the previous one.
+/// The temporary file is removed if the output is dropped without being committed.
+#[derive(Debug)]
+pub struct AtomicOutput {
+    path: PathBuf,
+    temp: PathBuf,
+    committed: bool,
+}
+
+impl AtomicOutput {
+    /// Creates the temporary file, returned to be written by the writer thread
+    pub fn create(path: &Path) -> io::Result<(AtomicOutput, File)> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::other(format!("{} is not a file path", path.display())))?;
+        // same directory so that the rename does not cross file systems
+        let temp =
+            path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), process::id()));
+        let file = File::create(&temp)?;
+        let output = AtomicOutput {
+            path: path.to_path_buf(),
+            temp,
+            committed: false,
+        };
+        Ok((output, file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Syncs the written file to disk and renames it over the path
+    pub fn commit(mut self) -> io::Result<()> {
+        File::open(&self.temp)?.sync_all()?;
+        fs::rename(&self.temp, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if !self.committed
+            && let Err(err) = fs::remove_file(&self.temp)
+        {
+            warn!(%err, temp = ?self.temp, "failed to remove the temporary output file");
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fs::File, path::Path};
+
+use parquet::{
+    file::reader::SerializedFileReader,
+    record::{Field, Row, reader::RowIter},
+};
+use rust_decimal::Decimal;
+use tracing::{info, instrument};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{ClientId, InputCsvRecord, Transaction, TransactionId, TransactionKind},
+};
+
+/// Streams the rows of a parquet file (one row group in memory at a time) as transactions.
+/// Expected columns, by name: `type` (string), `client` and `tx` (integers) and an optional
+/// `amount` (decimal, floating point or string).
+pub struct ParquetTransactions {
+    rows: RowIter<'static>,
+}
+
+// Opens the parquet file in path as a Iterator over transactions
+#[instrument]
+pub fn read_transactions_from_parquet(
+    parquet_path: &Path,
+) -> Result<ParquetTransactions, ConversionError> {
+    let file = File::open(parquet_path).map_err(|err| ConversionError::Parquet(err.to_string()))?;
+    let reader =
+        SerializedFileReader::new(file).map_err(|err| ConversionError::Parquet(err.to_string()))?;
+    info!(
+        row_groups = parquet::file::reader::FileReader::num_row_groups(&reader),
+        "Opened parquet file"
+    );
+    Ok(ParquetTransactions {
+        rows: reader.into_iter(),
+    })
+}
+
+fn integer(column: &str, field: Option<&Field>) -> Result<u64, ConversionError> {
+    let invalid =
+        || ConversionError::Parquet(format!("{column} must be an integer, found {field:?}"));
+    let value: i128 = match field {
+        Some(Field::Byte(value)) => *value as i128,
+        Some(Field::Short(value)) => *value as i128,
+        Some(Field::Int(value)) => *value as i128,
+        Some(Field::Long(value)) => *value as i128,
+        Some(Field::UByte(value)) => *value as i128,
+        Some(Field::UShort(value)) => *value as i128,
+        Some(Field::UInt(value)) => *value as i128,
+        Some(Field::ULong(value)) => *value as i128,
+        _ => return Err(invalid()),
+    };
+    u64::try_from(value).map_err(|_| invalid())
+}
+
+fn amount(field: Option<&Field>) -> Result<Option<Decimal>, ConversionError> {
+    Ok(Some(match field {
+        None | Some(Field::Null) => return Ok(None),
+        Some(Field::Decimal(decimal)) => {
+            // big endian two's complement unscaled value
+            let data = decimal.data();
+            if data.len() > 16 {
+                return Err(ConversionError::Parquet(
+                    "decimal wider than 128 bits".into(),
+                ));
+            }
+            let fill = if data.first().is_some_and(|byte| byte & 0x80 != 0) {
+                0xFF
+            } else {
+                0
+            };
+            let mut bytes = [fill; 16];
+            bytes[16 - data.len()..].copy_from_slice(data);
+            Decimal::try_from_i128_with_scale(i128::from_be_bytes(bytes), decimal.scale() as u32)?
+        }
+        // the shortest representation of the float is what the producer meant
+        Some(Field::Double(amount)) => Decimal::from_str_exact(&amount.to_string())?,
+        Some(Field::Float(amount)) => Decimal::from_str_exact(&amount.to_string())?,
+        Some(Field::Str(amount)) => Decimal::from_str_exact(amount)?,
+        Some(other) => {
+            return Err(ConversionError::Parquet(format!(
+                "amount must be a decimal, found {other}"
+            )));
+        }
+    }))
+}
+
+fn convert(row: &Row) -> Result<Transaction, ConversionError> {
+    let column = |name: &str| {
+        row.get_column_iter()
+            .find(|(column, _)| column.as_str() == name)
+            .map(|(_, field)| field)
+    };
+    let transaction_type = match column("type") {
+        Some(Field::Str(transaction_type)) => TransactionKind::from_name(transaction_type),
+        other => Err(ConversionError::Parquet(format!(
+            "type must be a string, found {other:?}"
+        )))?,
+    };
+    let client = u16::try_from(integer("client", column("client"))?)
+        .map_err(|_| ConversionError::Parquet("client id out of range".into()))?;
+    let tx = u32::try_from(integer("tx", column("tx"))?)
+        .map_err(|_| ConversionError::Parquet("tx id out of range".into()))?;
+    Transaction::try_from(InputCsvRecord {
+        transaction_type,
+        client: ClientId(client),
+        tx: TransactionId(tx),
+        amount: amount(column("amount"))?,
+    })
+}
+
+impl Iterator for ParquetTransactions {
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(match self.rows.next()? {
+            Ok(row) => convert(&row),
+            Err(err) => Err(ConversionError::Parquet(err.to_string())),
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use std::{io::Write, mem, sync::Arc};
+
+use parquet::{
+    data_type::{
+        BoolType, ByteArray, ByteArrayType, FixedLenByteArray, FixedLenByteArrayType, Int32Type,
+    },
+    errors::ParquetError,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use rust_decimal::Decimal;
+use tracing::{error, instrument};
+
+use crate::{
+    model::{Account, ClientId},
+    output::WriterSummary,
+};
+
+/// Accounts buffered before a row group is written
+pub const DEFAULT_ROW_GROUP_ROWS: usize = 64 * 1024;
+
+/// Amounts are DECIMAL(38, 4): the scale of the engine, with room for any rust_decimal mantissa.
+/// The custom columns follow as optional strings.
+const SCHEMA: &str = "message accounts {
+    required int32 client (INTEGER(16, false));
+    required fixed_len_byte_array(16) available (DECIMAL(38, 4));
+    required fixed_len_byte_array(16) held (DECIMAL(38, 4));
+    required fixed_len_byte_array(16) total (DECIMAL(38, 4));
+    required boolean locked;
+";
+
+/// Parquet writer of the output accounts, with the columns of the csv output.
+/// Accounts are buffered into row groups, a failed row group aborts the file since it cannot be
+/// retried or skipped on its own.
+pub struct ParquetAccountWriter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    rows: Vec<(ClientId, Account)>, // next row group
+    columns: Vec<String>,           // custom fields, after the csv columns
+    row_group_rows: usize,
+    failed: Option<ParquetError>,
+    summary: WriterSummary,
+}
+
+// big endian two's complement unscaled value at scale 4
+fn decimal(amount: Decimal) -> FixedLenByteArray {
+    let mut amount = amount.round_dp(4);
+    amount.rescale(4);
+    amount.mantissa().to_be_bytes().to_vec().into()
+}
+
+impl<W: Write + Send> ParquetAccountWriter<W> {
+    /// Starts the file (its magic bytes are written right away), with these custom columns
+    pub fn new(wtr: W, columns: &[String]) -> Result<ParquetAccountWriter<W>, ParquetError> {
+        let mut schema = SCHEMA.to_string();
+        for column in columns {
+            schema.push_str(&format!("    optional binary {column} (UTF8);\n"));
+        }
+        schema.push('}');
+        let schema = Arc::new(parse_message_type(&schema)?);
+        Ok(ParquetAccountWriter {
+            writer: SerializedFileWriter::new(wtr, schema, Arc::new(WriterProperties::default()))?,
+            rows: Vec::new(),
+            columns: columns.to_vec(),
+            row_group_rows: DEFAULT_ROW_GROUP_ROWS,
+            failed: None,
+            summary: WriterSummary::default(),
+        })
+    }
+
+    /// Accounts per row group instead of `DEFAULT_ROW_GROUP_ROWS`
+    pub fn with_row_group_rows(mut self, rows: usize) -> ParquetAccountWriter<W> {
+        self.row_group_rows = rows.max(1);
+        self
+    }
+
+    pub fn summary(&self) -> WriterSummary {
+        self.summary
+    }
+
+    /// Buffers one account, writes the row group once it is full.
+    /// Once a row group failed every following account is dropped.
+    pub fn write(&mut self, client: ClientId, account: &Account) {
+        if self.failed.is_some() {
+            return;
+        }
+        self.rows.push((client, account.clone()));
+        if self.rows.len() >= self.row_group_rows
+            && let Err(err) = self.write_row_group()
+        {
+            error!(%err, "failed to write a parquet row group, dropping the remaining accounts");
+            self.failed = Some(err);
+        }
+    }
+
+    #[instrument(skip(self), fields(rows = self.rows.len()))]
+    fn write_row_group(&mut self) -> Result<(), ParquetError> {
+        let rows = mem::take(&mut self.rows);
+        let mut row_group = self.writer.next_row_group()?;
+        let clients: Vec<i32> = rows.iter().map(|(client, _)| client.0 as i32).collect();
+        let mut column = row_group.next_column()?.ok_or(missing_column("client"))?;
+        column
+            .typed::<Int32Type>()
+            .write_batch(&clients, None, None)?;
+        column.close()?;
+        for (name, amount) in [
+            ("available", Account::available as fn(&Account) -> Decimal),
+            ("held", Account::held),
+            ("total", Account::total),
+        ] {
+            let amounts: Vec<FixedLenByteArray> = rows
+                .iter()
+                .map(|(_, account)| decimal(amount(account)))
+                .collect();
+            let mut column = row_group.next_column()?.ok_or(missing_column(name))?;
+            column
+                .typed::<FixedLenByteArrayType>()
+                .write_batch(&amounts, None, None)?;
+            column.close()?;
+        }
+        let locked: Vec<bool> = rows.iter().map(|(_, account)| account.locked()).collect();
+        let mut column = row_group.next_column()?.ok_or(missing_column("locked"))?;
+        column
+            .typed::<BoolType>()
+            .write_batch(&locked, None, None)?;
+        column.close()?;
+        for name in &self.columns {
+            let fields: Vec<Option<&str>> = rows
+                .iter()
+                .map(|(_, account)| account.field(name))
+                .collect();
+            let values: Vec<ByteArray> =
+                fields.iter().flatten().map(|&field| field.into()).collect();
+            let levels: Vec<i16> = fields.iter().map(|field| field.is_some() as i16).collect();
+            let mut column = row_group.next_column()?.ok_or(missing_column(name))?;
+            column
+                .typed::<ByteArrayType>()
+                .write_batch(&values, Some(&levels), None)?;
+            column.close()?;
+        }
+        row_group.close()?;
+        self.summary.written += rows.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the last row group and the footer, fails if a row group failed
+    pub fn finish(mut self) -> Result<(W, WriterSummary), ParquetError> {
+        if let Some(err) = self.failed {
+            return Err(err);
+        }
+        if !self.rows.is_empty() {
+            self.write_row_group()?;
+        }
+        let wtr = self.writer.into_inner()?;
+        Ok((wtr, self.summary))
+    }
+}
+
+fn missing_column(name: &str) -> ParquetError {
+    ParquetError::General(format!("the schema has no {name} column"))
+}
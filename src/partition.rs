@@ -0,0 +1,370 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{self, BufWriter},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    channel::{self, BoundedSender, Receiver},
+    csv_input::ConversionError,
+    model::{Account, ClientId, Transaction},
+    output::{OutputOptions, OutputWriter, SerializationFailurePolicy},
+    sink::AccountSink,
+};
+
+/// How clients are assigned to output partitions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PartitionScheme {
+    #[default]
+    Range, // contiguous client id ranges of (almost) equal size
+    Hash, // client id modulo the number of partitions
+}
+
+/// Splits the account output into a fixed number of partitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partitioner {
+    pub scheme: PartitionScheme,
+    pub partitions: u16,
+    pub channel_bound: usize, // accounts queued per partition writer before the router waits
+}
+
+impl Default for Partitioner {
+    fn default() -> Self {
+        Partitioner {
+            scheme: PartitionScheme::Range,
+            partitions: 4,
+            channel_bound: 1024,
+        }
+    }
+}
+
+const CLIENT_IDS: u32 = u16::MAX as u32 + 1;
+
+impl Partitioner {
+    pub fn partition_of(&self, client: ClientId) -> usize {
+        let partitions = self.partitions.max(1) as u32;
+        match self.scheme {
+            PartitionScheme::Range => (client.0 as u32 * partitions / CLIENT_IDS) as usize,
+            PartitionScheme::Hash => (client.0 as u32 % partitions) as usize,
+        }
+    }
+
+    /// First and last client id of a range partition
+    pub fn client_range(&self, partition: usize) -> (ClientId, ClientId) {
+        let partitions = self.partitions.max(1) as u32;
+        let first = (partition as u32 * CLIENT_IDS).div_ceil(partitions);
+        let last = ((partition as u32 + 1) * CLIENT_IDS).div_ceil(partitions) - 1;
+        (ClientId(first as u16), ClientId(last as u16))
+    }
+}
+
+/// Description of one output partition in the manifest
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ManifestPartition {
+    pub index: usize,
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_client: Option<u16>, // range partitions only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_client: Option<u16>,
+    pub accounts: u64,
+}
+
+/// Written as `manifest.json` next to the partitions, tells downstream loaders where each client is
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct PartitionManifest {
+    pub scheme: PartitionScheme,
+    pub partitions: Vec<ManifestPartition>,
+}
+
+/// One partition of a plan: a contiguous client id range and the load it was given
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PlannedPartition {
+    pub index: usize,
+    pub first_client: u16,
+    pub last_client: u16,
+    pub clients: u64,      // distinct clients of the input in the range
+    pub transactions: u64, // their transactions, the load being balanced
+}
+
+/// Client id ranges for multi-machine processing, each machine processes the clients of one
+/// partition (`--plan-partition <plan>:<index>`). Unlike the fixed `Range` scheme the ranges follow
+/// the load of the input, so a few busy clients do not end up in the same shard.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PartitionPlan {
+    pub scheme: PartitionScheme,
+    pub partitions: Vec<PlannedPartition>,
+}
+
+/// Number of transactions of each client, e.g. to plan the partitions of an input
+pub fn count_client_transactions<T: Iterator<Item = Result<Transaction, ConversionError>>>(
+    transactions: T,
+) -> BTreeMap<ClientId, u64> {
+    let mut counts = BTreeMap::new();
+    for transaction in transactions.flatten() {
+        *counts.entry(transaction.client_id()).or_default() += 1;
+    }
+    counts
+}
+
+impl PartitionPlan {
+    /// Splits the whole client id space in `partitions` contiguous ranges with about the same number
+    /// of transactions. There are fewer partitions when the input has fewer clients, a single
+    /// client is never split.
+    #[instrument(skip(transactions))]
+    pub fn balance(transactions: &BTreeMap<ClientId, u64>, partitions: u16) -> PartitionPlan {
+        let clients: Vec<(ClientId, u64)> = transactions.iter().map(|(c, n)| (*c, *n)).collect();
+        // prefix[i]: transactions of the first i clients
+        let prefix: Vec<u64> = std::iter::once(0)
+            .chain(clients.iter().scan(0, |sum, (_, count)| {
+                *sum += count;
+                Some(*sum)
+            }))
+            .collect();
+        let total = prefix[clients.len()];
+        let count = (partitions.max(1) as usize).min(clients.len()).max(1);
+        let mut ends = Vec::with_capacity(count); // exclusive end of each partition in `clients`
+        let mut start = 0;
+        for k in 1..count {
+            let target = total * k as u64 / count as u64;
+            // leave at least one client to each of the remaining partitions
+            let (lowest, highest) = (start + 1, clients.len() - (count - k));
+            let end = match (lowest..=highest).find(|end| prefix[*end] >= target) {
+                // the cut before the client that crosses the target is closer
+                Some(end) if end > lowest && target - prefix[end - 1] < prefix[end] - target => {
+                    end - 1
+                }
+                Some(end) => end,
+                None => highest,
+            };
+            ends.push(end);
+            start = end;
+        }
+        ends.push(clients.len());
+
+        let mut partitions = Vec::with_capacity(count);
+        let mut start = 0;
+        for (index, end) in ends.into_iter().enumerate() {
+            let first_client = match index {
+                0 => 0,
+                _ => clients[start - 1].0.0 + 1,
+            };
+            let last_client = match end == clients.len() {
+                true => u16::MAX,
+                false => clients[end - 1].0.0,
+            };
+            partitions.push(PlannedPartition {
+                index,
+                first_client,
+                last_client,
+                clients: (end - start) as u64,
+                transactions: prefix[end] - prefix[start],
+            });
+            start = end;
+        }
+        PartitionPlan {
+            scheme: PartitionScheme::Range,
+            partitions,
+        }
+    }
+
+    /// Every client id of a partition, None if there is no such partition
+    pub fn clients(&self, index: usize) -> Option<HashSet<ClientId>> {
+        let partition = self.partitions.get(index)?;
+        Some(
+            (partition.first_client..=partition.last_client)
+                .map(ClientId)
+                .collect(),
+        )
+    }
+}
+
+/// Like `spawn_writer_thread` but every account is written to `<dir>/accounts_<partition>.csv`
+/// (`.json` or `.ndjson` in those output formats).
+/// Each partition is owned by its own writer thread so that serialization and disk writes scale
+/// with the number of partitions, the returned thread only routes the accounts to them.
+/// The manifest is written once the channel is closed.
+#[instrument(skip(rx))]
+pub fn spawn_partitioned_writer_thread<R>(
+    dir: &Path,
+    partitioner: Partitioner,
+    rx: R,
+    policy: SerializationFailurePolicy,
+    options: OutputOptions,
+) -> io::Result<JoinHandle<io::Result<PartitionManifest>>>
+where
+    R: IntoIterator<Item = (ClientId, Account)> + Send + 'static,
+{
+    fs::create_dir_all(dir)?;
+    let mut partitions = Vec::new();
+    let mut shard_senders = Vec::new();
+    let mut shard_writers = Vec::new();
+    for index in 0..partitioner.partitions.max(1) as usize {
+        let file = format!("accounts_{index}.{}", options.format.extension());
+        let mut output_writer = OutputWriter::new(
+            BufWriter::new(File::create(dir.join(&file))?),
+            policy,
+            options.clone(),
+        )
+        .map_err(io::Error::other)?;
+        output_writer.write_header().map_err(io::Error::other)?; // so that empty partitions still have it
+        let (shard_tx, shard_rx) = channel::bounded(partitioner.channel_bound.max(1));
+        shard_senders.push(shard_tx);
+        shard_writers.push(spawn_shard_writer_thread(output_writer, shard_rx));
+        let range =
+            (partitioner.scheme == PartitionScheme::Range).then(|| partitioner.client_range(index));
+        partitions.push(ManifestPartition {
+            index,
+            file,
+            first_client: range.map(|(first, _)| first.0),
+            last_client: range.map(|(_, last)| last.0),
+            accounts: 0,
+        });
+    }
+    let manifest_path: PathBuf = dir.join("manifest.json");
+
+    Ok(thread::spawn(move || {
+        // the channel is closed when nothing else needs to be written
+        for (client, account) in rx {
+            let partition = partitioner.partition_of(client);
+            if shard_senders[partition].send((client, account)).is_err() {
+                error!(partition, %client, "partition writer thread stopped");
+            }
+        }
+        drop(shard_senders); // lets the shard writers finish
+        for (partition, shard_writer) in partitions.iter_mut().zip(shard_writers) {
+            partition.accounts = shard_writer
+                .join()
+                .expect("failed to join partition writer thread")?;
+        }
+        let manifest = PartitionManifest {
+            scheme: partitioner.scheme,
+            partitions,
+        };
+        serde_json::to_writer_pretty(File::create(&manifest_path)?, &manifest)?;
+        info!(?manifest_path, "Wrote partition manifest");
+        Ok(manifest)
+    }))
+}
+
+/// Writes one partition, returns the number of accounts written
+fn spawn_shard_writer_thread(
+    mut output_writer: OutputWriter<BufWriter<File>>,
+    rx: Receiver<(ClientId, Account)>,
+) -> JoinHandle<io::Result<u64>> {
+    thread::spawn(move || {
+        for (client, account) in rx {
+            output_writer.write(client, &account);
+        }
+        let (_wtr, summary) = output_writer.finish().map_err(io::Error::other)?;
+        if summary.failed > 0 {
+            warn!(%summary, "Some accounts of the partition failed to serialize");
+        }
+        Ok(summary.written)
+    })
+}
+
+/// Writers of the `<dir>/part-<n>.csv` files of a sharded run (`.json` or `.ndjson` in those output
+/// formats), one thread per part like the worker shards of `load_transactions_parallel`. Unlike
+/// `spawn_partitioned_writer_thread` there is no router thread: the accounts are sent to the part
+/// writers by the threads emitting them, see `Clients::send_to_output_sharded`.
+pub struct PartWriters {
+    senders: Vec<BoundedSender<(ClientId, Account)>>,
+    writers: Vec<JoinHandle<io::Result<u64>>>,
+    partitions: Vec<ManifestPartition>,
+    manifest_path: PathBuf,
+}
+
+impl PartWriters {
+    /// Creates the part files and starts their writer threads, `channel_bound` accounts are queued
+    /// per part before the senders wait
+    #[instrument]
+    pub fn create(
+        dir: &Path,
+        parts: NonZeroUsize,
+        channel_bound: usize,
+        policy: SerializationFailurePolicy,
+        options: OutputOptions,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut senders = Vec::new();
+        let mut writers = Vec::new();
+        let mut partitions = Vec::new();
+        for index in 0..parts.get() {
+            let file = format!("part-{index}.{}", options.format.extension());
+            let mut output_writer = OutputWriter::new(
+                BufWriter::new(File::create(dir.join(&file))?),
+                policy,
+                options.clone(),
+            )
+            .map_err(io::Error::other)?;
+            output_writer.write_header().map_err(io::Error::other)?;
+            let (tx, rx) = channel::bounded(channel_bound.max(1));
+            senders.push(tx);
+            writers.push(spawn_shard_writer_thread(output_writer, rx));
+            partitions.push(ManifestPartition {
+                index,
+                file,
+                first_client: None,
+                last_client: None,
+                accounts: 0,
+            });
+        }
+        Ok(PartWriters {
+            senders,
+            writers,
+            partitions,
+            manifest_path: dir.join("manifest.json"),
+        })
+    }
+
+    /// One sink per part, in part order
+    pub fn sinks(&self) -> Vec<BoundedSender<(ClientId, Account)>> {
+        self.senders.clone()
+    }
+
+    /// A sink sending each account to the part of its client, e.g. for the accounts locked while
+    /// the transactions are applied
+    pub fn router(&self) -> PartRouter {
+        PartRouter {
+            senders: self.sinks(),
+        }
+    }
+
+    /// Waits for the part writers once every sink was dropped and writes the manifest
+    pub fn finish(mut self) -> io::Result<PartitionManifest> {
+        self.senders.clear(); // lets the writers finish once the other sinks are gone
+        for (partition, writer) in self.partitions.iter_mut().zip(self.writers) {
+            partition.accounts = writer.join().expect("failed to join part writer thread")?;
+        }
+        let manifest = PartitionManifest {
+            scheme: PartitionScheme::Hash,
+            partitions: self.partitions,
+        };
+        serde_json::to_writer_pretty(File::create(&self.manifest_path)?, &manifest)?;
+        info!(manifest_path = ?self.manifest_path, "Wrote part manifest");
+        Ok(manifest)
+    }
+}
+
+/// Sends each account to the part writer of its client: client id modulo the number of parts
+#[derive(Clone)]
+pub struct PartRouter {
+    senders: Vec<BoundedSender<(ClientId, Account)>>,
+}
+
+impl AccountSink for PartRouter {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        let part = client.0 as usize % self.senders.len();
+        if self.senders[part].send((client, account.clone())).is_err() {
+            error!(part, %client, "part writer thread stopped");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::{collections::HashSet, io};
+
+use tracing::{instrument, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{ClientId, InputCsvRecord, Transaction, TransactionId},
+};
+
+/// Filters of the `query` subcommand, an empty filter matches everything
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransactionQuery {
+    pub clients: HashSet<ClientId>,
+    pub types: HashSet<&'static str>, // names from `filter::TRANSACTION_TYPES`
+    pub tx: Option<TransactionId>,
+}
+
+impl TransactionQuery {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        (self.clients.is_empty() || self.clients.contains(&transaction.client_id()))
+            && (self.types.is_empty() || self.types.contains(transaction.type_name()))
+            && self.tx.is_none_or(|tx| tx == transaction.tx_id())
+    }
+}
+
+/// Number of transactions read and printed by a query
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QuerySummary {
+    pub scanned: u64,
+    pub matched: u64,
+    pub invalid: u64, // rows that failed conversion, logged and skipped
+}
+
+/// Writes the matching transactions as csv in the input format, nothing is applied
+#[instrument(skip(transactions, wtr))]
+pub fn run_query<I: Iterator<Item = Result<Transaction, ConversionError>>, W: io::Write>(
+    query: &TransactionQuery,
+    transactions: I,
+    wtr: W,
+) -> Result<QuerySummary, csv::Error> {
+    let mut csv_writer = csv::WriterBuilder::new().from_writer(wtr);
+    let mut summary = QuerySummary::default();
+    for transaction in transactions {
+        summary.scanned += 1;
+        match transaction {
+            Ok(transaction) if query.matches(&transaction) => {
+                csv_writer.serialize(InputCsvRecord::from(&transaction))?;
+                summary.matched += 1;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                warn!(%err, "Skipping invalid row");
+                summary.invalid += 1;
+            }
+        }
+    }
+    csv_writer.flush()?;
+    Ok(summary)
+}
@@ -0,0 +1,20 @@
+use std::io;
+
+use tracing::{info, instrument};
+
+use crate::{csv_input::read_input, encoding::InputEncoding};
+
+/// Streams the body of a GET of `url` (feature `remote`), decompressed and transcoded like a file
+/// input (`open_input_with_encoding`): the rows are parsed while they are downloaded, nothing is
+/// staged on disk, so settlement files hosted on object storage (e.g. behind a presigned URL) are
+/// processed without a manual download. Redirects are followed; an error status or a failed
+/// connection fails the open, a connection lost later fails the read like a disk error would.
+#[instrument]
+pub fn open_url(
+    url: &str,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    let response = ureq::get(url).call().map_err(io::Error::other)?;
+    info!(url, status = %response.status(), "Downloading input");
+    read_input(response.into_body().into_reader(), encoding)
+}
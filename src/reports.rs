@@ -0,0 +1,321 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    csv_input::ConversionError,
+    model::{Account, ClientId, Clients, Transaction, TransactionId},
+    observer::TransactionObserver,
+};
+
+/// One open dispute: a deposit whose amount is currently part of the client's `held` funds
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvExposureRecord {
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub amount: Decimal,
+}
+
+/// Lists the open disputes that make up each client's held funds, sorted by client and tx
+pub fn open_disputes(clients: &Clients) -> Vec<CsvExposureRecord> {
+    let mut records: Vec<CsvExposureRecord> = clients
+        .disputed_clients()
+        .flat_map(|client| {
+            clients
+                .client_disputes(client)
+                .map(move |(tx, amount)| CsvExposureRecord {
+                    client,
+                    tx,
+                    amount: amount.round_dp(4), // same bankers rounding as the account output
+                })
+        })
+        .collect();
+    records.sort_unstable_by_key(|record| (record.client.0, record.tx.0));
+    records
+}
+
+/// Writes the held-funds exposure report (client, tx, amount) as csv
+#[instrument(skip(clients, wtr))]
+pub fn write_exposure_report<W: io::Write>(clients: &Clients, wtr: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::WriterBuilder::new()
+        .has_headers(false) // header is written explicitly so that a report without disputes still has it
+        .from_writer(wtr);
+    csv_writer.write_record(["client", "tx", "amount"])?;
+    for record in open_disputes(clients) {
+        csv_writer.serialize(record)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Activity of a client over the processed input: balances and what happened in between.
+/// Transactions carry no timestamp so the statement period is the whole run, and the engine charges no fees.
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvActivityRecord {
+    pub client: ClientId,
+    pub opening_balance: Decimal, // total funds before the first transaction of the period
+    pub deposits: Decimal,
+    pub withdrawals: Decimal,
+    pub disputes_opened: u32,
+    pub disputes_resolved: u32,
+    pub chargebacks: u32,
+    pub closing_balance: Decimal, // total funds after the last transaction of the period
+}
+
+/// Builds the per-client activity statements, only transactions that had an effect are counted
+#[derive(Debug, Default)]
+pub struct ActivityRecorder {
+    pub activity: HashMap<ClientId, CsvActivityRecord>,
+}
+
+impl TransactionObserver for ActivityRecorder {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        if before == after {
+            return; // ignored (not enough funds, unknown tx, locked account...)
+        }
+        let record = self
+            .activity
+            .entry(client)
+            .or_insert_with(|| CsvActivityRecord {
+                client,
+                opening_balance: before.total(),
+                deposits: Decimal::ZERO,
+                withdrawals: Decimal::ZERO,
+                disputes_opened: 0,
+                disputes_resolved: 0,
+                chargebacks: 0,
+                closing_balance: before.total(),
+            });
+        match transaction {
+            Transaction::Deposit { amount, .. } => record.deposits += amount,
+            Transaction::Withdrawal { amount, .. } => record.withdrawals += amount,
+            Transaction::Dispute { .. } => record.disputes_opened += 1,
+            Transaction::Resolve { .. } => record.disputes_resolved += 1,
+            Transaction::Chargeback { .. } => record.chargebacks += 1,
+            Transaction::Custom(_) => {} // only reflected in the balances
+        }
+        record.closing_balance = after.total();
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.activity.remove(&client);
+    }
+}
+
+impl ActivityRecorder {
+    /// Writes the activity statements of every client as csv, sorted by client
+    #[instrument(skip(self, wtr))]
+    pub fn write_activity_report<W: io::Write>(&self, wtr: W) -> Result<(), csv::Error> {
+        let mut records: Vec<&CsvActivityRecord> = self.activity.values().collect();
+        records.sort_unstable_by_key(|record| record.client.0);
+        let mut csv_writer = csv::Writer::from_writer(wtr);
+        for record in records {
+            csv_writer.serialize(CsvActivityRecord {
+                deposits: record.deposits.round_dp(4),
+                withdrawals: record.withdrawals.round_dp(4),
+                ..record.clone()
+            })?;
+        }
+        csv_writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Relative cost of the work done for each kind of transaction, ignored transactions cost the same
+/// since they were looked up and checked before being rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostWeights {
+    pub deposit: u64,
+    pub withdrawal: u64,
+    pub dispute: u64, // looks up the disputed deposit and moves funds to held
+    pub resolve: u64,
+    pub chargeback: u64,
+    pub custom: u64, // any custom transaction type
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        CostWeights {
+            deposit: 1,
+            withdrawal: 1,
+            dispute: 2,
+            resolve: 2,
+            chargeback: 2,
+            custom: 1,
+        }
+    }
+}
+
+/// Processing done for a client, including the transactions the engine ignored
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvCostRecord {
+    pub client: ClientId,
+    pub segment: String,       // business unit the cost is charged to
+    pub transactions: u64,     // every valid transaction of the client
+    pub applied: u64,          // transactions that changed the account
+    pub ignored: u64,          // not enough funds, unknown tx, locked account...
+    pub disputes_handled: u64, // disputes, resolves and chargebacks, applied or not
+    pub cost: u64,             // in units of the cost weights
+}
+
+/// Counts the work done per client for the internal chargeback of platform costs
+#[derive(Debug, Default)]
+pub struct CostRecorder {
+    pub weights: CostWeights,
+    pub costs: HashMap<ClientId, CsvCostRecord>,
+}
+
+impl TransactionObserver for CostRecorder {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        let record = self.costs.entry(client).or_insert_with(|| CsvCostRecord {
+            client,
+            segment: String::new(), // filled in when the report is written
+            transactions: 0,
+            applied: 0,
+            ignored: 0,
+            disputes_handled: 0,
+            cost: 0,
+        });
+        record.transactions += 1;
+        if before == after {
+            record.ignored += 1;
+        } else {
+            record.applied += 1;
+        }
+        record.cost += match transaction {
+            Transaction::Deposit { .. } => self.weights.deposit,
+            Transaction::Withdrawal { .. } => self.weights.withdrawal,
+            Transaction::Dispute { .. } => self.weights.dispute,
+            Transaction::Resolve { .. } => self.weights.resolve,
+            Transaction::Chargeback { .. } => self.weights.chargeback,
+            Transaction::Custom(_) => self.weights.custom,
+        };
+        if matches!(
+            transaction,
+            Transaction::Dispute { .. }
+                | Transaction::Resolve { .. }
+                | Transaction::Chargeback { .. }
+        ) {
+            record.disputes_handled += 1;
+        }
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.costs.remove(&client);
+    }
+}
+
+impl CostRecorder {
+    /// Writes the cost of every client as csv, sorted by client, with the segment it is charged to
+    #[instrument(skip(self, segments, wtr))]
+    pub fn write_cost_report<W: io::Write>(
+        &self,
+        segments: &SegmentMap,
+        wtr: W,
+    ) -> Result<(), csv::Error> {
+        let mut records: Vec<&CsvCostRecord> = self.costs.values().collect();
+        records.sort_unstable_by_key(|record| record.client.0);
+        let mut csv_writer = csv::Writer::from_writer(wtr);
+        for record in records {
+            let segment = segments
+                .get(&record.client)
+                .map_or(UNSEGMENTED, String::as_str);
+            csv_writer.serialize(CsvCostRecord {
+                segment: segment.to_string(),
+                ..record.clone()
+            })?;
+        }
+        csv_writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Segment (retail, institutional, internal...) of each client, loaded from a `client, segment` csv
+pub type SegmentMap = HashMap<ClientId, String>;
+
+/// Clients without a segment are aggregated under this name
+pub const UNSEGMENTED: &str = "unsegmented";
+
+#[derive(Debug, Deserialize)]
+struct CsvSegmentAssignment {
+    client: ClientId,
+    segment: String,
+}
+
+/// Reads the client segments sidecar file
+pub fn load_segments<R: io::Read>(
+    csv_reader: csv::Reader<R>,
+) -> Result<SegmentMap, ConversionError> {
+    csv_reader
+        .into_deserialize::<CsvSegmentAssignment>()
+        .map(|record| {
+            let record = record?;
+            Ok((record.client, record.segment))
+        })
+        .collect()
+}
+
+/// Aggregated balances of the clients of a segment
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvSegmentRecord {
+    pub segment: String,
+    pub clients: u64,
+    pub locked: u64,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+}
+
+/// Per-segment aggregates of every account, sorted by segment
+pub fn segment_summary(clients: &Clients, segments: &SegmentMap) -> Vec<CsvSegmentRecord> {
+    let mut summary: BTreeMap<&str, CsvSegmentRecord> = BTreeMap::new();
+    for (client, account) in &clients.accounts {
+        let segment = segments.get(client).map_or(UNSEGMENTED, String::as_str);
+        let record = summary.entry(segment).or_insert_with(|| CsvSegmentRecord {
+            segment: segment.to_string(),
+            clients: 0,
+            locked: 0,
+            available: Decimal::ZERO,
+            held: Decimal::ZERO,
+            total: Decimal::ZERO,
+        });
+        record.clients += 1;
+        record.locked += account.locked() as u64;
+        record.available += account.available();
+        record.held += account.held();
+        record.total += account.total();
+    }
+    summary.into_values().collect()
+}
+
+/// Writes the per-segment aggregates as csv
+#[instrument(skip(clients, segments, wtr))]
+pub fn write_segment_report<W: io::Write>(
+    clients: &Clients,
+    segments: &SegmentMap,
+    wtr: W,
+) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(wtr);
+    for record in segment_summary(clients, segments) {
+        csv_writer.serialize(record)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,28 @@
+/// xorshift64*: tiny non cryptographic generator, deterministic for a given seed.
+/// Used to roll dice for injected faults and synthetic load, not for anything security related.
+#[derive(Debug, Clone)]
+pub(crate) struct XorShiftRng(u64);
+
+impl XorShiftRng {
+    pub(crate) fn new(seed: u64) -> XorShiftRng {
+        XorShiftRng(seed.max(1)) // the state must never be zero
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// true with the given probability
+    #[cfg(feature = "fault-injection")]
+    pub(crate) fn roll(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+}
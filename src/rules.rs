@@ -0,0 +1,391 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use crate::{model::Transaction, reports::SegmentMap};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RuleError {
+    #[error("Invalid rule on line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// Transaction attributes a rule can test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Type,    // `type`: deposit, withdrawal, dispute, resolve or chargeback
+    Client,  // `client`: client id
+    Tx,      // `tx`: transaction id
+    Amount,  // `amount`: missing for disputes, resolves and chargebacks
+    Segment, // `client.segment`: from the segments sidecar, missing for unsegmented clients
+}
+
+impl Field {
+    fn kind(self) -> Kind {
+        match self {
+            Field::Type | Field::Segment => Kind::Text,
+            Field::Client | Field::Tx | Field::Amount => Kind::Number,
+        }
+    }
+}
+
+/// Type of an expression, checked when the rules are compiled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Text,
+    Bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(Decimal),
+    Text(String),
+    Bool(bool),
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Eq => ordering.is_eq(),
+            Comparison::Ne => ordering.is_ne(),
+            Comparison::Lt => ordering.is_lt(),
+            Comparison::Le => ordering.is_le(),
+            Comparison::Gt => ordering.is_gt(),
+            Comparison::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Field(Field),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, transaction: &Transaction, segments: &SegmentMap) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Field(Field::Type) => Value::Text(transaction.type_name().to_string()),
+            Expr::Field(Field::Client) => Value::Number(transaction.client_id().0.into()),
+            Expr::Field(Field::Tx) => Value::Number(transaction.tx_id().0.into()),
+            Expr::Field(Field::Amount) => match transaction {
+                Transaction::Deposit { amount, .. } | Transaction::Withdrawal { amount, .. } => {
+                    Value::Number(*amount)
+                }
+                Transaction::Custom(custom) => custom.amount.map_or(Value::Missing, Value::Number),
+                _ => Value::Missing,
+            },
+            Expr::Field(Field::Segment) => segments
+                .get(&transaction.client_id())
+                .map_or(Value::Missing, |segment| Value::Text(segment.clone())),
+            Expr::Not(expr) => Value::Bool(!expr.holds(transaction, segments)),
+            Expr::And(left, right) => {
+                Value::Bool(left.holds(transaction, segments) && right.holds(transaction, segments))
+            }
+            Expr::Or(left, right) => {
+                Value::Bool(left.holds(transaction, segments) || right.holds(transaction, segments))
+            }
+            Expr::Compare(comparison, left, right) => {
+                let ordering = match (
+                    left.eval(transaction, segments),
+                    right.eval(transaction, segments),
+                ) {
+                    (Value::Number(left), Value::Number(right)) => Some(left.cmp(&right)),
+                    (Value::Text(left), Value::Text(right)) => Some(left.cmp(&right)),
+                    (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(&right)),
+                    _ => None, // a missing value never matches, like a NULL in SQL
+                };
+                Value::Bool(ordering.is_some_and(|ordering| comparison.holds(ordering)))
+            }
+        }
+    }
+
+    fn holds(&self, transaction: &Transaction, segments: &SegmentMap) -> bool {
+        self.eval(transaction, segments) == Value::Bool(true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(Decimal),
+    Text(String),
+    Bool(bool),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 9] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(next) = rest.chars().next() {
+        let length = if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else if next == '(' || next == ')' {
+            tokens.push(if next == '(' {
+                Token::Open
+            } else {
+                Token::Close
+            });
+            1
+        } else if next == '"' {
+            let end = rest[1..]
+                .find('"')
+                .ok_or("unterminated string".to_string())?;
+            tokens.push(Token::Text(rest[1..=end].to_string()));
+            end + 2
+        } else if next.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = Decimal::from_str(&rest[..end])
+                .map_err(|_| format!("invalid number {}", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            end
+        } else if next.is_ascii_alphabetic() || next == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(match &rest[..end] {
+                "true" => Token::Bool(true),
+                "false" => Token::Bool(false),
+                ident => Token::Ident(ident.to_string()),
+            });
+            end
+        } else {
+            return Err(format!("unexpected character {next:?}"));
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over `or := and ("||" and)*`, `and := unary ("&&" unary)*`,
+/// `unary := "!" unary | comparison`, `comparison := primary (op primary)?`,
+/// `primary := "(" or ")" | literal | field`
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let matches = matches!(self.peek(), Some(Token::Op(found)) if *found == op);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn boolean(expr: &(Expr, Kind), operator: &str) -> Result<(), String> {
+        match expr.1 {
+            Kind::Bool => Ok(()),
+            _ => Err(format!("{operator} only applies to conditions")),
+        }
+    }
+
+    fn or(&mut self) -> Result<(Expr, Kind), String> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            let right = self.and()?;
+            Self::boolean(&left, "||")?;
+            Self::boolean(&right, "||")?;
+            left = (Expr::Or(Box::new(left.0), Box::new(right.0)), Kind::Bool);
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<(Expr, Kind), String> {
+        let mut left = self.unary()?;
+        while self.eat("&&") {
+            let right = self.unary()?;
+            Self::boolean(&left, "&&")?;
+            Self::boolean(&right, "&&")?;
+            left = (Expr::And(Box::new(left.0), Box::new(right.0)), Kind::Bool);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<(Expr, Kind), String> {
+        if self.eat("!") {
+            let expr = self.unary()?;
+            Self::boolean(&expr, "!")?;
+            return Ok((Expr::Not(Box::new(expr.0)), Kind::Bool));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<(Expr, Kind), String> {
+        let left = self.primary()?;
+        let comparison = match self.peek() {
+            Some(Token::Op("==")) => Comparison::Eq,
+            Some(Token::Op("!=")) => Comparison::Ne,
+            Some(Token::Op("<")) => Comparison::Lt,
+            Some(Token::Op("<=")) => Comparison::Le,
+            Some(Token::Op(">")) => Comparison::Gt,
+            Some(Token::Op(">=")) => Comparison::Ge,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.primary()?;
+        if left.1 != right.1 {
+            return Err(format!(
+                "cannot compare a {:?} with a {:?}",
+                left.1, right.1
+            ));
+        }
+        if left.1 != Kind::Number && !matches!(comparison, Comparison::Eq | Comparison::Ne) {
+            return Err(format!("only numbers can be ordered, not a {:?}", left.1));
+        }
+        Ok((
+            Expr::Compare(comparison, Box::new(left.0), Box::new(right.0)),
+            Kind::Bool,
+        ))
+    }
+
+    fn primary(&mut self) -> Result<(Expr, Kind), String> {
+        let token = self.peek().cloned().ok_or("unexpected end of the rule")?;
+        self.position += 1;
+        Ok(match token {
+            Token::Open => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing )".to_string());
+                }
+                self.position += 1;
+                expr
+            }
+            Token::Number(number) => (Expr::Literal(Value::Number(number)), Kind::Number),
+            Token::Text(text) => (Expr::Literal(Value::Text(text)), Kind::Text),
+            Token::Bool(bool) => (Expr::Literal(Value::Bool(bool)), Kind::Bool),
+            Token::Ident(ident) => {
+                let field = match ident.as_str() {
+                    "type" => Field::Type,
+                    "client" => Field::Client,
+                    "tx" => Field::Tx,
+                    "amount" => Field::Amount,
+                    "client.segment" => Field::Segment,
+                    other => return Err(format!("unknown field {other}")),
+                };
+                (Expr::Field(field), field.kind())
+            }
+            other => return Err(format!("unexpected {other:?}")),
+        })
+    }
+}
+
+/// A compiled `reject when <condition>` rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    source: String, // the condition as written, for the logs
+    condition: Expr,
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "reject when {}", self.source)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let source = rule
+            .trim()
+            .strip_prefix("reject when ")
+            .ok_or("a rule starts with `reject when`")?
+            .trim();
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let (condition, kind) = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {token:?}"));
+        }
+        if kind != Kind::Bool {
+            return Err("the rule is not a condition".to_string());
+        }
+        Ok(Rule {
+            source: source.to_string(),
+            condition,
+        })
+    }
+}
+
+/// User-defined rejection rules, compiled once when they are loaded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    segments: SegmentMap, // `client.segment` of the clients
+}
+
+impl RuleSet {
+    /// One rule per line, e.g. `reject when type == "withdrawal" && amount > 10000`.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(rules: &str) -> Result<RuleSet, RuleError> {
+        let rules = rules
+            .lines()
+            .enumerate()
+            .filter(|(_, rule)| !rule.trim().is_empty() && !rule.trim_start().starts_with('#'))
+            .map(|(index, rule)| {
+                rule.parse().map_err(|message| RuleError::Syntax {
+                    line: index + 1,
+                    message,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RuleSet {
+            rules,
+            segments: SegmentMap::new(),
+        })
+    }
+
+    /// Segments of the clients, tested by `client.segment`
+    pub fn with_segments(mut self, segments: SegmentMap) -> RuleSet {
+        self.segments = segments;
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Index of the first rule rejecting the transaction
+    pub fn rejecting(&self, transaction: &Transaction) -> Option<usize> {
+        self.rules
+            .iter()
+            .position(|rule| rule.condition.holds(transaction, &self.segments))
+    }
+}
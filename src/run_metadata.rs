@@ -0,0 +1,114 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, instrument};
+
+/// Provenance of one input file
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct InputDigest {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Everything needed to trace an output artifact back to the run that produced it
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct RunMetadata {
+    pub engine_version: String,
+    pub run_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>, // unix seconds, unset in reproducible runs
+    pub inputs: Vec<InputDigest>,
+    pub config_hash: String, // sha256 of the effective configuration
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Streams a file through sha256
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+impl RunMetadata {
+    /// Hashes the inputs and the configuration (any stable textual form of it, e.g. its Debug output)
+    #[instrument(skip(config))]
+    pub fn new(inputs: &[&Path], config: &str) -> io::Result<RunMetadata> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut metadata = RunMetadata::digest(inputs, config)?;
+        // unique enough to tell runs apart: start time, process and configuration
+        metadata.run_id = to_hex(&Sha256::digest(format!(
+            "{}:{}:{}",
+            now.as_nanos(),
+            process::id(),
+            metadata.config_hash
+        )))[..16]
+            .to_string();
+        metadata.started_at = Some(now.as_secs());
+        info!(run_id = metadata.run_id, "Run metadata");
+        Ok(metadata)
+    }
+
+    /// Like `new` without the start time, and with a run id derived from the configuration and the
+    /// inputs only: identical runs get byte-identical metadata
+    #[instrument(skip(config))]
+    pub fn reproducible(inputs: &[&Path], config: &str) -> io::Result<RunMetadata> {
+        let mut metadata = RunMetadata::digest(inputs, config)?;
+        let mut hasher = Sha256::new();
+        hasher.update(metadata.config_hash.as_bytes());
+        for input in &metadata.inputs {
+            hasher.update(input.sha256.as_bytes());
+        }
+        metadata.run_id = to_hex(&hasher.finalize())[..16].to_string();
+        info!(run_id = metadata.run_id, "Reproducible run metadata");
+        Ok(metadata)
+    }
+
+    fn digest(inputs: &[&Path], config: &str) -> io::Result<RunMetadata> {
+        let inputs = inputs
+            .iter()
+            .map(|path| {
+                Ok(InputDigest {
+                    path: path.to_path_buf(),
+                    sha256: file_sha256(path)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(RunMetadata {
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            run_id: String::new(),
+            started_at: None,
+            inputs,
+            config_hash: to_hex(&Sha256::digest(config.as_bytes())),
+        })
+    }
+
+    /// Path of the sidecar of an artifact: `<artifact>.meta.json`, or `<dir>/run_metadata.json` for a directory
+    pub fn sidecar_path(artifact: &Path) -> PathBuf {
+        if artifact.is_dir() {
+            return artifact.join("run_metadata.json");
+        }
+        let mut file_name = artifact.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".meta.json");
+        artifact.with_file_name(file_name)
+    }
+
+    /// Writes the metadata as json next to an output artifact
+    pub fn write_sidecar(&self, artifact: &Path) -> io::Result<()> {
+        let mut wtr = BufWriter::new(File::create(Self::sidecar_path(artifact))?);
+        serde_json::to_writer_pretty(&mut wtr, self)?;
+        writeln!(wtr)?;
+        wtr.flush()
+    }
+}
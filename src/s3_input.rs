@@ -0,0 +1,138 @@
+use std::{
+    io::{self, BufRead, Read},
+    ops::Not,
+    path::Path,
+    thread,
+};
+
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::{Client, error::DisplayErrorContext};
+use tracing::{info, instrument};
+
+use crate::{
+    channel::{self, BoundedSender, Receiver},
+    csv_input::{ConversionError, CsvOptions, read_input, read_transactions_from_csv_with_options},
+    encoding::InputEncoding,
+    model::Transaction,
+};
+
+/// Chunks of the object body downloaded ahead of the parser
+pub const S3_CHUNKS_AHEAD: usize = 16;
+
+/// The body of an S3 object (feature `s3`) as a blocking reader: a thread runs the download on its
+/// own tokio runtime and hands the chunks of the body over a bounded channel as they arrive, so
+/// that the object is parsed while it is downloaded and a slow parser pauses the download instead
+/// of buffering the whole object. A failed request is returned by the first read.
+pub struct S3ObjectReader {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>, // being consumed
+    pos: usize,     // consumed bytes of the chunk
+    eof: bool,
+}
+
+impl S3ObjectReader {
+    /// Starts downloading `key` of `bucket` with `client`, or with the configuration of the
+    /// environment (credentials, region, `AWS_ENDPOINT_URL`...) when none is given
+    pub fn new(client: Option<Client>, bucket: &str, key: &str) -> io::Result<S3ObjectReader> {
+        let (sender, chunks) = channel::bounded(S3_CHUNKS_AHEAD);
+        let (bucket, key) = (bucket.to_string(), key.to_string());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        thread::Builder::new()
+            .name("s3-input".to_string())
+            .spawn(move || runtime.block_on(download(client, bucket, key, sender)))?;
+        Ok(S3ObjectReader {
+            chunks,
+            chunk: Vec::new(),
+            pos: 0,
+            eof: false,
+        })
+    }
+}
+
+// sends the chunks of the object body, or the error of the download, until the reader is dropped
+async fn download(
+    client: Option<Client>,
+    bucket: String,
+    key: String,
+    sender: BoundedSender<io::Result<Vec<u8>>>,
+) {
+    let client = match client {
+        Some(client) => client,
+        None => Client::new(&aws_config::load_defaults(BehaviorVersion::latest()).await),
+    };
+    let mut body = match client.get_object().bucket(&bucket).key(&key).send().await {
+        Ok(object) => {
+            info!(
+                bucket,
+                key,
+                length = object.content_length,
+                "Downloading input"
+            );
+            object.body
+        }
+        Err(err) => {
+            let err = format!("s3://{bucket}/{key}: {}", DisplayErrorContext(err));
+            let _ = sender.send(Err(io::Error::other(err)));
+            return;
+        }
+    };
+    // a blocking send: this runtime only serves this download, which waits for the parser while
+    // the channel is full
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map(Vec::from).map_err(io::Error::other);
+        let failed = chunk.is_err();
+        if sender.send(chunk).is_err() || failed {
+            return; // the reader was dropped, or the download failed
+        }
+    }
+}
+
+impl BufRead for S3ObjectReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.chunk.len() && self.eof.not() {
+            match self.chunks.recv() {
+                Ok(chunk) => (self.chunk, self.pos) = (chunk?, 0),
+                Err(_) => self.eof = true, // the whole body was received
+            }
+        }
+        Ok(&self.chunk[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.chunk.len());
+    }
+}
+
+impl Read for S3ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+/// Like `open_input_with_encoding` for the S3 object `key` of `bucket`, downloaded with the
+/// configuration of the environment: compressed objects are decompressed, then transcoded
+#[instrument]
+pub fn open_s3_input(
+    bucket: &str,
+    key: &str,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    read_input(S3ObjectReader::new(None, bucket, key)?, encoding)
+}
+
+/// Streams the transactions of the csv object `key` of `bucket`, like `read_transactions_from_csv`
+pub fn read_transactions_from_s3(
+    bucket: &str,
+    key: &str,
+) -> Result<Box<dyn Iterator<Item = Result<Transaction, ConversionError>>>, ConversionError> {
+    read_transactions_from_csv_with_options(
+        Path::new(&format!("s3://{bucket}/{key}")),
+        &CsvOptions::default(),
+    )
+}
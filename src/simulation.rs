@@ -0,0 +1,341 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rust_decimal::Decimal;
+use tracing::{info, instrument};
+
+use crate::{
+    model::{ClientId, Clients, Transaction, TransactionId},
+    rng::XorShiftRng,
+};
+
+/// Relative weights of each transaction type in the synthetic stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMix {
+    pub deposit: f64,
+    pub withdrawal: f64,
+    pub dispute: f64,
+    pub resolve: f64,
+    pub chargeback: f64,
+}
+
+impl Default for TypeMix {
+    // same mix as the criterion benchmark
+    fn default() -> Self {
+        TypeMix {
+            deposit: 0.5,
+            withdrawal: 0.2,
+            dispute: 0.1,
+            resolve: 0.15,
+            chargeback: 0.05,
+        }
+    }
+}
+
+impl FromStr for TypeMix {
+    type Err = String;
+
+    /// Parses a spec like `deposit=0.5,withdrawal=0.2,dispute=0.1,resolve=0.15,chargeback=0.05`,
+    /// types that are not listed keep their default weight
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut mix = TypeMix::default();
+        for setting in spec.split(',').filter(|setting| !setting.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or(format!("expected type=weight, got {setting}"))?;
+            let weight: f64 = value
+                .parse()
+                .ok()
+                .filter(|weight: &f64| *weight >= 0.0)
+                .ok_or(format!("invalid weight for {key}: {value}"))?;
+            match key {
+                "deposit" => mix.deposit = weight,
+                "withdrawal" => mix.withdrawal = weight,
+                "dispute" => mix.dispute = weight,
+                "resolve" => mix.resolve = weight,
+                "chargeback" => mix.chargeback = weight,
+                _ => return Err(format!("unknown transaction type: {key}")),
+            }
+        }
+        Ok(mix)
+    }
+}
+
+/// Settings of a load simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationConfig {
+    pub transactions: u64,
+    pub clients: u16,
+    pub zipf_exponent: f64, // client skew: 0 is uniform, ~1 is a few very hot clients
+    pub mix: TypeMix,
+    pub rate: Option<u64>, // target transactions per second, as fast as possible if None
+    pub seed: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            transactions: 1_000_000,
+            clients: u16::MAX,
+            zipf_exponent: 1.0,
+            mix: TypeMix::default(),
+            rate: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Draws client ids following a Zipf distribution (rank k has weight 1/k^s)
+#[derive(Debug)]
+struct ZipfClients {
+    cumulative: Vec<f64>, // normalized cumulative weights, one per client
+}
+
+impl ZipfClients {
+    fn new(clients: u16, exponent: f64) -> ZipfClients {
+        let mut total = 0.0;
+        let mut cumulative: Vec<f64> = (1..=clients.max(1))
+            .map(|rank| {
+                total += 1.0 / (rank as f64).powf(exponent);
+                total
+            })
+            .collect();
+        cumulative.iter_mut().for_each(|weight| *weight /= total);
+        ZipfClients { cumulative }
+    }
+
+    fn sample(&self, rng: &mut XorShiftRng) -> ClientId {
+        let target = rng.next_f64();
+        let rank = self.cumulative.partition_point(|weight| *weight < target);
+        ClientId(rank.min(self.cumulative.len() - 1) as u16 + 1)
+    }
+}
+
+/// Infinite stream of valid looking transactions: disputes, resolves and chargebacks reference
+/// earlier deposits of the same client so that they exercise the dispute paths
+#[derive(Debug)]
+pub struct TransactionGenerator {
+    rng: XorShiftRng,
+    clients: ZipfClients,
+    mix: TypeMix,
+    next_tx: u32,
+    deposits: Vec<(ClientId, TransactionId)>, // deposits that can be disputed
+    disputed: Vec<(ClientId, TransactionId)>, // disputes that can be resolved or charged back
+}
+
+impl TransactionGenerator {
+    pub fn new(config: &SimulationConfig) -> TransactionGenerator {
+        TransactionGenerator {
+            rng: XorShiftRng::new(config.seed),
+            clients: ZipfClients::new(config.clients, config.zipf_exponent),
+            mix: config.mix.clone(),
+            next_tx: 1,
+            deposits: Vec::new(),
+            disputed: Vec::new(),
+        }
+    }
+
+    fn amount(&mut self, max: u64) -> Decimal {
+        // between 0.0001 and max with 4 decimal places
+        Decimal::new((self.rng.next_u64() % (max * 10_000)) as i64 + 1, 4)
+    }
+
+    fn take_random(
+        rng: &mut XorShiftRng,
+        pool: &mut Vec<(ClientId, TransactionId)>,
+    ) -> Option<(ClientId, TransactionId)> {
+        if pool.is_empty() {
+            return None;
+        }
+        let index = (rng.next_u64() % pool.len() as u64) as usize;
+        Some(pool.swap_remove(index))
+    }
+}
+
+impl Iterator for TransactionGenerator {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let TypeMix {
+            deposit,
+            withdrawal,
+            dispute,
+            resolve,
+            chargeback,
+        } = self.mix;
+        let total = deposit + withdrawal + dispute + resolve + chargeback;
+        let roll = self.rng.next_f64() * total;
+        if roll >= deposit + withdrawal {
+            // dispute family, falls back to a deposit while there is nothing to reference yet
+            if roll < deposit + withdrawal + dispute {
+                if let Some((client, tx)) = Self::take_random(&mut self.rng, &mut self.deposits) {
+                    self.disputed.push((client, tx));
+                    return Some(Transaction::Dispute { client, tx });
+                }
+            } else if let Some((client, tx)) = Self::take_random(&mut self.rng, &mut self.disputed)
+            {
+                return Some(if roll < deposit + withdrawal + dispute + resolve {
+                    self.deposits.push((client, tx)); // a resolved deposit can be disputed again
+                    Transaction::Resolve { client, tx }
+                } else {
+                    Transaction::Chargeback { client, tx }
+                });
+            }
+        }
+
+        let client = self.clients.sample(&mut self.rng);
+        let tx = TransactionId(self.next_tx);
+        self.next_tx = self.next_tx.wrapping_add(1);
+        Some(
+            if deposit + withdrawal > 0.0 && roll >= deposit && roll < deposit + withdrawal {
+                Transaction::Withdrawal {
+                    client,
+                    tx,
+                    amount: self.amount(500),
+                }
+            } else {
+                self.deposits.push((client, tx));
+                Transaction::Deposit {
+                    client,
+                    tx,
+                    amount: self.amount(1_000),
+                }
+            },
+        )
+    }
+}
+
+/// Log-linear latency histogram: 16 sub-buckets per power of two (about 6% precision)
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max: Duration,
+}
+
+const SUB_BUCKETS: u64 = 16;
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            buckets: vec![0; 64 * SUB_BUCKETS as usize],
+            count: 0,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn bucket(nanos: u64) -> usize {
+        if nanos < SUB_BUCKETS {
+            return nanos as usize;
+        }
+        let power = 63 - nanos.leading_zeros() as u64; // >= 4
+        let sub_bucket = (nanos >> (power - 4)) - SUB_BUCKETS;
+        ((power - 3) * SUB_BUCKETS + sub_bucket) as usize
+    }
+
+    // lowest value of a bucket
+    fn bucket_value(bucket: usize) -> u64 {
+        let bucket = bucket as u64;
+        if bucket < SUB_BUCKETS {
+            return bucket;
+        }
+        let power = bucket / SUB_BUCKETS + 3;
+        (SUB_BUCKETS + bucket % SUB_BUCKETS) << (power - 4)
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[Self::bucket(nanos)] += 1;
+        self.count += 1;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Approximate latency below which `percentile` (0-100) of the samples fall
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let target = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        if target >= self.count {
+            return self.max; // exact, buckets only know their lowest value
+        }
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Duration::from_nanos(Self::bucket_value(bucket)).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// Result of a simulation run
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    pub transactions: u64,
+    pub elapsed: Duration,
+    pub latencies: LatencyHistogram, // time to apply each transaction
+}
+
+impl SimulationReport {
+    pub fn throughput(&self) -> f64 {
+        self.transactions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl Display for SimulationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "transactions: {}", self.transactions)?;
+        writeln!(f, "elapsed: {:?}", self.elapsed)?;
+        writeln!(f, "throughput: {:.0} tx/s", self.throughput())?;
+        for percentile in [50.0, 90.0, 99.0, 99.9] {
+            writeln!(
+                f,
+                "latency p{percentile}: {:?}",
+                self.latencies.percentile(percentile)
+            )?;
+        }
+        writeln!(f, "latency max: {:?}", self.latencies.max())
+    }
+}
+
+/// Generates the synthetic stream directly into `clients`, pacing it at the target rate if any
+#[instrument(skip(clients))]
+pub fn run_simulation(config: &SimulationConfig, clients: &mut Clients) -> SimulationReport {
+    let mut latencies = LatencyHistogram::default();
+    let rate = config.rate.filter(|rate| *rate > 0);
+    let start = Instant::now();
+    for (index, transaction) in TransactionGenerator::new(config)
+        .take(config.transactions as usize)
+        .enumerate()
+    {
+        if let Some(rate) = rate {
+            let scheduled = Duration::from_secs_f64(index as f64 / rate as f64);
+            if let Some(wait) = scheduled.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        let applied_at = Instant::now();
+        clients.apply_transaction(transaction);
+        latencies.record(applied_at.elapsed());
+    }
+    let report = SimulationReport {
+        transactions: latencies.count(),
+        elapsed: start.elapsed(),
+        latencies,
+    };
+    info!(throughput = report.throughput(), "Simulation finished");
+    report
+}
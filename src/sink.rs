@@ -0,0 +1,155 @@
+use std::{
+    fmt::Debug,
+    mem,
+    num::NonZeroUsize,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Sender, SyncSender},
+    },
+};
+
+use tracing::error;
+
+use crate::{
+    channel,
+    model::{Account, ClientId},
+};
+
+/// Destination of the accounts of `Clients`: an account is emitted as soon as it is locked (its final
+/// state), the others when the clients are sent to the output.
+pub trait AccountSink: Send {
+    fn emit(&mut self, client: ClientId, account: &Account);
+}
+
+impl Debug for dyn AccountSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AccountSink")
+    }
+}
+
+/// Channel to a writer thread, see `spawn_writer_thread`.
+/// If the writer thread stopped the account is dropped, the failure surfaces when joining it.
+impl AccountSink for Sender<(ClientId, Account)> {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        if self.send((client, account.clone())).is_err() {
+            error!(%client, "the output writer stopped, dropping the account");
+        }
+    }
+}
+
+/// Crossbeam channel to a writer thread (feature `crossbeam`), bounded or not
+#[cfg(feature = "crossbeam")]
+impl AccountSink for crossbeam_channel::Sender<(ClientId, Account)> {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        if self.send((client, account.clone())).is_err() {
+            error!(%client, "the output writer stopped, dropping the account");
+        }
+    }
+}
+
+/// Bounded channel to a writer thread, the emitter waits while the writer is behind
+impl AccountSink for SyncSender<(ClientId, Account)> {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        if self.send((client, account.clone())).is_err() {
+            error!(%client, "the output writer stopped, dropping the account");
+        }
+    }
+}
+
+/// Collects the accounts in memory, in the order they were emitted
+impl AccountSink for Vec<(ClientId, Account)> {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        self.push((client, account.clone()));
+    }
+}
+
+impl<S: AccountSink + ?Sized> AccountSink for Box<S> {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        (**self).emit(client, account);
+    }
+}
+
+/// Shared sinks let the caller keep a handle to read what was emitted after processing
+impl<S: AccountSink> AccountSink for Arc<Mutex<S>> {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        self.lock()
+            .expect("account sink mutex poisoned")
+            .emit(client, account);
+    }
+}
+
+/// Channel to a writer thread sending the accounts `batch_size` at a time, so that a writer
+/// receiving many accounts (e.g. many locking at once) is woken up once per batch instead of once
+/// per account. The last batch is sent by `flush`, or when the sender is dropped (e.g. at the end of
+/// `Clients::send_to_output`). An account locked early is only written once its batch is sent.
+#[derive(Debug)]
+pub struct BatchingSender {
+    sender: channel::Sender<Vec<(ClientId, Account)>>,
+    batch: Vec<(ClientId, Account)>,
+    batch_size: usize,
+}
+
+impl BatchingSender {
+    pub fn new(
+        sender: channel::Sender<Vec<(ClientId, Account)>>,
+        batch_size: NonZeroUsize,
+    ) -> Self {
+        BatchingSender {
+            sender,
+            batch: Vec::with_capacity(batch_size.get()),
+            batch_size: batch_size.get(),
+        }
+    }
+
+    /// Sends the accounts of the pending batch, if any
+    pub fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let batch = mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        if let Err(channel::SendError(batch)) = self.sender.send(batch) {
+            error!(
+                accounts = batch.len(),
+                "the output writer stopped, dropping the accounts"
+            );
+        }
+    }
+}
+
+impl AccountSink for BatchingSender {
+    fn emit(&mut self, client: ClientId, account: &Account) {
+        self.batch.push((client, account.clone()));
+        if self.batch.len() >= self.batch_size {
+            self.flush();
+        }
+    }
+}
+
+impl Drop for BatchingSender {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Accounts received by a writer thread, from an `account_channel`
+pub type AccountReceiver = Box<dyn Iterator<Item = (ClientId, Account)> + Send>;
+
+/// Channel to a writer thread (`spawn_writer_thread_with_policy`...): the sink sends the accounts
+/// one at a time, or `batch_size` at a time through a `BatchingSender`
+pub fn account_channel(
+    batch_size: Option<NonZeroUsize>,
+) -> (Box<dyn AccountSink>, AccountReceiver) {
+    match batch_size {
+        Some(batch_size) => {
+            let (tx, rx) = channel::unbounded();
+            (
+                Box::new(BatchingSender::new(tx, batch_size)),
+                Box::new(rx.into_iter().flatten()),
+            )
+        }
+        None => {
+            let (tx, rx) = channel::unbounded();
+            (Box::new(tx), Box::new(rx.into_iter()))
+        }
+    }
+}
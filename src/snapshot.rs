@@ -0,0 +1,315 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    ops::Bound,
+    sync::{Arc, RwLock},
+};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::{error, instrument, trace};
+
+use crate::{
+    model::{Account, ClientId, Transaction, TransactionId},
+    observer::TransactionObserver,
+};
+
+/// Immutable view of every account at a point of the stream, sorted by client for stable paging
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AccountSnapshot {
+    pub epoch: u64,        // incremented on every publication
+    pub transactions: u64, // transactions applied when the snapshot was taken
+    pub accounts: BTreeMap<ClientId, Account>,
+}
+
+impl AccountSnapshot {
+    /// Up to `limit` accounts following the `after` client (from the start when `None`).
+    /// Paging over the same snapshot never skips nor repeats a client.
+    pub fn page(&self, after: Option<ClientId>, limit: usize) -> Vec<(ClientId, &Account)> {
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        self.accounts
+            .range((start, Bound::Unbounded))
+            .take(limit)
+            .map(|(client, account)| (*client, account))
+            .collect()
+    }
+}
+
+/// Cheap handle for readers (e.g. a query API thread): the latest snapshot is shared, never copied,
+/// so a slow paginated listing does not hold any lock while ingestion continues.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotReader {
+    latest: Arc<RwLock<Arc<AccountSnapshot>>>,
+}
+
+impl SnapshotReader {
+    pub fn latest(&self) -> Arc<AccountSnapshot> {
+        self.latest.read().expect("snapshot lock poisoned").clone()
+    }
+}
+
+/// Observer keeping a copy of the accounts and publishing it as a new `AccountSnapshot` every
+/// `every` transactions (copy-on-write epochs). The lock is only held to swap the pointer.
+#[derive(Debug)]
+pub struct SnapshotPublisher {
+    every: u64,
+    transactions: u64,
+    epoch: u64,
+    accounts: BTreeMap<ClientId, Account>,
+    reader: SnapshotReader,
+}
+
+impl SnapshotPublisher {
+    pub fn new(every: u64) -> SnapshotPublisher {
+        SnapshotPublisher {
+            every: every.max(1),
+            transactions: 0,
+            epoch: 0,
+            accounts: BTreeMap::new(),
+            reader: SnapshotReader::default(),
+        }
+    }
+
+    /// Handle to give to the readers, can be cloned across threads
+    pub fn reader(&self) -> SnapshotReader {
+        self.reader.clone()
+    }
+
+    /// Publishes the current state, e.g. once the input is exhausted
+    pub fn publish(&mut self) {
+        self.epoch += 1;
+        let snapshot = Arc::new(AccountSnapshot {
+            epoch: self.epoch,
+            transactions: self.transactions,
+            accounts: self.accounts.clone(),
+        });
+        trace!(epoch = self.epoch, "Publishing account snapshot");
+        *self.reader.latest.write().expect("snapshot lock poisoned") = snapshot;
+    }
+}
+
+impl TransactionObserver for SnapshotPublisher {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        _transaction: &Transaction,
+        _before: &Account,
+        after: &Account,
+    ) {
+        self.accounts.insert(client, after.clone());
+        self.transactions += 1;
+        if self.transactions.is_multiple_of(self.every) {
+            self.publish();
+        }
+    }
+
+    /// An erased client must disappear from what readers can see right away
+    fn on_erasure(&mut self, client: ClientId) {
+        if self.accounts.remove(&client).is_some() {
+            self.publish();
+        }
+    }
+
+    fn on_merge(&mut self, from: ClientId, into: ClientId, account: &Account) {
+        self.accounts.remove(&from);
+        self.accounts.insert(into, account.clone());
+        self.publish();
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        self.accounts.insert(client, account.clone());
+    }
+}
+
+/// Transactions between two emissions of the delta stream unless configured
+pub const DEFAULT_DELTA_EVERY: u64 = 1000;
+
+/// What happened to an account since the previous emission of the delta stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Created,
+    Updated,
+    Erased, // the balances are empty
+}
+
+/// One row of the delta stream
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct CsvAccountDelta {
+    pub epoch: u64,
+    pub change: ChangeType,
+    pub client: ClientId,
+    pub available: Option<Decimal>,
+    pub held: Option<Decimal>,
+    pub total: Option<Decimal>,
+    pub locked: Option<bool>,
+}
+
+/// Observer writing, every `every` transactions, only the accounts whose state changed since the
+/// previous emission, for downstream caches that apply deltas instead of reloading full snapshots.
+/// An account that changed and came back to its emitted state within an emission is not written.
+#[derive(Debug)]
+pub struct DeltaEmitter<W: io::Write> {
+    every: u64,
+    transactions: u64,
+    epoch: u64,
+    emitted: HashMap<ClientId, Account>, // state of each account as last written
+    changed: BTreeMap<ClientId, Option<Account>>, // None once erased
+    wtr: csv::Writer<W>,
+}
+
+impl<W: io::Write> DeltaEmitter<W> {
+    pub fn new(every: u64, wtr: W) -> DeltaEmitter<W> {
+        DeltaEmitter {
+            every: every.max(1),
+            transactions: 0,
+            epoch: 0,
+            emitted: HashMap::new(),
+            changed: BTreeMap::new(),
+            wtr: csv::Writer::from_writer(wtr),
+        }
+    }
+
+    /// Writes the changes since the previous emission (nothing if there are none), e.g. once the
+    /// input is exhausted
+    #[instrument(level = "trace", skip(self))]
+    pub fn emit(&mut self) -> Result<(), csv::Error> {
+        let mut deltas = Vec::new();
+        for (client, account) in std::mem::take(&mut self.changed) {
+            let (change, account) = match (self.emitted.get(&client), account) {
+                (Some(emitted), Some(account)) if *emitted == account => continue,
+                (Some(_), Some(account)) => (ChangeType::Updated, Some(account)),
+                (None, Some(account)) => (ChangeType::Created, Some(account)),
+                (Some(_), None) => (ChangeType::Erased, None),
+                (None, None) => continue, // created and erased before it was ever written
+            };
+            deltas.push(CsvAccountDelta {
+                epoch: self.epoch + 1,
+                change,
+                client,
+                available: account.as_ref().map(Account::available),
+                held: account.as_ref().map(Account::held),
+                total: account.as_ref().map(Account::total),
+                locked: account.as_ref().map(Account::locked),
+            });
+            match account {
+                Some(account) => self.emitted.insert(client, account),
+                None => self.emitted.remove(&client),
+            };
+        }
+        if deltas.is_empty() {
+            return Ok(());
+        }
+        self.epoch += 1;
+        trace!(
+            epoch = self.epoch,
+            deltas = deltas.len(),
+            "Emitting account deltas"
+        );
+        for delta in deltas {
+            self.wtr.serialize(delta)?;
+        }
+        self.wtr.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: io::Write + Send> TransactionObserver for DeltaEmitter<W> {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        _transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        // a new client is created even when its first transaction is ignored, as in the output
+        let new = !self.emitted.contains_key(&client) && !self.changed.contains_key(&client);
+        if before != after || new {
+            self.changed.insert(client, Some(after.clone()));
+        }
+        self.transactions += 1;
+        if self.transactions.is_multiple_of(self.every)
+            && let Err(err) = self.emit()
+        {
+            error!(%err, "failed to write account deltas");
+        }
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.changed.insert(client, None);
+    }
+
+    fn on_merge(&mut self, from: ClientId, into: ClientId, account: &Account) {
+        self.changed.insert(from, None);
+        self.changed.insert(into, Some(account.clone()));
+    }
+
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        self.changed.insert(client, Some(account.clone()));
+    }
+}
+
+/// One line of the account update feed
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct AccountUpdate {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+    pub tx: TransactionId, // transaction that changed the account
+}
+
+/// Observer writing the account state after every transaction that changed it, as NDJSON, so a
+/// dashboard can follow the balances while the batch runs. Unlike the delta stream nothing is
+/// batched: give it a `LineWriter` to make each update visible as soon as it is written.
+#[derive(Debug)]
+pub struct AccountUpdateFeed<W: io::Write> {
+    wtr: W,
+    written: u64,
+}
+
+impl<W: io::Write> AccountUpdateFeed<W> {
+    pub fn new(wtr: W) -> AccountUpdateFeed<W> {
+        AccountUpdateFeed { wtr, written: 0 }
+    }
+
+    /// Number of updates written so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    fn write(&mut self, update: &AccountUpdate) -> io::Result<()> {
+        serde_json::to_writer(&mut self.wtr, update)?;
+        self.wtr.write_all(b"\n")
+    }
+}
+
+impl<W: io::Write + Send> TransactionObserver for AccountUpdateFeed<W> {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        if before == after {
+            return; // ignored transaction
+        }
+        let update = AccountUpdate {
+            client,
+            available: after.available(),
+            held: after.held(),
+            total: after.total(),
+            locked: after.locked(),
+            tx: transaction.tx_id(),
+        };
+        match self.write(&update) {
+            Ok(()) => self.written += 1,
+            Err(err) => error!(%err, "failed to write the account update"),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use rust_decimal::Decimal;
+use tracing::{info, instrument};
+
+use crate::{
+    model::{Account, ClientId, OPENING_TX, Transaction, TransactionId},
+    observer::TransactionObserver,
+};
+
+/// One line of a client statement: a transaction that changed the client's total funds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementEntry {
+    pub tx: TransactionId,
+    pub transaction_type: &'static str,
+    pub amount: Decimal, // signed change of the total funds
+}
+
+/// Retains, per client, the transactions that moved money in or out of the account.
+/// Disputes and resolves only move funds between available and held so they are not statement lines.
+#[derive(Debug, Default)]
+pub struct StatementRecorder {
+    pub history: HashMap<ClientId, Vec<StatementEntry>>,
+}
+
+impl TransactionObserver for StatementRecorder {
+    fn on_transaction(
+        &mut self,
+        client: ClientId,
+        transaction: &Transaction,
+        before: &Account,
+        after: &Account,
+    ) {
+        let amount = after.total() - before.total();
+        if amount.is_zero() {
+            return;
+        }
+        self.history
+            .entry(client)
+            .or_default()
+            .push(StatementEntry {
+                tx: transaction.tx_id(),
+                transaction_type: transaction.type_name(),
+                amount: amount.normalize(),
+            });
+    }
+
+    fn on_erasure(&mut self, client: ClientId) {
+        self.history.remove(&client);
+    }
+
+    /// The opening balance is the first line of the statement
+    fn on_opening(&mut self, client: ClientId, account: &Account) {
+        if account.total().is_zero() {
+            return;
+        }
+        self.history
+            .entry(client)
+            .or_default()
+            .push(StatementEntry {
+                tx: OPENING_TX,
+                transaction_type: "opening",
+                amount: account.total().normalize(),
+            });
+    }
+}
+
+impl StatementRecorder {
+    /// Writes the statement of a client in QIF (Quicken Interchange Format, bank account type).
+    /// Transactions carry no timestamp so every line is dated with `date` (MM/DD/YYYY).
+    pub fn write_qif<W: Write>(&self, client: ClientId, date: &str, mut wtr: W) -> io::Result<()> {
+        writeln!(wtr, "!Type:Bank")?;
+        for entry in self.history.get(&client).into_iter().flatten() {
+            writeln!(wtr, "D{date}")?;
+            writeln!(wtr, "T{}", entry.amount)?;
+            writeln!(wtr, "N{}", entry.tx)?;
+            writeln!(wtr, "P{}", entry.transaction_type)?;
+            writeln!(wtr, "^")?;
+        }
+        wtr.flush()
+    }
+
+    /// Writes one `client_<id>.qif` statement per client into `dir`
+    #[instrument(skip(self))]
+    pub fn write_qif_statements(&self, dir: &Path, date: &str) -> io::Result<()> {
+        for client in self.history.keys() {
+            let file = File::create(dir.join(format!("client_{client}.qif")))?;
+            self.write_qif(*client, date, BufWriter::new(file))?;
+        }
+        info!(clients = self.history.len(), "Wrote client statements");
+        Ok(())
+    }
+}
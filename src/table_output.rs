@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+
+use crate::{
+    model::{Account, ClientId},
+    output::WriterSummary,
+};
+
+const HEADER: [&str; 5] = ["client", "available", "held", "total", "locked"];
+const NUMERIC_COLUMNS: usize = 4; // client and the amounts are right aligned
+const SEPARATOR: &str = "  ";
+
+/// Aligned console table of the output accounts, for humans (e.g. during incident response).
+/// Column widths depend on every row, so the accounts are buffered and the table is rendered by
+/// `finish`. Custom columns follow the standard ones, empty when an account has no such field.
+pub struct TableAccountWriter<W: Write> {
+    wtr: W,
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl<W: Write> TableAccountWriter<W> {
+    pub fn new(wtr: W, columns: &[String]) -> TableAccountWriter<W> {
+        TableAccountWriter {
+            wtr,
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn summary(&self) -> WriterSummary {
+        WriterSummary {
+            written: self.rows.len() as u64,
+            failed: 0,
+        }
+    }
+
+    pub fn write(&mut self, client: ClientId, account: &Account) {
+        let mut row = vec![
+            client.to_string(),
+            account.available().to_string(),
+            account.held().to_string(),
+            account.total().to_string(),
+            account.locked().to_string(),
+        ];
+        row.extend(
+            (self.columns.iter())
+                .map(|column| account.field(column).unwrap_or_default().to_string()),
+        );
+        self.rows.push(row);
+    }
+
+    /// Renders the table, with its header even when there is no account
+    pub fn finish(mut self) -> io::Result<(W, WriterSummary)> {
+        let header: Vec<String> = (HEADER.iter().map(ToString::to_string))
+            .chain(self.columns.iter().cloned())
+            .collect();
+        let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        for row in [&header, &rule].into_iter().chain(&self.rows) {
+            let mut line = String::new();
+            for (index, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                if index > 0 {
+                    line.push_str(SEPARATOR);
+                }
+                if index < NUMERIC_COLUMNS {
+                    line.push_str(&format!("{cell:>width$}"));
+                } else {
+                    line.push_str(&format!("{cell:<width$}"));
+                }
+            }
+            writeln!(self.wtr, "{}", line.trim_end())?;
+        }
+        self.wtr.flush()?;
+        let summary = self.summary();
+        Ok((self.wtr, summary))
+    }
+}
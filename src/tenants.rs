@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+
+use csv::Reader;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{Level, error, info, instrument, span};
+
+use crate::{
+    channel,
+    csv_input::ConversionError,
+    model::{
+        ClientId, Clients, InputCsvRecord, OutputMode, Transaction, TransactionId, TransactionKind,
+    },
+    spawn_writer_thread,
+};
+
+/// Identifies an isolated ledger hosted by the process
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Serialize)]
+pub struct TenantId(pub String);
+
+impl Display for TenantId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Per tenant settings
+#[derive(Debug, Clone)]
+pub struct TenantConfig {
+    pub output_path: PathBuf, // file where the tenant accounts are written
+}
+
+/// A tenant ledger: its own Clients and its own writer thread
+#[derive(Debug)]
+pub struct TenantLedger {
+    pub clients: Clients,
+    writer_thread: JoinHandle<File>,
+}
+
+/// Tenants hosts several independent `Clients` in the same process.
+/// Transactions are routed by tenant, accounts and disputable transactions are never shared between tenants.
+#[derive(Debug)]
+pub struct Tenants {
+    output_dir: PathBuf, // tenants without an explicit config write to <output_dir>/<tenant>.csv
+    configs: HashMap<TenantId, TenantConfig>,
+    pub ledgers: HashMap<TenantId, TenantLedger>,
+    pub sorted_output: bool, // see `Clients::sorted_output`
+}
+
+impl Tenants {
+    pub fn new(output_dir: &Path) -> Tenants {
+        Tenants {
+            output_dir: output_dir.to_path_buf(),
+            configs: HashMap::new(),
+            ledgers: HashMap::new(),
+            sorted_output: false,
+        }
+    }
+
+    /// Register an explicit config for a tenant (must be done before the tenant receives transactions)
+    pub fn configure(&mut self, tenant: TenantId, config: TenantConfig) {
+        self.configs.insert(tenant, config);
+    }
+
+    fn output_path(&self, tenant: &TenantId) -> PathBuf {
+        match self.configs.get(tenant) {
+            Some(config) => config.output_path.clone(),
+            None => self.output_dir.join(format!("{tenant}.csv")),
+        }
+    }
+
+    /// Get the ledger of a tenant, creating it (and its output file) on first use
+    pub fn ledger(&mut self, tenant: &TenantId) -> io::Result<&mut TenantLedger> {
+        if !self.ledgers.contains_key(tenant) {
+            let output_path = self.output_path(tenant);
+            info!(%tenant, ?output_path, "Creating tenant ledger");
+            let file = File::create(&output_path)?;
+            let (tx, rx) = channel::unbounded();
+            let writer_thread = spawn_writer_thread(file, rx);
+            let mut clients = Clients::new(tx);
+            clients.sorted_output = self.sorted_output;
+            self.ledgers.insert(
+                tenant.clone(),
+                TenantLedger {
+                    clients,
+                    writer_thread,
+                },
+            );
+        }
+        Ok(self
+            .ledgers
+            .get_mut(tenant)
+            .expect("ledger was inserted above"))
+    }
+
+    /// Route each transaction to the ledger of its tenant
+    #[instrument(skip(self, transactions))]
+    pub fn load_transactions<
+        T: Iterator<Item = Result<(TenantId, Transaction), ConversionError>>,
+    >(
+        &mut self,
+        transactions: T,
+    ) -> io::Result<()> {
+        for transaction in transactions {
+            match transaction {
+                Err(err) => error!(error=%err, "Skipping invalid transaction in file"),
+                Ok((tenant, transaction)) => {
+                    let span = span!(Level::TRACE, "routing transaction", %tenant);
+                    let _enter = span.enter();
+                    let ledger = self.ledger(&tenant)?;
+                    ledger.clients.apply_transaction(transaction);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the remaining accounts of every tenant and wait for all the writers to finish
+    pub fn finish(self) -> io::Result<()> {
+        for (tenant, ledger) in self.ledgers {
+            ledger.clients.send_to_output(OutputMode::SkipLocked);
+            ledger.writer_thread.join().map_err(|_| {
+                io::Error::other(format!("tenant {tenant}: writer thread panicked"))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Type used to deserialize input csv lines that carry a tenant column
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TenantInputCsvRecord {
+    pub tenant: TenantId,
+    #[serde(rename = "type")]
+    pub transaction_type: TransactionKind,
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub amount: Option<Decimal>,
+}
+
+// Transforms a reader over a file with a tenant column into a iterator over (tenant, transaction)
+#[instrument(skip(csv_reader))]
+pub fn tenant_transactions_from_reader<T: std::io::Read>(
+    csv_reader: Reader<T>,
+) -> impl Iterator<Item = Result<(TenantId, Transaction), ConversionError>> {
+    csv_reader
+        .into_deserialize()
+        .map(|record: Result<TenantInputCsvRecord, csv::Error>| {
+            let TenantInputCsvRecord {
+                tenant,
+                transaction_type,
+                client,
+                tx,
+                amount,
+            } = record.map_err(ConversionError::from)?;
+            let transaction = Transaction::try_from(InputCsvRecord {
+                transaction_type,
+                client,
+                tx,
+                amount,
+            })?;
+            Ok((tenant, transaction))
+        })
+}
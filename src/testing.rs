@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    csv_input::transactions_from_reader,
+    model::{Account, ClientId, Clients, ProcessingStats},
+};
+
+/// Engine wired for tests, e.g. contract tests of a downstream service: `Clients` emitting to an
+/// in-memory sink, fed with csv text, and its accounts read back in client order.
+#[derive(Debug)]
+pub struct TestEngine {
+    pub clients: Clients, // to configure (observers, sparse accounts, rounding...) before applying
+    emitted: Arc<Mutex<Vec<(ClientId, Account)>>>,
+}
+
+impl Default for TestEngine {
+    fn default() -> TestEngine {
+        TestEngine::new()
+    }
+}
+
+impl TestEngine {
+    pub fn new() -> TestEngine {
+        let emitted = Arc::new(Mutex::new(Vec::new()));
+        TestEngine {
+            clients: Clients::new(Arc::clone(&emitted)),
+            emitted,
+        }
+    }
+
+    /// Applies csv text with a header row, the whitespace around the fields is trimmed so that the
+    /// rows can be indented. Can be called again to apply more transactions to the same accounts.
+    pub fn apply_csv(&mut self, csv: &str) -> ProcessingStats {
+        let csv_reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(csv.as_bytes());
+        self.clients
+            .load_transactions(transactions_from_reader(csv_reader))
+    }
+
+    /// The accounts by client id, locked ones included, rounded like the output
+    pub fn accounts(&self) -> Vec<(ClientId, Account)> {
+        let mut accounts: Vec<(ClientId, Account)> = self
+            .clients
+            .accounts
+            .iter()
+            .map(|(client, account)| (*client, self.clients.rounding.apply(account).into_owned()))
+            .collect();
+        accounts.sort_unstable_by_key(|(client, _)| client.0);
+        accounts
+    }
+
+    /// The account of a client, if it has one
+    pub fn account(&self, client: u16) -> Option<&Account> {
+        self.clients.accounts.get(&ClientId(client))
+    }
+
+    /// What the sink received so far, in order: the accounts emitted as soon as they were locked
+    pub fn emitted(&self) -> Vec<(ClientId, Account)> {
+        self.emitted
+            .lock()
+            .expect("account sink mutex poisoned")
+            .clone()
+    }
+}
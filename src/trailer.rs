@@ -0,0 +1,221 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{info, instrument};
+
+use crate::{
+    output::{OutputFormat, OutputOptions},
+    run_metadata::file_sha256,
+};
+
+const STANDARD_COLUMNS: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+#[derive(Error, Debug)]
+pub enum TrailerError {
+    #[error("failed to read: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid trailer: {0}")]
+    Trailer(#[from] serde_json::Error),
+    #[error("unknown output format: {0}")]
+    UnknownFormat(String),
+    #[error("checksum mismatch: the trailer has {expected}, the file {actual}")]
+    Checksum { expected: String, actual: String },
+    #[error("row count mismatch: the trailer has {expected}, the file {actual}")]
+    RowCount { expected: u64, actual: u64 },
+    #[error("schema mismatch: {0}")]
+    Schema(String),
+}
+
+/// Handoff contract of an account output file, written next to it as `<output>.trailer.json` once
+/// the output was committed: consumers check the file against it (`verify_output`) before loading.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OutputTrailer {
+    pub format: String,
+    pub delimiter: char, // csv dialect, to read the file back
+    pub quote: char,
+    pub columns: Vec<String>, // standard columns then the custom ones
+    pub rows: u64,            // accounts, placeholders included
+    pub sha256: String,
+}
+
+impl OutputTrailer {
+    /// Path of the trailer of an output file
+    pub fn path(artifact: &Path) -> PathBuf {
+        let mut file_name = artifact.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".trailer.json");
+        artifact.with_file_name(file_name)
+    }
+
+    /// Describes a written output file: its rows are counted, and their schema checked, by reading
+    /// it back
+    #[instrument(skip(options))]
+    pub fn create(artifact: &Path, options: &OutputOptions) -> Result<OutputTrailer, TrailerError> {
+        let mut trailer = OutputTrailer {
+            format: options.format.to_string(),
+            delimiter: options.dialect.delimiter as char,
+            quote: options.dialect.quote as char,
+            columns: (STANDARD_COLUMNS.iter().map(ToString::to_string))
+                .chain(options.columns.iter().cloned())
+                .collect(),
+            rows: 0,
+            sha256: file_sha256(artifact)?,
+        };
+        trailer.rows = trailer.inspect(artifact)?;
+        Ok(trailer)
+    }
+
+    pub fn read(artifact: &Path) -> Result<OutputTrailer, TrailerError> {
+        Ok(serde_json::from_slice(&fs::read(Self::path(artifact))?)?)
+    }
+
+    pub fn write(&self, artifact: &Path) -> io::Result<()> {
+        let mut wtr = BufWriter::new(File::create(Self::path(artifact))?);
+        serde_json::to_writer_pretty(&mut wtr, self)?;
+        writeln!(wtr)?;
+        wtr.flush()
+    }
+
+    // number of rows of the file, failing on the first one that does not match the columns
+    fn inspect(&self, artifact: &Path) -> Result<u64, TrailerError> {
+        let format = OutputFormat::from_str(&self.format).map_err(TrailerError::UnknownFormat)?;
+        match format {
+            OutputFormat::Csv => self.inspect_csv(artifact),
+            OutputFormat::Json => {
+                let accounts: Vec<Value> =
+                    serde_json::from_reader(BufReader::new(File::open(artifact)?))
+                        .map_err(|err| TrailerError::Schema(err.to_string()))?;
+                for account in &accounts {
+                    self.check_object(account)?;
+                }
+                Ok(accounts.len() as u64)
+            }
+            OutputFormat::NdJson => {
+                let mut rows = 0;
+                for line in BufReader::new(File::open(artifact)?).lines() {
+                    let line = line?;
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let account: Value = serde_json::from_str(&line)
+                        .map_err(|err| TrailerError::Schema(format!("row {}: {err}", rows + 1)))?;
+                    self.check_object(&account)?;
+                    rows += 1;
+                }
+                Ok(rows)
+            }
+            OutputFormat::Table => {
+                let content = fs::read_to_string(artifact)?;
+                let mut lines = content.lines();
+                let header: Vec<&str> = lines
+                    .next()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .collect();
+                if header != self.columns {
+                    return Err(TrailerError::Schema(format!("header {header:?}")));
+                }
+                Ok(lines.skip(1).count() as u64) // the rule under the header
+            }
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => {
+                use parquet::file::reader::{FileReader, SerializedFileReader};
+                let reader = SerializedFileReader::new(File::open(artifact)?)
+                    .map_err(|err| TrailerError::Schema(err.to_string()))?;
+                let metadata = reader.metadata().file_metadata();
+                let columns: Vec<&str> = (metadata.schema_descr().columns().iter())
+                    .map(|column| column.name())
+                    .collect();
+                if columns != self.columns {
+                    return Err(TrailerError::Schema(format!("columns {columns:?}")));
+                }
+                Ok(metadata.num_rows() as u64)
+            }
+        }
+    }
+
+    fn inspect_csv(&self, artifact: &Path) -> Result<u64, TrailerError> {
+        let schema = |err: csv::Error| TrailerError::Schema(err.to_string());
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .quote(self.quote as u8)
+            .from_path(artifact)
+            .map_err(schema)?;
+        let header = csv_reader.headers().map_err(schema)?;
+        if header.iter().ne(&self.columns) {
+            return Err(TrailerError::Schema(format!("header {header:?}")));
+        }
+        let mut rows = 0;
+        for record in csv_reader.records() {
+            let record = record.map_err(schema)?;
+            rows += 1;
+            let valid = record
+                .get(0)
+                .is_some_and(|client| client.parse::<u16>().is_ok())
+                && (1..4).all(|index| {
+                    record.get(index).is_some_and(|amount| {
+                        amount.is_empty() || Decimal::from_str(amount).is_ok()
+                    })
+                })
+                && record
+                    .get(4)
+                    .is_some_and(|locked| locked.is_empty() || locked.parse::<bool>().is_ok());
+            if !valid {
+                return Err(TrailerError::Schema(format!("row {rows}: {record:?}")));
+            }
+        }
+        Ok(rows)
+    }
+
+    // a json account: the standard columns (empty balances for placeholders), custom ones optional
+    fn check_object(&self, account: &Value) -> Result<(), TrailerError> {
+        let valid = account
+            .get("client")
+            .and_then(Value::as_u64)
+            .is_some_and(|client| client <= u16::MAX as u64)
+            && ["available", "held", "total"]
+                .iter()
+                .all(|column| match account.get(column) {
+                    Some(Value::String(amount)) => Decimal::from_str(amount).is_ok(),
+                    Some(Value::Number(_) | Value::Null) => true,
+                    _ => false,
+                })
+            && matches!(account.get("locked"), Some(Value::Bool(_) | Value::Null))
+            && account
+                .as_object()
+                .is_some_and(|object| object.keys().all(|key| self.columns.contains(key)));
+        if !valid {
+            return Err(TrailerError::Schema(account.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Checks an output file against its trailer: checksum, schema and row count
+#[instrument]
+pub fn verify_output(artifact: &Path) -> Result<OutputTrailer, TrailerError> {
+    let trailer = OutputTrailer::read(artifact)?;
+    let actual = file_sha256(artifact)?;
+    if actual != trailer.sha256 {
+        return Err(TrailerError::Checksum {
+            expected: trailer.sha256,
+            actual,
+        });
+    }
+    let rows = trailer.inspect(artifact)?;
+    if rows != trailer.rows {
+        return Err(TrailerError::RowCount {
+            expected: trailer.rows,
+            actual: rows,
+        });
+    }
+    info!(rows, "Verified output");
+    Ok(trailer)
+}
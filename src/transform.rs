@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tracing::trace;
+
+use crate::{
+    csv_input::ConversionError,
+    model::{ClientId, Transaction},
+};
+
+/// Rewrites a transaction before it is applied
+pub trait TransactionTransform {
+    fn transform(&mut self, transaction: Transaction) -> Transaction;
+}
+
+/// Any closure can be used as a transform
+impl<F: FnMut(Transaction) -> Transaction> TransactionTransform for F {
+    fn transform(&mut self, transaction: Transaction) -> Transaction {
+        self(transaction)
+    }
+}
+
+/// Multiplies deposit and withdrawal amounts, e.g. by 0.01 for a partner that sends cents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScaleAmounts(pub Decimal);
+
+impl TransactionTransform for ScaleAmounts {
+    fn transform(&mut self, transaction: Transaction) -> Transaction {
+        match transaction {
+            Transaction::Deposit { client, tx, amount } => Transaction::Deposit {
+                client,
+                tx,
+                amount: amount * self.0,
+            },
+            Transaction::Withdrawal { client, tx, amount } => Transaction::Withdrawal {
+                client,
+                tx,
+                amount: amount * self.0,
+            },
+            other => other,
+        }
+    }
+}
+
+/// Replaces client ids (external id -> engine id), unmapped clients are left as they are
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemapClients(pub HashMap<ClientId, ClientId>);
+
+#[derive(Debug, Deserialize)]
+struct CsvClientMapping {
+    from: ClientId,
+    to: ClientId,
+}
+
+impl RemapClients {
+    /// Reads the mapping from a `from, to` csv
+    pub fn from_reader<R: std::io::Read>(
+        csv_reader: csv::Reader<R>,
+    ) -> Result<RemapClients, ConversionError> {
+        csv_reader
+            .into_deserialize::<CsvClientMapping>()
+            .map(|record| {
+                let record = record?;
+                Ok((record.from, record.to))
+            })
+            .collect::<Result<_, _>>()
+            .map(RemapClients)
+    }
+}
+
+impl TransactionTransform for RemapClients {
+    fn transform(&mut self, transaction: Transaction) -> Transaction {
+        let Some(&to) = self.0.get(&transaction.client_id()) else {
+            return transaction;
+        };
+        match transaction {
+            Transaction::Deposit { tx, amount, .. } => Transaction::Deposit {
+                client: to,
+                tx,
+                amount,
+            },
+            Transaction::Withdrawal { tx, amount, .. } => Transaction::Withdrawal {
+                client: to,
+                tx,
+                amount,
+            },
+            Transaction::Dispute { tx, .. } => Transaction::Dispute { client: to, tx },
+            Transaction::Resolve { tx, .. } => Transaction::Resolve { client: to, tx },
+            Transaction::Chargeback { tx, .. } => Transaction::Chargeback { client: to, tx },
+            Transaction::Custom(mut custom) => {
+                custom.client = to;
+                Transaction::Custom(custom)
+            }
+        }
+    }
+}
+
+/// Wraps a transaction stream, running every valid transaction through the transforms in order
+pub fn transform_transactions<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+    transforms: Vec<Box<dyn TransactionTransform>>,
+    transactions: I,
+) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+    let mut transforms = transforms;
+    transactions.map(move |transaction| {
+        transaction.map(|transaction| {
+            transforms
+                .iter_mut()
+                .fold(transaction, |transaction, transform| {
+                    let transformed = transform.transform(transaction);
+                    trace!(?transformed, "Transformed transaction");
+                    transformed
+                })
+        })
+    })
+}
+
+/// Wraps a transaction stream whose amounts are integers in minor units (e.g. cents with 2 decimals),
+/// converting them to decimal amounts. A fractional amount means the input is not in minor units and
+/// is rejected instead of being silently rescaled.
+pub fn minor_unit_amounts<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+    decimals: u32,
+    transactions: I,
+) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+    let to_major = move |amount: Decimal| {
+        let mut major = amount.normalize();
+        if major.scale() > 0 || major.set_scale(decimals).is_err() {
+            return Err(ConversionError::MinorUnits(amount.to_string()));
+        }
+        Ok(major)
+    };
+    transactions.map(move |transaction| match transaction? {
+        Transaction::Deposit { client, tx, amount } => Ok(Transaction::Deposit {
+            client,
+            tx,
+            amount: to_major(amount)?,
+        }),
+        Transaction::Withdrawal { client, tx, amount } => Ok(Transaction::Withdrawal {
+            client,
+            tx,
+            amount: to_major(amount)?,
+        }),
+        other => Ok(other),
+    })
+}
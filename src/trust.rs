@@ -0,0 +1,187 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    csv_input::ConversionError,
+    model::{Transaction, TransactionId},
+};
+
+/// Decimal places of the amounts the engine keeps, a strict source may not send more
+const AMOUNT_DECIMALS: u32 = 4;
+
+/// How much an input source is trusted, it selects the `ValidationProfile` of its transactions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrustLevel {
+    #[default]
+    Trusted, // internal feeds: only the parsing checks
+    Standard, // duplicate tx ids are rejected
+    Strict,   // partner feeds: every check of the profile
+}
+
+impl FromStr for TrustLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trusted" => Ok(TrustLevel::Trusted),
+            "standard" => Ok(TrustLevel::Standard),
+            "strict" => Ok(TrustLevel::Strict),
+            _ => Err(format!(
+                "unknown trust level {s}, expected trusted, standard or strict"
+            )),
+        }
+    }
+}
+
+/// Checks applied to the transactions of a source on top of the parsing ones, a transaction that
+/// fails one of them is invalid (counted and logged like a row that failed to parse)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationProfile {
+    pub duplicate_tx: bool, // a deposit or withdrawal reusing a tx id of the source
+    pub exact_amounts: bool, // zero amounts, or more decimal places than the engine keeps
+    pub unknown_references: bool, // a dispute, resolve or chargeback of a tx the source never deposited
+}
+
+impl TrustLevel {
+    pub fn profile(&self) -> ValidationProfile {
+        match self {
+            TrustLevel::Trusted => ValidationProfile::default(),
+            TrustLevel::Standard => ValidationProfile {
+                duplicate_tx: true,
+                ..ValidationProfile::default()
+            },
+            TrustLevel::Strict => ValidationProfile {
+                duplicate_tx: true,
+                exact_amounts: true,
+                unknown_references: true,
+            },
+        }
+    }
+}
+
+/// Trust level of each input file, the ones not listed get the default level
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceTrust {
+    pub default: TrustLevel,
+    pub sources: HashMap<PathBuf, TrustLevel>,
+}
+
+impl SourceTrust {
+    pub fn level(&self, source: &Path) -> TrustLevel {
+        self.sources.get(source).copied().unwrap_or(self.default)
+    }
+}
+
+/// Transactions of one source checked against its validation profile
+#[derive(Debug)]
+pub struct ValidatedTransactions<I> {
+    inner: I,
+    source: String,
+    profile: ValidationProfile,
+    seen: HashSet<TransactionId>,     // deposits and withdrawals
+    deposits: HashSet<TransactionId>, // that can be referenced
+    rejected: u64,
+}
+
+/// Checks the transactions of `source` against the profile of its trust level
+pub fn validate_source<I: Iterator<Item = Result<Transaction, ConversionError>>>(
+    transactions: I,
+    source: String,
+    level: TrustLevel,
+) -> ValidatedTransactions<I> {
+    info!(source, ?level, "Validation profile of the source");
+    ValidatedTransactions {
+        inner: transactions,
+        source,
+        profile: level.profile(),
+        seen: HashSet::new(),
+        deposits: HashSet::new(),
+        rejected: 0,
+    }
+}
+
+impl<I> ValidatedTransactions<I> {
+    /// Transactions that failed a check of the profile so far
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    // the first failed check of the profile
+    fn check(&mut self, transaction: &Transaction) -> Result<(), String> {
+        let tx = transaction.tx_id();
+        let amount = match transaction {
+            Transaction::Deposit { amount, .. } | Transaction::Withdrawal { amount, .. } => {
+                Some(*amount)
+            }
+            _ => None,
+        };
+        if self.profile.exact_amounts
+            && let Some(amount) = amount
+        {
+            if amount.is_zero() {
+                return Err(format!("zero amount in tx {tx}"));
+            }
+            if amount.normalize().scale() > AMOUNT_DECIMALS {
+                return Err(format!(
+                    "amount {amount} of tx {tx} has more than {AMOUNT_DECIMALS} decimal places"
+                ));
+            }
+        }
+        match transaction {
+            Transaction::Deposit { .. } | Transaction::Withdrawal { .. } => {
+                if self.profile.duplicate_tx && !self.seen.insert(tx) {
+                    return Err(format!("duplicate tx {tx}"));
+                }
+                if self.profile.unknown_references
+                    && matches!(transaction, Transaction::Deposit { .. })
+                {
+                    self.deposits.insert(tx);
+                }
+            }
+            Transaction::Dispute { .. }
+            | Transaction::Resolve { .. }
+            | Transaction::Chargeback { .. }
+                if self.profile.unknown_references && !self.deposits.contains(&tx) =>
+            {
+                return Err(format!(
+                    "{} of tx {tx}, not deposited by this source",
+                    transaction.type_name()
+                ));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = Result<Transaction, ConversionError>>> Iterator
+    for ValidatedTransactions<I>
+{
+    type Item = Result<Transaction, ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let transaction = match self.inner.next()? {
+            Ok(transaction) => transaction,
+            err => return Some(err),
+        };
+        Some(match self.check(&transaction) {
+            Ok(()) => Ok(transaction),
+            Err(reason) => {
+                self.rejected += 1;
+                warn!(
+                    source = self.source,
+                    reason, "Transaction failed validation"
+                );
+                Err(ConversionError::Validation(format!(
+                    "{}: {reason}",
+                    self.source
+                )))
+            }
+        })
+    }
+}
@@ -0,0 +1,70 @@
+use std::{fs, path::Path, thread};
+
+use tracing::{info, instrument};
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+const INPUT_PER_PARTITION: u64 = 16 * MIB; // smaller outputs are not worth another writer thread
+const ACCOUNT_BYTES: u64 = 256; // queued (client, account) with the channel overhead, roughly
+
+/// What the knobs of a run are derived from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemResources {
+    pub cores: usize,
+    pub input_bytes: Option<u64>, // unknown for streams (e.g. kafka)
+    pub available_memory: Option<u64>, // unknown outside of linux
+}
+
+impl SystemResources {
+    /// Cores, size of the input file and available memory of this machine
+    #[instrument]
+    pub fn detect(input: &Path) -> SystemResources {
+        SystemResources {
+            cores: thread::available_parallelism().map_or(1, |cores| cores.get()),
+            input_bytes: fs::metadata(input).ok().map(|metadata| metadata.len()),
+            available_memory: fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| available_memory(&meminfo)),
+        }
+    }
+}
+
+// `MemAvailable:  123456 kB` line of /proc/meminfo, in bytes
+fn available_memory(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * KIB)
+}
+
+/// Knobs of the pipeline picked by `--auto-tune`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    pub partitions: u16,            // output partitions, one writer thread each
+    pub channel_bound: usize,       // accounts queued per partition writer
+    pub reader_buffer_bytes: usize, // csv read buffer
+}
+
+impl Tuning {
+    /// One partition per core but at most one per 16 MiB of input, a read buffer of about 1/64 of
+    /// the input (8 KiB to 4 MiB), and queues that stay within 1% of the available memory.
+    pub fn auto(resources: &SystemResources) -> Tuning {
+        let input_bytes = resources.input_bytes.unwrap_or(INPUT_PER_PARTITION);
+        let cores = resources.cores.clamp(1, u16::MAX as usize) as u16;
+        let partitions = ((input_bytes / INPUT_PER_PARTITION).max(1)).min(cores as u64) as u16;
+        let mut reader_buffer_bytes = (input_bytes / 64).clamp(8 * KIB, 4 * MIB);
+        let mut channel_bound = 1024;
+        if let Some(memory) = resources.available_memory {
+            reader_buffer_bytes = reader_buffer_bytes.min((memory / 64).max(8 * KIB));
+            channel_bound = (memory / 100 / ACCOUNT_BYTES / partitions as u64).clamp(64, 65536);
+        }
+        let tuning = Tuning {
+            partitions,
+            channel_bound: channel_bound as usize,
+            reader_buffer_bytes: reader_buffer_bytes as usize,
+        };
+        info!(?resources, ?tuning, "Auto-tuned the pipeline");
+        tuning
+    }
+}
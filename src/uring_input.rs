@@ -0,0 +1,157 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, Read},
+    ops::Not,
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use io_uring::{IoUring, opcode, types};
+use tracing::{info, warn};
+
+use crate::{
+    csv_input::{GZIP_MAGIC, ZSTD_MAGIC, open_input_file, open_input_with_encoding},
+    encoding::{InputEncoding, decode_input},
+};
+
+/// Bytes of each of the two buffers of a `UringReader`
+pub const URING_BUFFER: usize = 1 << 20;
+
+/// Reads a file through io_uring (feature `uring`, Linux only) with two buffers: while the rows of
+/// one buffer are parsed, the kernel reads the next part of the file into the other one, so that
+/// with a cold page cache the disk reads overlap with the parsing instead of alternating with it.
+/// At most one read is in flight, the reader hands out the buffers themselves (`BufRead`).
+pub struct UringReader {
+    ring: IoUring,
+    file: File,
+    buffers: [Vec<u8>; 2],
+    current: usize,         // buffer being consumed
+    pos: usize,             // consumed bytes of the current buffer
+    filled: usize,          // bytes read into the current buffer
+    offset: u64,            // file offset of the next read
+    pending: Option<usize>, // buffer with a read in flight
+    eof: bool,
+}
+
+impl UringReader {
+    /// Starts reading the file into the first buffer, fails when io_uring is not available (kernels
+    /// before 5.6, or disabled e.g. by a container's seccomp profile)
+    pub fn new(file: File, buffer_size: usize) -> io::Result<Self> {
+        let mut reader = UringReader {
+            ring: IoUring::new(2)?,
+            file,
+            buffers: [vec![0; buffer_size.max(1)], vec![0; buffer_size.max(1)]],
+            current: 1, // the second buffer starts empty and consumed
+            pos: 0,
+            filled: 0,
+            offset: 0,
+            pending: None,
+            eof: false,
+        };
+        reader.submit(0)?;
+        Ok(reader)
+    }
+
+    // queues a read of the file from `offset` into a buffer
+    fn submit(&mut self, index: usize) -> io::Result<()> {
+        let buffer = &mut self.buffers[index];
+        let read = opcode::Read::new(
+            types::Fd(self.file.as_raw_fd()),
+            buffer.as_mut_ptr(),
+            buffer.len().min(u32::MAX as usize) as u32,
+        )
+        .offset(self.offset)
+        .build()
+        .user_data(index as u64);
+        // SAFETY: the buffer and the file outlive the read, neither is touched before its
+        // completion was reaped, which `Drop` waits for
+        unsafe { self.ring.submission().push(&read) }.map_err(io::Error::other)?;
+        self.ring.submit()?;
+        self.pending = Some(index);
+        Ok(())
+    }
+
+    // waits for the read in flight, returns its buffer and the bytes read
+    fn complete(&mut self) -> io::Result<(usize, usize)> {
+        let index = self.pending.expect("no read in flight");
+        loop {
+            match self.ring.submit_and_wait(1) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => result?,
+            };
+            if let Some(completion) = self.ring.completion().next() {
+                self.pending = None;
+                return match completion.result() {
+                    read if read >= 0 => Ok((index, read as usize)),
+                    err => Err(io::Error::from_raw_os_error(-err)),
+                };
+            }
+        }
+    }
+}
+
+impl BufRead for UringReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.filled && self.eof.not() {
+            if self.pending.is_none() {
+                self.submit(1 - self.current)?; // again, after a failed read
+            }
+            let (index, read) = match self.complete() {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => result?,
+            };
+            if read == 0 {
+                self.eof = true;
+                break;
+            }
+            (self.current, self.pos, self.filled) = (index, 0, read);
+            self.offset += read as u64;
+            self.submit(1 - index)?; // read ahead into the buffer that was just consumed
+        }
+        Ok(&self.buffers[self.current][self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.filled);
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl Drop for UringReader {
+    fn drop(&mut self) {
+        // the kernel may still write into the buffer of the read in flight
+        if self.pending.is_some() && self.complete().is_err() {
+            warn!("io_uring read failed while closing the input");
+        }
+    }
+}
+
+/// Like `open_input_with_encoding`, reading an uncompressed file through a `UringReader`.
+/// Compressed files, and every file when io_uring is not available, are read as usual.
+pub fn open_input_uring(
+    path: &Path,
+    encoding: Option<InputEncoding>,
+) -> io::Result<Box<dyn io::Read + Send>> {
+    let mut reader = match UringReader::new(open_input_file(path)?, URING_BUFFER) {
+        Ok(reader) => reader,
+        Err(err) => {
+            warn!(%err, ?path, "io_uring is not available, reading the input as usual");
+            return open_input_with_encoding(path, encoding);
+        }
+    };
+    let head = reader.fill_buf()?;
+    if head.starts_with(GZIP_MAGIC) || head.starts_with(ZSTD_MAGIC) {
+        info!(?path, "Compressed input, decompressing it as usual");
+        return open_input_with_encoding(path, encoding);
+    }
+    decode_input(reader, encoding)
+}
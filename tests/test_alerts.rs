@@ -0,0 +1,87 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    alerts::{AlertKind, BalanceAlert, BalanceThresholds, Threshold, ThresholdMonitor},
+    csv_input::transactions_from_reader,
+    model::{ClientId, Clients, TransactionId},
+    spawn_writer_thread,
+};
+
+#[test]
+/// Alerts fire when a threshold is crossed, not on every transaction beyond it
+fn threshold_alerts() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 5.0
+        withdrawal, 1, 2, 4.5
+        withdrawal, 1, 3, 0.1
+        deposit, 1, 4, 1.0
+        withdrawal, 1, 5, 1.0
+        deposit, 2, 6, 50.0
+        deposit, 2, 7, 60.0
+        deposit, 3, 8, 60.0"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let mut thresholds = BalanceThresholds {
+        global: Threshold {
+            min_available: Some(dec!(1)),
+            max_total: None,
+        },
+        ..Default::default()
+    };
+    let overrides = "client, min_available, max_total\n2, , 100\n";
+    thresholds
+        .load_per_client(
+            csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(overrides.as_bytes()),
+        )
+        .expect("valid thresholds");
+
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let sink = alerts.clone();
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    clients.add_observer(ThresholdMonitor::new(thresholds, move |alert| {
+        sink.lock().expect("poisoned").push(alert.clone())
+    }));
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    assert_eq!(
+        *alerts.lock().expect("poisoned"),
+        vec![
+            BalanceAlert {
+                client: ClientId(1),
+                tx: TransactionId(2),
+                kind: AlertKind::AvailableBelowMinimum,
+                balance: dec!(0.5),
+                threshold: dec!(1),
+            },
+            // tx 3 stays below, tx 4 comes back to 1.4
+            BalanceAlert {
+                client: ClientId(1),
+                tx: TransactionId(5),
+                kind: AlertKind::AvailableBelowMinimum,
+                balance: dec!(0.4),
+                threshold: dec!(1),
+            },
+            // client 2 overrides the global threshold: no minimum
+            BalanceAlert {
+                client: ClientId(2),
+                tx: TransactionId(7),
+                kind: AlertKind::TotalAboveMaximum,
+                balance: dec!(110),
+                threshold: dec!(100),
+            },
+        ]
+    );
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "async")]
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use rust_decimal::dec;
+use tokio::sync::mpsc;
+use tx_engine::{
+    async_engine::AsyncClients,
+    model::{Account, ClientId, OutputMode, Transaction, TransactionId},
+};
+
+// a stream over an iterator, ready every time
+struct IterStream<I>(I);
+
+impl<I: Iterator + Unpin> Stream for IterStream<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.0.next())
+    }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("failed to build the runtime")
+}
+
+#[test]
+/// A stream is applied like an iterator, the locked account is sent first
+fn load_stream() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let transactions: Vec<Result<Transaction, String>> = vec![
+        Ok(Transaction::Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: dec!(10),
+        }),
+        Ok(Transaction::Deposit {
+            client: ClientId(2),
+            tx: TransactionId(2),
+            amount: dec!(5),
+        }),
+        Err("connection reset".to_string()),
+        Ok(Transaction::Dispute {
+            client: ClientId(2),
+            tx: TransactionId(2),
+        }),
+        Ok(Transaction::Chargeback {
+            client: ClientId(2),
+            tx: TransactionId(2),
+        }),
+        Ok(Transaction::Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(3),
+            amount: dec!(20),
+        }),
+    ];
+    runtime().block_on(async {
+        // a single slot: the loading waits for the consumer
+        let (output, mut accounts) = mpsc::channel(1);
+        let consumer = tokio::spawn(async move {
+            let mut received = Vec::new();
+            while let Some(account) = accounts.recv().await {
+                received.push(account);
+            }
+            received
+        });
+        let mut clients = AsyncClients::new(output);
+        clients.clients.sorted_output = true;
+        let stats = clients
+            .load_transactions(IterStream(transactions.into_iter()))
+            .await;
+        assert_eq!(stats.invalid, 1);
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(stats.locked_accounts, 1);
+        assert_eq!(stats.deposits, dec!(15));
+        clients.send_to_output(OutputMode::SkipLocked).await;
+
+        let mut locked = Account::default();
+        locked.lock();
+        assert_eq!(
+            consumer.await.expect("consumer panicked"),
+            vec![
+                (ClientId(2), locked),
+                (ClientId(1), Account::new(dec!(10), dec!(0), false)),
+            ]
+        );
+    });
+}
+
+#[test]
+/// The accounts are dropped, not awaited forever, once the consumer is gone
+fn consumer_gone() {
+    runtime().block_on(async {
+        let (output, accounts) = mpsc::channel(1);
+        drop(accounts);
+        let mut clients = AsyncClients::new(output);
+        let deposits = (1..=3000u32).map(|tx| {
+            Ok::<_, String>(Transaction::Deposit {
+                client: ClientId((tx % 7) as u16),
+                tx: TransactionId(tx),
+                amount: dec!(1),
+            })
+        });
+        let stats = clients.load_transactions(IterStream(deposits)).await;
+        assert_eq!(stats.deposits, dec!(3000));
+        assert_eq!(clients.clients.accounts.len(), 7);
+        clients.send_to_output(OutputMode::All).await;
+    });
+}
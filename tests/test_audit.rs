@@ -0,0 +1,58 @@
+use std::fs;
+
+use rust_decimal::dec;
+use tx_engine::{
+    audit::{AuditJournal, JournalEntry, read_journal},
+    csv_input::transactions_from_reader,
+    model::{ClientId, Clients, TransactionId},
+};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,2.0
+withdrawal,1,2,5.0
+dispute,1,1,
+chargeback,1,1,
+deposit,1,3,1.0
+";
+
+#[test]
+/// Applied transactions are journaled with their effect, the journal grows across runs
+fn journal_applied_transactions() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let dir = std::env::temp_dir().join("tx_engine_test_audit");
+    fs::create_dir_all(&dir).expect("failed to create the journal dir");
+    let path = dir.join("journal.csv");
+    let _ = fs::remove_file(&path);
+
+    for _run in 0..2 {
+        let mut clients = Clients::new(Vec::new());
+        clients.add_observer(AuditJournal::open(&path).expect("failed to open the journal"));
+        clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+            INPUT.as_bytes(),
+        )));
+    }
+
+    let entries: Vec<JournalEntry> =
+        read_journal(csv::Reader::from_path(&path).expect("failed to read the journal"))
+            .collect::<Result<_, _>>()
+            .expect("invalid journal");
+    // the withdrawal lacks funds and the deposit hits a locked account
+    assert_eq!(entries.len(), 6);
+    assert_eq!(entries[..3], entries[3..]);
+    assert_eq!(
+        entries[1],
+        JournalEntry {
+            tx: TransactionId(1),
+            transaction_type: "dispute".to_string(),
+            client: ClientId(1),
+            available_before: dec!(2),
+            held_before: dec!(0),
+            available_after: dec!(0),
+            held_after: dec!(2),
+            held_delta: dec!(2),
+            locked: false,
+        }
+    );
+    assert_eq!(entries[2].held_delta, dec!(-2));
+    assert!(entries[2].locked);
+}
@@ -0,0 +1,61 @@
+use std::{io::Read, thread, time::Duration};
+
+use rust_decimal::dec;
+use tx_engine::{
+    cancel::{CancellationToken, cancellable},
+    csv_input::{FollowConfig, FollowReader},
+    model::{ClientId, Transaction, TransactionId},
+};
+
+fn deposit(tx: u32) -> Transaction {
+    Transaction::Deposit {
+        client: ClientId(1),
+        tx: TransactionId(tx),
+        amount: dec!(1),
+    }
+}
+
+#[test]
+/// Ingestion stops at the next transaction once cancelled, the records read are the checkpoint
+fn cancel_ingestion() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let token = CancellationToken::new();
+    let (mut transactions, progress) = cancellable(&token, (1..=5).map(|tx| Ok(deposit(tx))));
+    assert!(transactions.next().is_some());
+    assert!(transactions.next().is_some());
+    token.cancel();
+    assert!(transactions.next().is_none());
+    assert!(progress.interrupted());
+    assert_eq!(progress.records(), 2);
+
+    // an input read to the end was not interrupted, even if cancelled afterwards
+    let token = CancellationToken::new();
+    let (transactions, progress) = cancellable(&token, (1..=3).map(|tx| Ok(deposit(tx))));
+    assert_eq!(transactions.count(), 3);
+    token.cancel();
+    assert!(!progress.interrupted());
+    assert_eq!(progress.records(), 3);
+}
+
+#[test]
+/// A followed input without idle timeout ends once cancelled
+fn cancel_follow() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let token = CancellationToken::new();
+    let config = FollowConfig {
+        poll_interval: Duration::from_millis(5),
+        idle_timeout: None,
+    };
+    let mut reader =
+        FollowReader::new(&b"type,client,tx,amount\n"[..], config).with_cancellation(token.clone());
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+    let mut read = String::new();
+    reader
+        .read_to_string(&mut read)
+        .expect("read until cancelled");
+    assert_eq!(read, "type,client,tx,amount\n");
+    canceller.join().expect("canceller thread");
+}
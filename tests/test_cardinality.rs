@@ -0,0 +1,49 @@
+use tx_engine::{
+    cardinality::{CardinalityEstimate, CardinalityLimits, HyperLogLog, estimate_cardinality},
+    csv_input::transactions_from_reader,
+};
+
+#[test]
+/// Small counts are exact enough, large counts within a few percent
+fn hyperloglog_accuracy() {
+    let mut small = HyperLogLog::default();
+    for value in [1, 2, 3, 2, 1] {
+        small.insert(value);
+    }
+    assert_eq!(small.estimate(), 3);
+
+    let mut large = HyperLogLog::default();
+    for value in 0..1_000_000 {
+        large.insert(value % 200_000);
+    }
+    let estimate = large.estimate();
+    assert!(estimate.abs_diff(200_000) < 200_000 / 20, "{estimate}");
+}
+
+#[test]
+fn pre_pass_and_limits() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        dispute, 1, 1,
+        deposit, 1, bad, 1.0"#;
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input.as_bytes());
+    let estimate = estimate_cardinality(transactions_from_reader(csv_reader));
+    assert_eq!(
+        estimate,
+        CardinalityEstimate {
+            clients: 2,
+            transactions: 2,
+            rows: 4
+        }
+    );
+    assert!(estimate.check(&CardinalityLimits::default()));
+    assert!(!estimate.check(&CardinalityLimits {
+        max_clients: Some(1),
+        max_transactions: None
+    }));
+}
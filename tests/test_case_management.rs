@@ -0,0 +1,136 @@
+use std::{io, sync::mpsc};
+
+use rust_decimal::dec;
+use tx_engine::{
+    case_management::{
+        CaseImportSummary, DecisionImpact, apply_case_decisions, preview_case_decisions,
+        read_case_decisions, write_decision_preview, write_dispute_cases,
+    },
+    csv_input::transactions_from_reader,
+    model::{ClientId, Clients},
+    spawn_writer_thread,
+};
+
+fn csv_reader(input: &str) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input.as_bytes())
+}
+
+#[test]
+/// Open disputes go out as cases, decisions come back as resolves and chargebacks
+fn export_and_import_decisions() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        deposit, 2, 3, 0.5
+        dispute, 1, 1,
+        dispute, 2, 2,"#;
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    clients.load_transactions(transactions_from_reader(csv_reader(input)));
+
+    let mut out: Vec<u8> = Vec::new();
+    write_dispute_cases(&clients, &mut out).expect("failed to write cases");
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "case_id,client,tx,amount,status\n1-1,1,1,1,open\n2-2,2,2,2,open\n"
+    );
+
+    let decisions = r#"
+        case_id, client, tx, outcome, decided_by
+        1-1, 1, 1, resolve, analyst-7
+        2-2, 2, 2, chargeback, fraud-team
+        2-3, 2, 3, chargeback, fraud-team
+        2-2, 2, 2, escalate, fraud-team"#;
+    let summary = apply_case_decisions(&mut clients, csv_reader(decisions));
+    assert_eq!(
+        summary,
+        CaseImportSummary {
+            applied: 2,
+            rejected: 2 // tx 3 is not disputed, escalate is not an outcome
+        }
+    );
+    assert_eq!(clients.accounts[&ClientId(1)].available(), dec!(1));
+    assert_eq!(clients.accounts[&ClientId(1)].held(), dec!(0));
+    assert!(clients.accounts[&ClientId(2)].locked());
+    assert_eq!(clients.accounts[&ClientId(2)].total(), dec!(0.5));
+
+    let mut out: Vec<u8> = Vec::new();
+    write_dispute_cases(&clients, &mut out).expect("failed to write cases");
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "case_id,client,tx,amount,status\n"
+    );
+}
+
+#[test]
+/// Previewed decisions report the projected balances and leave the clients as they were
+fn preview_decisions() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        deposit, 2, 3, 0.5
+        dispute, 1, 1,
+        dispute, 2, 2,
+        dispute, 2, 3,"#;
+    let mut clients = Clients::new(Vec::new());
+    clients.load_transactions(transactions_from_reader(csv_reader(input)));
+
+    let decisions = r#"
+        case_id, client, tx, outcome, decided_by
+        2-2, 2, 2, chargeback, what-if
+        2-3, 2, 3, resolve, what-if
+        1-1, 1, 1, resolve, what-if
+        1-1, 1, 1, chargeback, what-if"#;
+    let preview = preview_case_decisions(&clients, &read_case_decisions(csv_reader(decisions)));
+    assert_eq!(
+        preview.summary,
+        CaseImportSummary {
+            applied: 2,
+            rejected: 2 // client 2 would be locked by the chargeback, tx 1 would be resolved
+        }
+    );
+    assert_eq!(
+        preview.impacts[1],
+        DecisionImpact {
+            client: ClientId(2),
+            available: dec!(0),
+            held: dec!(2.5),
+            total: dec!(2.5),
+            locked: false,
+            projected_available: dec!(0),
+            projected_held: dec!(0.5),
+            projected_total: dec!(0.5),
+            projected_locked: true,
+        }
+    );
+    let mut out: Vec<u8> = Vec::new();
+    write_decision_preview(&preview, &mut out).expect("failed to write the preview");
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "client,available,held,total,locked,projected_available,projected_held,projected_total,projected_locked
+1,0,1,1,false,1,0,1,false
+2,0.0,2.5,2.5,false,0.0,0.5,0.5,true
+"
+    );
+
+    // nothing was applied
+    assert_eq!(clients.accounts[&ClientId(1)].held(), dec!(1));
+    assert!(!clients.accounts[&ClientId(2)].locked());
+    let mut out: Vec<u8> = Vec::new();
+    write_dispute_cases(&clients, &mut out).expect("failed to write cases");
+    assert_eq!(
+        String::from_utf8(out)
+            .expect("invalid utf8")
+            .lines()
+            .count(),
+        4
+    );
+}
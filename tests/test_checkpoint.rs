@@ -0,0 +1,99 @@
+use std::{
+    num::{NonZeroU64, NonZeroUsize},
+    sync::{Arc, Mutex},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    checkpoint::{SNAPSHOT_VERSION, Snapshot},
+    csv_input::{ConversionError, transactions_from_reader},
+    model::{Account, ClientId, Clients, OutputMode, Transaction},
+};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+dispute,2,2,
+chargeback,2,2,
+deposit,3,3,7.5
+dispute,3,3,
+deposit,1,4,1.25
+resolve,3,3,
+withdrawal,1,5,2.0
+dispute,1,4,
+";
+
+fn transactions() -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+    transactions_from_reader(csv::Reader::from_reader(INPUT.as_bytes()))
+}
+
+fn sorted(accounts: &[(ClientId, Account)]) -> Vec<(ClientId, Account)> {
+    let mut accounts = accounts.to_vec();
+    accounts.sort_by_key(|(client, _)| client.0);
+    accounts
+}
+
+#[test]
+/// A run resumed from any checkpoint ends with the accounts of an uninterrupted one, the disputes
+/// open at the checkpoint included
+fn resume_from_checkpoint() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let uninterrupted = Arc::new(Mutex::new(Vec::new()));
+    let mut clients = Clients::new(uninterrupted.clone());
+    let mut snapshots = Vec::new();
+    clients.load_transactions_with_checkpoints(
+        transactions(),
+        NonZeroU64::new(3).expect("not zero"),
+        None,
+        |clients| {
+            let mut snapshot = clients.snapshot();
+            snapshot.input_offset = 3 * (snapshots.len() as u64 + 1);
+            snapshots.push(snapshot);
+        },
+    );
+    clients.send_to_output(OutputMode::SkipLocked);
+    let uninterrupted = sorted(&uninterrupted.lock().expect("poisoned"));
+    assert_eq!(snapshots.len(), 4); // after 3, 6, 9 and the last transaction
+    assert_eq!(snapshots[1].version, SNAPSHOT_VERSION);
+    assert!(snapshots[1].disputable_transactions[1].disputed); // tx 3, disputed
+    assert!(snapshots[1].accounts[1].locked);
+
+    for snapshot in snapshots {
+        let path = std::env::temp_dir().join("tx_engine_test_checkpoint.json");
+        snapshot
+            .write(&path)
+            .expect("failed to write the checkpoint");
+        let snapshot = Snapshot::read(&path).expect("failed to read the checkpoint");
+        let offset = snapshot.input_offset as usize;
+
+        let resumed = Arc::new(Mutex::new(Vec::new()));
+        let mut clients = Clients::from_snapshot(snapshot, resumed.clone());
+        clients.emit_locked_accounts();
+        clients.load_transactions_parallel(
+            transactions().skip(offset),
+            NonZeroUsize::new(2).expect("not zero"),
+        );
+        clients.send_to_output(OutputMode::SkipLocked);
+        assert_eq!(
+            sorted(&resumed.lock().expect("poisoned")),
+            uninterrupted,
+            "resumed after {offset} transactions"
+        );
+    }
+    assert_eq!(
+        uninterrupted[0],
+        (ClientId(1), Account::new(dec!(8), dec!(1.25), false))
+    );
+}
+
+#[test]
+/// A checkpoint of another format version is refused
+fn checkpoint_version() {
+    let path = std::env::temp_dir().join("tx_engine_test_checkpoint_version.json");
+    let mut snapshot = Clients::new(Vec::new()).snapshot();
+    snapshot.version = SNAPSHOT_VERSION + 1;
+    snapshot
+        .write(&path)
+        .expect("failed to write the checkpoint");
+    assert!(Snapshot::read(&path).is_err());
+}
@@ -0,0 +1,491 @@
+use std::{
+    num::{NonZeroU64, NonZeroUsize},
+    path::{Path, PathBuf},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    cli::{Args, ArgsError, Command, InputFormat},
+    csv_input::{Column, ColumnMapping},
+    encoding::InputEncoding,
+    filter::ClientFilter,
+    invariants::InvariantMode,
+    model::{ClientId, ErasurePolicy, RoundingPolicy, RoundingStrategy, TransactionId},
+    normalize::CanonicalFormat,
+    notify::OverflowPolicy,
+    output::{CsvWriterOptions, LineEnding, OutputFormat, Quoting},
+    partition::{PartitionScheme, Partitioner},
+    trust::TrustLevel,
+};
+
+fn args(line: &str) -> Result<Args, ArgsError> {
+    Args::parse(line.split_whitespace().map(String::from))
+}
+
+#[test]
+fn input_and_options() {
+    let parsed = args("data/input_example.csv --exposure-report held.csv").expect("valid args");
+    assert_eq!(parsed.input, PathBuf::from("data/input_example.csv"));
+    assert_eq!(parsed.exposure_report, Some(PathBuf::from("held.csv")));
+    assert_eq!(parsed.tenant_output_dir, None);
+    assert_eq!(parsed.input_format, InputFormat::Csv);
+
+    let parsed = args("statement.xml --input-format iso20022").expect("valid args");
+    assert_eq!(parsed.input_format, InputFormat::Iso20022);
+
+    let parsed =
+        args("in.csv --erase-clients 3,7 --erasure-policy transfer-out").expect("valid args");
+    assert_eq!(parsed.erase_clients, vec![ClientId(3), ClientId(7)]);
+    assert_eq!(parsed.erasure_policy, ErasurePolicy::TransferOut);
+
+    let parsed = args("in.csv --merge-clients 3:1,7:1 --merge-clients 8:2").expect("valid args");
+    assert_eq!(
+        parsed.merge_clients,
+        vec![
+            (ClientId(3), ClientId(1)),
+            (ClientId(7), ClientId(1)),
+            (ClientId(8), ClientId(2))
+        ]
+    );
+    assert_eq!(
+        args("in.csv --merge-clients 3-1"),
+        Err(ArgsError::InvalidValue(
+            "--merge-clients".to_string(),
+            "3-1".to_string()
+        ))
+    );
+
+    let parsed = args("in.csv --update-feed updates.ndjson").expect("valid args");
+    assert_eq!(parsed.update_feed, Some(PathBuf::from("updates.ndjson")));
+    assert!(
+        args("in.csv --sparse-accounts")
+            .expect("valid args")
+            .sparse_accounts
+    );
+    assert_eq!(
+        args("in.csv --output-batch 256")
+            .expect("valid args")
+            .output_batch,
+        NonZeroUsize::new(256)
+    );
+    let parsed = args("in.csv --expected-clients 65536 --expected-transactions 100000000")
+        .expect("valid args");
+    assert_eq!(
+        (parsed.expected_clients, parsed.expected_transactions),
+        (Some(65_536), Some(100_000_000))
+    );
+    assert_eq!(
+        args("in.csv --settle-horizon 1000000")
+            .expect("valid args")
+            .settle_horizon,
+        NonZeroU64::new(1_000_000)
+    );
+    assert_eq!(
+        args("in.csv --settle-horizon 0"),
+        Err(ArgsError::InvalidValue(
+            "--settle-horizon".to_string(),
+            "0".to_string()
+        ))
+    );
+    let parsed = args("in.csv --output-scale 2 --output-rounding half-up").expect("valid args");
+    assert_eq!(
+        parsed.rounding,
+        RoundingPolicy {
+            scale: 2,
+            strategy: RoundingStrategy::HalfUp
+        }
+    );
+    assert_eq!(
+        args("in.csv --output-scale 6"),
+        Err(ArgsError::InvalidValue(
+            "--output-scale".to_string(),
+            "6".to_string()
+        ))
+    );
+    let parsed = args("in.csv --stats-output -").expect("valid args");
+    assert_eq!(parsed.stats_output, Some(PathBuf::from("-")));
+    let parsed = args("in.csv --checkpoint state.json --checkpoint-every 500 --resume old.json")
+        .expect("valid args");
+    assert_eq!(parsed.checkpoint, Some(PathBuf::from("state.json")));
+    assert_eq!(parsed.checkpoint_every.map(|every| every.get()), Some(500));
+    assert_eq!(parsed.resume, Some(PathBuf::from("old.json")));
+    assert!(args("in.csv --checkpoint-every 0").is_err());
+    let parsed = args("in.csv --metrics-every 10").expect("valid args");
+    assert_eq!(
+        parsed.metrics_every,
+        Some(std::time::Duration::from_secs(10))
+    );
+    assert!(args("in.csv --metrics-every 0").is_err());
+    let parsed = args("in.csv --audit-journal journal.csv").expect("valid args");
+    assert_eq!(parsed.audit_journal, Some(PathBuf::from("journal.csv")));
+
+    let parsed = args("in.csv --block-clients 4,5").expect("valid args");
+    assert_eq!(
+        parsed.client_filters,
+        vec![ClientFilter::Block([ClientId(4), ClientId(5)].into())]
+    );
+
+    assert!(
+        args("in.csv --run-metadata")
+            .expect("valid args")
+            .run_metadata
+    );
+
+    let parsed = args(
+        "in.csv --check-balance-floor strict --balance-floor -10 --balance-floor-report floor.csv",
+    )
+    .expect("valid args");
+    assert_eq!(parsed.balance_floor_check, Some(InvariantMode::Strict));
+    assert_eq!(parsed.balance_floor, dec!(-10));
+    assert_eq!(
+        parsed.balance_floor_report,
+        Some(PathBuf::from("floor.csv"))
+    );
+    assert!(args("in.csv --balance-floor ten").is_err());
+
+    let parsed = args("in.csv --preview-decisions what-if.csv --preview-report impact.csv")
+        .expect("valid args");
+    assert_eq!(parsed.preview_decisions, Some(PathBuf::from("what-if.csv")));
+    assert_eq!(parsed.preview_report, Some(PathBuf::from("impact.csv")));
+
+    assert!(
+        args("in.csv --reproducible")
+            .expect("valid args")
+            .reproducible
+    );
+    let parsed = args("in.csv --allow-clients 9,2,5").expect("valid args");
+    assert_eq!(
+        format!("{:?}", parsed.client_filters),
+        "[Allow([2, 5, 9])]",
+        "stable in the config hash"
+    );
+
+    assert_eq!(
+        args("in.csv --format table")
+            .expect("valid args")
+            .output_options
+            .format,
+        OutputFormat::Table
+    );
+
+    let parsed = args("in.csv --output accounts.csv").expect("valid args");
+    assert_eq!(parsed.output, Some(PathBuf::from("accounts.csv")));
+    let parsed = args("in.csv --extra-output json:audit.json --extra-output ndjson:a:b.ndjson")
+        .expect("valid args");
+    assert_eq!(
+        parsed.extra_outputs,
+        [
+            (OutputFormat::Json, PathBuf::from("audit.json")),
+            (OutputFormat::NdJson, PathBuf::from("a:b.ndjson"))
+        ]
+    );
+    assert!(args("in.csv --extra-output audit.json").is_err());
+    assert!(args("in.csv --extra-output xml:audit.xml").is_err());
+
+    let parsed = args("in.csv --threads 4 --parts-dir parts").expect("valid args");
+    assert_eq!(parsed.parts_dir, Some(PathBuf::from("parts")));
+    assert!(args("in.csv --parts-dir").is_err());
+
+    let parsed = args("in.csv --partition-dir parts --partitions 8 --partition-by hash")
+        .expect("valid args");
+    assert_eq!(parsed.partition_dir, Some(PathBuf::from("parts")));
+    assert_eq!(
+        parsed.partitioner,
+        Partitioner {
+            scheme: PartitionScheme::Hash,
+            partitions: 8,
+            ..Default::default()
+        }
+    );
+
+    let parsed = args("in.csv --delta-output deltas.csv --delta-every 50").expect("valid args");
+    assert_eq!(parsed.delta_output, Some(PathBuf::from("deltas.csv")));
+    assert_eq!(parsed.delta_every, Some(50));
+    assert!(args("in.csv --delta-every 0").is_err());
+
+    assert!(args("in.csv --auto-tune").expect("valid args").auto_tune);
+
+    let parsed =
+        args("in.csv --output-delimiter ; --output-line-ending crlf --output-quoting always")
+            .expect("valid args");
+    assert_eq!(
+        parsed.output_options.dialect,
+        CsvWriterOptions {
+            delimiter: b';',
+            quoting: Quoting::Always,
+            line_ending: LineEnding::Crlf,
+            ..Default::default()
+        }
+    );
+    assert!(args("in.csv --output-line-ending cr").is_err());
+    let parsed = args("in.csv --output-format ndjson").expect("valid args");
+    assert_eq!(parsed.output_options.format, OutputFormat::NdJson);
+
+    let parsed = args("in.csv --rules rules.txt").expect("valid args");
+    assert_eq!(parsed.rules, Some(PathBuf::from("rules.txt")));
+
+    let parsed = args("in.csv --disable-types dispute,chargeback").expect("valid args");
+    assert_eq!(parsed.disabled_types, ["dispute", "chargeback"].into());
+
+    let parsed = args("in.csv --follow-idle-timeout 1.5").expect("valid args");
+    assert_eq!(
+        parsed.follow.and_then(|follow| follow.idle_timeout),
+        Some(std::time::Duration::from_millis(1500))
+    );
+
+    let parsed = args("in.csv --max-clients 1000").expect("valid args");
+    assert!(parsed.estimate_cardinality);
+    assert_eq!(parsed.cardinality_limits.max_clients, Some(1000));
+
+    let parsed = args("in.csv --delimiter ; --no-headers").expect("valid args");
+    assert_eq!(parsed.csv_options.delimiter, b';');
+    assert!(!parsed.csv_options.has_headers);
+    assert!(
+        args("in.csv --fast-csv")
+            .expect("valid args")
+            .csv_options
+            .byte_records
+    );
+    let parsed = args("in.csv --columns tx=0,type=kind,client=2,amount=3").expect("valid args");
+    assert_eq!(
+        parsed.csv_options.column_mapping,
+        Some(ColumnMapping {
+            transaction_type: Column::Name("kind".to_string()),
+            client: Column::Position(2),
+            tx: Column::Position(0),
+            amount: Column::Position(3),
+        })
+    );
+    assert!(args("in.csv --columns tx=0,type=1,client=2").is_err());
+    assert_eq!(
+        args("in.csv --delimiter tab").map(|parsed| parsed.csv_options.delimiter),
+        Ok(b'\t')
+    );
+    assert_eq!(
+        args("in.csv --input-encoding latin1").map(|parsed| parsed.csv_options.encoding),
+        Ok(Some(InputEncoding::Latin1))
+    );
+    assert!(args("in.csv --input-encoding ebcdic").is_err());
+    assert_eq!(
+        args("in.csv --threads 8").map(|parsed| parsed.threads.map(|threads| threads.get())),
+        Ok(Some(8))
+    );
+    assert!(args("in.csv --threads 0").is_err());
+
+    let parsed = args("a.csv --merge-input b.csv --merge-input c.csv --tx-id-map ids.csv")
+        .expect("valid args");
+    assert_eq!(
+        parsed.merge_inputs,
+        vec![PathBuf::from("b.csv"), PathBuf::from("c.csv")]
+    );
+    assert_eq!(parsed.tx_id_map, Some(PathBuf::from("ids.csv")));
+
+    let parsed = args("a.csv --merge-input b.csv --trust standard --source-trust b.csv=strict")
+        .expect("valid args");
+    assert_eq!(parsed.trust.level(Path::new("a.csv")), TrustLevel::Standard);
+    assert_eq!(parsed.trust.level(Path::new("b.csv")), TrustLevel::Strict);
+    assert!(args("in.csv --trust paranoid").is_err());
+    assert!(args("in.csv --source-trust b.csv").is_err());
+    assert_eq!(
+        args("in.csv --opening-balances legacy.csv").map(|parsed| parsed.opening_balances),
+        Ok(Some(PathBuf::from("legacy.csv")))
+    );
+
+    let parsed =
+        args("in.csv --alerts-buffer 16 --alerts-overflow spill:alerts.spill").expect("valid args");
+    assert_eq!(parsed.alerts_buffer.capacity, 16);
+    assert_eq!(
+        parsed.alerts_buffer.overflow,
+        OverflowPolicy::Spill(PathBuf::from("alerts.spill"))
+    );
+    assert!(args("in.csv --alerts-buffer 0").is_err());
+
+    let parsed = args("in.csv --sample 2.5% --sample-seed 9").expect("valid args");
+    assert_eq!(
+        parsed.client_filters,
+        vec![ClientFilter::Sample {
+            percent: 2.5,
+            seed: 9
+        }]
+    );
+}
+
+#[test]
+fn client_ids_from_file() {
+    let path = std::env::temp_dir().join("tx_engine_test_allowlist.txt");
+    std::fs::write(&path, "1\n2,3\n").expect("failed to write the allowlist");
+    let parsed = args(&format!("in.csv --allow-clients @{}", path.display())).expect("valid args");
+    assert_eq!(
+        parsed.client_filters,
+        vec![ClientFilter::Allow(
+            [ClientId(1), ClientId(2), ClientId(3)].into()
+        )]
+    );
+}
+
+#[test]
+fn invalid_args() {
+    assert_eq!(args(""), Err(ArgsError::MissingInput));
+    assert_eq!(
+        args("in.csv --exposure-report"),
+        Err(ArgsError::MissingValue("--exposure-report".to_string()))
+    );
+    assert_eq!(
+        args("in.csv --unknown"),
+        Err(ArgsError::UnknownOption("--unknown".to_string()))
+    );
+    assert_eq!(
+        args("in.csv --erase-clients 3,x"),
+        Err(ArgsError::InvalidValue(
+            "--erase-clients".to_string(),
+            "3,x".to_string()
+        ))
+    );
+    assert_eq!(
+        args("in.csv --disable-types deposit,refund"),
+        Err(ArgsError::InvalidValue(
+            "--disable-types".to_string(),
+            "refund".to_string()
+        ))
+    );
+    assert_eq!(
+        args("in.csv --delimiter ;;"),
+        Err(ArgsError::InvalidValue(
+            "--delimiter".to_string(),
+            ";;".to_string()
+        ))
+    );
+    assert_eq!(
+        args("in.csv --sample 101%"),
+        Err(ArgsError::InvalidValue(
+            "--sample".to_string(),
+            "101%".to_string()
+        ))
+    );
+}
+
+#[test]
+fn query_command() {
+    let command = Command::parse(
+        "query audit.csv --client 42 --type chargeback,dispute --tx 7"
+            .split_whitespace()
+            .map(String::from),
+    )
+    .expect("valid args");
+    let Command::Query(config) = command else {
+        panic!("expected a query, got {command:?}");
+    };
+    assert_eq!(config.input, PathBuf::from("audit.csv"));
+    assert_eq!(config.query.clients, [ClientId(42)].into());
+    assert_eq!(config.query.types, ["chargeback", "dispute"].into());
+    assert_eq!(config.query.tx, Some(TransactionId(7)));
+
+    let since = Command::parse(
+        "query audit.csv --since 2024-01-01"
+            .split_whitespace()
+            .map(String::from),
+    );
+    assert_eq!(since, Err(ArgsError::UnknownOption("--since".to_string())));
+}
+
+#[test]
+fn normalize_command() {
+    let command = Command::parse(
+        "normalize in.json --input-format json --to csv --output out.csv"
+            .split_whitespace()
+            .map(String::from),
+    )
+    .expect("valid args");
+    let Command::Normalize(config) = command else {
+        panic!("expected a normalize, got {command:?}");
+    };
+    assert_eq!(config.input, PathBuf::from("in.json"));
+    assert_eq!(config.input_format, InputFormat::Json);
+    assert_eq!(config.format, CanonicalFormat::Csv);
+    assert_eq!(config.output, Some(PathBuf::from("out.csv")));
+
+    let missing = Command::parse(["normalize".to_string()]);
+    assert_eq!(missing, Err(ArgsError::MissingInput));
+    let to = Command::parse(
+        "normalize in.csv --to xml"
+            .split_whitespace()
+            .map(String::from),
+    );
+    assert_eq!(
+        to,
+        Err(ArgsError::InvalidValue(
+            "--to".to_string(),
+            "xml".to_string()
+        ))
+    );
+}
+
+#[test]
+fn verify_command() {
+    let command = Command::parse(["verify".to_string(), "out.csv".to_string()]);
+    assert_eq!(command, Ok(Command::Verify(PathBuf::from("out.csv"))));
+    let missing = Command::parse(["verify".to_string()]);
+    assert_eq!(missing, Err(ArgsError::MissingInput));
+    assert!(
+        args("in.csv --output out.csv --output-trailer")
+            .expect("valid args")
+            .output_trailer
+    );
+}
+
+#[test]
+fn run_command() {
+    let command = Command::parse(["run".to_string(), "run.json".to_string()]);
+    assert_eq!(command, Ok(Command::Run(PathBuf::from("run.json"))));
+    assert_eq!(
+        Command::parse(["run".to_string()]),
+        Err(ArgsError::MissingInput)
+    );
+}
+
+#[test]
+fn plan_command() {
+    let command = Command::parse(
+        "plan in.csv --partitions 8 --output plan.json"
+            .split_whitespace()
+            .map(String::from),
+    )
+    .expect("valid args");
+    let Command::Plan(config) = command else {
+        panic!("expected a plan, got {command:?}");
+    };
+    assert_eq!(config.partitions, 8);
+    assert_eq!(config.output, Some(PathBuf::from("plan.json")));
+    assert_eq!(
+        Command::parse(
+            "plan in.csv --partitions 0"
+                .split_whitespace()
+                .map(String::from)
+        ),
+        Err(ArgsError::InvalidValue(
+            "--partitions".to_string(),
+            "0".to_string()
+        ))
+    );
+
+    let dir = std::env::temp_dir().join("tx_engine_test_cli_plan");
+    std::fs::create_dir_all(&dir).expect("failed to create the plan dir");
+    let plan = dir.join("plan.json");
+    std::fs::write(
+        &plan,
+        r#"{"scheme": "range", "partitions": [
+            {"index": 0, "first_client": 0, "last_client": 9, "clients": 2, "transactions": 5},
+            {"index": 1, "first_client": 10, "last_client": 65535, "clients": 1, "transactions": 5}]}"#,
+    )
+    .expect("failed to write the plan");
+    let parsed =
+        args(&format!("in.csv --plan-partition {}:0", plan.display())).expect("valid args");
+    let [ClientFilter::Allow(clients)] = parsed.client_filters.as_slice() else {
+        panic!("expected an allowlist, got {:?}", parsed.client_filters);
+    };
+    assert_eq!(clients.len(), 10);
+    assert!(matches!(
+        args(&format!("in.csv --plan-partition {}:2", plan.display())),
+        Err(ArgsError::InvalidValue(..))
+    ));
+}
@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    invariants::ConservationCheck,
+    model::{Account, ClientId, ClientMerge, Clients, ErasurePolicy, MergeError},
+    reports::open_disputes,
+};
+
+fn clients(input: &str) -> (Clients, Arc<Mutex<ConservationCheck>>) {
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input.as_bytes());
+    let mut clients = Clients::new(Vec::new());
+    let check = Arc::new(Mutex::new(ConservationCheck::default()));
+    clients.add_observer(check.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+    (clients, check)
+}
+
+#[test]
+/// Balances are summed and the open disputes follow the funds to the surviving client
+fn merge_clients() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (mut clients, check) = clients(
+        r#"
+        type, client, tx, amount
+        deposit, 1, 1, 2.0
+        deposit, 2, 2, 1.0
+        deposit, 2, 3, 0.5
+        dispute, 2, 3,"#,
+    );
+
+    let merge = clients
+        .merge_clients(ClientId(2), ClientId(1))
+        .expect("valid merge");
+    assert_eq!(
+        merge,
+        ClientMerge {
+            from: ClientId(2),
+            into: ClientId(1),
+            available: dec!(1.0),
+            held: dec!(0.5),
+            disputes: 1,
+        }
+    );
+    assert!(!clients.accounts.contains_key(&ClientId(2)));
+    assert_eq!(
+        clients.accounts[&ClientId(1)],
+        Account::new(dec!(3.0), dec!(0.5), false)
+    );
+    let disputes = open_disputes(&clients);
+    assert_eq!(disputes.len(), 1);
+    assert_eq!(disputes[0].client, ClientId(1));
+
+    // the reassigned deposits are disputed and resolved through the surviving client
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        "type,client,tx,amount\nresolve,1,3,\ndispute,1,2,\nchargeback,1,2,\n".as_bytes(),
+    )));
+    assert_eq!(
+        clients.accounts[&ClientId(1)],
+        Account::new(dec!(2.5), dec!(0), true)
+    );
+
+    // the funds of the merged client are accounted for when the surviving one is erased
+    clients.erase_client(ClientId(1), ErasurePolicy::Zero);
+    check
+        .lock()
+        .expect("poisoned")
+        .verify(&clients)
+        .expect("funds are conserved");
+}
+
+#[test]
+/// Merges that would lose funds or a lock are refused and change nothing
+fn refused_merges() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (mut clients, _) = clients(
+        r#"
+        type, client, tx, amount
+        deposit, 1, 1, 2.0
+        deposit, 2, 2, 1.0
+        dispute, 2, 2,
+        chargeback, 2, 2,
+        deposit, 3, 3, 1.0"#,
+    );
+    clients.erase_client(ClientId(3), ErasurePolicy::Zero);
+
+    assert_eq!(
+        clients.merge_clients(ClientId(1), ClientId(1)),
+        Err(MergeError::SameClient(ClientId(1)))
+    );
+    assert_eq!(
+        clients.merge_clients(ClientId(9), ClientId(1)),
+        Err(MergeError::UnknownClient(ClientId(9)))
+    );
+    assert_eq!(
+        clients.merge_clients(ClientId(1), ClientId(2)),
+        Err(MergeError::Locked(ClientId(2)))
+    );
+    assert_eq!(
+        clients.merge_clients(ClientId(3), ClientId(1)),
+        Err(MergeError::Erased(ClientId(3)))
+    );
+    assert_eq!(
+        clients.accounts[&ClientId(1)],
+        Account::new(dec!(2.0), dec!(0), false)
+    );
+}
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use tx_engine::csv_input::read_transactions_from_csv;
+
+#[cfg(feature = "compression")]
+const INPUT: &str = "type,client,tx,amount\ndeposit,1,1,1.0\nwithdrawal,1,2,0.5\n";
+
+fn write_input(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, bytes).expect("failed to write the input");
+    path
+}
+
+#[cfg(feature = "compression")]
+#[test]
+/// Compressed inputs are detected by their magic bytes and read like plain csv
+fn gzip_and_zstd() {
+    use std::io::Write;
+    use tx_engine::csv_input::ConversionError;
+
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(INPUT.as_bytes())
+        .expect("failed to compress");
+    let gzip = write_input("tx_engine_test_input.csv.gz", &gzip.finish().expect("gzip"));
+    let zstd = zstd::encode_all(INPUT.as_bytes(), 3).expect("failed to compress");
+    let zstd = write_input("tx_engine_test_input.csv.zst", &zstd);
+
+    for path in [gzip, zstd] {
+        let transactions: Vec<_> = read_transactions_from_csv(&path)
+            .expect("failed to open the input")
+            .collect::<Result<_, ConversionError>>()
+            .expect("invalid transactions");
+        assert_eq!(transactions.len(), 2, "{path:?}");
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+/// Without the feature a compressed input is refused instead of being parsed as garbage
+fn compression_disabled() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let path = write_input("tx_engine_test_disabled.csv.gz", &[0x1f, 0x8b, 0x08, 0x00]);
+    assert!(read_transactions_from_csv(&path).is_err());
+}
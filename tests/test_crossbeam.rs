@@ -0,0 +1,57 @@
+#![cfg(feature = "crossbeam")]
+
+use std::{num::NonZeroUsize, ops::Not, time::Duration};
+
+use tx_engine::{
+    channel,
+    csv_input::transactions_from_reader,
+    model::{Clients, OutputMode},
+    spawn_writer_thread,
+};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+dispute,2,2,
+chargeback,2,2,
+deposit,3,3,3.0
+withdrawal,1,4,0.5
+";
+
+#[test]
+/// A crossbeam sender feeds a writer thread, through the parallel pipeline too
+fn crossbeam_writer_thread() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let writer = spawn_writer_thread(Vec::new(), rx);
+    let mut clients = Clients::new(tx);
+    clients.sorted_output = true;
+    clients.load_transactions_parallel(
+        transactions_from_reader(csv::Reader::from_reader(INPUT.as_bytes())),
+        NonZeroUsize::new(2).expect("not zero"),
+    );
+    clients.send_to_output(OutputMode::SkipLocked);
+    let output =
+        String::from_utf8(writer.join().expect("writer thread panicked")).expect("not utf-8");
+    assert_eq!(
+        output,
+        "client,available,held,total,locked\n2,0,0,0,true\n1,0.5,0,0.5,false\n3,3,0,3,false\n"
+    );
+}
+
+#[test]
+/// The shutdown of a background thread is seen by `select!`, when signalled or disconnected
+fn select_shutdown() {
+    let (stop, stopped) = channel::unbounded::<()>();
+    assert!(channel::wait_for_shutdown(&stopped, Duration::from_millis(1)).not());
+    stop.send(()).expect("failed to signal");
+    assert!(channel::wait_for_shutdown(
+        &stopped,
+        Duration::from_secs(60)
+    ));
+    drop(stop);
+    assert!(channel::wait_for_shutdown(
+        &stopped,
+        Duration::from_secs(60)
+    ));
+}
@@ -1,5 +1,12 @@
 use std::path::Path;
-use tx_engine::csv_input::{ConversionError, read_transactions_from_csv, transactions_from_reader};
+use tx_engine::cancel::CancellationToken;
+use tx_engine::csv_input::{
+    ColumnMapping, ConversionError, CsvOptions, FollowConfig, follow_transactions_from_csv,
+    long_path, read_transactions_from_csv, read_transactions_from_csv_with_options,
+    transactions_from_byte_reader, transactions_from_mapped_reader, transactions_from_reader,
+    transactions_with_quarantine,
+};
+use tx_engine::model::{InputCsvRecord, TransactionKind};
 
 /// loads the sample csv
 #[test]
@@ -50,6 +57,40 @@ fn invalid_transaction_type() {
     );
 }
 
+#[test]
+/// The type column is read into its kind and written back by name, an unknown type keeps its name
+fn transaction_kinds() {
+    let input = "type,client,tx,amount\nwithdrawal,1,1,1\nchargeback,1,1,\nmove,1,2,1\n";
+    let records: Vec<InputCsvRecord> = csv::Reader::from_reader(input.as_bytes())
+        .into_deserialize()
+        .collect::<Result<_, _>>()
+        .expect("valid records");
+    let kinds: Vec<&TransactionKind> = records
+        .iter()
+        .map(|record| &record.transaction_type)
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            &TransactionKind::Withdrawal,
+            &TransactionKind::Chargeback,
+            &TransactionKind::Unknown("move".to_string())
+        ]
+    );
+
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    for record in &records {
+        wtr.serialize(record).expect("failed to write the record");
+    }
+    assert_eq!(String::from_utf8(wtr.into_inner().unwrap()).unwrap(), input);
+
+    let mut transactions = transactions_from_reader(csv::Reader::from_reader(input.as_bytes()));
+    assert!(matches!(
+        transactions.nth(2),
+        Some(Err(ConversionError::InvalidTransactionType(name))) if name == "move"
+    ));
+}
+
 #[test]
 fn missing_amount() {
     let _ = tracing_subscriber::fmt().with_test_writer().try_init();
@@ -110,3 +151,322 @@ fn invalid_decimal() {
             .any(|t| t.is_err_and(|e| matches!(e, ConversionError::NegativeAmount(_))))
     );
 }
+
+/// rows that fail conversion are copied to the quarantine with the input header, valid rows are not
+#[test]
+fn quarantine_failed_rows() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 1
+        transfer, 1, 2, 1.0
+        withdrawal, 1, 3, 0.5
+        deposit, 1, 4, -2.0"#
+        .as_bytes();
+
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .flexible(true)
+        .from_reader(input_reader);
+    let mut quarantine = Vec::new();
+    let results: Vec<bool> =
+        transactions_with_quarantine(csv_reader, &mut quarantine, &CsvOptions::default())
+            .expect("failed to read the header")
+            .map(|t| t.is_ok())
+            .collect();
+    assert_eq!(results, vec![true, false, false, true, false]);
+    assert_eq!(
+        String::from_utf8(quarantine).expect("invalid utf8"),
+        "type,client,tx,amount\ndeposit,1\ntransfer,1,2,1.0\ndeposit,1,4,-2.0\n"
+    );
+}
+
+#[test]
+/// Semicolon delimited and headerless tab delimited inputs, headerless rows may omit the amount
+fn dialects() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let semicolon = CsvOptions {
+        delimiter: b';',
+        ..Default::default()
+    };
+    let input = "type;client;tx;amount\ndeposit;1;1;\"1.5\"\nwithdrawal;1;2;0.5\n";
+    let transactions: Vec<_> =
+        transactions_from_reader(semicolon.reader_builder().from_reader(input.as_bytes()))
+            .collect::<Result<_, ConversionError>>()
+            .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+
+    let headerless_tsv = CsvOptions {
+        delimiter: b'\t',
+        has_headers: false,
+        flexible: true,
+        ..Default::default()
+    };
+    let input = "deposit\t1\t1\t1.5\ndispute\t1\t1\n";
+    let transactions: Vec<_> = transactions_from_reader(
+        headerless_tsv
+            .reader_builder()
+            .from_reader(input.as_bytes()),
+    )
+    .collect::<Result<_, ConversionError>>()
+    .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[1].type_name(), "dispute");
+
+    let mut quarantine = Vec::new();
+    let results: Vec<bool> = transactions_with_quarantine(
+        headerless_tsv
+            .reader_builder()
+            .from_reader("deposit\t1\t1\t1.5\ndeposit\t1\tx\t1\n".as_bytes()),
+        &mut quarantine,
+        &headerless_tsv,
+    )
+    .expect("failed to create the quarantine")
+    .map(|t| t.is_ok())
+    .collect();
+    assert_eq!(results, vec![true, false]);
+    assert_eq!(
+        String::from_utf8(quarantine).expect("invalid utf8"),
+        "deposit\t1\tx\t1\n"
+    );
+}
+
+#[test]
+/// Columns mapped by name or by position, unknown names are refused up front
+fn column_mapping() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let by_name: ColumnMapping = "type=kind,client=customer,tx=tx_id,amount=value"
+        .parse()
+        .expect("valid mapping");
+    let input = "tx_id, kind, customer, value\n1, deposit, 7, 2.0\n2, withdrawal, 7, 0.5\n";
+    let transactions: Vec<_> = transactions_from_mapped_reader(
+        CsvOptions::default()
+            .reader_builder()
+            .from_reader(input.as_bytes()),
+        &by_name,
+    )
+    .expect("valid mapping")
+    .collect::<Result<_, ConversionError>>()
+    .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0].type_name(), "deposit");
+    assert_eq!(transactions[0].client_id().0, 7);
+    assert_eq!(transactions[1].tx_id().0, 2);
+
+    let by_position: ColumnMapping = "tx=0,type=1,client=2,amount=3"
+        .parse()
+        .expect("valid mapping");
+    let headerless = CsvOptions {
+        has_headers: false,
+        flexible: true,
+        ..Default::default()
+    };
+    let input = "3, deposit, 7, 2.0\n4, dispute, 7\n";
+    let transactions: Vec<_> = transactions_from_mapped_reader(
+        headerless.reader_builder().from_reader(input.as_bytes()),
+        &by_position,
+    )
+    .expect("valid mapping")
+    .collect::<Result<_, ConversionError>>()
+    .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[1].type_name(), "dispute");
+
+    // names need a header row
+    let mapped = transactions_from_mapped_reader(
+        headerless.reader_builder().from_reader(input.as_bytes()),
+        &by_name,
+    );
+    assert!(matches!(mapped, Err(ConversionError::ColumnMapping(_))));
+}
+
+#[test]
+/// Lines appended while following are read, even a line written in two parts
+fn follow_growing_file() {
+    use std::{io::Write, thread, time::Duration};
+
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let path = std::env::temp_dir().join("tx_engine_test_follow.csv");
+    std::fs::write(&path, "type,client,tx,amount\ndeposit,1,1,1.0\n").expect("failed to write");
+    let follow = FollowConfig {
+        poll_interval: Duration::from_millis(10),
+        idle_timeout: Some(Duration::from_millis(300)),
+    };
+    let reader_path = path.clone();
+    let reader = thread::spawn(move || {
+        follow_transactions_from_csv(
+            &reader_path,
+            &CsvOptions::default(),
+            follow,
+            &CancellationToken::new(),
+        )
+        .expect("failed to open")
+        .collect::<Result<Vec<_>, ConversionError>>()
+    });
+
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .expect("failed to open for append");
+    thread::sleep(Duration::from_millis(50));
+    file.write_all(b"withdrawal,1,").expect("failed to append");
+    file.flush().expect("failed to flush");
+    thread::sleep(Duration::from_millis(50));
+    file.write_all(b"2,0.5\n").expect("failed to append");
+
+    let transactions = reader
+        .join()
+        .expect("reader panicked")
+        .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[1].type_name(), "withdrawal");
+}
+
+#[test]
+/// Files saved by Windows editors start with a byte order mark, it must not end up in the header
+fn bom_prefixed_header() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let path = std::env::temp_dir().join("tx_engine_test_bom.csv");
+    std::fs::write(
+        &path,
+        "\u{feff}type,client,tx,amount\r\ndeposit,1,1,1.0\r\nwithdrawal,1,2,0.5\r\n",
+    )
+    .expect("failed to write");
+
+    let transactions = read_transactions_from_csv(&path)
+        .expect("failed to load the csv")
+        .collect::<Result<Vec<_>, ConversionError>>()
+        .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+
+    // the mapping looks the columns up by name
+    let options = CsvOptions {
+        column_mapping: Some(
+            "type=type,client=client,tx=tx,amount=amount"
+                .parse()
+                .expect("valid mapping"),
+        ),
+        ..CsvOptions::default()
+    };
+    let mapped = read_transactions_from_csv_with_options(&path, &options)
+        .expect("failed to load the csv")
+        .collect::<Result<Vec<_>, ConversionError>>()
+        .expect("invalid transactions");
+    assert_eq!(mapped.len(), 2);
+
+    let followed = follow_transactions_from_csv(
+        &path,
+        &CsvOptions::default(),
+        FollowConfig {
+            poll_interval: std::time::Duration::from_millis(10),
+            idle_timeout: Some(std::time::Duration::from_millis(50)),
+        },
+        &CancellationToken::new(),
+    )
+    .expect("failed to open")
+    .collect::<Result<Vec<_>, ConversionError>>()
+    .expect("invalid transactions");
+    assert_eq!(followed.len(), 2);
+}
+
+#[test]
+/// Only absolute Windows paths over `MAX_PATH` get the extended form
+fn long_paths() {
+    let short = Path::new("data/input_example.csv");
+    assert_eq!(long_path(short), short);
+    #[cfg(windows)]
+    {
+        let dir = "d".repeat(250);
+        assert_eq!(long_path(Path::new(r"C:\in.csv")), Path::new(r"C:\in.csv"));
+        assert_eq!(
+            long_path(Path::new(&format!(r"C:\{dir}\in.csv"))),
+            Path::new(&format!(r"\\?\C:\{dir}\in.csv"))
+        );
+        assert_eq!(
+            long_path(Path::new(&format!(r"\\server\share\{dir}\in.csv"))),
+            Path::new(&format!(r"\\?\UNC\server\share\{dir}\in.csv"))
+        );
+    }
+    #[cfg(not(windows))]
+    {
+        let long = format!("/{}/in.csv", "d".repeat(300));
+        assert_eq!(long_path(Path::new(&long)), Path::new(&long));
+    }
+}
+
+// transactions, errors as text
+type Results = Vec<Result<String, String>>;
+
+// the transactions of both readers and the rows the byte reader left to serde
+fn serde_and_byte_transactions(input: &str, options: &CsvOptions) -> (Results, Results, u64) {
+    let debug = |result: Result<_, ConversionError>| {
+        result
+            .map(|transaction| format!("{transaction:?}"))
+            .map_err(|err| err.to_string())
+    };
+    let serde = transactions_from_reader(options.reader_builder().from_reader(input.as_bytes()))
+        .map(debug)
+        .collect();
+    let mut byte_records =
+        transactions_from_byte_reader(options.reader_builder().from_reader(input.as_bytes()));
+    let bytes = byte_records.by_ref().map(debug).collect();
+    (serde, bytes, byte_records.fallbacks())
+}
+
+#[test]
+/// The byte records give the same transactions as serde, the ambiguous rows going through serde
+fn byte_records() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = "type, client, tx, amount
+        deposit, 1, 1, 1.50
+        withdrawal, 01, 2, 0.0001
+        deposit, 2, 3, 100.000
+        dispute, 1, 1,
+        resolve, 1, 1,
+        chargeback, 1, 1,
+        deposit, 1, 4,
+        deposit, +3, 5, 2
+        deposit, 3, 6, 1e3
+        deposit, 3, 7, -2
+        deposit, 3, 8, .5
+        deposit, 3, 9, 5.
+        deposit, 3, 10, 12345678901234567.1
+        deposit, 3, 11, 0.000000000000000000000000000001
+        deposit, 70000, 12, 1
+        deposit, 3, 4294967296, 1
+        deposit, 3, x, 1
+        transfer, 3, 13, 1
+        deposit, 3, 14, 1, 2
+        deposit, 3, 15, 999999999999999";
+    let options = CsvOptions {
+        flexible: true,
+        ..Default::default()
+    };
+    let (serde, bytes, fallbacks) = serde_and_byte_transactions(input, &options);
+    assert_eq!(bytes, serde);
+    assert_eq!(fallbacks, 12);
+    // trailing zeros are dropped like serde does
+    assert!(serde[0].as_ref().is_ok_and(|t| t.contains("amount: 1.5 }")));
+
+    // headerless rows, the amount may be omitted
+    let headerless = CsvOptions {
+        has_headers: false,
+        flexible: true,
+        ..Default::default()
+    };
+    let (serde, bytes, fallbacks) =
+        serde_and_byte_transactions("deposit,1,1,2.5\ndispute,1,1\n", &headerless);
+    assert_eq!(bytes, serde);
+    assert_eq!(fallbacks, 1);
+
+    // reordered columns are found by name, without the expected columns every row goes through serde
+    let (serde, bytes, fallbacks) =
+        serde_and_byte_transactions("amount,tx,client,type\n2.5,1,1,deposit\n", &options);
+    assert_eq!(bytes, serde);
+    assert_eq!(fallbacks, 0);
+    let (serde, bytes, fallbacks) =
+        serde_and_byte_transactions("type,client,tx\ndeposit,1,1\n", &options);
+    assert_eq!(bytes, serde);
+    assert_eq!(fallbacks, 1);
+}
@@ -0,0 +1,130 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::{ConversionError, transactions_from_reader},
+    custom_types::{CustomTransactionHandler, RegistrationError, register_transaction_type},
+    invariants::ConservationCheck,
+    model::{
+        Account, ClientId, Clients, CustomTransaction, DisputableTransaction,
+        DisputableTransactionStatus, DisputableTransactions, InputCsvRecord, Transaction,
+        TransactionId, TransactionKind,
+    },
+    spawn_writer_thread,
+};
+
+/// A disputable credit that requires a positive amount
+struct Bonus;
+
+impl CustomTransactionHandler for Bonus {
+    fn validate(&self, transaction: &CustomTransaction) -> Result<(), ConversionError> {
+        match transaction.amount {
+            Some(amount) if amount.is_sign_positive() => Ok(()),
+            _ => Err(ConversionError::MissingAmount(
+                transaction.type_name.to_string(),
+            )),
+        }
+    }
+
+    fn apply(
+        &self,
+        transaction: &CustomTransaction,
+        account: &mut Account,
+        disputable_transactions: &mut DisputableTransactions,
+    ) {
+        let amount = transaction.amount.unwrap_or_default();
+        account.adjust(amount, dec!(0));
+        disputable_transactions.insert(
+            transaction.tx,
+            DisputableTransaction {
+                client: transaction.client,
+                status: DisputableTransactionStatus::NotDisputedAmount(amount),
+            },
+        );
+    }
+}
+
+/// Locks the account without an amount
+struct Freeze;
+
+impl CustomTransactionHandler for Freeze {
+    fn apply(&self, _: &CustomTransaction, account: &mut Account, _: &mut DisputableTransactions) {
+        account.lock();
+    }
+}
+
+const INPUT: &str = r#"
+    type, client, tx, amount
+    deposit, 1, 1, 10.0
+    bonus, 1, 2, 5.0
+    dispute, 1, 2,
+    bonus, 2, 3,
+    bonus, 2, 4, 1.5
+    freeze, 2, 5,
+    bonus, 2, 6, 1.0
+    refund, 2, 7, 1.0"#;
+
+#[test]
+/// Registered types are parsed, applied, observed and written back like the built-in ones
+fn custom_transaction_types() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    register_transaction_type("bonus", Bonus).expect("not a built-in type");
+    register_transaction_type("freeze", Freeze).expect("not a built-in type");
+    assert_eq!(
+        register_transaction_type("deposit", Freeze),
+        Err(RegistrationError::BuiltIn("deposit".to_string()))
+    );
+
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+    let transactions: Vec<_> = transactions_from_reader(csv_reader).collect();
+    assert!(matches!(
+        transactions[3],
+        Err(ConversionError::MissingAmount(_)) // rejected by validate
+    ));
+    assert!(matches!(
+        transactions[7],
+        Err(ConversionError::InvalidTransactionType(_)) // not registered
+    ));
+    let bonus = transactions[4].as_ref().expect("valid bonus");
+    assert_eq!(bonus.type_name(), "bonus");
+    assert_eq!(InputCsvRecord::from(bonus).amount, Some(dec!(1.5)));
+    assert_eq!(
+        InputCsvRecord::from(bonus).transaction_type,
+        TransactionKind::Custom("bonus")
+    );
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let check = Arc::new(Mutex::new(ConservationCheck::default()));
+    clients.add_observer(check.clone());
+    clients.load_transactions(transactions.into_iter());
+
+    // the bonus can be disputed like a deposit
+    assert_eq!(
+        clients.accounts[&ClientId(1)],
+        Account::new(dec!(10), dec!(5), false)
+    );
+    // frozen before the last bonus
+    assert_eq!(
+        clients.accounts[&ClientId(2)],
+        Account::new(dec!(1.5), dec!(0), true)
+    );
+    let check = check.lock().expect("poisoned");
+    assert_eq!(check.custom, dec!(6.5));
+    assert_eq!(check.verify(&clients), Ok(()));
+
+    let constructed = Transaction::Custom(Box::new(CustomTransaction {
+        type_name: "bonus",
+        client: ClientId(3),
+        tx: TransactionId(8),
+        amount: Some(dec!(1)),
+    }));
+    assert_eq!(constructed.client_id(), ClientId(3));
+    assert_eq!(constructed.tx_id(), TransactionId(8));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "dense-accounts")]
+
+use std::sync::{Arc, Mutex};
+
+use rust_decimal::dec;
+use tx_engine::model::{Account, ClientId, Clients, OutputMode, Transaction, TransactionId};
+
+fn deposit(client: u16, tx: u32, amount: rust_decimal::Decimal) -> Transaction {
+    Transaction::Deposit {
+        client: ClientId(client),
+        tx: TransactionId(tx),
+        amount,
+    }
+}
+
+#[test]
+/// The accounts of the whole id range are kept by client id, the output comes out sorted
+fn full_id_range() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut clients = Clients::new(Vec::new());
+    assert!(clients.accounts.is_empty());
+    for (tx, client) in [u16::MAX, 0, 7].into_iter().enumerate() {
+        clients.apply_transaction(deposit(client, tx as u32, dec!(1.5)));
+    }
+    clients.apply_transaction(deposit(7, 3, dec!(1)));
+    assert_eq!(clients.accounts.len(), 3);
+    assert_eq!(
+        clients.accounts[&ClientId(7)],
+        Account::new(dec!(2.5), dec!(0), false)
+    );
+    assert_eq!(
+        clients.accounts.keys().copied().collect::<Vec<_>>(),
+        vec![ClientId(0), ClientId(7), ClientId(u16::MAX)]
+    );
+
+    clients.merge_clients(ClientId(0), ClientId(7)).unwrap();
+    assert!(!clients.accounts.contains_key(&ClientId(0)));
+    assert_eq!(clients.accounts.len(), 2);
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    clients.add_sink(output.clone());
+    clients.send_to_output(OutputMode::All);
+    let output = output.lock().unwrap();
+    assert_eq!(
+        output.iter().map(|(client, _)| *client).collect::<Vec<_>>(),
+        vec![ClientId(7), ClientId(u16::MAX)]
+    );
+}
@@ -0,0 +1,112 @@
+use std::io::{BufReader, Read};
+
+use tx_engine::csv_input::{
+    ConversionError, CsvOptions, read_transactions_from_csv,
+    read_transactions_from_csv_with_options,
+};
+use tx_engine::encoding::{InputEncoding, TranscodingReader, decode_input};
+
+const INPUT: &str = "type,client,tx,amount,memo\ndeposit,1,1,1.0,café\nwithdrawal,1,2,0.5,€ 𝄞\n";
+
+fn utf16(text: &str, bom: bool, little_endian: bool) -> Vec<u8> {
+    let units = bom.then_some(0xfeff).into_iter().chain(text.encode_utf16());
+    units
+        .flat_map(|unit| match little_endian {
+            true => unit.to_le_bytes(),
+            false => unit.to_be_bytes(),
+        })
+        .collect()
+}
+
+fn decoded(input: Vec<u8>, encoding: Option<InputEncoding>) -> std::io::Result<String> {
+    let mut text = String::new();
+    // a 1 byte buffer splits every code unit and surrogate pair across reads
+    decode_input(
+        BufReader::with_capacity(1, std::io::Cursor::new(input)),
+        encoding,
+    )?
+    .read_to_string(&mut text)?;
+    Ok(text)
+}
+
+#[test]
+/// UTF-16 is detected from its byte order mark, or read as given without one
+fn utf16_inputs() {
+    assert_eq!(decoded(utf16(INPUT, true, true), None).unwrap(), INPUT);
+    assert_eq!(decoded(utf16(INPUT, true, false), None).unwrap(), INPUT);
+    assert_eq!(
+        decoded(utf16(INPUT, false, true), Some(InputEncoding::Utf16Le)).unwrap(),
+        INPUT
+    );
+    assert_eq!(
+        decoded(utf16(INPUT, false, false), Some(InputEncoding::Utf16Be)).unwrap(),
+        INPUT
+    );
+    // the byte order mark wins over the given byte order
+    assert_eq!(
+        decoded(utf16(INPUT, true, false), Some(InputEncoding::Utf16Le)).unwrap(),
+        INPUT
+    );
+}
+
+#[test]
+/// A lone surrogate is not silently replaced
+fn invalid_utf16() {
+    let mut input = utf16("deposit,1,1,", true, true);
+    input.extend_from_slice(&0xdc00u16.to_le_bytes());
+    let err = decoded(input, None).expect_err("unpaired surrogate");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let mut reader = TranscodingReader::new(&[0x00, 0xd8, 0x41, 0x00][..], InputEncoding::Utf16Le);
+    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+/// Latin-1 must be given, every byte is a character
+fn latin1_input() {
+    let input: Vec<u8> = "deposit,1,1,1.0,caf\u{e9}"
+        .chars()
+        .map(|char| char as u8)
+        .collect();
+    assert_eq!(
+        decoded(input.clone(), Some(InputEncoding::Latin1)).unwrap(),
+        "deposit,1,1,1.0,café"
+    );
+    assert!(decoded(input, None).is_err()); // not valid UTF-8
+}
+
+#[test]
+/// Partner exports in UTF-16 or Latin-1 are read like UTF-8 ones, instead of failing the first record
+fn transcoded_csv_files() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let dir = std::env::temp_dir();
+
+    let path = dir.join("tx_engine_test_utf16.csv");
+    std::fs::write(&path, utf16(INPUT, true, true)).expect("failed to write");
+    let transactions = read_transactions_from_csv(&path)
+        .expect("failed to load the csv")
+        .collect::<Result<Vec<_>, ConversionError>>()
+        .expect("invalid transactions");
+    assert_eq!(transactions.len(), 2);
+
+    let path = dir.join("tx_engine_test_latin1.csv");
+    let latin1: Vec<u8> = "type,client,tx,amount,memo\ndeposit,1,1,1.0,caf\u{e9}\n"
+        .chars()
+        .map(|char| char as u8)
+        .collect();
+    std::fs::write(&path, latin1).expect("failed to write");
+    assert!(
+        read_transactions_from_csv(&path)
+            .expect("failed to load the csv")
+            .any(|transaction| transaction.is_err())
+    );
+    let options = CsvOptions {
+        encoding: Some(InputEncoding::Latin1),
+        ..Default::default()
+    };
+    let transactions = read_transactions_from_csv_with_options(&path, &options)
+        .expect("failed to load the csv")
+        .collect::<Result<Vec<_>, ConversionError>>()
+        .expect("invalid transactions");
+    assert_eq!(transactions.len(), 1);
+}
@@ -0,0 +1,78 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    model::{ClientId, Clients, Erasure, ErasurePolicy, Transaction, TransactionId},
+    reports::open_disputes,
+    spawn_writer_thread,
+    statement_export::StatementRecorder,
+};
+
+#[test]
+/// An erased client disappears from the accounts, the open disputes and the observers' history,
+/// and its id is rejected afterwards
+fn erase_client() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 2.0
+        deposit, 2, 2, 1.0
+        deposit, 1, 3, 0.5
+        dispute, 1, 3,
+        dispute, 2, 2,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let recorder = Arc::new(Mutex::new(StatementRecorder::default()));
+    clients.add_observer(recorder.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    let erasure = clients.erase_client(ClientId(1), ErasurePolicy::TransferOut);
+    assert_eq!(
+        erasure,
+        Erasure {
+            client: ClientId(1),
+            policy: ErasurePolicy::TransferOut,
+            amount: dec!(2.5),
+        }
+    );
+    assert!(!clients.accounts.contains_key(&ClientId(1)));
+    assert!(
+        open_disputes(&clients)
+            .iter()
+            .all(|dispute| dispute.client != ClientId(1))
+    );
+    assert!(
+        !recorder
+            .lock()
+            .expect("poisoned")
+            .history
+            .contains_key(&ClientId(1))
+    );
+
+    // tombstone: the id does not come back
+    clients.apply_transaction(Transaction::Deposit {
+        client: ClientId(1),
+        tx: TransactionId(4),
+        amount: dec!(1),
+    });
+    assert!(!clients.accounts.contains_key(&ClientId(1)));
+
+    // other clients are untouched
+    assert_eq!(clients.accounts[&ClientId(2)].held(), dec!(1));
+    assert_eq!(open_disputes(&clients).len(), 1);
+
+    // erasing an unknown client still records the tombstone
+    let erasure = clients.erase_client(ClientId(9), ErasurePolicy::Zero);
+    assert_eq!(erasure.amount, dec!(0));
+    assert!(clients.erased.contains(&ClientId(9)));
+}
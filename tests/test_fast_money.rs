@@ -0,0 +1,62 @@
+#![cfg(feature = "fast-money")]
+
+use rust_decimal::dec;
+use tx_engine::model::{Account, ClientId, Clients, MergeError, Transaction, TransactionId};
+
+fn deposit(client: u16, tx: u32, amount: rust_decimal::Decimal) -> Transaction {
+    Transaction::Deposit {
+        client: ClientId(client),
+        tx: TransactionId(tx),
+        amount,
+    }
+}
+
+#[test]
+/// The balances are written like decimals, amounts are rounded to 4 decimal places when applied
+fn fixed_point_balances() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut clients = Clients::new(Vec::new());
+    clients.apply_transaction(deposit(1, 1, dec!(2.00)));
+    clients.apply_transaction(deposit(1, 2, dec!(0.5)));
+    clients.apply_transaction(Transaction::Dispute {
+        client: ClientId(1),
+        tx: TransactionId(2),
+    });
+    let account = &clients.accounts[&ClientId(1)];
+    assert_eq!(account.available().to_string(), "2.00");
+    assert_eq!(account.held().to_string(), "0.5");
+    assert_eq!(account.total().to_string(), "2.50");
+
+    clients.apply_transaction(deposit(2, 3, dec!(0.00005)));
+    clients.apply_transaction(deposit(2, 4, dec!(0.00015)));
+    assert_eq!(
+        clients.accounts[&ClientId(2)],
+        Account::new(dec!(0.0002), dec!(0), false)
+    );
+}
+
+#[test]
+/// A transaction overflowing the balances is ignored instead of wrapping around
+fn overflow() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut clients = Clients::new(Vec::new());
+    assert!(clients.apply_transaction(deposit(1, 1, dec!(900_000_000_000_000))));
+    assert!(!clients.apply_transaction(deposit(1, 2, dec!(900_000_000_000_000))));
+    assert!(!clients.apply_transaction(deposit(2, 3, dec!(1_000_000_000_000_000))));
+    assert!(
+        !clients
+            .disputable_transactions
+            .contains_key(&TransactionId(2))
+    );
+    assert_eq!(
+        clients.accounts[&ClientId(1)].available(),
+        dec!(900_000_000_000_000)
+    );
+
+    clients.apply_transaction(deposit(2, 4, dec!(100_000_000_000_000)));
+    assert_eq!(
+        clients.merge_clients(ClientId(2), ClientId(1)),
+        Err(MergeError::Overflow(ClientId(2), ClientId(1)))
+    );
+    assert!(clients.accounts.contains_key(&ClientId(2)));
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "fault-injection")]
+use std::{
+    io::{self, Read, Write},
+    sync::mpsc,
+};
+
+use tx_engine::{
+    csv_input::{ConversionError, transactions_from_reader},
+    fault_injection::{FaultConfig, FaultyReader, FaultyWriter},
+    model::{ClientId, Clients},
+    spawn_writer_thread,
+};
+
+#[test]
+fn parse_spec() {
+    let config: FaultConfig = "io_error=0.5,truncate_at=10,seed=7"
+        .parse()
+        .expect("valid spec");
+    assert_eq!(config.io_error, 0.5);
+    assert_eq!(config.truncate_at, Some(10));
+    assert_eq!(config.seed, 7);
+    assert!("bogus=1".parse::<FaultConfig>().is_err());
+}
+
+#[test]
+/// truncating the input mid-row drops the rows after the cut
+fn truncated_input() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,2,2,2.0\n";
+    let config = FaultConfig {
+        truncate_at: Some(input.find("deposit,2").expect("row exists") as u64 + 4),
+        ..Default::default()
+    };
+    let csv_reader =
+        csv::ReaderBuilder::new().from_reader(FaultyReader::new(input.as_bytes(), config));
+    let transactions: Vec<_> = transactions_from_reader(csv_reader).collect();
+    assert_eq!(transactions.len(), 2);
+    assert!(transactions[0].is_ok());
+    assert!(matches!(transactions[1], Err(ConversionError::CsvError(_))));
+}
+
+#[test]
+fn io_errors_are_deterministic() {
+    let config = FaultConfig {
+        io_error: 0.5,
+        ..Default::default()
+    };
+    let outcomes = |config: FaultConfig| -> Vec<bool> {
+        let mut reader = FaultyReader::new(io::repeat(b'a'), config);
+        (0..64)
+            .map(|_| reader.read(&mut [0u8; 4]).is_ok())
+            .collect()
+    };
+    let first = outcomes(config.clone());
+    assert!(first.contains(&true) && first.contains(&false));
+    assert_eq!(first, outcomes(config));
+}
+
+#[test]
+/// a panic in the writer thread surfaces when joining it, the accounts are still applied
+fn writer_panic() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let config = FaultConfig {
+        panic: 1.0,
+        ..Default::default()
+    };
+    let input = "type,client,tx,amount\ndeposit,1,1,1.0\n".as_bytes();
+    let (tx, rx) = mpsc::channel();
+    let thread_id = spawn_writer_thread(FaultyWriter::new(io::sink(), config), rx);
+    let mut clients = Clients::new(tx);
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(input)));
+    assert!(clients.accounts.contains_key(&ClientId(1)));
+    clients.send_to_output(tx_engine::model::OutputMode::All);
+    assert!(thread_id.join().is_err());
+
+    let mut writer = FaultyWriter::new(Vec::new(), FaultConfig::default());
+    writer.write_all(b"no faults").expect("write failed");
+    assert_eq!(writer.into_inner(), b"no faults");
+}
@@ -0,0 +1,108 @@
+use std::{io, sync::mpsc};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    filter::{ClientFilter, filter_transactions},
+    model::{ClientId, Clients},
+    spawn_writer_thread,
+};
+
+const INPUT: &str = r#"
+    type, client, tx, amount
+    deposit, 1, 1, 1.0
+    deposit, 2, 2, 2.0
+    deposit, 3, 3, 3.0
+    withdrawal, 2, 4, 1.5
+    deposit, 1, bad, 1.0"#;
+
+fn load(filter: ClientFilter) -> (Clients, u64) {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let mut filtered = filter.apply(transactions_from_reader(csv_reader));
+    clients.load_transactions(&mut filtered);
+    let skipped = filtered.skipped();
+    (clients, skipped)
+}
+
+#[test]
+/// Only allowlisted clients get an account, invalid rows are not counted as skipped
+fn allowlist() {
+    let (clients, skipped) = load(ClientFilter::Allow([ClientId(2)].into()));
+    assert_eq!(skipped, 2);
+    assert_eq!(clients.accounts.len(), 1);
+    assert_eq!(clients.accounts[&ClientId(2)].total(), dec!(0.5));
+}
+
+#[test]
+fn blocklist() {
+    let (clients, skipped) = load(ClientFilter::Block([ClientId(2)].into()));
+    assert_eq!(skipped, 2);
+    assert_eq!(clients.accounts.len(), 2);
+    assert!(!clients.accounts.contains_key(&ClientId(2)));
+}
+
+#[test]
+/// Disabled types are skipped for every client and counted per type
+fn disabled_types() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let mut filtered = filter_transactions(vec![], transactions_from_reader(csv_reader))
+        .with_disabled_types(["withdrawal"].into());
+    clients.load_transactions(&mut filtered);
+    assert_eq!(filtered.skipped(), 0);
+    assert_eq!(filtered.skipped_types().get("withdrawal"), Some(&1));
+    assert_eq!(filtered.skipped_types().get("deposit"), None);
+    assert_eq!(clients.accounts[&ClientId(2)].total(), dec!(2.0));
+}
+
+#[test]
+/// The sample is about the requested size, and the same clients are picked for the same seed
+fn sample() {
+    let sample = ClientFilter::Sample {
+        percent: 10.0,
+        seed: 1,
+    };
+    let sampled: Vec<u16> = (0..=u16::MAX)
+        .filter(|client| sample.keeps(ClientId(*client)))
+        .collect();
+    let expected = u16::MAX as usize / 10;
+    assert!(
+        sampled.len().abs_diff(expected) < expected / 20,
+        "{}",
+        sampled.len()
+    );
+
+    let same_seed: Vec<u16> = (0..=u16::MAX)
+        .filter(|client| sample.keeps(ClientId(*client)))
+        .collect();
+    assert_eq!(sampled, same_seed);
+
+    let other_seed = ClientFilter::Sample {
+        percent: 10.0,
+        seed: 2,
+    };
+    assert!(
+        sampled
+            .iter()
+            .any(|client| !other_seed.keeps(ClientId(*client)))
+    );
+
+    let everyone = ClientFilter::Sample {
+        percent: 100.0,
+        seed: 1,
+    };
+    assert!((0..=u16::MAX).all(|client| everyone.keeps(ClientId(client))));
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "fix")]
+use std::{io::Write, net::TcpListener, thread};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::ConversionError,
+    fix_input::{FixMessage, FixReader, FixSession, transactions_from_fix_reader},
+    model::{ClientId, Transaction, TransactionId},
+};
+
+fn fill(exec_id: &str, account: &str, side: &str, qty: &str, px: &str) -> Vec<u8> {
+    FixMessage::encode(
+        "8",
+        &[
+            (1, account.to_string()),
+            (17, exec_id.to_string()),
+            (150, "F".to_string()),
+            (54, side.to_string()),
+            (32, qty.to_string()),
+            (31, px.to_string()),
+        ],
+    )
+}
+
+#[test]
+/// sells are deposits, buys withdrawals, non fill messages are skipped
+fn drop_copy_file() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut input = Vec::new();
+    input.extend(fill("1", "3", "2", "10", "1.5"));
+    input.push(b'\n');
+    input.extend(FixMessage::encode("0", &[])); // heartbeat
+    input.push(b'\n');
+    input.extend(fill("2", "3", "1", "2", "0.25"));
+    let mut corrupted = fill("3", "3", "1", "2", "0.25");
+    corrupted[20] = b'X';
+    input.extend(corrupted);
+
+    let transactions: Vec<_> = transactions_from_fix_reader(input.as_slice()).collect();
+    assert!(matches!(
+        &transactions[..],
+        [
+            Ok(Transaction::Deposit { client: ClientId(3), tx: TransactionId(1), amount: a1 }),
+            Ok(Transaction::Withdrawal { client: ClientId(3), tx: TransactionId(2), amount: a2 }),
+            Err(ConversionError::Fix(_)),
+        ] if *a1 == dec!(15) && *a2 == dec!(0.5)
+    ));
+}
+
+#[test]
+/// every account of a new allocation is booked, by net money or quantity times average price
+fn allocations() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let allocation = |msg_type: &str, trans_type: &str| {
+        FixMessage::encode(
+            msg_type,
+            &[
+                (70, "A1".to_string()),
+                (71, trans_type.to_string()),
+                (54, "1".to_string()),
+                (6, "2.5".to_string()),
+                (78, "2".to_string()),
+                (79, "4".to_string()),
+                (80, "10".to_string()),
+                (467, "7".to_string()),
+                (79, "5".to_string()),
+                (80, "2".to_string()),
+                (154, "4.9".to_string()),
+                (467, "8".to_string()),
+            ],
+        )
+    };
+    let mut input = allocation("J", "0");
+    input.extend(allocation("AS", "2")); // cancel, not booked
+    input.extend(FixMessage::encode("J", &[(71, "0".to_string())]));
+
+    let transactions: Vec<_> = transactions_from_fix_reader(input.as_slice()).collect();
+    assert_eq!(transactions.len(), 3);
+    assert_eq!(
+        transactions[0].as_ref().expect("valid"),
+        &Transaction::Withdrawal {
+            client: ClientId(4),
+            tx: TransactionId(7),
+            amount: dec!(25)
+        }
+    );
+    assert_eq!(
+        transactions[1].as_ref().expect("valid"),
+        &Transaction::Withdrawal {
+            client: ClientId(5),
+            tx: TransactionId(8),
+            amount: dec!(4.9)
+        }
+    );
+    assert!(matches!(transactions[2], Err(ConversionError::Fix(_))));
+}
+
+#[test]
+/// the session logs on, answers test requests and yields the fills
+fn drop_copy_session() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+    let addr = listener.local_addr().expect("no local addr");
+    let counterparty = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("failed to accept");
+        let mut messages = FixReader::new(stream.try_clone().expect("failed to clone"));
+        let logon = messages.next().expect("no logon").expect("invalid logon");
+        assert_eq!(logon.msg_type(), Some("A"));
+        assert_eq!(logon.get(49), Some("ENGINE"));
+
+        stream
+            .write_all(&FixMessage::encode("A", &[(108, "30".to_string())]))
+            .expect("failed to write");
+        stream
+            .write_all(&FixMessage::encode("1", &[(112, "ping".to_string())]))
+            .expect("failed to write");
+        let heartbeat = messages
+            .next()
+            .expect("no heartbeat")
+            .expect("invalid heartbeat");
+        assert_eq!(heartbeat.msg_type(), Some("0"));
+        assert_eq!(heartbeat.get(112), Some("ping"));
+
+        stream
+            .write_all(&fill("7", "1", "2", "1", "4"))
+            .expect("failed to write");
+        // dropping the stream closes the session
+    });
+
+    let session = FixSession::connect(addr, "ENGINE", "BROKER", 30).expect("failed to connect");
+    let transactions: Vec<_> = session.transactions().collect();
+    counterparty.join().expect("counterparty failed");
+    assert!(matches!(
+        &transactions[..],
+        [Ok(Transaction::Deposit { client: ClientId(1), tx: TransactionId(7), amount })] if *amount == dec!(4)
+    ));
+}
@@ -0,0 +1,30 @@
+use std::{fs, path::Path};
+
+use tx_engine::input_registry::InputRegistry;
+
+#[test]
+/// Processed inputs survive across registry instances (runs)
+fn registry_persists_processed_inputs() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let registry_path = std::env::temp_dir().join("tx_engine_test_registry.csv");
+    let _ = fs::remove_file(&registry_path);
+
+    let mut registry = InputRegistry::open(&registry_path).expect("failed to open");
+    assert!(registry.find("abc").is_none());
+    registry
+        .record("abc", Path::new("monday.csv"))
+        .expect("failed to record");
+    registry
+        .record("def", Path::new("tuesday.csv"))
+        .expect("failed to record");
+
+    let registry = InputRegistry::open(&registry_path).expect("failed to reopen");
+    assert_eq!(
+        registry.find("abc").expect("recorded").path,
+        Path::new("monday.csv")
+    );
+    assert!(registry.find("def").is_some());
+    assert!(registry.find("ghi").is_none());
+    let content = fs::read_to_string(&registry_path).expect("missing registry");
+    assert_eq!(content.matches("sha256,path,processed_at").count(), 1);
+}
@@ -0,0 +1,131 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    invariants::{
+        BalanceFloorCheck, BalanceFloorError, ConservationCheck, ConservationError, FloorBreach,
+    },
+    model::{Account, ClientId, Clients, ErasurePolicy, TransactionId},
+    spawn_writer_thread,
+};
+
+const INPUT: &str = r#"
+    type, client, tx, amount
+    deposit, 1, 1, 10.0
+    deposit, 1, 2, 3.0
+    withdrawal, 1, 3, 4.0
+    withdrawal, 1, 4, 100.0
+    deposit, 2, 5, 5.0
+    dispute, 1, 2,
+    chargeback, 1, 2,
+    deposit, 3, 6, 7.0
+    dispute, 3, 6,"#;
+
+#[test]
+/// Chargebacks, ignored transactions and erasures keep the counters in line with the accounts
+fn conserved_and_tampered() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let check = Arc::new(Mutex::new(ConservationCheck::default()));
+    clients.add_observer(check.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+    clients.erase_client(ClientId(2), ErasurePolicy::Zero);
+
+    let check = check.lock().expect("poisoned");
+    assert_eq!(check.deposits, dec!(25));
+    assert_eq!(check.withdrawals, dec!(4));
+    assert_eq!(check.chargebacks, dec!(3));
+    assert_eq!(check.erased, dec!(5));
+    assert_eq!(check.verify(&clients), Ok(()));
+
+    // a balance changed behind the engine's back
+    clients
+        .accounts
+        .insert(ClientId(9), Account::new(dec!(1), dec!(0), false));
+    assert_eq!(
+        check.verify(&clients),
+        Err(ConservationError {
+            expected: dec!(13),
+            actual: dec!(14)
+        })
+    );
+}
+
+#[test]
+/// Disputes of withdrawn deposits take the available funds below the floor, even if resolved later
+fn balance_floor() -> Result<(), csv::Error> {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = "type,client,tx,amount
+deposit,1,1,10.0
+withdrawal,1,2,8.0
+dispute,1,1,
+resolve,1,1,
+deposit,2,3,5.0
+withdrawal,2,4,5.0
+dispute,2,3,
+deposit,3,5,1.0
+dispute,3,5,
+deposit,4,6,1.0
+withdrawal,4,7,1.0
+dispute,4,6,
+";
+    let mut clients = Clients::new(Vec::new());
+    let check = Arc::new(Mutex::new(BalanceFloorCheck::default()));
+    clients.add_observer(check.clone());
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        input.as_bytes(),
+    )));
+    clients.erase_client(ClientId(4), ErasurePolicy::Zero);
+
+    let check = check.lock().expect("poisoned");
+    assert_eq!(
+        check.breaches().get(&ClientId(1)),
+        Some(&FloorBreach {
+            lowest: dec!(-8),
+            available: dec!(2),
+            txs: vec![TransactionId(1)],
+        })
+    );
+    assert_eq!(
+        check.breaches().len(),
+        2,
+        "client 3 stays at 0, client 4 is erased"
+    );
+    assert_eq!(
+        check.verify(),
+        Err(BalanceFloorError {
+            floor: dec!(0),
+            clients: 2
+        })
+    );
+    let mut report = Vec::new();
+    check.write_report(&mut report)?;
+    assert_eq!(
+        String::from_utf8(report).expect("utf8 report"),
+        "client,lowest_available,available,txs\n1,-8,2,1\n2,-5,-5,3\n"
+    );
+
+    // with a lower floor only client 1 went below it
+    let lowered = Arc::new(Mutex::new(BalanceFloorCheck::new(dec!(-6))));
+    let mut clients = Clients::new(Vec::new());
+    clients.add_observer(lowered.clone());
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        input.as_bytes(),
+    )));
+    let lowered = lowered.lock().expect("poisoned");
+    assert_eq!(
+        lowered.breaches().keys().collect::<Vec<_>>(),
+        [&ClientId(1)]
+    );
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::ConversionError,
+    iso20022_input::{read_transactions_from_iso20022, transactions_from_iso20022_reader},
+    model::{ClientId, Transaction, TransactionId},
+};
+
+#[test]
+/// credits are deposits, debits withdrawals and a return reverses the original deposit
+fn camt053_statement() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let transactions: Vec<Transaction> =
+        read_transactions_from_iso20022(Path::new("data/camt053_example.xml"))
+            .expect("failed to load the xml")
+            .collect::<Result<_, _>>()
+            .expect("invalid transaction");
+
+    let client = ClientId(1);
+    assert!(matches!(
+        transactions[..],
+        [
+            Transaction::Deposit { client: c1, tx: TransactionId(1), amount: a1 },
+            Transaction::Withdrawal { client: c2, tx: TransactionId(2), amount: a2 },
+            Transaction::Dispute { client: c3, tx: TransactionId(1) },
+            Transaction::Chargeback { client: c4, tx: TransactionId(1) },
+        ] if [c1, c2, c3, c4] == [client; 4] && a1 == dec!(10.50) && a2 == dec!(3.00)
+    ));
+}
+
+#[test]
+/// camt.054 notifications: entries without NtryRef use their EndToEndId and a return charges back
+fn camt054_notification() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let transactions: Vec<Transaction> =
+        read_transactions_from_iso20022(Path::new("data/camt054_example.xml"))
+            .expect("failed to load the xml")
+            .collect::<Result<_, _>>()
+            .expect("invalid transaction");
+
+    let client = ClientId(2);
+    assert!(matches!(
+        transactions[..],
+        [
+            Transaction::Deposit { client: c1, tx: TransactionId(11), amount: a1 },
+            Transaction::Withdrawal { client: c2, tx: TransactionId(12), amount: a2 },
+            Transaction::Dispute { client: c3, tx: TransactionId(11) },
+            Transaction::Chargeback { client: c4, tx: TransactionId(11) },
+        ] if [c1, c2, c3, c4] == [client; 4] && a1 == dec!(25.00) && a2 == dec!(5.00)
+    ));
+}
+
+#[test]
+fn pain001_payments() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let xml = r#"
+        <Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03">
+          <CstmrCdtTrfInitn>
+            <PmtInf>
+              <DbtrAcct><Id><Othr><Id>7</Id></Othr></Id></DbtrAcct>
+              <CdtTrfTxInf>
+                <PmtId><EndToEndId>42</EndToEndId></PmtId>
+                <Amt><InstdAmt Ccy="EUR">1.25</InstdAmt></Amt>
+              </CdtTrfTxInf>
+              <CdtTrfTxInf>
+                <PmtId><EndToEndId>not-a-number</EndToEndId></PmtId>
+                <Amt><InstdAmt Ccy="EUR">1.00</InstdAmt></Amt>
+              </CdtTrfTxInf>
+            </PmtInf>
+          </CstmrCdtTrfInitn>
+        </Document>"#;
+    let mut transactions = transactions_from_iso20022_reader(xml.as_bytes());
+    assert!(matches!(
+        transactions.next(),
+        Some(Ok(Transaction::Withdrawal { client: ClientId(7), tx: TransactionId(42), amount })) if amount == dec!(1.25)
+    ));
+    assert!(matches!(
+        transactions.next(),
+        Some(Err(ConversionError::Iso20022(_)))
+    ));
+    assert!(transactions.next().is_none());
+}
@@ -0,0 +1,52 @@
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::ConversionError,
+    json_input::transactions_from_json_reader,
+    model::{ClientId, Transaction, TransactionId},
+};
+
+#[test]
+fn json_lines() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 1.0001}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "0.5"}
+
+{"type": "dispute", "client": 1, "tx": 1}
+{"type": "chargeback", "client": 1, "tx": 1, "amount": null}
+{"type": "deposit", "client": 1, "tx": 3}
+{"type": "deposit", "client": 1, "tx": 4, "amount": -1}
+{"type": "deposit", "client": 1"#;
+
+    let transactions: Vec<_> = transactions_from_json_reader(input.as_bytes()).collect();
+    assert_eq!(transactions.len(), 7);
+    assert_eq!(
+        transactions[0].as_ref().expect("valid"),
+        &Transaction::Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: dec!(1.0001)
+        }
+    );
+    assert_eq!(
+        transactions[1].as_ref().expect("valid"),
+        &Transaction::Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(2),
+            amount: dec!(0.5)
+        }
+    );
+    assert!(matches!(transactions[2], Ok(Transaction::Dispute { .. })));
+    assert!(matches!(
+        transactions[3],
+        Ok(Transaction::Chargeback { .. })
+    ));
+    assert!(matches!(
+        transactions[4],
+        Err(ConversionError::MissingAmount(_))
+    ));
+    assert!(matches!(
+        transactions[5],
+        Err(ConversionError::NegativeAmount(_))
+    ));
+    assert!(matches!(transactions[6], Err(ConversionError::Json(_))));
+}
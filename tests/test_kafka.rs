@@ -0,0 +1,45 @@
+#![cfg(feature = "kafka")]
+
+use rust_decimal::dec;
+use tx_engine::{
+    kafka_input::{KafkaConfig, PayloadFormat, decode_payload},
+    model::{ClientId, Transaction, TransactionId},
+};
+
+#[test]
+/// json objects and headerless csv rows decode to the same transactions
+fn payloads() {
+    let deposit = Transaction::Deposit {
+        client: ClientId(1),
+        tx: TransactionId(2),
+        amount: dec!(1.5),
+    };
+    let json = br#"{"type": "deposit", "client": 1, "tx": 2, "amount": "1.5"}"#;
+    assert_eq!(
+        decode_payload(PayloadFormat::Json, json).expect("valid json"),
+        deposit
+    );
+    assert_eq!(
+        decode_payload(PayloadFormat::Csv, b"deposit, 1, 2, 1.5").expect("valid csv"),
+        deposit
+    );
+    assert_eq!(
+        decode_payload(PayloadFormat::Csv, b"dispute,1,2").expect("valid csv"),
+        Transaction::Dispute {
+            client: ClientId(1),
+            tx: TransactionId(2)
+        }
+    );
+    assert!(decode_payload(PayloadFormat::Csv, b"").is_err());
+    assert!(decode_payload(PayloadFormat::Json, b"deposit,1,2,1.5").is_err());
+}
+
+#[test]
+fn source() {
+    let config =
+        KafkaConfig::from_source("broker-1:9092,broker-2:9092/transactions").expect("valid source");
+    assert_eq!(config.brokers, "broker-1:9092,broker-2:9092");
+    assert_eq!(config.topic, "transactions");
+    assert_eq!(config.group_id, "tx_engine");
+    assert!(KafkaConfig::from_source("transactions").is_none());
+}
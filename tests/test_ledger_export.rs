@@ -0,0 +1,81 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    ledger_export::{LedgerExportConfig, LedgerExporter, LedgerFormat},
+    model::Clients,
+    spawn_writer_thread,
+};
+
+fn export(config: LedgerExportConfig) -> String {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 2.0
+        withdrawal, 1, 2, 5.0
+        withdrawal, 1, 3, 0.5
+        dispute, 1, 1,
+        chargeback, 1, 1,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let exporter = Arc::new(Mutex::new(LedgerExporter::new(Vec::new(), config)));
+    clients.add_observer(exporter.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+    drop(clients);
+
+    let exporter = Arc::into_inner(exporter).expect("exporter is still shared");
+    let out = exporter.into_inner().expect("poisoned").into_inner();
+    String::from_utf8(out).expect("invalid utf8")
+}
+
+#[test]
+/// ignored transactions (withdrawal without funds) produce no entry and every entry balances
+fn beancount_export() {
+    let expected = "\
+1970-01-01 open Assets:Clients:C1:Available
+1970-01-01 open Equity:Engine:Clearing
+1970-01-01 * \"deposit tx 1 client 1\"
+  Assets:Clients:C1:Available  2 CUR
+  Equity:Engine:Clearing  -2 CUR
+
+1970-01-01 * \"withdrawal tx 3 client 1\"
+  Assets:Clients:C1:Available  -0.5 CUR
+  Equity:Engine:Clearing  0.5 CUR
+
+1970-01-01 open Assets:Clients:C1:Held
+1970-01-01 * \"dispute tx 1 client 1\"
+  Assets:Clients:C1:Available  -2 CUR
+  Assets:Clients:C1:Held  2 CUR
+
+1970-01-01 * \"chargeback tx 1 client 1\"
+  Assets:Clients:C1:Held  -2 CUR
+  Equity:Engine:Clearing  2 CUR
+
+";
+    assert_eq!(export(LedgerExportConfig::default()), expected);
+}
+
+#[test]
+fn ledger_cli_export() {
+    let config = LedgerExportConfig {
+        format: LedgerFormat::LedgerCli,
+        date: "2024-05-01".to_string(),
+        commodity: "EUR".to_string(),
+    };
+    let output = export(config);
+    assert!(!output.contains(" open "));
+    assert!(
+        output.starts_with(
+            "2024-05-01 * deposit tx 1 client 1\n  Assets:Clients:C1:Available  2 EUR\n"
+        )
+    );
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tx_engine::{
+    cli::Args,
+    manifest::{
+        CompletionManifest, CountCheck, ExpectedCounts, ManifestError, RunManifest, RunStatus,
+    },
+    model::ProcessingStats,
+};
+
+fn manifest(json: &str) -> RunManifest {
+    serde_json::from_str(json).expect("valid manifest")
+}
+
+#[test]
+/// A manifest is the process command line it stands for
+fn manifest_to_args() {
+    let run = manifest(
+        r#"{
+            "inputs": ["a.csv", "b.csv"],
+            "outputs": {"output": "accounts.csv", "stats-output": "stats.json"},
+            "options": ["--sparse-accounts", "--threads", "2"],
+            "expected": {"invalid": 0}
+        }"#,
+    );
+    let args = run.to_args().expect("valid manifest");
+    assert_eq!(
+        args,
+        [
+            "a.csv",
+            "--merge-input",
+            "b.csv",
+            "--output",
+            "accounts.csv",
+            "--stats-output",
+            "stats.json",
+            "--sparse-accounts",
+            "--threads",
+            "2"
+        ]
+    );
+    let parsed = Args::parse(args).expect("valid args");
+    assert_eq!(parsed.output, Some(PathBuf::from("accounts.csv")));
+    assert!(parsed.sparse_accounts);
+    assert_eq!(
+        run.completion_path(Path::new("runs/daily.json")),
+        Path::new("runs/daily.completion.json")
+    );
+
+    assert!(matches!(
+        manifest(r#"{"inputs": ["a.csv"], "outputs": {"input-format": "json"}}"#).to_args(),
+        Err(ManifestError::UnknownOutput(_))
+    ));
+    assert!(matches!(
+        manifest(r#"{"inputs": []}"#).to_args(),
+        Err(ManifestError::NoInput)
+    ));
+    assert!(matches!(
+        manifest(r#"{"inputs": ["a.csv"], "snapshot": "accounts.json"}"#).to_args(),
+        Err(ManifestError::Unsupported(_))
+    ));
+    assert!(serde_json::from_str::<RunManifest>(r#"{"inputs": [], "input": "a.csv"}"#).is_err());
+}
+
+#[test]
+/// The completion reports the expected counts that were not met and hashes the artifacts
+fn completion_manifest() {
+    let dir = std::env::temp_dir().join("tx_engine_test_manifest");
+    std::fs::create_dir_all(&dir).expect("failed to create the directory");
+    let accounts = dir.join("accounts.csv");
+    std::fs::write(&accounts, "client,available,held,total,locked\n").expect("failed to write");
+    let run = RunManifest {
+        inputs: vec![PathBuf::from("in.csv")],
+        outputs: BTreeMap::from([
+            ("output".to_string(), accounts.clone()),
+            ("exposure-report".to_string(), dir.join("missing.csv")),
+        ]),
+        expected: ExpectedCounts {
+            transactions: Some(3),
+            invalid: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let stats = ProcessingStats {
+        transactions: [("deposit", 2), ("withdrawal", 1)].into(),
+        invalid: 2,
+        ..Default::default()
+    };
+    let started = SystemTime::now() - Duration::from_secs(60);
+
+    let completion =
+        CompletionManifest::new(&run, started, Ok(Some(stats.clone()))).expect("completion");
+    assert_eq!(completion.status, RunStatus::Failed);
+    assert_eq!(
+        completion.mismatches,
+        [CountCheck {
+            count: "invalid".to_string(),
+            expected: 1,
+            actual: 2
+        }]
+    );
+    let [missing, written] = &completion.artifacts[..] else {
+        panic!("unexpected artifacts {:?}", completion.artifacts);
+    };
+    assert!(!missing.written && missing.sha256.is_none());
+    assert!(written.written);
+    assert_eq!(written.bytes, Some(35));
+    assert_eq!(written.sha256.as_ref().map(String::len), Some(64));
+
+    // left by an earlier run
+    let later = SystemTime::now() + Duration::from_secs(60);
+    let completion = CompletionManifest::new(&run, later, Ok(None)).expect("completion");
+    assert_eq!(completion.status, RunStatus::Skipped);
+    assert!(
+        completion
+            .artifacts
+            .iter()
+            .all(|artifact| !artifact.written)
+    );
+
+    let completion = CompletionManifest::new(&run, started, Err("no such file".to_string()))
+        .expect("completion");
+    assert_eq!(completion.status, RunStatus::Failed);
+    assert_eq!(completion.error.as_deref(), Some("no such file"));
+    let path = dir.join("run.completion.json");
+    completion.write(&path).expect("failed to write");
+    let written: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(written["status"], "failed");
+}
@@ -0,0 +1,70 @@
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::{ConversionError, transactions_from_reader},
+    merge::{TxIdNamespaces, merge_sources},
+    model::{ClientId, Clients, Transaction, TransactionId},
+};
+
+fn source(input: &'static str) -> Box<dyn Iterator<Item = Result<Transaction, ConversionError>>> {
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input.as_bytes());
+    Box::new(transactions_from_reader(csv_reader))
+}
+
+#[test]
+/// Two sources reusing the same tx ids are merged, disputes only reference their own source
+fn merge_colliding_sources() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let bank_a = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 10
+        deposit, 1, 2, 5"#;
+    let bank_b = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 100
+        dispute, 1, 1,
+        dispute, 1, 9,
+        deposit, 1, bad, 1"#;
+
+    let mut mapping = Vec::new();
+    let transactions: Vec<_> = merge_sources(
+        vec![source(bank_a), source(bank_b)],
+        TxIdNamespaces::new(vec!["a".to_string(), "b".to_string()], Some(&mut mapping)),
+    )
+    .collect();
+    assert_eq!(transactions.len(), 6);
+    assert!(transactions[5].is_err()); // invalid rows are passed through
+    assert_eq!(
+        transactions[2].as_ref().expect("valid"),
+        &Transaction::Deposit {
+            client: ClientId(1),
+            tx: TransactionId(2),
+            amount: dec!(100)
+        }
+    );
+    assert_eq!(
+        transactions[3].as_ref().expect("valid"),
+        &Transaction::Dispute {
+            client: ClientId(1),
+            tx: TransactionId(2)
+        }
+    );
+    // an unknown reference gets a fresh id, matching nothing
+    assert_eq!(
+        transactions[4].as_ref().expect("valid").tx_id(),
+        TransactionId(3)
+    );
+
+    let (tx, _rx) = std::sync::mpsc::channel();
+    let mut clients = Clients::new(tx);
+    clients.load_transactions(transactions.into_iter());
+    let account = &clients.accounts[&ClientId(1)];
+    assert_eq!(account.held(), dec!(100));
+    assert_eq!(account.total(), dec!(115));
+
+    assert_eq!(
+        String::from_utf8(mapping).expect("utf8"),
+        "source,source_tx,engine_tx\na,1,0\na,2,1\nb,1,2\nb,9,3\n"
+    );
+}
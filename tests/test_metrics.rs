@@ -0,0 +1,52 @@
+use std::{ops::Not, time::Duration};
+
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    metrics::Metrics,
+    model::{Clients, OutputMode},
+    sink::account_channel,
+    spawn_writer_thread,
+};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+withdrawal,1,3,0.5
+dispute,2,2,
+not_a_type,1,4,1.0
+";
+
+#[test]
+/// The transactions are counted by type as they are read, the accounts as they go to the writer
+fn metrics_snapshot() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let metrics = Metrics::new();
+    let reporter = metrics.spawn_reporter(Duration::from_millis(1));
+    let (tx, rx) = metrics.meter_output(account_channel(None));
+    let mut clients = Clients::new(tx);
+    let transactions = metrics.meter(transactions_from_reader(csv::Reader::from_reader(
+        INPUT.as_bytes(),
+    )));
+    clients.load_transactions(transactions);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.transactions, 4);
+    assert_eq!(snapshot.invalid, 1);
+    assert_eq!(snapshot.by_type["deposit"], 2);
+    assert_eq!(snapshot.by_type["dispute"], 1);
+    assert!(snapshot.by_type.contains_key("resolve").not());
+    assert!(snapshot.transactions_per_second > 0.0);
+
+    clients.send_to_output(OutputMode::All);
+    assert_eq!(metrics.snapshot().output_queue_depth, 2); // no writer yet
+    let output = spawn_writer_thread(Vec::new(), rx)
+        .join()
+        .expect("writer thread panicked");
+    assert_eq!(output.iter().filter(|byte| **byte == b'\n').count(), 3);
+    reporter.stop();
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.output_queue_depth, 0);
+    assert_eq!(snapshot.max_output_queue_depth, 2);
+    assert!(snapshot.to_string().contains("deposit 2, dispute 1"));
+}
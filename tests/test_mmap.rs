@@ -0,0 +1,72 @@
+#![cfg(feature = "mmap")]
+
+use std::path::{Path, PathBuf};
+
+use tx_engine::{
+    cli::Args,
+    csv_input::{
+        ConversionError, CsvOptions, read_transactions_from_csv_with_options,
+        read_transactions_from_csv_with_quarantine,
+    },
+    encoding::InputEncoding,
+    model::Transaction,
+};
+
+fn write_input(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, bytes).expect("failed to write the input");
+    path
+}
+
+fn transactions(path: &Path, options: &CsvOptions) -> Vec<Transaction> {
+    read_transactions_from_csv_with_options(path, options)
+        .expect("failed to open the input")
+        .collect::<Result<_, ConversionError>>()
+        .expect("invalid transactions")
+}
+
+#[test]
+/// A mapped input gives the same transactions as a read one, in any encoding
+fn mapped_input() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mapped = CsvOptions {
+        memory_map: true,
+        ..Default::default()
+    };
+    let input = Path::new("data/input_example.csv");
+    assert_eq!(
+        transactions(input, &mapped),
+        transactions(input, &CsvOptions::default())
+    );
+
+    let bom = write_input(
+        "tx_engine_test_mmap_bom.csv",
+        b"\xef\xbb\xbftype,client,tx,amount\ndeposit,1,1,1.0\n",
+    );
+    assert_eq!(transactions(&bom, &mapped).len(), 1);
+    let latin1 = CsvOptions {
+        encoding: Some(InputEncoding::Latin1),
+        ..mapped.clone()
+    };
+    let latin1_input = write_input(
+        "tx_engine_test_mmap_latin1.csv",
+        b"type,client,tx,amount\ndeposit,1,1,1.0\n",
+    );
+    assert_eq!(transactions(&latin1_input, &latin1).len(), 1);
+
+    let empty = write_input("tx_engine_test_mmap_empty.csv", b"");
+    assert!(transactions(&empty, &mapped).is_empty());
+
+    let quarantine = std::env::temp_dir().join("tx_engine_test_mmap_quarantine.csv");
+    let rows = read_transactions_from_csv_with_quarantine(input, &quarantine, &mapped)
+        .expect("failed to open the input")
+        .count();
+    assert_eq!(rows, transactions(input, &mapped).len());
+}
+
+#[test]
+/// `--mmap` maps the csv input
+fn mmap_option() {
+    let args = Args::parse(["in.csv", "--mmap"].map(String::from)).expect("valid args");
+    assert!(args.csv_options.memory_map);
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "msgpack")]
+
+use rust_decimal::dec;
+use serde::Serialize;
+use tx_engine::{
+    csv_input::ConversionError,
+    model::{ClientId, Transaction, TransactionId},
+    msgpack_input::transactions_from_msgpack_reader,
+};
+
+#[derive(Serialize)]
+struct Record {
+    #[serde(rename = "type")]
+    transaction_type: &'static str,
+    client: u16,
+    tx: u32,
+    amount: Option<&'static str>,
+}
+
+#[test]
+/// Maps and arrays are both accepted, a record of the wrong shape is skipped
+fn load_msgpack_stream() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut input = Vec::new();
+    let deposit = Record {
+        transaction_type: "deposit",
+        client: 1,
+        tx: 1,
+        amount: Some("1.0001"),
+    };
+    rmp_serde::encode::write_named(&mut input, &deposit).expect("encoded"); // map
+    rmp_serde::encode::write(&mut input, &("withdrawal", 1, 2, 0.5)).expect("encoded"); // array, float amount
+    rmp_serde::encode::write_named(&mut input, &("deposit", "bad")).expect("encoded");
+    rmp_serde::encode::write(&mut input, &("dispute", 1, 1, None::<&str>)).expect("encoded");
+
+    let transactions: Vec<_> = transactions_from_msgpack_reader(&input[..]).collect();
+    assert_eq!(transactions.len(), 4);
+    assert_eq!(
+        transactions[0].as_ref().expect("valid"),
+        &Transaction::Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: dec!(1.0001)
+        }
+    );
+    assert_eq!(
+        transactions[1].as_ref().expect("valid"),
+        &Transaction::Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(2),
+            amount: dec!(0.5)
+        }
+    );
+    assert!(matches!(transactions[2], Err(ConversionError::Msgpack(_))));
+    assert_eq!(
+        transactions[3].as_ref().expect("valid"),
+        &Transaction::Dispute {
+            client: ClientId(1),
+            tx: TransactionId(1)
+        }
+    );
+}
+
+#[test]
+/// A truncated record stops the stream with an error
+fn truncated_msgpack_stream() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut input = Vec::new();
+    rmp_serde::encode::write(&mut input, &("deposit", 1, 1, "2")).expect("encoded");
+    rmp_serde::encode::write(&mut input, &("deposit", 1, 2, "3")).expect("encoded");
+    input.truncate(input.len() - 2);
+
+    let transactions: Vec<_> = transactions_from_msgpack_reader(&input[..]).collect();
+    assert_eq!(transactions.len(), 2);
+    assert!(transactions[0].is_ok());
+    assert!(matches!(transactions[1], Err(ConversionError::Msgpack(_))));
+}
@@ -0,0 +1,91 @@
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    json_input::transactions_from_json_reader,
+    normalize::{CanonicalFormat, NormalizeSummary, normalize},
+};
+
+#[test]
+/// Invalid rows are dropped, the valid ones are written as canonical csv
+fn normalize_csv() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = "type;client;tx;amount
+deposit; 1; 1; 1.5
+withdrawal; 1; 2; -1.0
+dispute; 1; 1;
+refund; 2; 3; 1.0
+";
+    let csv_reader = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let (out, summary) = normalize(
+        transactions_from_reader(csv_reader),
+        CanonicalFormat::Csv,
+        Vec::new(),
+    )
+    .expect("failed to normalize");
+    assert_eq!(
+        summary,
+        NormalizeSummary {
+            read: 4,
+            written: 2,
+            invalid: 2 // negative amount, unknown type
+        }
+    );
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "type,client,tx,amount\ndeposit,1,1,1.5\ndispute,1,1,\n"
+    );
+}
+
+#[test]
+/// Other input formats normalize to the same csv, with its header even when nothing is valid
+fn normalize_json() {
+    let input = r#"{"type": "withdrawal", "client": 7, "tx": 9, "amount": "0.25"}"#;
+    let (out, summary) = normalize(
+        transactions_from_json_reader(input.as_bytes()),
+        CanonicalFormat::Csv,
+        Vec::new(),
+    )
+    .expect("failed to normalize");
+    assert_eq!(summary.written, 1);
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "type,client,tx,amount\nwithdrawal,7,9,0.25\n"
+    );
+
+    let (out, summary) = normalize(
+        transactions_from_json_reader(r#"{"type": "deposit"}"#.as_bytes()),
+        CanonicalFormat::Csv,
+        Vec::new(),
+    )
+    .expect("failed to normalize");
+    assert_eq!(summary.invalid, 1);
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "type,client,tx,amount\n"
+    );
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+/// The msgpack output is read back by the msgpack input
+fn normalize_msgpack_round_trip() {
+    use tx_engine::msgpack_input::transactions_from_msgpack_reader;
+
+    let input = "type,client,tx,amount\ndeposit,1,1,2.0\ndeposit,2,2,1.0\ndispute,2,2,\n";
+    let (out, summary) = normalize(
+        transactions_from_reader(csv::Reader::from_reader(input.as_bytes())),
+        CanonicalFormat::Msgpack,
+        Vec::new(),
+    )
+    .expect("failed to normalize");
+    assert_eq!(summary.written, 3);
+    let transactions: Vec<_> = transactions_from_msgpack_reader(out.as_slice())
+        .collect::<Result<_, _>>()
+        .expect("invalid msgpack");
+    let expected: Vec<_> = transactions_from_reader(csv::Reader::from_reader(input.as_bytes()))
+        .collect::<Result<_, _>>()
+        .expect("invalid csv");
+    assert_eq!(transactions, expected);
+}
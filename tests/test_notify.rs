@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex, mpsc};
+
+use tx_engine::notify::{BufferConfig, OverflowPolicy, spawn_notification_sink};
+
+// Sends 0..10 to a sink with room for 2 notifications, the consumer is stuck until all were sent
+fn run_slow_consumer(
+    overflow: OverflowPolicy,
+) -> (Vec<u32>, tx_engine::notify::NotificationSummary) {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (release, gate) = mpsc::channel::<()>();
+    let gate = Mutex::new(Some(gate));
+    let delivered = Arc::new(Mutex::new(Vec::new()));
+    let received = delivered.clone();
+    let config = BufferConfig {
+        capacity: 2,
+        overflow,
+    };
+    let (sender, thread_id) = spawn_notification_sink(config, move |notification: u32| {
+        // the first notification waits for the producer to be done
+        if let Some(gate) = gate.lock().expect("gate").take() {
+            let _ = gate.recv();
+        }
+        received.lock().expect("received").push(notification);
+    })
+    .expect("sink spawned");
+    for notification in 0..10 {
+        sender.send(notification);
+    }
+    drop(release);
+    drop(sender);
+    let summary = thread_id.join().expect("sink thread");
+    let delivered = delivered.lock().expect("delivered").clone();
+    (delivered, summary)
+}
+
+#[test]
+/// Drop oldest never waits for the consumer, the latest notifications are kept
+fn drop_oldest() {
+    let (delivered, summary) = run_slow_consumer(OverflowPolicy::DropOldest);
+    assert!(summary.dropped >= 7);
+    assert_eq!(summary.delivered + summary.dropped, 10);
+    assert_eq!(delivered.len() as u64, summary.delivered);
+    assert!(delivered.is_sorted());
+    assert_eq!(delivered.last(), Some(&9));
+}
+
+#[test]
+/// The overflow goes through the spill file, every notification is delivered in order
+fn spill_to_disk() {
+    let spill = std::env::temp_dir().join(format!("tx_engine_spill_{}.jsonl", std::process::id()));
+    let (delivered, summary) = run_slow_consumer(OverflowPolicy::Spill(spill.clone()));
+    assert_eq!(delivered, (0..10).collect::<Vec<_>>());
+    assert_eq!(summary.delivered, 10);
+    assert!(summary.spilled >= 7);
+    assert_eq!(summary.dropped, 0);
+    let _ = std::fs::remove_file(spill);
+}
+
+#[test]
+fn overflow_policy_from_str() {
+    assert_eq!("block".parse(), Ok(OverflowPolicy::Block));
+    assert_eq!("drop-oldest".parse(), Ok(OverflowPolicy::DropOldest));
+    assert_eq!(
+        "spill:alerts.jsonl".parse(),
+        Ok(OverflowPolicy::Spill("alerts.jsonl".into()))
+    );
+    assert!("spill:".parse::<OverflowPolicy>().is_err());
+    assert!("lossy".parse::<OverflowPolicy>().is_err());
+}
@@ -0,0 +1,195 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    audit::{AuditJournal, JournalEntry, read_journal},
+    csv_input::transactions_from_reader,
+    invariants::ConservationCheck,
+    ledger_export::{LedgerExportConfig, LedgerExporter},
+    model::{Account, ClientId, Clients, OPENING_TX, OpeningError},
+    opening_balances::{OpeningBalance, OpeningBalanceError, open_accounts, read_opening_balances},
+    statement_export::StatementRecorder,
+};
+
+fn opening_balances(input: &str) -> Result<Vec<OpeningBalance>, OpeningBalanceError> {
+    read_opening_balances(
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(input.as_bytes()),
+    )
+}
+
+fn invalid_line(input: &str) -> Option<u64> {
+    match opening_balances(input) {
+        Err(OpeningBalanceError::Invalid { line, .. }) => Some(line),
+        _ => None,
+    }
+}
+
+#[test]
+/// The whole file is validated, the first invalid row is reported with its line
+fn validation() {
+    let balances = opening_balances(
+        "client, available, held, locked
+        1, 100, 0, false
+        2, -5.5, 2.25, true",
+    )
+    .expect("valid opening balances");
+    assert_eq!(
+        balances,
+        vec![
+            OpeningBalance {
+                client: ClientId(1),
+                available: dec!(100),
+                held: dec!(0),
+                locked: false,
+            },
+            OpeningBalance {
+                client: ClientId(2),
+                available: dec!(-5.5),
+                held: dec!(2.25),
+                locked: true,
+            },
+        ]
+    );
+
+    let header = "client, available, held, locked\n1, 1, 0, false\n";
+    assert_eq!(invalid_line(&format!("{header}2, 1, -1, false")), Some(3));
+    assert_eq!(
+        invalid_line(&format!("{header}2, 1.00001, 0, false")),
+        Some(3)
+    );
+    assert_eq!(invalid_line(&format!("{header}1, 2, 0, false")), Some(3));
+    // trailing zeros are not precision
+    assert!(opening_balances(&format!("{header}2, 1.500000, 0, false")).is_ok());
+    assert!(matches!(
+        opening_balances(&format!("{header}2, 1, 0, maybe")),
+        Err(OpeningBalanceError::Csv(_))
+    ));
+}
+
+#[test]
+/// Transactions apply on top of the opening balances, a locked account is emitted right away
+fn seeded_accounts() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let emitted = Arc::new(Mutex::new(Vec::new()));
+    let mut clients = Clients::new(emitted.clone());
+    let check = Arc::new(Mutex::new(ConservationCheck::default()));
+    clients.add_observer(check.clone());
+    let balances = opening_balances(
+        "client, available, held, locked
+        1, 100, 0, false
+        2, 5, 2, false
+        3, 7, 0, true",
+    )
+    .expect("valid opening balances");
+    open_accounts(&mut clients, &balances).expect("failed to open the accounts");
+
+    let mut locked = Account::new(dec!(7), dec!(0), false);
+    locked.lock();
+    assert_eq!(
+        *emitted.lock().unwrap(),
+        vec![(ClientId(3), locked.clone())]
+    );
+
+    let stats = clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        "type,client,tx,amount
+deposit,1,1,10
+withdrawal,2,2,6
+withdrawal,2,3,5
+deposit,3,4,1
+"
+        .as_bytes(),
+    )));
+    assert_eq!(stats.rejected, 2); // not enough available funds, locked account
+    assert_eq!(
+        clients.accounts[&ClientId(1)],
+        Account::new(dec!(110), dec!(0), false)
+    );
+    assert_eq!(
+        clients.accounts[&ClientId(2)],
+        Account::new(dec!(0), dec!(2), false)
+    );
+    assert_eq!(clients.accounts[&ClientId(3)], locked);
+    let check = check.lock().unwrap();
+    assert_eq!(check.opening, dec!(114));
+    assert_eq!(check.verify(&clients), Ok(()));
+
+    assert_eq!(
+        clients.open_account(ClientId(1), Account::default()),
+        Err(OpeningError::Existing(ClientId(1)))
+    );
+    clients.erased.insert(ClientId(9));
+    assert_eq!(
+        clients.open_account(ClientId(9), Account::default()),
+        Err(OpeningError::Erased(ClientId(9)))
+    );
+}
+
+#[test]
+/// The journal, statements and ledger start with the opening balance
+fn opening_entries() {
+    let dir = std::env::temp_dir().join("tx_engine_test_opening_balances");
+    fs::create_dir_all(&dir).expect("failed to create the journal dir");
+    let path = dir.join("journal.csv");
+    let _ = fs::remove_file(&path);
+    let statements = Arc::new(Mutex::new(StatementRecorder::default()));
+    let ledger = Arc::new(Mutex::new(LedgerExporter::new(
+        Vec::new(),
+        LedgerExportConfig::default(),
+    )));
+    {
+        let mut clients = Clients::new(Vec::new());
+        clients.add_observer(AuditJournal::open(&path).expect("failed to open the journal"));
+        clients.add_observer(statements.clone());
+        clients.add_observer(ledger.clone());
+        clients
+            .open_account(ClientId(1), Account::new(dec!(5), dec!(1.5), false))
+            .expect("failed to open the account");
+        clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+            "type,client,tx,amount\ndeposit,1,1,2\n".as_bytes(),
+        )));
+    }
+
+    let entries: Vec<JournalEntry> =
+        read_journal(csv::Reader::from_path(&path).expect("failed to read the journal"))
+            .collect::<Result<_, _>>()
+            .expect("invalid journal");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(
+        entries[0],
+        JournalEntry {
+            tx: OPENING_TX,
+            transaction_type: "opening".to_string(),
+            client: ClientId(1),
+            available_before: dec!(0),
+            held_before: dec!(0),
+            available_after: dec!(5),
+            held_after: dec!(1.5),
+            held_delta: dec!(1.5),
+            locked: false,
+        }
+    );
+    assert_eq!(entries[1].available_before, dec!(5));
+
+    let statements = statements.lock().unwrap();
+    let history = &statements.history[&ClientId(1)];
+    assert_eq!(
+        (history[0].transaction_type, history[0].amount),
+        ("opening", dec!(6.5))
+    );
+    assert_eq!(history.len(), 2);
+
+    let ledger = Arc::into_inner(ledger).unwrap().into_inner().unwrap();
+    let ledger = String::from_utf8(ledger.into_inner()).unwrap();
+    assert!(ledger.contains(
+        "* \"opening balance client 1\"
+  Assets:Clients:C1:Available  5 CUR
+  Assets:Clients:C1:Held  1.5 CUR
+  Equity:Opening-Balances  -6.5 CUR
+"
+    ));
+}
@@ -0,0 +1,308 @@
+use std::{fs, io, sync::mpsc};
+
+use rust_decimal::dec;
+use tx_engine::{
+    model::{Account, ClientId, Clients, OutputMode, Transaction, TransactionId},
+    observer::TransactionObserver,
+    output::{
+        AtomicOutput, CsvWriterOptions, LineEnding, OutputFormat, OutputOptions,
+        SerializationFailurePolicy, WriterError,
+    },
+    spawn_writer_thread_with_policy,
+};
+
+/// Fails the first `failures` writes
+struct FlakyWriter {
+    failures: usize,
+    written: Vec<u8>,
+}
+
+impl io::Write for FlakyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(io::Error::other("disk hiccup"));
+        }
+        self.written.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// enough accounts to overflow the csv writer buffer, so the failures surface while serializing
+const ACCOUNTS: u16 = 2000;
+
+fn write_accounts(
+    failures: usize,
+    policy: SerializationFailurePolicy,
+) -> Result<(Vec<u8>, u64, u64), WriterError> {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let wtr = FlakyWriter {
+        failures,
+        written: Vec::new(),
+    };
+    let (tx, rx) = mpsc::channel();
+    let thread_id = spawn_writer_thread_with_policy(wtr, rx, policy, OutputOptions::default());
+    for client in 0..ACCOUNTS {
+        tx.send((ClientId(client), Account::new(dec!(1.5), dec!(0), false)))
+            .expect("failed to send");
+    }
+    drop(tx);
+    let (wtr, summary) = thread_id.join().expect("writer thread panicked")?;
+    Ok((wtr.written, summary.written, summary.failed))
+}
+
+#[test]
+fn parse_policy() {
+    assert_eq!("skip".parse(), Ok(SerializationFailurePolicy::Skip));
+    assert_eq!("retry".parse(), Ok(SerializationFailurePolicy::Retry(3)));
+    assert_eq!("retry:5".parse(), Ok(SerializationFailurePolicy::Retry(5)));
+    assert_eq!(
+        "placeholder".parse(),
+        Ok(SerializationFailurePolicy::Placeholder)
+    );
+    assert_eq!("abort".parse(), Ok(SerializationFailurePolicy::Abort));
+    assert!("retry:x".parse::<SerializationFailurePolicy>().is_err());
+    assert!("ignore".parse::<SerializationFailurePolicy>().is_err());
+}
+
+#[test]
+/// A transient failure is absorbed by the retries, every account is written once
+fn retry() {
+    let (written, ok, failed) =
+        write_accounts(2, SerializationFailurePolicy::Retry(3)).expect("no abort");
+    assert_eq!((ok, failed), (ACCOUNTS as u64, 0));
+    let output = String::from_utf8(written).expect("invalid utf8");
+    assert_eq!(output.lines().count(), ACCOUNTS as usize + 1);
+}
+
+#[test]
+/// Skipped accounts are counted, abort fails the run
+fn skip_and_abort() {
+    let (_, ok, failed) = write_accounts(1, SerializationFailurePolicy::Skip).expect("no abort");
+    assert_eq!(failed, 1);
+    assert_eq!(ok, ACCOUNTS as u64 - 1);
+
+    let aborted = write_accounts(1, SerializationFailurePolicy::Abort);
+    assert!(matches!(aborted, Err(WriterError::Aborted { .. })));
+}
+
+#[test]
+/// A semicolon delimited CRLF output for consumers that cannot read the default dialect
+fn semicolon_crlf_output() -> Result<(), WriterError> {
+    let options = OutputOptions {
+        dialect: CsvWriterOptions {
+            delimiter: b';',
+            line_ending: LineEnding::Crlf,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel();
+    let thread_id =
+        spawn_writer_thread_with_policy(Vec::new(), rx, SerializationFailurePolicy::Skip, options);
+    tx.send((ClientId(1), Account::new(dec!(1.5), dec!(0.5), false)))
+        .expect("failed to send");
+    drop(tx);
+    let (wtr, _summary) = thread_id.join().expect("writer thread panicked")?;
+    assert_eq!(
+        String::from_utf8(wtr).expect("utf8 output"),
+        "client;available;held;total;locked\r\n1;1.5;0.5;2.0;false\r\n"
+    );
+    Ok(())
+}
+
+// output of the accounts of clients 1 to `accounts` in this format, client 2 is locked
+fn json_output(format: OutputFormat, accounts: u16) -> Result<String, WriterError> {
+    let options = OutputOptions {
+        format,
+        ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel();
+    let thread_id =
+        spawn_writer_thread_with_policy(Vec::new(), rx, SerializationFailurePolicy::Skip, options);
+    for client in 1..=accounts {
+        tx.send((
+            ClientId(client),
+            Account::new(dec!(1.5), dec!(0), client == 2),
+        ))
+        .expect("failed to send");
+    }
+    drop(tx);
+    let (wtr, _summary) = thread_id.join().expect("writer thread panicked")?;
+    Ok(String::from_utf8(wtr).expect("utf8 output"))
+}
+
+#[test]
+/// Json array and newline delimited json outputs, amounts are strings to stay exact
+fn json_outputs() -> Result<(), WriterError> {
+    let first = r#"{"client":1,"available":"1.5","held":"0","total":"1.5","locked":false}"#;
+    let second = r#"{"client":2,"available":"1.5","held":"0","total":"1.5","locked":true}"#;
+    assert_eq!(
+        json_output(OutputFormat::Json, 2)?,
+        format!("[\n{first},\n{second}\n]\n")
+    );
+    assert_eq!(
+        json_output(OutputFormat::NdJson, 2)?,
+        format!("{first}\n{second}\n")
+    );
+    assert_eq!(json_output(OutputFormat::Json, 0)?, "[]\n");
+    assert_eq!(json_output(OutputFormat::NdJson, 0)?, "");
+    assert_eq!("ndjson".parse(), Ok(OutputFormat::NdJson));
+    Ok(())
+}
+
+/// Scores the clients by their available funds
+struct RiskObserver;
+
+impl TransactionObserver for RiskObserver {
+    fn on_transaction(&mut self, _: ClientId, _: &Transaction, _: &Account, _: &Account) {}
+
+    fn annotate(&mut self, _client: ClientId, account: &mut Account) {
+        let score = if account.available() < dec!(5) {
+            "high"
+        } else {
+            "low"
+        };
+        account.set_field("risk_score", score);
+    }
+}
+
+fn annotated_output(format: OutputFormat) -> Result<String, WriterError> {
+    let options = OutputOptions {
+        format,
+        columns: vec!["risk_score".to_string(), "reason".to_string()],
+        ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel();
+    let thread_id =
+        spawn_writer_thread_with_policy(Vec::new(), rx, SerializationFailurePolicy::Skip, options);
+    let mut clients = Clients::new(tx);
+    clients.add_observer(RiskObserver);
+    clients.load_transactions(
+        [
+            Transaction::Deposit {
+                client: ClientId(1),
+                tx: TransactionId(1),
+                amount: dec!(2),
+            },
+            Transaction::Withdrawal {
+                client: ClientId(1),
+                tx: TransactionId(2),
+                amount: dec!(1),
+            },
+        ]
+        .into_iter()
+        .map(Ok),
+    );
+    clients
+        .accounts
+        .entry(ClientId(2))
+        .or_default()
+        .set_field("reason", "manual review");
+    clients.send_to_output(OutputMode::All);
+    let (wtr, _summary) = thread_id.join().expect("writer thread panicked")?;
+    Ok(String::from_utf8(wtr).expect("utf8 output"))
+}
+
+#[test]
+/// Fields attached by observers are written as extra columns, empty (or null) when missing
+fn custom_columns() -> Result<(), WriterError> {
+    let csv = annotated_output(OutputFormat::Csv)?;
+    let mut lines: Vec<&str> = csv.lines().collect();
+    lines[1..].sort();
+    assert_eq!(
+        lines,
+        [
+            "client,available,held,total,locked,risk_score,reason",
+            "1,1,0,1,false,high,",
+            "2,0,0,0,false,,manual review",
+        ]
+    );
+    let ndjson = annotated_output(OutputFormat::NdJson)?;
+    let mut lines: Vec<&str> = ndjson.lines().collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            r#"{"client":1,"available":"1","held":"0","total":"1","locked":false,"risk_score":"high","reason":null}"#,
+            r#"{"client":2,"available":"0","held":"0","total":"0","locked":false,"risk_score":null,"reason":"manual review"}"#,
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+/// The output file only replaces the previous one once committed, a dropped output leaves no trace
+fn atomic_output_file() -> Result<(), WriterError> {
+    let dir = std::env::temp_dir().join("tx_engine_test_atomic_output");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("accounts.csv");
+    fs::write(&path, "previous run\n")?;
+
+    let (atomic_output, file) = AtomicOutput::create(&path)?;
+    let (tx, rx) = mpsc::channel();
+    let thread_id = spawn_writer_thread_with_policy(
+        file,
+        rx,
+        SerializationFailurePolicy::Skip,
+        OutputOptions::default(),
+    );
+    tx.send((ClientId(1), Account::new(dec!(1), dec!(0), false)))
+        .expect("failed to send");
+    drop(tx);
+    let (file, _summary) = thread_id.join().expect("writer thread panicked")?;
+    drop(file);
+    assert_eq!(fs::read_to_string(&path)?, "previous run\n");
+    atomic_output.commit()?;
+    assert_eq!(
+        fs::read_to_string(&path)?,
+        "client,available,held,total,locked\n1,1,0,1,false\n"
+    );
+
+    let (atomic_output, mut file) = AtomicOutput::create(&path)?;
+    io::Write::write_all(&mut file, b"partial")?;
+    drop(atomic_output);
+    assert_eq!(
+        fs::read_dir(&dir)?.count(),
+        1,
+        "the temporary file is removed"
+    );
+    assert!(fs::read_to_string(&path)?.starts_with("client"));
+    Ok(())
+}
+
+#[test]
+/// The table is aligned on its widest cells, amounts to the right
+fn table_output() -> Result<(), WriterError> {
+    let options = OutputOptions {
+        format: OutputFormat::Table,
+        columns: vec!["reason".to_string()],
+        ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel();
+    let thread_id =
+        spawn_writer_thread_with_policy(Vec::new(), rx, SerializationFailurePolicy::Skip, options);
+    let mut flagged = Account::new(dec!(-12.3456), dec!(100), true);
+    flagged.set_field("reason", "chargeback");
+    tx.send((ClientId(65535), flagged)).expect("failed to send");
+    tx.send((ClientId(2), Account::new(dec!(1.5), dec!(0), false)))
+        .expect("failed to send");
+    drop(tx);
+    let (wtr, summary) = thread_id.join().expect("writer thread panicked")?;
+    assert_eq!(summary.written, 2);
+    assert_eq!(
+        String::from_utf8(wtr).expect("utf8 output"),
+        "\
+client  available  held    total  locked  reason
+------  ---------  ----  -------  ------  ----------
+ 65535   -12.3456   100  87.6544  true    chargeback
+     2        1.5     0      1.5  false
+"
+    );
+    assert_eq!("table".parse(), Ok(OutputFormat::Table));
+    Ok(())
+}
@@ -0,0 +1,243 @@
+#![cfg(feature = "parquet")]
+
+use std::{fs::File, sync::Arc};
+
+use parquet::{
+    data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type},
+    file::{
+        properties::WriterProperties,
+        reader::{FileReader, SerializedFileReader},
+        writer::SerializedFileWriter,
+    },
+    record::Field,
+    schema::parser::parse_message_type,
+};
+use rust_decimal::{Decimal, dec};
+use tx_engine::{
+    csv_input::ConversionError,
+    model::{Account, ClientId, Transaction, TransactionId},
+    output::{OutputFormat, OutputOptions, SerializationFailurePolicy},
+    parquet_input::read_transactions_from_parquet,
+    parquet_output::ParquetAccountWriter,
+    spawn_writer_thread_with_policy,
+};
+
+/// Writes a parquet file with the given rows, amounts as DECIMAL(18, 4), in two row groups
+fn write_parquet(path: &std::path::Path, rows: &[(&str, i32, i64, Option<i64>)]) {
+    let schema = Arc::new(
+        parse_message_type(
+            "message transactions {
+                required binary type (UTF8);
+                required int32 client;
+                required int64 tx;
+                optional int64 amount (DECIMAL(18, 4));
+            }",
+        )
+        .expect("valid schema"),
+    );
+    let file = File::create(path).expect("failed to create the parquet file");
+    let mut writer = SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::default()))
+        .expect("failed to create the writer");
+    for row_group in rows.chunks(rows.len().div_ceil(2)) {
+        let mut row_group_writer = writer.next_row_group().expect("row group");
+        let types: Vec<ByteArray> = row_group.iter().map(|row| row.0.into()).collect();
+        let clients: Vec<i32> = row_group.iter().map(|row| row.1).collect();
+        let txs: Vec<i64> = row_group.iter().map(|row| row.2).collect();
+        let amounts: Vec<i64> = row_group.iter().filter_map(|row| row.3).collect();
+        let levels: Vec<i16> = row_group.iter().map(|row| row.3.is_some() as i16).collect();
+
+        let mut column = row_group_writer
+            .next_column()
+            .expect("column")
+            .expect("type");
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(&types, None, None)
+            .expect("write type");
+        column.close().expect("close");
+        let mut column = row_group_writer
+            .next_column()
+            .expect("column")
+            .expect("client");
+        column
+            .typed::<Int32Type>()
+            .write_batch(&clients, None, None)
+            .expect("write client");
+        column.close().expect("close");
+        let mut column = row_group_writer.next_column().expect("column").expect("tx");
+        column
+            .typed::<Int64Type>()
+            .write_batch(&txs, None, None)
+            .expect("write tx");
+        column.close().expect("close");
+        let mut column = row_group_writer
+            .next_column()
+            .expect("column")
+            .expect("amount");
+        column
+            .typed::<Int64Type>()
+            .write_batch(&amounts, Some(&levels), None)
+            .expect("write amount");
+        column.close().expect("close");
+        row_group_writer.close().expect("close row group");
+    }
+    writer.close().expect("close file");
+}
+
+#[test]
+fn parquet_rows() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let path = std::env::temp_dir().join("tx_engine_test_transactions.parquet");
+    write_parquet(
+        &path,
+        &[
+            ("deposit", 1, 1, Some(15_000)),
+            ("withdrawal", 1, 2, Some(1)),
+            ("dispute", 1, 1, None),
+            ("deposit", 2, 3, Some(-10_000)),
+            ("deposit", 70_000, 4, Some(10_000)),
+        ],
+    );
+
+    let transactions: Vec<_> = read_transactions_from_parquet(&path)
+        .expect("failed to open the parquet file")
+        .collect();
+    assert_eq!(transactions.len(), 5);
+    assert_eq!(
+        transactions[0].as_ref().expect("valid"),
+        &Transaction::Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: dec!(1.5)
+        }
+    );
+    assert_eq!(
+        transactions[1].as_ref().expect("valid"),
+        &Transaction::Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(2),
+            amount: dec!(0.0001)
+        }
+    );
+    assert_eq!(
+        transactions[2].as_ref().expect("valid"),
+        &Transaction::Dispute {
+            client: ClientId(1),
+            tx: TransactionId(1)
+        }
+    );
+    assert!(matches!(
+        transactions[3],
+        Err(ConversionError::NegativeAmount(_))
+    ));
+    assert!(matches!(transactions[4], Err(ConversionError::Parquet(_))));
+}
+
+fn decimal(field: &Field) -> Decimal {
+    let Field::Decimal(decimal) = field else {
+        panic!("not a decimal: {field}");
+    };
+    let bytes: [u8; 16] = decimal.data().try_into().expect("16 bytes");
+    Decimal::from_i128_with_scale(i128::from_be_bytes(bytes), decimal.scale() as u32)
+}
+
+#[test]
+/// The accounts are written with the csv columns, amounts as DECIMAL(38, 4), in row groups
+fn parquet_accounts() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let path = std::env::temp_dir().join("tx_engine_test_accounts.parquet");
+    let file = File::create(&path).expect("failed to create the parquet file");
+    let mut writer = ParquetAccountWriter::new(file, &[])
+        .expect("valid schema")
+        .with_row_group_rows(2);
+    writer.write(ClientId(1), &Account::new(dec!(1.5), dec!(0), false));
+    writer.write(ClientId(2), &Account::new(dec!(-0.0001), dec!(2), true));
+    writer.write(ClientId(65535), &Account::new(dec!(0), dec!(0), false));
+    let (_file, summary) = writer.finish().expect("failed to write");
+    assert_eq!(summary.written, 3);
+
+    let reader = SerializedFileReader::new(File::open(&path).expect("failed to open"))
+        .expect("valid parquet");
+    assert_eq!(reader.num_row_groups(), 2);
+    let rows: Vec<(u16, Decimal, Decimal, Decimal, bool)> = reader
+        .into_iter()
+        .map(|row| {
+            let row = row.expect("valid row");
+            let fields: Vec<&Field> = row.get_column_iter().map(|(_, field)| field).collect();
+            let [
+                Field::UShort(client),
+                available,
+                held,
+                total,
+                Field::Bool(locked),
+            ] = fields[..]
+            else {
+                panic!("unexpected columns: {row}");
+            };
+            (
+                *client,
+                decimal(available),
+                decimal(held),
+                decimal(total),
+                *locked,
+            )
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            (1, dec!(1.5), dec!(0), dec!(1.5), false),
+            (2, dec!(-0.0001), dec!(2), dec!(1.9999), true),
+            (65535, dec!(0), dec!(0), dec!(0), false),
+        ]
+    );
+}
+
+#[test]
+/// The writer thread writes a parquet output when asked for that format
+fn parquet_output_format() {
+    let options = OutputOptions {
+        format: OutputFormat::Parquet,
+        ..Default::default()
+    };
+    let (tx, rx) = std::sync::mpsc::channel();
+    let thread_id =
+        spawn_writer_thread_with_policy(Vec::new(), rx, SerializationFailurePolicy::Skip, options);
+    tx.send((ClientId(1), Account::new(dec!(1), dec!(0), false)))
+        .expect("failed to send");
+    drop(tx);
+    let (file, summary) = thread_id
+        .join()
+        .expect("writer thread panicked")
+        .expect("failed to write");
+    assert_eq!(summary.written, 1);
+    assert!(file.starts_with(b"PAR1") && file.ends_with(b"PAR1"));
+    assert_eq!("parquet".parse(), Ok(OutputFormat::Parquet));
+}
+
+#[test]
+/// Custom columns are optional strings after the csv columns
+fn parquet_custom_columns() {
+    let path = std::env::temp_dir().join("tx_engine_test_custom_columns.parquet");
+    let file = File::create(&path).expect("failed to create the parquet file");
+    let mut writer =
+        ParquetAccountWriter::new(file, &["risk_score".to_string()]).expect("valid schema");
+    let mut flagged = Account::new(dec!(1), dec!(0), false);
+    flagged.set_field("risk_score", "high");
+    writer.write(ClientId(1), &flagged);
+    writer.write(ClientId(2), &Account::new(dec!(1), dec!(0), false));
+    writer.finish().expect("failed to write");
+
+    let reader = SerializedFileReader::new(File::open(&path).expect("failed to open"))
+        .expect("valid parquet");
+    let scores: Vec<Field> = reader
+        .into_iter()
+        .map(|row| {
+            let row = row.expect("valid row");
+            let (name, field) = row.get_column_iter().last().expect("columns");
+            assert_eq!(name, "risk_score");
+            field.clone()
+        })
+        .collect();
+    assert_eq!(scores, [Field::Str("high".to_string()), Field::Null]);
+}
@@ -0,0 +1,181 @@
+use std::{collections::BTreeMap, fs, num::NonZeroUsize, sync::mpsc};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    model::{Account, ClientId, Clients, OutputMode},
+    partition::{
+        PartWriters, PartitionPlan, PartitionScheme, Partitioner, PlannedPartition,
+        count_client_transactions, spawn_partitioned_writer_thread,
+    },
+};
+
+#[test]
+/// Range partitions cover every client id exactly once
+fn range_partitions() {
+    let partitioner = Partitioner {
+        scheme: PartitionScheme::Range,
+        partitions: 3,
+        ..Default::default()
+    };
+    assert_eq!(partitioner.client_range(0), (ClientId(0), ClientId(21845)));
+    assert_eq!(
+        partitioner.client_range(2),
+        (ClientId(43691), ClientId(u16::MAX))
+    );
+    for partition in 0..3 {
+        let (first, last) = partitioner.client_range(partition);
+        assert_eq!(partitioner.partition_of(first), partition);
+        assert_eq!(partitioner.partition_of(last), partition);
+    }
+
+    let hash = Partitioner {
+        scheme: PartitionScheme::Hash,
+        partitions: 3,
+        ..Default::default()
+    };
+    assert_eq!(hash.partition_of(ClientId(7)), 1);
+}
+
+#[test]
+fn partitioned_output_and_manifest() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let dir = std::env::temp_dir().join("tx_engine_test_partitions");
+    let partitioner = Partitioner {
+        scheme: PartitionScheme::Range,
+        partitions: 2,
+        ..Default::default()
+    };
+    let (tx, rx) = mpsc::channel();
+    let thread_id = spawn_partitioned_writer_thread(
+        &dir,
+        partitioner,
+        rx,
+        Default::default(),
+        Default::default(),
+    )
+    .expect("failed to create");
+    tx.send((ClientId(1), Account::new(dec!(1.5), dec!(0), false)))
+        .expect("failed to send");
+    tx.send((ClientId(40000), Account::new(dec!(2), dec!(1), true)))
+        .expect("failed to send");
+    tx.send((ClientId(2), Account::new(dec!(3), dec!(0), false)))
+        .expect("failed to send");
+    drop(tx);
+    let manifest = thread_id
+        .join()
+        .expect("writer thread panicked")
+        .expect("failed to write the partitions");
+
+    assert_eq!(manifest.partitions[0].accounts, 2);
+    assert_eq!(manifest.partitions[1].accounts, 1);
+    assert_eq!(manifest.partitions[1].first_client, Some(32768));
+    assert_eq!(
+        fs::read_to_string(dir.join("accounts_0.csv")).expect("missing partition"),
+        "client,available,held,total,locked\n1,1.5,0,1.5,false\n2,3,0,3,false\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("accounts_1.csv")).expect("missing partition"),
+        "client,available,held,total,locked\n40000,2,1,3,true\n"
+    );
+    let manifest_json = fs::read_to_string(dir.join("manifest.json")).expect("missing manifest");
+    assert!(manifest_json.contains("\"scheme\": \"range\""));
+    assert!(manifest_json.contains("\"last_client\": 65535"));
+}
+
+#[test]
+/// Each part gets the accounts of its worker shard, locked early or emitted concurrently at the end
+fn sharded_parts_and_manifest() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let dir = std::env::temp_dir().join("tx_engine_test_parts");
+    let parts = PartWriters::create(
+        &dir,
+        NonZeroUsize::new(2).expect("not zero"),
+        16,
+        Default::default(),
+        Default::default(),
+    )
+    .expect("failed to create");
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+deposit,3,3,3.0
+dispute,3,3,
+chargeback,3,3,
+deposit,4,4,4.0";
+    let mut clients = Clients::new(parts.router());
+    clients.sorted_output = true;
+    let transactions = transactions_from_reader(csv::Reader::from_reader(input.as_bytes()));
+    clients.load_transactions_parallel(transactions, NonZeroUsize::new(2).expect("not zero"));
+    clients.send_to_output_sharded(OutputMode::SkipLocked, parts.sinks());
+    let manifest = parts.finish().expect("failed to write the parts");
+
+    assert_eq!(manifest.scheme, PartitionScheme::Hash);
+    assert_eq!(manifest.partitions[0].accounts, 2);
+    assert_eq!(manifest.partitions[1].accounts, 2);
+    assert_eq!(manifest.partitions[1].file, "part-1.csv");
+    assert_eq!(
+        fs::read_to_string(dir.join("part-0.csv")).expect("missing part"),
+        "client,available,held,total,locked\n2,2,0,2,false\n4,4,0,4,false\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("part-1.csv")).expect("missing part"),
+        "client,available,held,total,locked\n3,0,0,0,true\n1,1,0,1,false\n"
+    );
+    let manifest_json = fs::read_to_string(dir.join("manifest.json")).expect("missing manifest");
+    assert!(manifest_json.contains("\"scheme\": \"hash\""));
+}
+
+#[test]
+/// Planned ranges cover every client id and follow the load, a busy client gets a shard of its own
+fn balanced_plan() {
+    let input = "type,client,tx,amount
+deposit,2,1,1.0
+deposit,2,2,1.0
+deposit,2,3,1.0
+deposit,2,4,1.0
+deposit,5,5,1.0
+deposit,7,6,1.0
+deposit,9,7,1.0
+withdrawal,9,8,1.0
+";
+    let counts = count_client_transactions(transactions_from_reader(csv::Reader::from_reader(
+        input.as_bytes(),
+    )));
+    assert_eq!(counts[&ClientId(2)], 4);
+    let plan = PartitionPlan::balance(&counts, 2);
+    assert_eq!(plan.scheme, PartitionScheme::Range);
+    assert_eq!(
+        plan.partitions,
+        [
+            PlannedPartition {
+                index: 0,
+                first_client: 0,
+                last_client: 2,
+                clients: 1,
+                transactions: 4
+            },
+            PlannedPartition {
+                index: 1,
+                first_client: 3,
+                last_client: u16::MAX,
+                clients: 3,
+                transactions: 4
+            },
+        ]
+    );
+    assert_eq!(plan.clients(0).map(|clients| clients.len()), Some(3));
+    assert_eq!(plan.clients(2), None);
+
+    // never more partitions than clients, and a single one without clients
+    assert_eq!(PartitionPlan::balance(&counts, 10).partitions.len(), 4);
+    let empty = PartitionPlan::balance(&BTreeMap::new(), 3);
+    assert_eq!(empty.partitions.len(), 1);
+    assert_eq!(
+        (
+            empty.partitions[0].first_client,
+            empty.partitions[0].last_client
+        ),
+        (0, u16::MAX)
+    );
+}
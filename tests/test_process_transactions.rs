@@ -1,9 +1,21 @@
-use std::{io, path::Path, sync::mpsc};
+use std::{
+    io,
+    num::{NonZeroU64, NonZeroUsize},
+    ops::Not,
+    path::Path,
+    sync::{Arc, Mutex, mpsc},
+};
 
 use rust_decimal::dec;
 use tx_engine::{
     csv_input::{read_transactions_from_csv, transactions_from_reader},
-    model::{Account, ClientId, Clients, OutputMode},
+    model::{
+        Account, ClientId, Clients, DisputableTransactionStatus, ErasurePolicy, InputCsvRecord,
+        OutputMode, ProcessingStats, RoundingPolicy, RoundingStrategy, Transaction, TransactionId,
+    },
+    observer::TransactionObserver,
+    reports::open_disputes,
+    simulation::{SimulationConfig, TransactionGenerator, TypeMix},
     spawn_writer_thread,
 };
 
@@ -151,6 +163,321 @@ fn locked() {
     assert_eq!(clients.accounts[&ClientId(2)], expected_client_2);
 }
 
+#[test]
+/// Clients whose transactions were all ignored get no account with sparse accounts
+fn sparse_accounts() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        dispute, 2, 99,
+        resolve, 3, 98,
+        withdrawal, 4, 2, 1.0
+        dispute, 5, 1,"#;
+    let load = |sparse_accounts| {
+        let mut clients = Clients::new(Vec::new());
+        clients.sparse_accounts = sparse_accounts;
+        clients.load_transactions(transactions_from_reader(
+            csv::ReaderBuilder::new()
+                .trim(csv::Trim::All) //trim whitespace around fields
+                .from_reader(input.as_bytes()),
+        ));
+        let mut accounts: Vec<u16> = clients.accounts.keys().map(|client| client.0).collect();
+        accounts.sort_unstable();
+        accounts
+    };
+    assert_eq!(load(false), [1, 2, 3, 4, 5]);
+    // client 5 disputed the deposit of client 1, which moved funds to its held balance
+    assert_eq!(load(true), [1, 5]);
+}
+
+#[test]
+/// Undisputed deposits older than the settle horizon are dropped, disputed ones are kept
+fn settle_horizon() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 1, 2, 2.0
+        dispute, 1, 2,
+        deposit, 2, 3, 3.0
+        withdrawal, 2, 4, 1.0
+        dispute, 1, 1,
+        dispute, 2, 3,
+        resolve, 1, 2,"#;
+    let mut clients = Clients::new(Vec::new());
+    clients.settle_horizon = NonZeroU64::new(3);
+    clients.load_transactions(transactions_from_reader(
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_reader(input.as_bytes()),
+    ));
+    // tx 1 settled 5 transactions later, before its dispute, tx 2 and tx 3 were disputed in time
+    assert_eq!(
+        clients.accounts[&ClientId(1)],
+        Account::new(dec!(3.0), dec!(0), false)
+    );
+    assert_eq!(
+        clients.accounts[&ClientId(2)],
+        Account::new(dec!(-1.0), dec!(3.0), false)
+    );
+    let mut disputable: Vec<u32> = clients
+        .disputable_transactions
+        .keys()
+        .map(|tx| tx.0)
+        .collect();
+    disputable.sort_unstable();
+    assert_eq!(disputable, [2, 3]);
+}
+
+#[test]
+/// Loading reports what was applied, rejected and locked
+fn processing_stats() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        withdrawal, 1, 3, 5.0
+        withdrawal, 2, 4, 0.5
+        deposit, 2, 5, -1.0
+        dispute, 2, 2,
+        chargeback, 2, 2,
+        deposit, 2, 6, 3.0"#;
+    let mut clients = Clients::new(Vec::new());
+    let stats = clients.load_transactions(transactions_from_reader(
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All) //trim whitespace around fields
+            .from_reader(input.as_bytes()),
+    ));
+    assert!(stats.elapsed_seconds >= 0.0);
+    assert_eq!(
+        stats,
+        ProcessingStats {
+            transactions: [
+                ("chargeback", 1),
+                ("deposit", 3),
+                ("dispute", 1),
+                ("withdrawal", 2)
+            ]
+            .into(),
+            invalid: 1,  // negative deposit
+            rejected: 2, // withdrawal without funds, deposit to the locked account
+            locked_accounts: 1,
+            deposits: dec!(3.0),
+            withdrawals: dec!(0.5),
+            elapsed_seconds: stats.elapsed_seconds,
+        }
+    );
+}
+
+#[derive(Default)]
+struct CountingObserver(u64);
+
+impl TransactionObserver for CountingObserver {
+    fn on_transaction(&mut self, _: ClientId, _: &Transaction, _: &Account, _: &Account) {
+        self.0 += 1;
+    }
+}
+
+#[test]
+/// Sharding the clients over worker threads gives the same accounts, stats and locked accounts
+fn parallel_loading() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let transactions: Vec<_> = TransactionGenerator::new(&SimulationConfig {
+        clients: 50,
+        seed: 7,
+        ..SimulationConfig::default()
+    })
+    .take(5_000)
+    .collect();
+    let load = |threads: Option<usize>| {
+        let locked = Arc::new(Mutex::new(Vec::new()));
+        let observer = Arc::new(Mutex::new(CountingObserver::default()));
+        let mut clients = Clients::new(locked.clone());
+        clients.add_observer(observer.clone());
+        let transactions = transactions.iter().cloned().map(Ok);
+        let mut stats = match threads {
+            Some(threads) => clients
+                .load_transactions_parallel(transactions, NonZeroUsize::new(threads).unwrap()),
+            None => clients.load_transactions(transactions),
+        };
+        stats.elapsed_seconds = 0.0;
+        let mut locked = locked.lock().unwrap().clone();
+        locked.sort_unstable_by_key(|(client, _)| client.0);
+        let observed = observer.lock().unwrap().0;
+        let disputes = open_disputes(&clients);
+        (
+            disputes,
+            clients.accounts,
+            clients.disputable_transactions.len(),
+            stats,
+            locked,
+            observed,
+        )
+    };
+    let sequential = load(None);
+    assert!(sequential.3.locked_accounts > 0);
+    assert_eq!(sequential.5, 5_000);
+    for threads in [1, 4, 7] {
+        assert_eq!(load(Some(threads)), sequential);
+    }
+}
+
+#[test]
+/// Pre-sized maps hold the expected accounts and deposits without growing, also when sharded
+fn capacity_hints() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut clients = Clients::with_capacity(Vec::new(), 50, 2_000);
+    let capacity = (
+        clients.accounts.capacity(),
+        clients.disputable_transactions.capacity(),
+    );
+    assert!(capacity.0 >= 50 && capacity.1 >= 2_000);
+    let transactions = TransactionGenerator::new(&SimulationConfig {
+        clients: 50,
+        mix: TypeMix {
+            deposit: 1.0,
+            withdrawal: 0.0,
+            dispute: 0.0,
+            resolve: 0.0,
+            chargeback: 0.0,
+        },
+        ..SimulationConfig::default()
+    })
+    .take(2_000)
+    .map(Ok);
+    clients.load_transactions_parallel(transactions, NonZeroUsize::new(4).unwrap());
+    assert_eq!(clients.disputable_transactions.len(), 2_000);
+    assert_eq!(
+        (
+            clients.accounts.capacity(),
+            clients.disputable_transactions.capacity()
+        ),
+        capacity
+    );
+
+    let mut clients = Clients::new(Vec::new());
+    clients.reserve(10, 100);
+    assert!(clients.disputable_transactions.capacity() >= 100);
+}
+
+#[test]
+/// The open disputes of a client are indexed by owner as they are opened, resolved and charged back
+fn dispute_index() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut clients = Clients::new(Vec::new());
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,2.0
+deposit,1,3,3.0
+deposit,2,4,4.0
+dispute,1,3,
+dispute,1,1,
+dispute,1,2,
+resolve,1,2,
+dispute,2,4,
+chargeback,2,4,
+"
+        .as_bytes(),
+    )));
+    let disputes = |clients: &Clients, client| -> Vec<_> {
+        clients.client_disputes(ClientId(client)).collect()
+    };
+    let expected: Vec<_> = vec![(TransactionId(1), dec!(1.0)), (TransactionId(3), dec!(3.0))];
+    assert_eq!(disputes(&clients, 1), expected);
+    assert_eq!(disputes(&clients, 2), vec![]);
+    assert_eq!(
+        clients.disputed_clients().collect::<Vec<_>>(),
+        vec![ClientId(1)]
+    );
+
+    // merged disputes belong to the client they were merged into, erased ones are gone
+    clients
+        .merge_clients(ClientId(1), ClientId(3))
+        .expect("failed to merge");
+    assert_eq!(disputes(&clients, 1), vec![]);
+    assert_eq!(disputes(&clients, 3), expected);
+    clients.apply_transaction(Transaction::Resolve {
+        client: ClientId(3),
+        tx: TransactionId(1),
+    });
+    assert_eq!(disputes(&clients, 3), expected[1..].to_vec());
+    clients.erase_client(ClientId(3), ErasurePolicy::default());
+    assert_eq!(clients.disputed_clients().count(), 0);
+
+    // the index matches a scan of the disputable transactions
+    let mut clients = Clients::new(Vec::new());
+    clients.load_transactions(
+        TransactionGenerator::new(&SimulationConfig {
+            clients: 200,
+            mix: TypeMix {
+                dispute: 0.3,
+                resolve: 0.05,
+                chargeback: 0.01,
+                ..TypeMix::default()
+            },
+            seed: 11,
+            ..SimulationConfig::default()
+        })
+        .take(5_000)
+        .map(Ok),
+    );
+    let mut scanned: Vec<_> = clients
+        .disputable_transactions
+        .iter()
+        .filter_map(|(tx, disputable)| match disputable.status {
+            DisputableTransactionStatus::DisputedAmount(amount) => {
+                Some((disputable.client, *tx, amount))
+            }
+            DisputableTransactionStatus::NotDisputedAmount(_) => None,
+        })
+        .collect();
+    scanned.sort_unstable_by_key(|(client, tx, _)| (*client, *tx));
+    let mut indexed: Vec<_> = clients
+        .disputed_clients()
+        .flat_map(|client| {
+            clients
+                .client_disputes(client)
+                .map(move |(tx, amount)| (client, tx, amount))
+        })
+        .collect();
+    indexed.sort_unstable_by_key(|(client, tx, _)| (*client, *tx));
+    assert!(scanned.is_empty().not());
+    assert_eq!(indexed, scanned);
+}
+
+#[test]
+/// Parsing on its own thread gives the same accounts and stats, invalid rows included
+fn pipelined_loading() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    for transaction in TransactionGenerator::new(&SimulationConfig {
+        clients: 50,
+        seed: 3,
+        ..SimulationConfig::default()
+    })
+    .take(5_000)
+    {
+        wtr.serialize(InputCsvRecord::from(&transaction)).unwrap();
+    }
+    wtr.write_record(["deposit", "1", "999999", "-1.0"])
+        .unwrap();
+    let input = wtr.into_inner().unwrap();
+
+    let mut sequential = Clients::new(Vec::new());
+    let mut expected = sequential.load_transactions(transactions_from_reader(
+        csv::Reader::from_reader(&input[..]),
+    ));
+    let mut pipelined = Clients::new(Vec::new());
+    let mut stats = pipelined.load_transactions_pipelined(csv::Reader::from_reader(&input[..]));
+    assert_eq!(stats.invalid, 1);
+    (stats.elapsed_seconds, expected.elapsed_seconds) = (0.0, 0.0);
+    assert_eq!(stats, expected);
+    assert_eq!(pipelined.accounts, sequential.accounts);
+}
+
 #[test]
 fn bankers_rounding() {
     let client_15 = Account::new(dec!(0.00015), dec!(0.0), false);
@@ -171,6 +498,49 @@ fn bankers_rounding() {
     assert_ne!(client_25.total(), client_35.total());
 }
 
+#[test]
+/// The balances are rounded to the configured scale when written, not while being applied
+fn output_rounding() {
+    let input = "type,client,tx,amount\ndeposit,1,1,0.125\ndeposit,2,2,0.135\ndeposit,2,3,0.0001\n";
+    let round = |policy: RoundingPolicy| {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut clients = Clients::new(output.clone());
+        clients.rounding = policy;
+        clients.sorted_output = true;
+        clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+            input.as_bytes(),
+        )));
+        assert_eq!(clients.accounts[&ClientId(1)].available(), dec!(0.125));
+        clients.send_to_output(OutputMode::All);
+        let output = output.lock().expect("poisoned");
+        output
+            .iter()
+            .map(|(_, account)| account.available())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        round(RoundingPolicy::default()),
+        [dec!(0.125), dec!(0.1351)]
+    );
+    let two_places = |strategy| RoundingPolicy { scale: 2, strategy };
+    assert_eq!(
+        round(two_places(RoundingStrategy::Bankers)),
+        [dec!(0.12), dec!(0.14)]
+    );
+    assert_eq!(
+        round(two_places(RoundingStrategy::HalfUp)),
+        [dec!(0.13), dec!(0.14)]
+    );
+    assert_eq!(
+        round(two_places(RoundingStrategy::Down)),
+        [dec!(0.12), dec!(0.13)]
+    );
+    assert_eq!(
+        round(two_places(RoundingStrategy::Up)),
+        [dec!(0.13), dec!(0.14)]
+    );
+}
+
 #[test]
 /// Validate that we print the expected output
 fn output() {
@@ -186,12 +556,9 @@ fn output() {
     clients.load_transactions(transactions_iter);
 
     // create a Vec to write to (instead of stdout)
-    clients
-        .send_to_output(OutputMode::SkipLocked)
-        .expect("failed to write to output");
+    clients.send_to_output(OutputMode::SkipLocked);
 
-    let csv_writer = thread_id.join().expect("error joining thread");
-    let out = csv_writer.into_inner().expect("failed to get inner");
+    let out = thread_id.join().expect("error joining thread");
 
     // sort the lines (Since the order of the csv lines is non-deterministic since we use a HashMap internally)
     let output_string = String::from_utf8(out).expect("invalid utf8");
@@ -0,0 +1,43 @@
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    model::ClientId,
+    query::{QuerySummary, TransactionQuery, run_query},
+};
+
+const INPUT: &str = r#"
+    type, client, tx, amount
+    deposit, 1, 1, 1.0
+    deposit, 2, 2, 2.0
+    dispute, 1, 1,
+    chargeback, 1, 1,
+    deposit, 1, bad, 1.0
+    chargeback, 2, 2,"#;
+
+#[test]
+/// Matching transactions are printed in the input format, invalid rows are counted
+fn client_and_type() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+    let query = TransactionQuery {
+        clients: [ClientId(1)].into(),
+        types: ["chargeback", "dispute"].into(),
+        tx: None,
+    };
+    let mut out = Vec::new();
+    let summary =
+        run_query(&query, transactions_from_reader(csv_reader), &mut out).expect("query failed");
+    assert_eq!(
+        summary,
+        QuerySummary {
+            scanned: 6,
+            matched: 2,
+            invalid: 1
+        }
+    );
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "type,client,tx,amount\ndispute,1,1,\nchargeback,1,1,\n"
+    );
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "remote")]
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    thread,
+};
+
+use tx_engine::{
+    csv_input::{ConversionError, CsvOptions, read_transactions_from_csv_with_options},
+    model::Transaction,
+};
+
+// serves one response with the given status and body, returns the url of the input
+fn serve_once(status: &'static str, body: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+    let address = listener.local_addr().expect("no local address");
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("failed to accept");
+        let mut request = BufReader::new(stream);
+        let mut line = String::new();
+        while request
+            .read_line(&mut line)
+            .expect("failed to read request")
+            > 2
+        {
+            line.clear(); // until the blank line ending the headers
+        }
+        let mut stream = request.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .and_then(|_| stream.write_all(&body))
+        .expect("failed to write response");
+    });
+    format!("http://{address}/settlement/input.csv")
+}
+
+fn transactions(input: &str) -> Vec<Transaction> {
+    read_transactions_from_csv_with_options(Path::new(input), &CsvOptions::default())
+        .expect("failed to open the input")
+        .collect::<Result<_, ConversionError>>()
+        .expect("invalid transactions")
+}
+
+#[test]
+/// An input given as a URL is read like the same file
+fn url_input() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut input = String::from("type,client,tx,amount\n");
+    for tx in 1..=200 {
+        input.push_str(&format!("deposit,{},{tx},2.5\n", tx % 5));
+    }
+    input.push_str("withdrawal,1,201,1.0\ndispute,2,2,\n");
+    let path = std::env::temp_dir().join("tx_engine_test_remote.csv");
+    std::fs::write(&path, &input).expect("failed to write the input");
+
+    let url = serve_once("200 OK", input.into_bytes());
+    let read = transactions(&url);
+    assert_eq!(read.len(), 202);
+    assert_eq!(read, transactions(path.to_str().unwrap()));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+/// A gzip file served as is is decompressed like a local one
+fn compressed_url_input() {
+    let input = "type,client,tx,amount\ndeposit,1,1,3.0\nwithdrawal,1,2,1.0\n";
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(input.as_bytes()).unwrap();
+    let url = serve_once("200 OK", encoder.finish().unwrap());
+    assert_eq!(transactions(&url).len(), 2);
+}
+
+#[test]
+/// An error status fails the open
+fn missing_url_input() {
+    let url = serve_once("404 Not Found", b"no such key".to_vec());
+    assert!(
+        read_transactions_from_csv_with_options(Path::new(&url), &CsvOptions::default()).is_err()
+    );
+}
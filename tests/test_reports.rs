@@ -0,0 +1,172 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    model::Clients,
+    reports::{
+        ActivityRecorder, CostRecorder, load_segments, write_exposure_report, write_segment_report,
+    },
+    spawn_writer_thread,
+};
+
+#[test]
+/// Only transactions currently in dispute are reported, chargedback and resolved ones are not
+fn exposure_report() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        deposit, 1, 3, 2.5
+        deposit, 2, 4, 3.0
+        deposit, 3, 5, 3.0
+        dispute, 1, 3,
+        dispute, 1, 1,
+        dispute, 2, 2,
+        dispute, 2, 4,
+        resolve, 2, 4,
+        dispute, 3, 5,
+        chargeback, 3, 5,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+    let transactions_iter = transactions_from_reader(csv_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    clients.load_transactions(transactions_iter);
+
+    let mut out: Vec<u8> = Vec::new();
+    write_exposure_report(&clients, &mut out).expect("failed to write report");
+
+    let expected = "client,tx,amount\n1,1,1\n1,3,2.5\n2,2,2\n";
+    assert_eq!(String::from_utf8(out).expect("invalid utf8"), expected);
+}
+
+#[test]
+/// Ignored transactions (insufficient funds, unknown tx) are not part of the activity
+fn activity_report() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 1, 2, 2.5
+        withdrawal, 1, 3, 0.5
+        withdrawal, 1, 4, 10.0
+        dispute, 1, 1,
+        resolve, 1, 1,
+        dispute, 1, 2,
+        chargeback, 1, 2,
+        deposit, 2, 5, 3.0
+        dispute, 2, 99,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let recorder = Arc::new(Mutex::new(ActivityRecorder::default()));
+    clients.add_observer(recorder.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    let mut out: Vec<u8> = Vec::new();
+    recorder
+        .lock()
+        .expect("poisoned")
+        .write_activity_report(&mut out)
+        .expect("failed to write report");
+
+    let expected = "client,opening_balance,deposits,withdrawals,disputes_opened,disputes_resolved,chargebacks,closing_balance\n\
+        1,0,3.5,0.5,2,1,1,0.5\n\
+        2,0,3,0,0,0,0,3\n";
+    assert_eq!(String::from_utf8(out).expect("invalid utf8"), expected);
+}
+
+#[test]
+/// Clients missing from the segments file are aggregated as unsegmented
+fn segment_report() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        deposit, 2, 2, 2.0
+        deposit, 3, 3, 3.5
+        deposit, 4, 4, 4.0
+        dispute, 3, 3,
+        dispute, 4, 4,
+        chargeback, 4, 4,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    let segments = "client, segment\n1, retail\n2, retail\n3, institutional\n";
+    let segments = load_segments(
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(segments.as_bytes()),
+    )
+    .expect("valid segments");
+
+    let mut out: Vec<u8> = Vec::new();
+    write_segment_report(&clients, &segments, &mut out).expect("failed to write report");
+
+    let expected = "segment,clients,locked,available,held,total\n\
+        institutional,1,0,0.0,3.5,3.5\n\
+        retail,2,0,3,0,3\n\
+        unsegmented,1,1,0,0,0\n";
+    assert_eq!(String::from_utf8(out).expect("invalid utf8"), expected);
+}
+
+#[test]
+/// Ignored transactions are charged like applied ones, disputes weigh more
+fn cost_report() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.0
+        withdrawal, 1, 2, 5.0
+        dispute, 1, 1,
+        dispute, 1, 99,
+        resolve, 1, 1,
+        deposit, 2, 3, 3.0"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let recorder = Arc::new(Mutex::new(CostRecorder::default()));
+    clients.add_observer(recorder.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    let segments = load_segments(csv::Reader::from_reader(
+        "client,segment\n1,retail\n".as_bytes(),
+    ))
+    .expect("valid segments");
+    let mut out: Vec<u8> = Vec::new();
+    recorder
+        .lock()
+        .expect("poisoned")
+        .write_cost_report(&segments, &mut out)
+        .expect("failed to write report");
+
+    let expected = "client,segment,transactions,applied,ignored,disputes_handled,cost\n\
+        1,retail,5,3,2,3,8\n\
+        2,unsegmented,1,1,0,0,1\n";
+    assert_eq!(String::from_utf8(out).expect("invalid utf8"), expected);
+}
@@ -0,0 +1,81 @@
+use rust_decimal::dec;
+use tx_engine::{
+    filter::filter_transactions,
+    model::{ClientId, Transaction, TransactionId},
+    rules::{RuleError, RuleSet},
+};
+
+fn withdrawal(client: u16, amount: rust_decimal::Decimal) -> Transaction {
+    Transaction::Withdrawal {
+        client: ClientId(client),
+        tx: TransactionId(1),
+        amount,
+    }
+}
+
+#[test]
+/// the first matching rule rejects, segments come from the sidecar and missing values never match
+fn rejecting_rules() {
+    let rules = RuleSet::parse(
+        r#"
+        # comments and blank lines are ignored
+        reject when type == "withdrawal" && amount > 10000 && client.segment == "basic"
+
+        reject when !(client < 100) || (type == "dispute" && amount >= 0)
+        "#,
+    )
+    .expect("valid rules")
+    .with_segments([(ClientId(1), "basic".to_string())].into());
+    assert_eq!(rules.rules().len(), 2);
+    assert_eq!(
+        rules.rules()[0].to_string(),
+        r#"reject when type == "withdrawal" && amount > 10000 && client.segment == "basic""#
+    );
+
+    assert_eq!(rules.rejecting(&withdrawal(1, dec!(10000.01))), Some(0));
+    assert_eq!(rules.rejecting(&withdrawal(1, dec!(10000))), None);
+    assert_eq!(rules.rejecting(&withdrawal(2, dec!(20000))), None); // unsegmented
+    assert_eq!(rules.rejecting(&withdrawal(100, dec!(1))), Some(1));
+    let dispute = Transaction::Dispute {
+        client: ClientId(1),
+        tx: TransactionId(1),
+    };
+    assert_eq!(rules.rejecting(&dispute), None); // disputes have no amount
+}
+
+#[test]
+/// rules are type checked when they are compiled
+fn invalid_rules() {
+    for rule in [
+        "type == \"withdrawal\"",              // not a rule
+        "reject when amount",                  // not a condition
+        "reject when amount > \"10\"",         // number compared to a text
+        "reject when type > \"deposit\"",      // texts are not ordered
+        "reject when balance > 1",             // unknown field
+        "reject when amount > 1 &&",           // incomplete
+        "reject when (amount > 1",             // unbalanced
+        "reject when type == \"deposit",       // unterminated string
+        "reject when client == 1 client == 2", // trailing tokens
+    ] {
+        assert!(
+            matches!(
+                RuleSet::parse(&format!("# header\n{rule}")),
+                Err(RuleError::Syntax { line: 2, .. })
+            ),
+            "{rule} should be invalid"
+        );
+    }
+}
+
+#[test]
+fn filter_stage() {
+    let rules = RuleSet::parse("reject when amount > 5").expect("valid rule");
+    let mut filtered = filter_transactions(
+        Vec::new(),
+        [Ok(withdrawal(1, dec!(1))), Ok(withdrawal(1, dec!(6)))].into_iter(),
+    )
+    .with_rules(rules);
+    assert_eq!(filtered.by_ref().count(), 1);
+    let rejected: Vec<u64> = filtered.rejected().map(|(_, rejected)| rejected).collect();
+    assert_eq!(rejected, [1]);
+}
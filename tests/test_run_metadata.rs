@@ -0,0 +1,65 @@
+use std::{fs, path::Path};
+
+use tx_engine::run_metadata::{RunMetadata, file_sha256};
+
+#[test]
+fn input_digest_and_sidecar() {
+    let input = Path::new("data/input_example.csv");
+    let metadata = RunMetadata::new(&[input], "config").expect("failed to hash the inputs");
+    assert_eq!(metadata.engine_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(metadata.run_id.len(), 16);
+    assert_eq!(metadata.inputs.len(), 1);
+    assert_eq!(
+        metadata.inputs[0].sha256,
+        file_sha256(input).expect("failed to hash the input")
+    );
+    // sha256("config")
+    assert_eq!(
+        metadata.config_hash,
+        "b79606fb3afea5bd1609ed40b622142f1c98125abcfe89a76a661b0e8e343910"
+    );
+
+    let dir = std::env::temp_dir().join("tx_engine_test_run_metadata");
+    fs::create_dir_all(&dir).expect("failed to create the output dir");
+    let artifact = dir.join("out.csv");
+    assert_eq!(
+        RunMetadata::sidecar_path(&artifact),
+        dir.join("out.csv.meta.json")
+    );
+    assert_eq!(
+        RunMetadata::sidecar_path(&dir),
+        dir.join("run_metadata.json")
+    );
+
+    metadata
+        .write_sidecar(&artifact)
+        .expect("failed to write the sidecar");
+    let sidecar = fs::read_to_string(dir.join("out.csv.meta.json")).expect("missing sidecar");
+    assert!(sidecar.contains(&format!("\"run_id\": \"{}\"", metadata.run_id)));
+    assert!(sidecar.contains(&metadata.inputs[0].sha256));
+}
+
+#[test]
+/// Reproducible metadata only depends on the inputs and the configuration
+fn reproducible_metadata() {
+    let input = Path::new("data/input_example.csv");
+    let metadata =
+        RunMetadata::reproducible(&[input], "config").expect("failed to hash the inputs");
+    assert_eq!(
+        metadata,
+        RunMetadata::reproducible(&[input], "config").expect("failed to hash the inputs")
+    );
+    assert_eq!(metadata.started_at, None);
+    assert_ne!(
+        metadata.run_id,
+        RunMetadata::reproducible(&[input], "other config")
+            .expect("failed to hash the inputs")
+            .run_id
+    );
+    assert!(
+        RunMetadata::new(&[input], "config")
+            .expect("failed to hash the inputs")
+            .started_at
+            .is_some()
+    );
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "s3")]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+use aws_sdk_s3::{
+    Client,
+    config::{BehaviorVersion, Credentials, Region},
+};
+use tx_engine::{
+    csv_input::{ConversionError, transactions_from_reader},
+    model::Transaction,
+    s3_input::S3ObjectReader,
+};
+
+// an S3 endpoint answering one request with the given status and body, returns a client of it and
+// the request line it received
+fn serve_once(status: &'static str, body: Vec<u8>) -> (Client, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+    let address = listener.local_addr().expect("no local address");
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("failed to accept");
+        let mut request = BufReader::new(stream);
+        let mut request_line = String::new();
+        request.read_line(&mut request_line).unwrap();
+        let mut line = String::new();
+        while request
+            .read_line(&mut line)
+            .expect("failed to read request")
+            > 2
+        {
+            line.clear(); // until the blank line ending the headers
+        }
+        let mut stream = request.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .and_then(|_| stream.write_all(&body))
+        .expect("failed to write response");
+        request_line
+    });
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .endpoint_url(format!("http://{address}"))
+        .force_path_style(true)
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .build();
+    (Client::from_conf(config), server)
+}
+
+#[test]
+/// The object body is streamed into the transactions
+fn s3_object_input() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut input = String::from("type,client,tx,amount\n");
+    for tx in 1..=300 {
+        input.push_str(&format!("deposit,{},{tx},1.25\n", tx % 3));
+    }
+    input.push_str("withdrawal,1,301,1.0\n");
+    let (client, server) = serve_once("200 OK", input.clone().into_bytes());
+
+    let reader = S3ObjectReader::new(Some(client), "settlement", "2024-01-31/input.csv")
+        .expect("failed to start the download");
+    let read: Vec<Transaction> = transactions_from_reader(csv::Reader::from_reader(reader))
+        .collect::<Result<_, ConversionError>>()
+        .expect("invalid transactions");
+    let expected: Vec<Transaction> =
+        transactions_from_reader(csv::Reader::from_reader(input.as_bytes()))
+            .collect::<Result<_, ConversionError>>()
+            .unwrap();
+    assert_eq!(read.len(), 301);
+    assert_eq!(read, expected);
+    let request_line = server.join().unwrap();
+    assert!(
+        request_line.starts_with("GET /settlement/2024-01-31/input.csv"),
+        "{request_line}"
+    );
+}
+
+#[test]
+/// A failed request is returned by the first read
+fn missing_s3_object() {
+    let error = b"<?xml version=\"1.0\"?><Error><Code>NoSuchKey</Code><Message>no such key</Message></Error>";
+    let (client, _) = serve_once("404 Not Found", error.to_vec());
+    let mut reader = S3ObjectReader::new(Some(client), "settlement", "missing.csv").unwrap();
+    let err = reader
+        .read(&mut [0; 16])
+        .expect_err("read a missing object");
+    assert!(
+        err.to_string().contains("s3://settlement/missing.csv"),
+        "{err}"
+    );
+}
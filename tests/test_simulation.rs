@@ -0,0 +1,203 @@
+use std::{io, sync::mpsc::channel, time::Duration};
+
+use tx_engine::{
+    cli::{ArgsError, Command},
+    model::{ClientId, Clients, Transaction},
+    simulation::{
+        LatencyHistogram, SimulationConfig, TransactionGenerator, TypeMix, run_simulation,
+    },
+    spawn_writer_thread,
+};
+
+fn client_of(transaction: &Transaction) -> ClientId {
+    match transaction {
+        Transaction::Deposit { client, .. }
+        | Transaction::Withdrawal { client, .. }
+        | Transaction::Dispute { client, .. }
+        | Transaction::Resolve { client, .. }
+        | Transaction::Chargeback { client, .. } => *client,
+        Transaction::Custom(custom) => custom.client,
+    }
+}
+
+#[test]
+fn test_generator_is_deterministic_per_seed() {
+    let config = SimulationConfig {
+        clients: 100,
+        seed: 7,
+        ..Default::default()
+    };
+    let first: Vec<Transaction> = TransactionGenerator::new(&config).take(1000).collect();
+    let second: Vec<Transaction> = TransactionGenerator::new(&config).take(1000).collect();
+    assert_eq!(first, second);
+
+    let other_seed = SimulationConfig { seed: 8, ..config };
+    let third: Vec<Transaction> = TransactionGenerator::new(&other_seed).take(1000).collect();
+    assert_ne!(first, third);
+}
+
+#[test]
+fn test_zipf_skew_concentrates_on_first_clients() {
+    let skewed = SimulationConfig {
+        clients: 1000,
+        zipf_exponent: 1.2,
+        ..Default::default()
+    };
+    let hot = TransactionGenerator::new(&skewed)
+        .take(10_000)
+        .filter(|transaction| client_of(transaction) == ClientId(1))
+        .count();
+    // rank 1 weight is 1/H(1000, 1.2) ~ 0.22
+    assert!(hot > 1500, "client 1 got {hot} transactions");
+
+    let uniform = SimulationConfig {
+        zipf_exponent: 0.0,
+        ..skewed
+    };
+    let hot = TransactionGenerator::new(&uniform)
+        .take(10_000)
+        .filter(|transaction| client_of(transaction) == ClientId(1))
+        .count();
+    assert!(hot < 100, "client 1 got {hot} transactions");
+}
+
+#[test]
+fn test_disputes_reference_earlier_deposits() {
+    let config = SimulationConfig {
+        clients: 10,
+        ..Default::default()
+    };
+    let mut deposits = Vec::new();
+    for transaction in TransactionGenerator::new(&config).take(5000) {
+        match transaction {
+            Transaction::Deposit { client, tx, .. } => deposits.push((client, tx)),
+            Transaction::Dispute { client, tx }
+            | Transaction::Resolve { client, tx }
+            | Transaction::Chargeback { client, tx } => {
+                assert!(deposits.contains(&(client, tx)), "{tx} was never deposited");
+            }
+            Transaction::Withdrawal { .. } => {}
+            Transaction::Custom(_) => panic!("the generator only emits built-in types"),
+        }
+    }
+}
+
+#[test]
+fn test_mix_spec() {
+    let mix: TypeMix = "deposit=1,withdrawal=0,chargeback=0.5".parse().unwrap();
+    assert_eq!(
+        mix,
+        TypeMix {
+            deposit: 1.0,
+            withdrawal: 0.0,
+            chargeback: 0.5,
+            ..Default::default()
+        }
+    );
+    assert!("deposit=-1".parse::<TypeMix>().is_err());
+    assert!("transfer=1".parse::<TypeMix>().is_err());
+
+    // only deposits
+    let config = SimulationConfig {
+        mix: "deposit=1,withdrawal=0,dispute=0,resolve=0,chargeback=0"
+            .parse()
+            .unwrap(),
+        ..Default::default()
+    };
+    assert!(
+        TransactionGenerator::new(&config)
+            .take(100)
+            .all(|transaction| matches!(transaction, Transaction::Deposit { .. }))
+    );
+}
+
+#[test]
+fn test_latency_histogram_percentiles() {
+    let mut histogram = LatencyHistogram::default();
+    for micros in 1..=1000 {
+        histogram.record(Duration::from_micros(micros));
+    }
+    assert_eq!(histogram.count(), 1000);
+    assert_eq!(histogram.max(), Duration::from_micros(1000));
+    let within = |percentile: f64, expected_micros: f64| {
+        let value = histogram.percentile(percentile).as_secs_f64() * 1e6;
+        assert!(
+            (value - expected_micros).abs() <= expected_micros * 0.07,
+            "p{percentile} is {value}us, expected ~{expected_micros}us"
+        );
+    };
+    within(50.0, 500.0);
+    within(90.0, 900.0);
+    within(99.0, 990.0);
+    assert_eq!(histogram.percentile(100.0), Duration::from_micros(1000));
+}
+
+#[test]
+fn test_run_simulation() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (tx, rx) = channel();
+    let thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+
+    let config = SimulationConfig {
+        transactions: 2000,
+        clients: 50,
+        ..Default::default()
+    };
+    let report = run_simulation(&config, &mut clients);
+    assert_eq!(report.transactions, 2000);
+    assert_eq!(report.latencies.count(), 2000);
+    assert!(report.throughput() > 0.0);
+    assert!(clients.accounts.len() <= 50);
+    assert!(report.to_string().contains("latency p99"));
+
+    drop(clients);
+    thread_id.join().unwrap();
+}
+
+#[test]
+fn test_parse_simulate_command() {
+    let parse = |args: &[&str]| Command::parse(args.iter().map(|arg| arg.to_string()));
+    assert_eq!(
+        parse(&["simulate"]).unwrap(),
+        Command::Simulate(SimulationConfig::default())
+    );
+    assert_eq!(
+        parse(&[
+            "simulate",
+            "--transactions",
+            "500",
+            "--clients",
+            "10",
+            "--zipf",
+            "0.8",
+            "--rate",
+            "1000",
+            "--seed",
+            "3",
+            "--mix",
+            "deposit=1",
+        ])
+        .unwrap(),
+        Command::Simulate(SimulationConfig {
+            transactions: 500,
+            clients: 10,
+            zipf_exponent: 0.8,
+            mix: TypeMix {
+                deposit: 1.0,
+                ..Default::default()
+            },
+            rate: Some(1000),
+            seed: 3,
+        })
+    );
+    assert_eq!(
+        parse(&["simulate", "--clients", "0"]),
+        Err(ArgsError::InvalidValue("--clients".into(), "0".into()))
+    );
+    assert_eq!(
+        parse(&["simulate", "--input-format"]),
+        Err(ArgsError::UnknownOption("--input-format".into()))
+    );
+    assert!(matches!(parse(&["input.csv"]), Ok(Command::Process(_))));
+}
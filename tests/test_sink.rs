@@ -0,0 +1,147 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    channel,
+    csv_input::transactions_from_reader,
+    model::{Account, ClientId, Clients, OutputMode},
+    sink::{AccountSink, BatchingSender, account_channel},
+    spawn_writer_thread,
+};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+dispute,2,2,
+chargeback,2,2,
+deposit,3,3,3.0
+";
+
+#[test]
+/// Accounts collected in memory, the locked one as soon as it is charged back
+fn vec_sink() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let mut clients = Clients::new(sink.clone());
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        INPUT.as_bytes(),
+    )));
+    assert_eq!(
+        *sink.lock().expect("poisoned"),
+        [(ClientId(2), Account::new(dec!(0), dec!(0), true))]
+    );
+
+    clients.send_to_output(OutputMode::SkipLocked);
+    let mut accounts = sink.lock().expect("poisoned").split_off(1);
+    accounts.sort_by_key(|(client, _)| client.0);
+    assert_eq!(
+        accounts,
+        [
+            (ClientId(1), Account::new(dec!(1), dec!(0), false)),
+            (ClientId(3), Account::new(dec!(3), dec!(0), false)),
+        ]
+    );
+}
+
+/// Keeps running totals instead of the accounts, like a sink upserting into a database would
+#[derive(Default)]
+struct Totals {
+    accounts: usize,
+    locked: usize,
+}
+
+impl AccountSink for Totals {
+    fn emit(&mut self, _client: ClientId, account: &Account) {
+        self.accounts += 1;
+        self.locked += account.locked() as usize;
+    }
+}
+
+#[test]
+/// Any sink can receive the accounts, without a writer thread
+fn custom_sink() {
+    let totals = Arc::new(Mutex::new(Totals::default()));
+    let mut clients = Clients::with_capacity(totals.clone(), 3, 3);
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        INPUT.as_bytes(),
+    )));
+    clients.send_to_output(OutputMode::All);
+    let totals = totals.lock().expect("poisoned");
+    assert_eq!((totals.accounts, totals.locked), (4, 2)); // the locked account is emitted twice
+}
+
+#[test]
+/// Every registered sink receives every account
+fn fan_out() {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let totals = Arc::new(Mutex::new(Totals::default()));
+    let mut clients = Clients::new(collected.clone());
+    clients.add_sink(totals.clone());
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        INPUT.as_bytes(),
+    )));
+    clients.send_to_output(OutputMode::SkipLocked);
+    assert_eq!(collected.lock().expect("poisoned").len(), 3);
+    let totals = totals.lock().expect("poisoned");
+    assert_eq!((totals.accounts, totals.locked), (3, 1));
+}
+
+#[test]
+/// Sorted output emits the remaining accounts by client id
+fn sorted_output() {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let mut clients = Clients::new(collected.clone());
+    clients.sorted_output = true;
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        "type,client,tx,amount\ndeposit,9,1,1.0\ndeposit,3,2,1.0\ndeposit,700,3,1.0\ndeposit,1,4,1.0\n"
+            .as_bytes(),
+    )));
+    clients.send_to_output(OutputMode::All);
+    let clients: Vec<u16> = (collected.lock().expect("poisoned").iter())
+        .map(|(client, _)| client.0)
+        .collect();
+    assert_eq!(clients, [1, 3, 9, 700]);
+}
+
+#[test]
+/// Batched accounts are sent once the batch is full, the last batch when the sender is dropped
+fn batching_sender() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (tx, rx) = channel::unbounded();
+    let mut clients = Clients::new(BatchingSender::new(tx, NonZeroUsize::new(2).unwrap()));
+    clients.sorted_output = true;
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        INPUT.as_bytes(),
+    )));
+    assert!(rx.try_recv().is_err()); // the locked account waits for its batch
+    clients.send_to_output(OutputMode::All);
+    let batches: Vec<Vec<u16>> = rx
+        .iter()
+        .map(|batch| batch.iter().map(|(client, _)| client.0).collect())
+        .collect();
+    assert_eq!(batches, [vec![2, 1], vec![2, 3]]);
+}
+
+#[test]
+/// The writer thread writes the same csv from a batched channel
+fn batched_writer_thread() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let write = |batch_size| {
+        let (tx, rx) = account_channel(batch_size);
+        let writer = spawn_writer_thread(Vec::new(), rx);
+        let mut clients = Clients::new(tx);
+        clients.sorted_output = true;
+        clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+            INPUT.as_bytes(),
+        )));
+        clients.send_to_output(OutputMode::SkipLocked);
+        String::from_utf8(writer.join().expect("writer thread panicked")).expect("utf-8")
+    };
+    let unbatched = write(None);
+    assert_eq!(unbatched.lines().count(), 4);
+    assert_eq!(write(NonZeroUsize::new(2)), unbatched);
+    assert_eq!(write(NonZeroUsize::new(1000)), unbatched);
+}
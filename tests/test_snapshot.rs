@@ -0,0 +1,157 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    model::{ClientId, Clients, ErasurePolicy},
+    snapshot::{AccountUpdateFeed, DeltaEmitter, SnapshotPublisher},
+    spawn_writer_thread,
+};
+
+const INPUT: &str = r#"
+    type, client, tx, amount
+    deposit, 3, 1, 3.0
+    deposit, 1, 2, 1.0
+    deposit, 2, 3, 2.0
+    withdrawal, 2, 4, 1.5
+    deposit, 4, 5, 4.0"#;
+
+#[test]
+/// Snapshots are only published every N transactions and a reader keeps the one it holds
+fn epochs() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let publisher = SnapshotPublisher::new(2);
+    let reader = publisher.reader();
+    assert_eq!(reader.latest().epoch, 0);
+    clients.add_observer(publisher);
+    clients.load_transactions(transactions_from_reader(csv_reader).take(3));
+
+    let held = reader.latest();
+    assert_eq!(held.epoch, 1);
+    assert_eq!(held.transactions, 2);
+    assert_eq!(held.accounts.len(), 2);
+
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+    clients.load_transactions(transactions_from_reader(csv_reader).skip(3));
+    let latest = thread::spawn(move || reader.latest())
+        .join()
+        .expect("reader thread panicked");
+    assert_eq!(latest.epoch, 2);
+    assert_eq!(latest.accounts[&ClientId(2)].total(), dec!(0.5));
+    assert_eq!(held.accounts.len(), 2); // the held snapshot is untouched by later transactions
+}
+
+#[test]
+/// Pages are sorted by client, never repeat a client, and erased clients disappear immediately
+fn pages_and_erasure() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let publisher = SnapshotPublisher::new(1);
+    let reader = publisher.reader();
+    clients.add_observer(publisher);
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    let snapshot = reader.latest();
+    let first: Vec<ClientId> = snapshot.page(None, 3).iter().map(|(c, _)| *c).collect();
+    assert_eq!(first, vec![ClientId(1), ClientId(2), ClientId(3)]);
+    let second: Vec<ClientId> = snapshot
+        .page(Some(ClientId(3)), 3)
+        .iter()
+        .map(|(c, _)| *c)
+        .collect();
+    assert_eq!(second, vec![ClientId(4)]);
+
+    clients.erase_client(ClientId(4), ErasurePolicy::Zero);
+    assert!(!reader.latest().accounts.contains_key(&ClientId(4)));
+}
+
+// Output of the delta emitter, still readable once the emitter was given to the clients
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().expect("buffer poisoned").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+/// Only the accounts that changed since the previous emission are written, erasures included
+fn delta_stream() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(INPUT.as_bytes());
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let output = SharedBuffer::default();
+    let emitter = Arc::new(Mutex::new(DeltaEmitter::new(2, output.clone())));
+    clients.add_observer(emitter.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+    clients.erase_client(ClientId(3), ErasurePolicy::Zero);
+    let mut emitter = emitter.lock().expect("emitter poisoned");
+    emitter.emit().expect("deltas written");
+    emitter.emit().expect("nothing to write");
+
+    let expected = "epoch,change,client,available,held,total,locked\n\
+        1,created,1,1,0,1,false\n\
+        1,created,3,3,0,3,false\n\
+        2,created,2,0.5,0,0.5,false\n\
+        3,erased,3,,,,\n\
+        3,created,4,4,0,4,false\n";
+    let written = output.0.lock().expect("buffer poisoned").clone();
+    assert_eq!(String::from_utf8(written).expect("utf8"), expected);
+}
+
+#[test]
+/// Every transaction that changed an account writes a line, the ignored ones do not
+fn update_feed() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,2,5.0
+dispute,1,1,
+chargeback,1,1,
+deposit,1,3,1.0
+";
+    let mut clients = Clients::new(Vec::new());
+    let output = SharedBuffer::default();
+    let feed = Arc::new(Mutex::new(AccountUpdateFeed::new(output.clone())));
+    clients.add_observer(feed.clone());
+    clients.load_transactions(transactions_from_reader(csv::Reader::from_reader(
+        input.as_bytes(),
+    )));
+    assert_eq!(feed.lock().expect("feed poisoned").written(), 3);
+
+    let expected = r#"{"client":1,"available":"1","held":"0","total":"1","locked":false,"tx":1}
+{"client":1,"available":"0","held":"1","total":"1","locked":false,"tx":1}
+{"client":1,"available":"0","held":"0","total":"0","locked":true,"tx":1}
+"#;
+    let written = output.0.lock().expect("buffer poisoned").clone();
+    assert_eq!(String::from_utf8(written).expect("utf8"), expected);
+}
@@ -0,0 +1,67 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::transactions_from_reader,
+    model::{ClientId, Clients, TransactionId},
+    spawn_writer_thread,
+    statement_export::{StatementEntry, StatementRecorder},
+};
+
+#[test]
+/// Only transactions that change the total funds are statement lines
+fn qif_statement() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 2.0
+        deposit, 2, 2, 1.0
+        withdrawal, 1, 3, 0.5
+        dispute, 1, 1,
+        chargeback, 1, 1,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let (tx, rx) = mpsc::channel();
+    let _thread_id = spawn_writer_thread(io::sink(), rx);
+    let mut clients = Clients::new(tx);
+    let recorder = Arc::new(Mutex::new(StatementRecorder::default()));
+    clients.add_observer(recorder.clone());
+    clients.load_transactions(transactions_from_reader(csv_reader));
+
+    let recorder = recorder.lock().expect("poisoned");
+    assert_eq!(
+        recorder.history[&ClientId(1)],
+        vec![
+            StatementEntry {
+                tx: TransactionId(1),
+                transaction_type: "deposit",
+                amount: dec!(2)
+            },
+            StatementEntry {
+                tx: TransactionId(3),
+                transaction_type: "withdrawal",
+                amount: dec!(-0.5)
+            },
+            StatementEntry {
+                tx: TransactionId(1),
+                transaction_type: "chargeback",
+                amount: dec!(-2)
+            },
+        ]
+    );
+
+    let mut out = Vec::new();
+    recorder
+        .write_qif(ClientId(2), "05/01/2024", &mut out)
+        .expect("failed to write qif");
+    assert_eq!(
+        String::from_utf8(out).expect("invalid utf8"),
+        "!Type:Bank\nD05/01/2024\nT1\nN2\nPdeposit\n^\n"
+    );
+}
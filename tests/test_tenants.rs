@@ -0,0 +1,63 @@
+use std::fs;
+
+use rust_decimal::dec;
+use tx_engine::{
+    model::{Account, ClientId},
+    tenants::{TenantConfig, TenantId, Tenants, tenant_transactions_from_reader},
+};
+
+#[test]
+/// The same client id in two tenants maps to two independent accounts and two output files
+fn tenants_are_isolated() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        tenant, type, client, tx, amount
+        acme, deposit, 1, 1, 1.0
+        globex, deposit, 1, 1, 5.0
+        acme, dispute, 1, 1,
+        globex, withdrawal, 1, 2, 2.0"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let output_dir = std::env::temp_dir().join(format!("tx_engine_tenants_{}", std::process::id()));
+    fs::create_dir_all(&output_dir).expect("failed to create output dir");
+    let globex_output = output_dir.join("globex_accounts.csv");
+
+    let mut tenants = Tenants::new(&output_dir);
+    tenants.configure(
+        TenantId("globex".to_string()),
+        TenantConfig {
+            output_path: globex_output.clone(),
+        },
+    );
+    tenants
+        .load_transactions(tenant_transactions_from_reader(csv_reader))
+        .expect("failed to load transactions");
+
+    let acme = &tenants.ledgers[&TenantId("acme".to_string())].clients;
+    let globex = &tenants.ledgers[&TenantId("globex".to_string())].clients;
+    assert_eq!(
+        acme.accounts[&ClientId(1)],
+        Account::new(dec!(0.0), dec!(1.0), false)
+    );
+    assert_eq!(
+        globex.accounts[&ClientId(1)],
+        Account::new(dec!(3.0), dec!(0.0), false)
+    );
+
+    tenants.finish().expect("failed to finish tenants");
+
+    let acme_output = fs::read_to_string(output_dir.join("acme.csv")).expect("missing acme output");
+    let globex_output = fs::read_to_string(globex_output).expect("missing globex output");
+    assert_eq!(
+        acme_output,
+        "client,available,held,total,locked\n1,0,1,1,false\n"
+    );
+    assert_eq!(
+        globex_output,
+        "client,available,held,total,locked\n1,3,0,3,false\n"
+    );
+    fs::remove_dir_all(output_dir).expect("failed to clean up");
+}
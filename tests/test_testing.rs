@@ -0,0 +1,62 @@
+#![cfg(feature = "testing")]
+
+use rust_decimal::dec;
+use tx_engine::{
+    model::{Account, ClientId, RoundingPolicy, RoundingStrategy},
+    testing::TestEngine,
+};
+
+#[test]
+/// Csv text in, sorted accounts out, over several calls
+fn apply_csv() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut engine = TestEngine::new();
+    let stats = engine.apply_csv(
+        r#"
+        type, client, tx, amount
+        deposit, 2, 1, 2.0
+        deposit, 1, 2, 1.0
+        withdrawal, 1, 3, 5.0
+        deposit, 1, bad, 1.0"#,
+    );
+    assert_eq!(stats.invalid, 1);
+    assert_eq!(stats.rejected, 1);
+    engine.apply_csv(
+        r#"
+        type, client, tx, amount
+        dispute, 2, 1,
+        chargeback, 2, 1,"#,
+    );
+
+    let mut locked = Account::default();
+    locked.lock();
+    assert_eq!(
+        engine.accounts(),
+        vec![
+            (ClientId(1), Account::new(dec!(1), dec!(0), false)),
+            (ClientId(2), locked.clone()),
+        ]
+    );
+    assert_eq!(engine.account(2), Some(&locked));
+    assert_eq!(engine.account(3), None);
+    assert_eq!(engine.emitted(), vec![(ClientId(2), locked)]);
+}
+
+#[test]
+/// The accounts are rounded like the output
+fn rounded_accounts() {
+    let mut engine = TestEngine::new();
+    engine.clients.rounding = RoundingPolicy {
+        scale: 2,
+        strategy: RoundingStrategy::HalfUp,
+    };
+    engine.apply_csv(
+        r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1.005"#,
+    );
+    assert_eq!(
+        engine.accounts(),
+        vec![(ClientId(1), Account::new(dec!(1.01), dec!(0), false))]
+    );
+}
@@ -0,0 +1,95 @@
+use std::{fs, path::Path};
+
+use tx_engine::{
+    output::{OutputFormat, OutputOptions},
+    trailer::{OutputTrailer, TrailerError, verify_output},
+};
+
+fn output_file(name: &str, content: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join("tx_engine_test_trailer");
+    fs::create_dir_all(&dir).expect("failed to create the output dir");
+    let path = dir.join(name);
+    fs::write(&path, content).expect("failed to write the output");
+    path
+}
+
+#[test]
+/// A committed output verifies against its trailer until it is modified
+fn verify_csv_output() {
+    let path = output_file(
+        "out.csv",
+        "client,available,held,total,locked,risk\n1,1.5,0,1.5,false,low\n2,,,,,\n",
+    );
+    let options = OutputOptions {
+        columns: vec!["risk".to_string()],
+        ..Default::default()
+    };
+    let trailer = OutputTrailer::create(&path, &options).expect("valid output");
+    assert_eq!(trailer.rows, 2); // the placeholder row included
+    assert_eq!(trailer.format, "csv");
+    trailer.write(&path).expect("failed to write the trailer");
+    assert_eq!(
+        OutputTrailer::path(&path),
+        path.with_file_name("out.csv.trailer.json")
+    );
+    assert_eq!(verify_output(&path).expect("verified output"), trailer);
+
+    let mut wrong_rows = trailer.clone();
+    wrong_rows.rows = 3;
+    wrong_rows
+        .write(&path)
+        .expect("failed to write the trailer");
+    assert!(matches!(
+        verify_output(&path),
+        Err(TrailerError::RowCount {
+            expected: 3,
+            actual: 2
+        })
+    ));
+
+    trailer.write(&path).expect("failed to write the trailer");
+    fs::write(
+        &path,
+        "client,available,held,total,locked,risk\n1,1.5,0,1.5,false,low\n",
+    )
+    .expect("failed to truncate the output");
+    assert!(matches!(
+        verify_output(&path),
+        Err(TrailerError::Checksum { .. })
+    ));
+}
+
+#[test]
+/// Files that do not match the columns of the output options get no trailer
+fn schema_mismatch() {
+    let options = OutputOptions::default();
+    let path = output_file("header.csv", "client,available\n1,1.5\n");
+    assert!(matches!(
+        OutputTrailer::create(&path, &options),
+        Err(TrailerError::Schema(_))
+    ));
+    let path = output_file(
+        "amount.csv",
+        "client,available,held,total,locked\n1,lots,0,1,false\n",
+    );
+    assert!(matches!(
+        OutputTrailer::create(&path, &options),
+        Err(TrailerError::Schema(_))
+    ));
+    let ndjson = OutputOptions {
+        format: OutputFormat::NdJson,
+        ..Default::default()
+    };
+    let path = output_file(
+        "out.ndjson",
+        "{\"client\":1,\"available\":\"1.5\",\"held\":\"0\",\"total\":\"1.5\",\"locked\":false}\n{\"client\":2,\"score\":3}\n",
+    );
+    assert!(matches!(
+        OutputTrailer::create(&path, &ndjson),
+        Err(TrailerError::Schema(_))
+    ));
+    assert!(matches!(
+        verify_output(Path::new("missing.csv")),
+        Err(TrailerError::Io(_))
+    ));
+}
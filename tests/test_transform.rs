@@ -0,0 +1,113 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use rust_decimal::dec;
+use tx_engine::{
+    csv_input::{ConversionError, transactions_from_reader},
+    model::{ClientId, Transaction, TransactionId},
+    transform::{
+        RemapClients, ScaleAmounts, TransactionTransform, minor_unit_amounts,
+        transform_transactions,
+    },
+};
+
+#[test]
+/// Transforms run in order on every valid transaction, invalid rows are passed through
+fn transform_pipeline() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 150
+        withdrawal, 2, 2, 25
+        dispute, 1, 1,
+        deposit, 1, bad, 1"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+
+    let mapping = "from, to\n1, 10\n";
+    let remap = RemapClients::from_reader(
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(mapping.as_bytes()),
+    )
+    .expect("valid mapping");
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counter = seen.clone();
+    let count = move |transaction: Transaction| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        transaction
+    };
+    let transforms: Vec<Box<dyn TransactionTransform>> = vec![
+        Box::new(remap),
+        Box::new(ScaleAmounts(dec!(0.01))),
+        Box::new(count),
+    ];
+
+    let transactions: Vec<_> =
+        transform_transactions(transforms, transactions_from_reader(csv_reader)).collect();
+    assert_eq!(transactions.len(), 4);
+    assert_eq!(
+        transactions[0].as_ref().expect("valid"),
+        &Transaction::Deposit {
+            client: ClientId(10),
+            tx: TransactionId(1),
+            amount: dec!(1.5)
+        }
+    );
+    assert_eq!(
+        transactions[1].as_ref().expect("valid"),
+        &Transaction::Withdrawal {
+            client: ClientId(2),
+            tx: TransactionId(2),
+            amount: dec!(0.25)
+        }
+    );
+    assert_eq!(
+        transactions[2].as_ref().expect("valid"),
+        &Transaction::Dispute {
+            client: ClientId(10),
+            tx: TransactionId(1),
+        }
+    );
+    assert!(transactions[3].is_err());
+    assert_eq!(seen.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+/// Integer cents become decimal amounts, fractional amounts are rejected
+fn minor_units() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let input_reader = r#"
+        type, client, tx, amount
+        deposit, 1, 1, 1050
+        withdrawal, 1, 2, 25.00
+        deposit, 1, 3, 10.5
+        dispute, 1, 1,"#
+        .as_bytes();
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input_reader);
+    let results: Vec<_> = minor_unit_amounts(2, transactions_from_reader(csv_reader)).collect();
+    assert_eq!(
+        results[0].as_ref().expect("valid deposit"),
+        &Transaction::Deposit {
+            client: ClientId(1),
+            tx: TransactionId(1),
+            amount: dec!(10.50)
+        }
+    );
+    assert_eq!(
+        results[1].as_ref().expect("valid withdrawal"),
+        &Transaction::Withdrawal {
+            client: ClientId(1),
+            tx: TransactionId(2),
+            amount: dec!(0.25)
+        }
+    );
+    assert!(matches!(results[2], Err(ConversionError::MinorUnits(_))));
+    assert!(results[3].is_ok());
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use tx_engine::{
+    csv_input::{ConversionError, transactions_from_reader},
+    model::Transaction,
+    trust::{SourceTrust, TrustLevel, ValidationProfile, validate_source},
+};
+
+const INPUT: &str = r#"
+    type, client, tx, amount
+    deposit, 1, 1, 10
+    deposit, 1, 1, 5
+    deposit, 1, 2, 0
+    withdrawal, 1, 3, 1.00001
+    deposit, 1, 4, 1.50000
+    dispute, 1, 4,
+    dispute, 1, 9,
+    resolve, 1, 3,
+    deposit, 1, bad, 1"#;
+
+fn transactions(input: &'static str) -> impl Iterator<Item = Result<Transaction, ConversionError>> {
+    let csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //trim whitespace around fields
+        .from_reader(input.as_bytes());
+    transactions_from_reader(csv_reader)
+}
+
+// the tx id of each valid transaction, 0 for the invalid ones
+fn validated(level: TrustLevel) -> (Vec<u32>, u64) {
+    let mut validated = validate_source(transactions(INPUT), "partner.csv".to_string(), level);
+    let txs = validated
+        .by_ref()
+        .map(|transaction| transaction.map_or(0, |transaction| transaction.tx_id().0))
+        .collect();
+    (txs, validated.rejected())
+}
+
+#[test]
+/// Each level adds checks to the one below it
+fn profiles() {
+    assert_eq!(TrustLevel::Trusted.profile(), ValidationProfile::default());
+    assert!(TrustLevel::Standard.profile().duplicate_tx);
+    assert!(!TrustLevel::Standard.profile().exact_amounts);
+    assert_eq!(
+        TrustLevel::Strict.profile(),
+        ValidationProfile {
+            duplicate_tx: true,
+            exact_amounts: true,
+            unknown_references: true,
+        }
+    );
+    assert_eq!("strict".parse(), Ok(TrustLevel::Strict));
+    assert!("paranoid".parse::<TrustLevel>().is_err());
+}
+
+#[test]
+/// A trusted source only fails the rows that do not parse
+fn trusted_source() {
+    let (txs, rejected) = validated(TrustLevel::Trusted);
+    assert_eq!(txs, [1, 1, 2, 3, 4, 4, 9, 3, 0]);
+    assert_eq!(rejected, 0);
+}
+
+#[test]
+/// A standard source also fails the reused tx ids
+fn standard_source() {
+    let (txs, rejected) = validated(TrustLevel::Standard);
+    assert_eq!(txs, [1, 0, 2, 3, 4, 4, 9, 3, 0]);
+    assert_eq!(rejected, 1);
+}
+
+#[test]
+/// A strict source also fails zero or over-precise amounts and references to txs it never
+/// deposited, trailing zeros are not precision
+fn strict_source() {
+    let (txs, rejected) = validated(TrustLevel::Strict);
+    assert_eq!(txs, [1, 0, 0, 0, 4, 4, 0, 0, 0]);
+    assert_eq!(rejected, 5);
+
+    let mut validated = validate_source(
+        transactions(INPUT).skip(2), // the zero amount first
+        "partner.csv".to_string(),
+        TrustLevel::Strict,
+    );
+    match validated.next() {
+        Some(Err(ConversionError::Validation(reason))) => {
+            assert!(reason.starts_with("partner.csv: "), "{reason}")
+        }
+        other => panic!("expected a validation error, got {other:?}"),
+    }
+}
+
+#[test]
+/// Sources not listed get the default level
+fn source_levels() {
+    let trust = SourceTrust {
+        default: TrustLevel::Standard,
+        sources: HashMap::from([(PathBuf::from("partner.csv"), TrustLevel::Strict)]),
+    };
+    assert_eq!(trust.level(Path::new("partner.csv")), TrustLevel::Strict);
+    assert_eq!(trust.level(Path::new("internal.csv")), TrustLevel::Standard);
+    assert_eq!(
+        SourceTrust::default().level(Path::new("partner.csv")),
+        TrustLevel::Trusted
+    );
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use tx_engine::tuning::{SystemResources, Tuning};
+
+const MIB: u64 = 1024 * 1024;
+
+#[test]
+/// Knobs scale with the input and the cores, the queues with the available memory
+fn auto_tuning() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let small = SystemResources {
+        cores: 8,
+        input_bytes: Some(MIB),
+        available_memory: None,
+    };
+    assert_eq!(
+        Tuning::auto(&small),
+        Tuning {
+            partitions: 1,
+            channel_bound: 1024,
+            reader_buffer_bytes: 16 * 1024,
+        }
+    );
+
+    let large = SystemResources {
+        cores: 8,
+        input_bytes: Some(1024 * MIB),
+        available_memory: Some(16 * 1024 * MIB),
+    };
+    assert_eq!(
+        Tuning::auto(&large),
+        Tuning {
+            partitions: 8,
+            channel_bound: 65536,
+            reader_buffer_bytes: 4 * MIB as usize,
+        }
+    );
+
+    let low_memory = SystemResources {
+        cores: 2,
+        input_bytes: Some(1024 * MIB),
+        available_memory: Some(256 * MIB),
+    };
+    let tuning = Tuning::auto(&low_memory);
+    assert_eq!(tuning.partitions, 2);
+    assert_eq!(tuning.channel_bound, 5242);
+
+    let stream = SystemResources {
+        cores: 4,
+        input_bytes: None,
+        available_memory: None,
+    };
+    assert_eq!(Tuning::auto(&stream).partitions, 1);
+}
+
+#[test]
+fn detect_resources() {
+    let resources = SystemResources::detect(Path::new("data/input_example.csv"));
+    assert!(resources.cores >= 1);
+    assert!(resources.input_bytes.is_some_and(|bytes| bytes > 0));
+    assert_eq!(
+        SystemResources::detect(Path::new("missing.csv")).input_bytes,
+        None
+    );
+}
@@ -0,0 +1,71 @@
+#![cfg(all(feature = "uring", target_os = "linux"))]
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use tx_engine::{
+    cli::Args,
+    csv_input::{ConversionError, CsvOptions, read_transactions_from_csv_with_options},
+    model::Transaction,
+    uring_input::UringReader,
+};
+
+fn write_input(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, bytes).expect("failed to write the input");
+    path
+}
+
+fn transactions(path: &Path, options: &CsvOptions) -> Vec<Transaction> {
+    read_transactions_from_csv_with_options(path, options)
+        .expect("failed to open the input")
+        .collect::<Result<_, ConversionError>>()
+        .expect("invalid transactions")
+}
+
+#[test]
+/// The input read through io_uring gives the same transactions as a read one, even when the rows
+/// straddle the buffers
+fn uring_input() {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let mut input = String::from("\u{feff}type,client,tx,amount\n");
+    for tx in 1..=500 {
+        input.push_str(&format!("deposit,{},{tx},1.5\n", tx % 7));
+    }
+    let path = write_input("tx_engine_test_uring.csv", input.as_bytes());
+    let options = CsvOptions {
+        io_uring: true,
+        ..Default::default()
+    };
+    let read = transactions(&path, &options);
+    assert_eq!(read.len(), 500);
+    assert_eq!(read, transactions(&path, &CsvOptions::default()));
+}
+
+#[test]
+/// Small buffers are refilled in turn until the end of the file
+fn double_buffered_reads() {
+    let bytes: Vec<u8> = (0..10_000u32).map(|byte| byte as u8).collect();
+    let path = write_input("tx_engine_test_uring.bin", &bytes);
+    let file = File::open(&path).expect("failed to open");
+    let mut reader = match UringReader::new(file, 333) {
+        Ok(reader) => reader,
+        Err(err) => {
+            eprintln!("io_uring is not available here ({err}), nothing to test");
+            return;
+        }
+    };
+    let mut read = Vec::new();
+    reader.read_to_end(&mut read).expect("failed to read");
+    assert_eq!(read, bytes);
+}
+
+#[test]
+/// `--io-uring` reads the csv input through io_uring
+fn uring_option() {
+    let args = Args::parse(["in.csv", "--io-uring"].map(String::from)).expect("valid args");
+    assert!(args.csv_options.io_uring);
+}